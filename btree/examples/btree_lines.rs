@@ -1,6 +1,10 @@
-use btree::btree::BTree;
+//! Reads lines from stdin and builds a btree with them as keys.
+//!
+//! With `--sorted`, the input is assumed to be sorted and the tree is bulk loaded.
 
-use buffer_pool::buffer_pool::{BufferPool, Result};
+use btree::btree::{BTree, Result};
+
+use buffer_pool::buffer_pool::BufferPool;
 use buffer_pool::disk_manager_mem::DiskManagerMem;
 
 use futures::stream::TryStreamExt;
@@ -9,12 +13,24 @@ use tokio::io::AsyncBufReadExt;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let sorted = std::env::args().any(|arg| arg == "--sorted");
     let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
-    let btree = BTree::new(&buffer_pool).await?;
     let mut lines = io::BufReader::new(io::stdin()).lines();
-    while let Some(line) = lines.try_next().await? {
-        btree.insert(line.as_bytes(), &[]).await?;
-    }
+    let btree = if sorted {
+        let lines: Vec<String> = lines.try_collect().await?;
+        BTree::bulk_load(
+            &buffer_pool,
+            100,
+            lines.iter().map(|line| (line.as_bytes(), &[][..])),
+        )
+        .await?
+    } else {
+        let btree = BTree::new(&buffer_pool).await?;
+        while let Some(line) = lines.try_next().await? {
+            btree.insert(line.as_bytes(), &[]).await?;
+        }
+        btree
+    };
     println!("{:#?}", btree.dump_tree().await?);
     Ok(())
 }
@@ -1,32 +1,372 @@
 #![allow(dead_code)]
 
-use crate::page;
-use crate::page::TupleBlockPage;
+use crate::overflow;
+use crate::overflow::OverflowPointer;
 use async_recursion::async_recursion;
-use buffer_pool::buffer_pool::{BufferPool, PinnedPageReadGuard, PinnedPageWriteGuard, Result};
-use buffer_pool::disk_manager::{PageData, PageId};
+use buffer_pool::buffer_pool::Error as BufferPoolError;
+use buffer_pool::buffer_pool::{BufferPool, PinnedPageReadGuard, PinnedPageWriteGuard};
+use buffer_pool::disk_manager::{PageData, PageId, PAGE_SIZE};
+pub use page::LayoutError;
+use page::TupleBlockPage;
+use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::io;
 use std::mem;
-use std::ops::{Deref, DerefMut};
+use std::ops::{Bound, Deref, DerefMut};
+
+/// Maximum size of a key.
+///
+/// Keys are always stored inline, since they are needed for binary search and as pivots in
+/// internal pages. Half a page guarantees that a new root fits the -inf pivot and one more.
+pub const MAX_KEY_SIZE: usize = PAGE_SIZE / 2;
+
+/// Leaf tuples larger than this have their value moved to overflow pages.
+pub const MAX_INLINE_TUPLE_SIZE: usize = PAGE_SIZE / 4;
+
+#[derive(Debug)]
+pub enum Error {
+    BufferPool(BufferPoolError),
+    /// The key (of the given size) is larger than `MAX_KEY_SIZE`.
+    KeyTooLarge(usize),
+    /// Input to `bulk_load` is not sorted by key, or contains duplicates.
+    UnsortedInput,
+    /// The page passed to `open` is not a btree meta page.
+    InvalidMetaPage(PageId),
+    /// The tree was written in an on-disk format version we don't understand.
+    UnsupportedVersion(u32),
+    /// The chain of overflow pages starting at the given page doesn't hold the value it should: a
+    /// page has no chunk, or the chunks don't add up to the value size.
+    CorruptOverflowChain(PageId),
+}
+
+impl From<BufferPoolError> for Error {
+    fn from(err: BufferPoolError) -> Self {
+        Error::BufferPool(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::BufferPool(err.into())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Defines the order of keys in a tree.
+///
+/// The comparator is not stored in the tree, so a tree has to be always opened with the
+/// comparator it was created with.
+pub trait KeyComparator: Send + Sync {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering;
+
+    /// Compare the key `prefix + suffix` with `key`. Leaf pages store keys split this way (see
+    /// prefix compression in the README).
+    ///
+    /// The default assembles the key in a buffer on the stack, so that binary search doesn't
+    /// allocate. Comparators which can compare the parts directly should override it.
+    fn compare_prefixed(&self, prefix: &[u8], suffix: &[u8], key: &[u8]) -> Ordering {
+        let mut buffer = [0; MAX_KEY_SIZE];
+        let size = prefix.len() + suffix.len();
+        buffer[..prefix.len()].copy_from_slice(prefix);
+        buffer[prefix.len()..size].copy_from_slice(suffix);
+        self.compare(&buffer[..size], key)
+    }
+}
+
+/// Lexicographic order of key bytes. This is the default.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Bytewise;
+
+impl KeyComparator for Bytewise {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        a.cmp(b)
+    }
+
+    fn compare_prefixed(&self, prefix: &[u8], suffix: &[u8], key: &[u8]) -> Ordering {
+        // Avoid concatenation: compare the prefix with the beginning of the key first.
+        let size = prefix.len().min(key.len());
+        match prefix.cmp(&key[..size]) {
+            Ordering::Equal => suffix.cmp(&key[size..]),
+            ordering => ordering,
+        }
+    }
+}
+
+/// Identifies a btree meta page ("BTRE").
+const MAGIC: u32 = 0x4254_5245;
+
+/// On-disk format version. Bump when the page layout changes incompatibly.
+const FORMAT_VERSION: u32 = 2;
 
 struct TreeMetadata {
+    magic: u32,
+    version: u32,
     root_page_id: PageId,
+    /// Size of the largest key ever inserted. Separators are prefixes of keys, so no pivot is
+    /// larger.
+    max_key_size: u32,
+}
+
+impl TreeMetadata {
+    fn new(root_page_id: PageId, max_key_size: usize) -> Self {
+        Self {
+            magic: MAGIC,
+            version: FORMAT_VERSION,
+            root_page_id,
+            max_key_size: max_key_size as u32,
+        }
+    }
 }
 
-pub struct BTree<'a> {
+pub struct BTree<'a, C = Bytewise> {
     buffer_pool: &'a BufferPool,
     meta_page_id: PageId,
+    comparator: C,
 }
 
 impl<'a> BTree<'a> {
     pub async fn new(buffer_pool: &'a BufferPool) -> Result<BTree<'a>> {
+        Self::with_comparator(buffer_pool, Bytewise).await
+    }
+
+    /// Open an existing tree, given the id of its meta page (as returned by `meta_page_id`).
+    pub async fn open(buffer_pool: &'a BufferPool, meta_page_id: PageId) -> Result<BTree<'a>> {
+        Self::open_with_comparator(buffer_pool, meta_page_id, Bytewise).await
+    }
+
+    /// Build a new tree from entries sorted by key, without duplicates. See
+    /// `bulk_load_with_comparator`.
+    pub async fn bulk_load<I, K, V>(
+        buffer_pool: &'a BufferPool,
+        fill_factor: usize,
+        entries: I,
+    ) -> Result<BTree<'a>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        Self::bulk_load_with_comparator(buffer_pool, Bytewise, fill_factor, entries).await
+    }
+
+    /// Start building a new tree from entries pushed one at a time. See `BulkLoader`.
+    pub async fn bulk_loader(
+        buffer_pool: &'a BufferPool,
+        fill_factor: usize,
+    ) -> Result<BulkLoader<'a>> {
+        BulkLoader::new(buffer_pool, Bytewise, fill_factor).await
+    }
+}
+
+/// Builds a new tree from entries pushed one at a time, sorted by key and without duplicates, the
+/// same way as `BTree::bulk_load_with_comparator`. Useful when the entries are produced
+/// asynchronously, e.g. read from a sort's temporary pages.
+///
+/// Leaf pages are written as soon as they are full. Only the leaf being filled and the separator
+/// keys of the leaves are kept in memory, until `finish` builds the internal levels. If loading
+/// fails, or the loader is dropped before `finish`, the pages written so far are freed.
+pub struct BulkLoader<'a, C = Bytewise> {
+    btree: BTree<'a, C>,
+    pages: LoaderPages<'a>,
+    target_size: usize,
+    /// Separator key and page id of each leaf written so far.
+    level: Vec<(Vec<u8>, PageId)>,
+    /// Entries of the next leaf. Size is tracked incrementally: the page prefix is the shortest of
+    /// the common prefixes of the first key and every other key.
+    current: Vec<LeafEntry>,
+    current_size: usize,
+    current_prefix_size: usize,
+    last_flushed_key: Option<Vec<u8>>,
+    max_key_size: usize,
+}
+
+impl<'a, C: KeyComparator> BulkLoader<'a, C> {
+    pub async fn new(
+        buffer_pool: &'a BufferPool,
+        comparator: C,
+        fill_factor: usize,
+    ) -> Result<BulkLoader<'a, C>> {
+        assert!(
+            (10..=100).contains(&fill_factor),
+            "fill factor must be between 10 and 100"
+        );
+        let meta_page_id = buffer_pool.allocate_page().await?.id();
+        Ok(BulkLoader {
+            btree: BTree {
+                buffer_pool,
+                meta_page_id,
+                comparator,
+            },
+            pages: LoaderPages {
+                buffer_pool,
+                page_ids: vec![meta_page_id],
+            },
+            target_size: page::capacity::<NodeMetadata>() * fill_factor / 100,
+            level: vec![],
+            current: vec![],
+            current_size: 0,
+            current_prefix_size: 0,
+            last_flushed_key: None,
+            max_key_size: 0,
+        })
+    }
+
+    /// Add an entry. Returns `UnsortedInput` if the key is not greater than the previous one.
+    pub async fn push(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        let previous_key = self
+            .current
+            .last()
+            .map(|entry| &entry.key)
+            .or(self.last_flushed_key.as_ref());
+        if let Some(previous_key) = previous_key {
+            if self.btree.comparator.compare(key, previous_key) != Ordering::Greater {
+                return Err(Error::UnsortedInput);
+            }
+        }
+
+        let (flags, value) = self
+            .btree
+            .prepare_entry(key, value, &mut self.pages.page_ids)
+            .await?;
+        self.max_key_size = self.max_key_size.max(key.len());
+        let entry_size = leaf_tuple::size(key, &value) + page::TUPLE_DESCRIPTOR_SIZE;
+        let prefix_size = match self.current.first() {
+            Some(first) => self
+                .current_prefix_size
+                .min(common_prefix_size(&first.key, key)),
+            None => key.len().min(MAX_PREFIX_SIZE),
+        };
+        if !self.current.is_empty()
+            && self.current_size + entry_size - (self.current.len() + 1) * prefix_size
+                > self.target_size
+        {
+            self.last_flushed_key = Some(
+                self.btree
+                    .flush_leaf(
+                        &self.current,
+                        self.last_flushed_key.as_deref(),
+                        &mut self.level,
+                    )
+                    .await?,
+            );
+            self.pages
+                .page_ids
+                .push(self.level.last().expect("no leaf written").1);
+            self.current.clear();
+            self.current_size = 0;
+        }
+        self.current_prefix_size = if self.current.is_empty() {
+            key.len().min(MAX_PREFIX_SIZE)
+        } else {
+            prefix_size
+        };
+        self.current.push(LeafEntry {
+            key: key.to_vec(),
+            flags,
+            value: value.into_owned(),
+        });
+        self.current_size += entry_size;
+        Ok(())
+    }
+
+    /// Write the last leaf and the internal levels, and return the tree.
+    pub async fn finish(self) -> Result<BTree<'a, C>> {
+        let BulkLoader {
+            btree,
+            mut pages,
+            target_size,
+            mut level,
+            current,
+            last_flushed_key,
+            max_key_size,
+            ..
+        } = self;
+        let buffer_pool = btree.buffer_pool;
+        if !current.is_empty() || level.is_empty() {
+            btree
+                .flush_leaf(&current, last_flushed_key.as_deref(), &mut level)
+                .await?;
+            pages
+                .page_ids
+                .push(level.last().expect("no leaf written").1);
+        }
+
+        // Internal levels. Each child is represented by its separator key (empty for the first
+        // child on a level) and page id.
+        let mut level_number = 1;
+        while level.len() > 1 {
+            let mut parents = vec![];
+            let mut parent: Option<NodePage<PinnedPageWriteGuard<'a>>> = None;
+            let mut parent_size = 0;
+            for (separator_key, child) in level {
+                let pivot_size = pivot_tuple::size(&separator_key) + page::TUPLE_DESCRIPTOR_SIZE;
+                if let Some(page) = &mut parent {
+                    if parent_size + pivot_size <= target_size {
+                        let index = page.tuple_count();
+                        let tuple = page
+                            .alloc_tuple_at(index, pivot_tuple::size(&separator_key))
+                            .expect("pivot does not fit");
+                        pivot_tuple::write(tuple, child, &separator_key);
+                        parent_size += pivot_size;
+                        continue;
+                    }
+                }
+                // Start a new page. The child becomes its -inf pivot, and the separator goes one
+                // level up.
+                let page = NodePage::new_internal(
+                    buffer_pool.allocate_page().await?.write().await,
+                    level_number,
+                    child,
+                );
+                page.dirty();
+                pages.page_ids.push(page.id());
+                parents.push((separator_key, page.id()));
+                parent = Some(page);
+                parent_size = pivot_tuple::size(&[]) + page::TUPLE_DESCRIPTOR_SIZE;
+            }
+            level = parents;
+            level_number += 1;
+        }
+
+        let mut meta_page = MetaPage::from_existing(
+            buffer_pool
+                .get_page(btree.meta_page_id)
+                .await?
+                .write()
+                .await,
+        );
+        *meta_page.metadata_mut() = TreeMetadata::new(level[0].1, max_key_size);
+        meta_page.data.dirty();
+        // The pages belong to the tree now.
+        pages.page_ids.clear();
+        Ok(btree)
+    }
+}
+
+/// Pages written by a `BulkLoader`, which are freed when it's dropped.
+struct LoaderPages<'a> {
+    buffer_pool: &'a BufferPool,
+    page_ids: Vec<PageId>,
+}
+
+impl Drop for LoaderPages<'_> {
+    fn drop(&mut self) {
+        for &page_id in &self.page_ids {
+            self.buffer_pool.free_page_later(page_id);
+        }
+    }
+}
+
+impl<'a, C: KeyComparator> BTree<'a, C> {
+    /// Create a new, empty tree ordered by the given comparator.
+    pub async fn with_comparator(buffer_pool: &'a BufferPool, comparator: C) -> Result<Self> {
         let meta_page = buffer_pool.allocate_page().await?;
         let root_page = buffer_pool.allocate_page().await?;
         let meta_page_data = meta_page.data().write().await;
-        let initial_metadata = TreeMetadata {
-            root_page_id: root_page.id(),
-        };
-        unsafe { (meta_page_data.as_ptr() as *mut TreeMetadata).write(initial_metadata) }
+        *MetaPage::from_existing(meta_page_data).metadata_mut() =
+            TreeMetadata::new(root_page.id(), 0);
         meta_page.dirty();
 
         let root_page_data = root_page.data().write().await;
@@ -36,150 +376,417 @@ impl<'a> BTree<'a> {
         Ok(Self {
             buffer_pool,
             meta_page_id: meta_page.id(),
+            comparator,
         })
     }
 
+    /// Open an existing tree created with the given comparator.
+    pub async fn open_with_comparator(
+        buffer_pool: &'a BufferPool,
+        meta_page_id: PageId,
+        comparator: C,
+    ) -> Result<Self> {
+        let meta_page = buffer_pool.get_page(meta_page_id).await?;
+        let meta_page = MetaPage::from_existing(meta_page.data().read().await);
+        let meta = meta_page.metadata();
+        if meta.magic != MAGIC {
+            return Err(Error::InvalidMetaPage(meta_page_id));
+        }
+        if meta.version != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion(meta.version));
+        }
+        Ok(Self {
+            buffer_pool,
+            meta_page_id,
+            comparator,
+        })
+    }
+
+    /// Id of the meta page, which identifies the tree on disk.
+    pub fn meta_page_id(&self) -> PageId {
+        self.meta_page_id
+    }
+
+    pub fn comparator(&self) -> &C {
+        &self.comparator
+    }
+
     /// Inserts the given key into the tree.
     /// When already there, overwrites the value.
     pub async fn insert(&self, key: &[u8], value: &[u8]) -> Result<()> {
-        let (meta, mut page) = self.get_root_page_write().await?;
-        let mut parent = Parent::MetaPage(meta);
+        let (flags, value) = self.prepare_entry(key, value, &mut vec![]).await?;
+        match self.insert_entry(key, flags, &value).await {
+            // The tree is no longer latched, and nothing refers to the old value.
+            Ok(Some(replaced)) => overflow::free(self.buffer_pool, replaced).await,
+            Ok(None) => Ok(()),
+            Err(err) => {
+                // Don't leak the overflow pages written by `prepare_entry`.
+                if flags & leaf_tuple::FLAG_OVERFLOW != 0 {
+                    let pointer = unsafe { slice_to_struct::<OverflowPointer>(&value) };
+                    overflow::free(self.buffer_pool, *pointer).await?;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Insert a prepared leaf entry. Returns the overflow pointer of the value it replaced, if
+    /// any, to be freed once the tree is unlatched.
+    async fn insert_entry(
+        &self,
+        key: &[u8],
+        flags: u16,
+        value: &[u8],
+    ) -> Result<Option<OverflowPointer>> {
+        // Latches are released top-down once a node is safe: it has room for a pivot as large as
+        // the largest key in the tree (see `has_room_for_pivot`). The maximum is raised while the
+        // meta page is latched, before the key can reach any leaf. The meta page is `None` when
+        // the root is no longer latched.
+        let (mut meta, mut page) = self.get_root_page_write().await?;
+        let max_key_size = {
+            let metadata = meta.metadata_mut();
+            if key.len() as u32 > metadata.max_key_size {
+                metadata.max_key_size = key.len() as u32;
+                meta.data.dirty();
+            }
+            meta.metadata().max_key_size as usize
+        };
+        let mut meta = Some(meta);
+        // Latched internal ancestors of `page`, with the index of the pivot followed in each.
+        let mut path: Vec<(NodePage<PinnedPageWriteGuard<'a>>, usize)> = vec![];
 
         loop {
             if page.metadata().is_leaf() {
-                match page.binary_search(key) {
-                    SearchResult::Found(_) => {
-                        unimplemented!("replacing existing key");
-                    }
-                    SearchResult::NotFound(insert_index) => {
-                        let tuple_size = leaf_tuple::size(key, value);
-                        match page.alloc_tuple_at(insert_index, tuple_size) {
-                            Ok(tuple) => {
-                                leaf_tuple::write(tuple, key, value);
-                                page.dirty();
-                                return Ok(());
-                            }
-                            Err(page::Error::PageFull) => {
-                                // FIXME: crash in case the tuple is too big to fit in any page - we don't
-                                // support overflow
-                                let new_sibling_page =
-                                    self.buffer_pool.allocate_page().await?.write().await;
-                                let mut new_sibling = NodePage::new_leaf(new_sibling_page);
-                                let split_index = page.get_split_index(insert_index, tuple_size);
-                                let num_tuples = page.tuple_count() + 1;
-
-                                for target_index in split_index..num_tuples {
-                                    if target_index == insert_index {
-                                        // this is the new tuple
-                                        match new_sibling
-                                            .alloc_tuple_at(insert_index - split_index, tuple_size)
-                                        {
-                                            Ok(tuple) => {
-                                                leaf_tuple::write(tuple, key, value);
-                                            }
-                                            Err(page::Error::PageFull) => {
-                                                panic!("new tuple does not fit after split")
-                                            }
-                                        }
-                                    } else {
-                                        let source_index = if target_index > insert_index {
-                                            target_index - 1
-                                        } else {
-                                            target_index
-                                        };
-                                        new_sibling
-                                            .insert_tuple(
-                                                page.get_tuple(source_index).expect("dead tuple"),
-                                            )
-                                            .expect("old tuple does not fit after split");
-                                    }
-                                }
-
-                                if insert_index < split_index {
-                                    // Inserted tuple lands on the old page.
-                                    unsafe { page.header_mut() }.tuple_count =
-                                        (split_index - 1) as u16;
-                                    let tuple = page
-                                        .alloc_tuple_at(insert_index, tuple_size)
-                                        .expect("new tuple does not fit after page split");
-                                    leaf_tuple::write(tuple, key, value);
-                                } else {
-                                    unsafe { page.header_mut() }.tuple_count = split_index as u16;
-                                }
-
-                                new_sibling.page.dirty();
-
-                                let split_key = new_sibling.get_tuple_key(0);
-
-                                match parent {
-                                    Parent::InternalPage {
-                                        mut parent_page,
-                                        index,
-                                    } => {
-                                        // TODO: write a test for this
-                                        let sibling_pointer_tuple = parent_page
-                                            .alloc_tuple_at(index + 1, pivot_tuple::size(split_key))
-                                            .expect("no space for split key in internal page"); // TODO: split recursively
-                                        pivot_tuple::write(
-                                            sibling_pointer_tuple,
-                                            new_sibling.id(),
-                                            split_key,
-                                        );
-                                    }
-                                    Parent::MetaPage(mut meta_page) => {
-                                        // We are splitting the root page. Create a new internal page to
-                                        // replace the root.
-                                        let new_root_page =
-                                            self.buffer_pool.allocate_page().await?.write().await;
-                                        let mut new_root = NodePage::new_internal(
-                                            new_root_page,
-                                            page.metadata().level + 1,
-                                            page.id(),
-                                        );
-                                        let sibling_pointer_tuple = new_root
-                                            .alloc_tuple_at(1, pivot_tuple::size(split_key))
-                                            .expect("no space for key in new root");
-                                        pivot_tuple::write(
-                                            sibling_pointer_tuple,
-                                            new_sibling.id(),
-                                            split_key,
-                                        );
-                                        new_root.page.dirty();
-
-                                        meta_page.metadata_mut().root_page_id = new_root.id();
-                                        meta_page.data.dirty();
-                                    }
-                                }
-
-                                return Ok(());
-                            }
+                let (index, found) = match page.binary_search(&self.comparator, key) {
+                    SearchResult::Found(index) => (index, true),
+                    SearchResult::NotFound(index) => (index, false),
+                };
+                // Overflow pages of the value being overwritten.
+                let replaced = if found {
+                    leaf_tuple::overflow_pointer(page.get_tuple(index).expect("dead tuple"))
+                } else {
+                    None
+                };
+
+                let prefix_size = page.metadata().prefix().len();
+                if key.starts_with(page.metadata().prefix()) {
+                    let suffix = &key[prefix_size..];
+                    let tuple_size = leaf_tuple::size(suffix, value);
+                    if found {
+                        let mut tuple = vec![0; tuple_size];
+                        leaf_tuple::write(&mut tuple, suffix, flags, value);
+                        if page.update_tuple(index, &tuple).is_ok() {
+                            page.dirty();
+                            return Ok(replaced);
                         }
+                    } else if let Ok(tuple) = page.alloc_tuple_at(index, tuple_size) {
+                        leaf_tuple::write(tuple, suffix, flags, value);
+                        page.dirty();
+                        return Ok(replaced);
                     }
                 }
-            } else {
-                // internal node; find descendant
-                let pivot_index = match page.binary_search(key) {
-                    SearchResult::Found(index) => index,
-                    SearchResult::NotFound(index) => index - 1,
+
+                // Either the page is full, or the key doesn't share the page prefix. The entries
+                // are taken from the compressed tuples as they are: the page keeps the ones before
+                // the split point (if there is one), and the rest are written to a new sibling.
+                let (keep, prefix, split) = {
+                    let mut entries = page.leaf_entry_refs();
+                    let entry = LeafEntryRef {
+                        prefix: &[],
+                        suffix: key,
+                        flags,
+                        value,
+                    };
+                    if found {
+                        entries[index] = entry;
+                    } else {
+                        entries.insert(index, entry);
+                    }
+                    let split_index = if leaf_size(&entries) <= page::capacity::<NodeMetadata>() {
+                        entries.len()
+                    } else {
+                        get_split_index(&entries)
+                    };
+                    let prefix = leaf_prefix(&entries[..split_index]);
+                    let split = if split_index < entries.len() {
+                        let mut new_sibling = NodePage::new_leaf(
+                            self.buffer_pool.allocate_page().await?.write().await,
+                        );
+                        new_sibling.write_leaf(&entries[split_index..]);
+                        new_sibling.page.dirty();
+                        let left = entries[split_index - 1].key();
+                        let right = entries[split_index].key();
+                        let split_key = separator(&self.comparator, &left, &right).to_vec();
+                        Some((new_sibling, split_key))
+                    } else {
+                        None
+                    };
+                    (split_index, prefix, split)
                 };
-                let downlink_pointer =
-                    pivot_tuple::get_header(page.get_tuple(pivot_index).unwrap()).downlink_pointer;
-                let next_page = self.get_node_page_write(downlink_pointer).await?;
-                let next_parent = page;
-                page = next_page;
-                parent = Parent::InternalPage {
-                    parent_page: next_parent,
-                    index: pivot_index,
+                page.rewrite_leaf_front(keep, &prefix, (index, found), key, flags, value);
+                page.dirty();
+                let (new_sibling, split_key) = match split {
+                    Some(split) => split,
+                    None => return Ok(replaced),
                 };
+
+                // Insert the pivot of the new page into the parent, splitting the ancestors as
+                // long as they are full.
+                let mut level = page.metadata().level;
+                let mut left_id = page.id();
+                let mut pivot = (split_key, new_sibling.id());
+                drop(new_sibling);
+                drop(page);
+                while let Some((mut parent_page, pivot_index)) = path.pop() {
+                    let (pivot_key, downlink) = &pivot;
+                    if let Ok(tuple) =
+                        parent_page.alloc_tuple_at(pivot_index + 1, pivot_tuple::size(pivot_key))
+                    {
+                        pivot_tuple::write(tuple, *downlink, pivot_key);
+                        parent_page.dirty();
+                        return Ok(replaced);
+                    }
+                    let new_sibling = self.buffer_pool.allocate_page().await?.write().await;
+                    let (new_sibling, split_key) = parent_page.split_internal(
+                        pivot_index + 1,
+                        pivot_key,
+                        *downlink,
+                        new_sibling,
+                    );
+                    parent_page.dirty();
+                    new_sibling.page.dirty();
+                    level = parent_page.metadata().level;
+                    left_id = parent_page.id();
+                    pivot = (split_key, new_sibling.id());
+                }
+
+                // We split the root. Create a new internal page to replace it.
+                let mut meta_page = meta.expect("root split without the meta page latched");
+                let (pivot_key, downlink) = &pivot;
+                let new_root_page = self.buffer_pool.allocate_page().await?.write().await;
+                let mut new_root = NodePage::new_internal(new_root_page, level + 1, left_id);
+                let tuple = new_root
+                    .alloc_tuple_at(1, pivot_tuple::size(pivot_key))
+                    .expect("no space for key in new root");
+                pivot_tuple::write(tuple, *downlink, pivot_key);
+                new_root.page.dirty();
+
+                meta_page.metadata_mut().root_page_id = new_root.id();
+                meta_page.data.dirty();
+                return Ok(replaced);
+            } else {
+                // internal node; find descendant
+                let (pivot_index, downlink_pointer) = page.find_child(&self.comparator, key);
+                let child = self.get_node_page_write(downlink_pointer).await?;
+                path.push((page, pivot_index));
+                if !child.metadata().is_leaf() && child.has_room_for_pivot(max_key_size) {
+                    // Splits below won't go past the child, so its ancestors can be released.
+                    path.clear();
+                    meta = None;
+                }
+                page = child;
             }
         }
     }
 
+    /// Build a new tree from entries sorted by key, without duplicates.
+    ///
+    /// Leaves are filled up to `fill_factor` percent of their capacity, and internal levels are
+    /// built bottom-up, filled the same way. This is much faster than repeated `insert`, and
+    /// produces fuller pages. With a high fill factor, later inserts split the pages right away.
+    ///
+    /// Returns `UnsortedInput` if the keys are not strictly increasing.
+    pub async fn bulk_load_with_comparator<I, K, V>(
+        buffer_pool: &'a BufferPool,
+        comparator: C,
+        fill_factor: usize,
+        entries: I,
+    ) -> Result<Self>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let mut loader = BulkLoader::new(buffer_pool, comparator, fill_factor).await?;
+        for (key, value) in entries {
+            loader.push(key.as_ref(), value.as_ref()).await?;
+        }
+        loader.finish().await
+    }
+
+    /// Write a leaf page during bulk load, and add it to the level. Returns the last key on the
+    /// page.
+    async fn flush_leaf(
+        &self,
+        entries: &[LeafEntry],
+        last_flushed_key: Option<&[u8]>,
+        level: &mut Vec<(Vec<u8>, PageId)>,
+    ) -> Result<Vec<u8>> {
+        let mut page = NodePage::new_leaf(self.buffer_pool.allocate_page().await?.write().await);
+        page.write_leaf(&entries.iter().map(LeafEntry::as_ref).collect::<Vec<_>>());
+        page.dirty();
+        let separator_key = match (last_flushed_key, entries.first()) {
+            (Some(left), Some(first)) => separator(&self.comparator, left, &first.key).to_vec(),
+            _ => vec![],
+        };
+        level.push((separator_key, page.id()));
+        Ok(entries
+            .last()
+            .map(|entry| entry.key.clone())
+            .unwrap_or_default())
+    }
+
+    /// Check the key size and move the value to overflow pages if the tuple would be too big.
+    /// Returns leaf tuple flags and the value to be stored in the tuple. The ids of overflow pages
+    /// are added to `overflow_page_ids`.
+    async fn prepare_entry<'v>(
+        &self,
+        key: &[u8],
+        value: &'v [u8],
+        overflow_page_ids: &mut Vec<PageId>,
+    ) -> Result<(u16, Cow<'v, [u8]>)> {
+        if key.len() > MAX_KEY_SIZE {
+            return Err(Error::KeyTooLarge(key.len()));
+        }
+        if leaf_tuple::size(key, value) > MAX_INLINE_TUPLE_SIZE {
+            let pointer = overflow::write(self.buffer_pool, value, overflow_page_ids).await?;
+            let mut bytes = vec![0; OverflowPointer::SIZE];
+            *unsafe { slice_to_struct_mut(&mut bytes) } = pointer;
+            Ok((leaf_tuple::FLAG_OVERFLOW, Cow::Owned(bytes)))
+        } else {
+            Ok((0, Cow::Borrowed(value)))
+        }
+    }
+
+    /// Returns the value stored under the given key, if any.
+    pub async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let mut page = self.get_root_page().await?;
+        while !page.metadata().is_leaf() {
+            let (_, downlink_pointer) = page.find_child(&self.comparator, key);
+            page = self.get_node_page(downlink_pointer).await?;
+        }
+        match page.binary_search(&self.comparator, key) {
+            SearchResult::Found(index) => Ok(Some(
+                self.read_value(page.get_tuple(index).expect("dead tuple"))
+                    .await?,
+            )),
+            SearchResult::NotFound(_) => Ok(None),
+        }
+    }
+
+    /// Removes the key from the tree. Returns whether it was there.
+    ///
+    /// Pages are never merged, so leaves may become empty. Overflow pages of the removed value are
+    /// returned to the buffer pool.
+    pub async fn delete(&self, key: &[u8]) -> Result<bool> {
+        let (_, mut page) = self.get_root_page_write().await?;
+        while !page.metadata().is_leaf() {
+            let (_, downlink_pointer) = page.find_child(&self.comparator, key);
+            page = self.get_node_page_write(downlink_pointer).await?;
+        }
+        let index = match page.binary_search(&self.comparator, key) {
+            SearchResult::Found(index) => index,
+            SearchResult::NotFound(_) => return Ok(false),
+        };
+        let pointer = leaf_tuple::overflow_pointer(page.get_tuple(index).expect("dead tuple"));
+        // The remaining keys still share the page prefix, so it can stay.
+        page.delete_tuple(index);
+        page.dirty();
+        drop(page);
+        if let Some(pointer) = pointer {
+            overflow::free(self.buffer_pool, pointer).await?;
+        }
+        Ok(true)
+    }
+
+    /// Returns all entries with keys between the given bounds, in key order.
+    pub async fn range(
+        &self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut result = vec![];
+        self.collect_range(self.get_root_page().await?, (start, end), &mut result)
+            .await?;
+        Ok(result)
+    }
+
+    #[async_recursion]
+    async fn collect_range(
+        &self,
+        page: NodePage<PinnedPageReadGuard<'a>>,
+        bounds: (Bound<&'async_recursion [u8]>, Bound<&'async_recursion [u8]>),
+        result: &mut Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<()> {
+        if page.metadata().is_leaf() {
+            let prefix = page.metadata().prefix();
+            for tuple in page.dump_tuples() {
+                let key = [prefix, leaf_tuple::get_key(&tuple)].concat();
+                if self.in_range(&key, bounds) {
+                    let value = self.read_value(&tuple).await?;
+                    result.push((key, value));
+                }
+            }
+            return Ok(());
+        }
+
+        let tuples = page.dump_tuples();
+        for (index, tuple) in tuples.iter().enumerate() {
+            // Keys in this subtree are in [pivot, next pivot).
+            let pivot = pivot_tuple::get_key(tuple);
+            let past_end = index > 0 && !self.before_end(pivot, bounds.1);
+            if past_end {
+                break;
+            }
+            let before_start = match (bounds.0, tuples.get(index + 1)) {
+                (Bound::Included(start), Some(next)) | (Bound::Excluded(start), Some(next)) => {
+                    self.comparator.compare(pivot_tuple::get_key(next), start) != Ordering::Greater
+                }
+                _ => false,
+            };
+            if before_start {
+                continue;
+            }
+            let child = self
+                .get_node_page(pivot_tuple::get_header(tuple).downlink_pointer)
+                .await?;
+            self.collect_range(child, bounds, result).await?;
+        }
+        Ok(())
+    }
+
+    fn in_range(&self, key: &[u8], bounds: (Bound<&[u8]>, Bound<&[u8]>)) -> bool {
+        let after_start = match bounds.0 {
+            Bound::Included(start) => self.comparator.compare(key, start) != Ordering::Less,
+            Bound::Excluded(start) => self.comparator.compare(key, start) == Ordering::Greater,
+            Bound::Unbounded => true,
+        };
+        after_start && self.before_end(key, bounds.1)
+    }
+
+    fn before_end(&self, key: &[u8], end: Bound<&[u8]>) -> bool {
+        match end {
+            Bound::Included(end) => self.comparator.compare(key, end) != Ordering::Greater,
+            Bound::Excluded(end) => self.comparator.compare(key, end) == Ordering::Less,
+            Bound::Unbounded => true,
+        }
+    }
+
+    /// Returns the value of a leaf tuple, reading it from overflow pages if needed.
+    async fn read_value(&self, tuple: &[u8]) -> Result<Vec<u8>> {
+        if leaf_tuple::is_overflow(tuple) {
+            overflow::read(self.buffer_pool, *leaf_tuple::get_overflow_pointer(tuple)).await
+        } else {
+            Ok(leaf_tuple::get_value(tuple).to_vec())
+        }
+    }
+
     async fn get_root_page(&self) -> Result<NodePage<PinnedPageReadGuard<'a>>> {
         let meta_page = self.buffer_pool.get_page(self.meta_page_id).await?;
-        let meta_page_data = meta_page.data().read().await;
-        let meta: &TreeMetadata = unsafe { slice_to_struct(&meta_page_data[0..]) };
-        Ok(self.get_node_page(meta.root_page_id).await?)
+        let root_page_id = MetaPage::from_existing(meta_page.data().read().await)
+            .metadata()
+            .root_page_id;
+        Ok(self.get_node_page(root_page_id).await?)
     }
 
     async fn get_node_page(&self, page_id: PageId) -> Result<NodePage<PinnedPageReadGuard<'a>>> {
@@ -214,6 +821,173 @@ impl<'a> BTree<'a> {
         Ok((meta_page, NodePage::from_existing(root_page_data)))
     }
 
+    /// Walk the whole tree and collect its shape statistics.
+    pub async fn stats(&self) -> Result<TreeStats> {
+        let root = self.get_root_page().await?;
+        let mut stats = TreeStats {
+            height: root.metadata().level as usize + 1,
+            ..TreeStats::default()
+        };
+        self.collect_stats(root, &mut stats).await?;
+        Ok(stats)
+    }
+
+    #[async_recursion]
+    async fn collect_stats(
+        &self,
+        page: NodePage<PinnedPageReadGuard<'a>>,
+        stats: &mut TreeStats,
+    ) -> Result<()> {
+        if page.metadata().is_leaf() {
+            stats.leaf_pages += 1;
+            stats.tuples += page.tuple_count();
+            stats.prefix_bytes_saved += page.tuple_count() * page.metadata().prefix().len();
+        } else {
+            stats.internal_pages += 1;
+            for tuple in page.dump_tuples() {
+                let child = self
+                    .get_node_page(pivot_tuple::get_header(&tuple).downlink_pointer)
+                    .await?;
+                self.collect_stats(child, stats).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Walk the whole tree and check its structural invariants (like `amcheck` in Postgres).
+    ///
+    /// Returns all violations found; an empty list means the tree is consistent. Subtrees of
+    /// pages which can't be interpreted (bad layout, malformed tuples, reached twice) are not
+    /// checked further.
+    pub async fn verify(&self) -> Result<Vec<Violation>> {
+        let root_page_id = MetaPage::from_existing(
+            self.buffer_pool
+                .get_page(self.meta_page_id)
+                .await?
+                .read()
+                .await,
+        )
+        .metadata()
+        .root_page_id;
+        let mut visited = HashSet::new();
+        visited.insert(self.meta_page_id);
+        let mut violations = vec![];
+        self.verify_node(
+            root_page_id,
+            None,
+            None,
+            None,
+            &mut visited,
+            &mut violations,
+        )
+        .await?;
+        Ok(violations)
+    }
+
+    /// Check the subtree rooted at `page_id`. All keys in it should be in `[low, high)` (`None`
+    /// meaning -inf and +inf, respectively), and the page should be at `expected_level`, if given.
+    #[async_recursion]
+    async fn verify_node(
+        &self,
+        page_id: PageId,
+        expected_level: Option<u8>,
+        low: Option<&'async_recursion [u8]>,
+        high: Option<&'async_recursion [u8]>,
+        visited: &mut HashSet<PageId>,
+        violations: &mut Vec<Violation>,
+    ) -> Result<()> {
+        if !visited.insert(page_id) {
+            violations.push(Violation::MultipleParents { page_id });
+            return Ok(());
+        }
+        let data = self.buffer_pool.get_page(page_id).await?.read().await;
+        if let Err(error) = page::check_layout::<NodeMetadata>(&data) {
+            violations.push(Violation::PageLayout { page_id, error });
+            return Ok(());
+        }
+        let page = NodePage::from_existing(data);
+        let level = page.metadata().level;
+        if let Some(expected) = expected_level {
+            if level != expected {
+                violations.push(Violation::LevelMismatch {
+                    page_id,
+                    expected,
+                    actual: level,
+                });
+            }
+        }
+        if page.metadata().prefix_size as usize > MAX_PREFIX_SIZE {
+            violations.push(Violation::InvalidPrefix { page_id });
+            return Ok(());
+        }
+
+        let mut keys: Vec<Vec<u8>> = vec![];
+        let mut children = vec![];
+        for index in 0..page.tuple_count() {
+            let tuple = match page.get_tuple(index) {
+                Some(tuple) if page.metadata().is_leaf() && leaf_tuple::is_well_formed(tuple) => {
+                    tuple
+                }
+                Some(tuple) if !page.metadata().is_leaf() && pivot_tuple::is_well_formed(tuple) => {
+                    tuple
+                }
+                _ => {
+                    violations.push(Violation::MalformedTuple { page_id, index });
+                    return Ok(());
+                }
+            };
+            if page.metadata().is_leaf() {
+                keys.push([page.metadata().prefix(), leaf_tuple::get_key(tuple)].concat());
+            } else {
+                keys.push(pivot_tuple::get_key(tuple).to_vec());
+                children.push(pivot_tuple::get_header(tuple).downlink_pointer);
+            }
+        }
+
+        // The first pivot of an internal page is -inf; its subtree starts at `low`.
+        let first_key = if page.metadata().is_leaf() {
+            0
+        } else {
+            if !matches!(keys.first(), Some(key) if key.is_empty()) {
+                violations.push(Violation::FirstPivotNotEmpty { page_id });
+            }
+            1
+        };
+        for index in first_key..keys.len() {
+            let key = &keys[index][..];
+            if index > first_key && self.comparator.compare(&keys[index - 1], key) != Ordering::Less
+            {
+                violations.push(Violation::KeyOrder { page_id, index });
+            }
+            if matches!(low, Some(low) if self.comparator.compare(key, low) == Ordering::Less)
+                || matches!(high, Some(high) if self.comparator.compare(key, high) != Ordering::Less)
+            {
+                violations.push(Violation::KeyOutOfBounds { page_id, index });
+            }
+        }
+
+        if !page.metadata().is_leaf() {
+            for (index, &child) in children.iter().enumerate() {
+                let child_low = if index == 0 {
+                    low
+                } else {
+                    Some(&keys[index][..])
+                };
+                let child_high = keys.get(index + 1).map(|key| &key[..]).or(high);
+                self.verify_node(
+                    child,
+                    Some(level - 1),
+                    child_low,
+                    child_high,
+                    visited,
+                    violations,
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
     pub async fn dump_tree(&self) -> Result<NodeDump> {
         self.dump_node(self.get_root_page().await?).await
     }
@@ -221,17 +995,15 @@ impl<'a> BTree<'a> {
     #[async_recursion]
     async fn dump_node(&self, page: NodePage<PinnedPageReadGuard<'a>>) -> Result<NodeDump> {
         if page.metadata().is_leaf() {
-            Ok(NodeDump::Leaf(
-                page.dump_tuples()
-                    .iter()
-                    .map(|tuple| {
-                        (
-                            leaf_tuple::get_key(tuple).to_vec(),
-                            leaf_tuple::get_value(tuple).to_vec(),
-                        )
-                    })
-                    .collect(),
-            ))
+            let prefix = page.metadata().prefix();
+            let mut result = vec![];
+            for tuple in page.dump_tuples() {
+                result.push((
+                    [prefix, leaf_tuple::get_key(&tuple)].concat(),
+                    self.read_value(&tuple).await?,
+                ));
+            }
+            Ok(NodeDump::Leaf(result))
         } else {
             let mut result = vec![];
             for tuple in page.dump_tuples() {
@@ -248,12 +1020,40 @@ impl<'a> BTree<'a> {
     }
 }
 
-enum Parent<T> {
-    MetaPage(MetaPage<T>),
-    InternalPage {
-        parent_page: NodePage<T>,
-        index: usize,
+/// Shape of the tree, for measuring the effect of storage optimizations.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TreeStats {
+    pub height: usize,
+    pub leaf_pages: usize,
+    pub internal_pages: usize,
+    pub tuples: usize,
+    /// Key bytes not stored in leaf tuples thanks to prefix compression.
+    pub prefix_bytes_saved: usize,
+}
+
+/// A broken invariant found by `BTree::verify`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// Page header or tuple descriptors are inconsistent.
+    PageLayout { page_id: PageId, error: LayoutError },
+    /// Prefix stored in the metadata of a leaf page is too long.
+    InvalidPrefix { page_id: PageId },
+    /// Tuple is dead, or too short for its header or the key size it declares.
+    MalformedTuple { page_id: PageId, index: usize },
+    /// The key is not greater than the previous one on the page.
+    KeyOrder { page_id: PageId, index: usize },
+    /// The key is outside the range given by the pivots pointing to the page.
+    KeyOutOfBounds { page_id: PageId, index: usize },
+    /// The first pivot of an internal page is not the empty (-inf) key.
+    FirstPivotNotEmpty { page_id: PageId },
+    /// The page level is not one less than the level of its parent.
+    LevelMismatch {
+        page_id: PageId,
+        expected: u8,
+        actual: u8,
     },
+    /// The page is reachable more than once, through shared children or a cycle.
+    MultipleParents { page_id: PageId },
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -311,14 +1111,22 @@ impl<T: Deref<Target = PageData>> NodePage<T> {
         self.page.metadata()
     }
 
-    fn binary_search(&self, key: &[u8]) -> SearchResult {
-        let mut start = 0;
+    fn binary_search<C: KeyComparator>(&self, comparator: &C, key: &[u8]) -> SearchResult {
+        let prefix = self.metadata().prefix();
+
+        // The first pivot of an internal page is -inf, regardless of the comparator.
+        let mut start = if self.metadata().is_leaf() { 0 } else { 1 };
         let mut end = self.page.tuple_count();
 
         while start < end {
             let mid = (start + end) / 2;
             let tuple_key = self.get_tuple_key(mid);
-            match tuple_key.cmp(key) {
+            let ordering = if prefix.is_empty() {
+                comparator.compare(tuple_key, key)
+            } else {
+                comparator.compare_prefixed(prefix, tuple_key, key)
+            };
+            match ordering {
                 Ordering::Greater => {
                     end = mid;
                 }
@@ -334,6 +1142,24 @@ impl<T: Deref<Target = PageData>> NodePage<T> {
         SearchResult::NotFound(start)
     }
 
+    /// In an internal page, find the pivot whose subtree may contain the given key. Returns the
+    /// pivot index and its downlink.
+    fn find_child<C: KeyComparator>(&self, comparator: &C, key: &[u8]) -> (usize, PageId) {
+        let pivot_index = match self.binary_search(comparator, key) {
+            SearchResult::Found(index) => index,
+            SearchResult::NotFound(index) => index - 1,
+        };
+        let tuple = self.page.get_tuple(pivot_index).expect("found null tuple");
+        (pivot_index, pivot_tuple::get_header(tuple).downlink_pointer)
+    }
+
+    /// Whether an internal page can take one more pivot with a key of up to `max_key_size` bytes,
+    /// so that it won't be split by an insert below it.
+    fn has_room_for_pivot(&self, max_key_size: usize) -> bool {
+        self.page.free_space_after_compaction()
+            >= pivot_tuple::size(&[0; MAX_KEY_SIZE][..max_key_size]) + page::TUPLE_DESCRIPTOR_SIZE
+    }
+
     fn get_tuple_key(&self, index: usize) -> &[u8] {
         let tuple = self.page.get_tuple(index).expect("found null tuple");
         if self.metadata().is_leaf() {
@@ -343,43 +1169,236 @@ impl<T: Deref<Target = PageData>> NodePage<T> {
         }
     }
 
-    /// Compute tuple index at which to split the page. Tuples >= this index will go to the new
-    /// page. The tuple bytes will be divided evenly (as much as possible) between the pages.
-    ///
-    /// During calculation a new tuple to be inserted at `insert_index` is taken into account.
-    /// The returned index is shifted after `insert_index`.
-    ///
-    /// TODO: in some cases this will result in the new tuple not fitting on the page. Handle these
-    /// cases.
-    pub fn get_split_index(&self, insert_index: usize, tuple_size: usize) -> usize {
-        let total_size = self.page.total_tuple_size() + tuple_size;
-        let split_at_byte = total_size / 2;
-        let mut bytes_so_far = 0;
-        // After insert there will be one more tuple
-        let num_tuples = self.page.tuple_count() + 1;
-        for target_index in 0..num_tuples {
-            if target_index == insert_index {
-                // this is the new tuple
-                bytes_so_far += tuple_size;
-            } else {
-                // If we're after the inserted tuple, be sure to read tuple size from the original
-                // offset, not the one after insert
-                let source_index = if target_index > insert_index {
-                    target_index - 1
-                } else {
-                    target_index
-                };
-                bytes_so_far += self
-                    .page
-                    .get_tuple(source_index)
-                    .expect("expected no dead tuples")
-                    .len();
+    /// Returns all entries of a leaf page, borrowed from its (prefix-compressed) tuples.
+    fn leaf_entry_refs(&self) -> Vec<LeafEntryRef<'_>> {
+        let prefix = self.metadata().prefix();
+        (0..self.page.tuple_count())
+            .map(|index| {
+                let tuple = self.page.get_tuple(index).expect("dead tuple");
+                LeafEntryRef {
+                    prefix,
+                    suffix: leaf_tuple::get_key(tuple),
+                    flags: leaf_tuple::get_header(tuple).flags,
+                    value: leaf_tuple::get_value(tuple),
+                }
+            })
+            .collect()
+    }
+}
+
+/// An entry to be written to a leaf, with the full key. Used by bulk loading.
+/// If `flags` contains `FLAG_OVERFLOW`, `value` is an `OverflowPointer`.
+struct LeafEntry {
+    key: Vec<u8>,
+    flags: u16,
+    value: Vec<u8>,
+}
+
+impl LeafEntry {
+    fn as_ref(&self) -> LeafEntryRef<'_> {
+        LeafEntryRef {
+            prefix: &[],
+            suffix: &self.key,
+            flags: self.flags,
+            value: &self.value,
+        }
+    }
+}
+
+/// A leaf entry borrowed from a page tuple (or from the caller). The key is `prefix + suffix`,
+/// and is kept in two parts, so that entries of a page don't have to be decompressed.
+#[derive(Clone, Copy)]
+struct LeafEntryRef<'e> {
+    prefix: &'e [u8],
+    suffix: &'e [u8],
+    flags: u16,
+    value: &'e [u8],
+}
+
+impl<'e> LeafEntryRef<'e> {
+    fn key_size(&self) -> usize {
+        self.prefix.len() + self.suffix.len()
+    }
+
+    fn key_bytes(&self) -> impl Iterator<Item = &'e u8> {
+        self.prefix.iter().chain(self.suffix)
+    }
+
+    /// The full key. Allocates, so it's only used for separator keys on split.
+    fn key(&self) -> Vec<u8> {
+        [self.prefix, self.suffix].concat()
+    }
+
+    /// The part of the key after the first `prefix_size` bytes, in two parts.
+    fn key_after(&self, prefix_size: usize) -> (&'e [u8], &'e [u8]) {
+        if prefix_size <= self.prefix.len() {
+            (&self.prefix[prefix_size..], self.suffix)
+        } else {
+            (&self.suffix[prefix_size - self.prefix.len()..], &[])
+        }
+    }
+
+    /// Length of the common prefix of the keys of both entries, up to `MAX_PREFIX_SIZE`.
+    fn common_prefix_size(&self, other: &LeafEntryRef) -> usize {
+        self.key_bytes()
+            .zip(other.key_bytes())
+            .take(MAX_PREFIX_SIZE)
+            .take_while(|(x, y)| x == y)
+            .count()
+    }
+
+    /// Size of the leaf tuple with the first `prefix_size` bytes of the key stripped.
+    fn tuple_size(&self, prefix_size: usize) -> usize {
+        leaf_tuple::Header::SIZE + self.key_size() - prefix_size + self.value.len()
+    }
+
+    /// Write the entry as a leaf tuple, stripping the first `prefix_size` bytes of the key.
+    fn write(&self, tuple: &mut [u8], prefix_size: usize) {
+        let (key_start, key_end) = self.key_after(prefix_size);
+        leaf_tuple::write_parts(tuple, key_start, key_end, self.flags, self.value);
+    }
+}
+
+/// Length of the longest common prefix of two byte strings.
+fn common_prefix_size(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// The prefix a leaf page with the given entries will strip from its keys.
+///
+/// In bytewise order it's the common prefix of the first and the last key, but with a custom
+/// comparator keys sharing a prefix don't have to be adjacent, so all keys are checked.
+fn leaf_prefix(entries: &[LeafEntryRef]) -> Vec<u8> {
+    match entries.first() {
+        Some(first) => {
+            let size = entries
+                .iter()
+                .map(|entry| first.common_prefix_size(entry))
+                .min()
+                .unwrap();
+            first.key_bytes().take(size).copied().collect()
+        }
+        None => vec![],
+    }
+}
+
+/// Space the entries take when written to a leaf page, including tuple descriptors.
+fn leaf_size(entries: &[LeafEntryRef]) -> usize {
+    let prefix_size = leaf_prefix(entries).len();
+    entries
+        .iter()
+        .map(|entry| entry.tuple_size(prefix_size) + page::TUPLE_DESCRIPTOR_SIZE)
+        .sum()
+}
+
+/// Compute the index at which to split the entries of an overflowing leaf page. Entries >= this
+/// index will go to the new page. The bytes will be divided evenly (as much as possible) between
+/// the pages, but both pages have to be non-empty, and splits after which both halves fit are
+/// preferred.
+///
+/// Sizes are computed after prefix compression of each half.
+fn get_split_index(entries: &[LeafEntryRef]) -> usize {
+    assert!(entries.len() >= 2, "cannot split less than two entries");
+    // Sizes with full keys; prefix_sums[i] is the total size of entries[..i].
+    let mut prefix_sums = vec![0];
+    for entry in entries {
+        let size = entry.tuple_size(0) + page::TUPLE_DESCRIPTOR_SIZE;
+        prefix_sums.push(prefix_sums.last().unwrap() + size);
+    }
+    // Prefix sizes of entries[..i] and entries[i..], computed incrementally like in `leaf_prefix`.
+    let first = &entries[0];
+    let mut left_prefix_sizes = vec![MAX_PREFIX_SIZE];
+    for entry in entries {
+        let size = first.common_prefix_size(entry);
+        left_prefix_sizes.push(size.min(*left_prefix_sizes.last().unwrap()));
+    }
+    let last = &entries[entries.len() - 1];
+    let mut right_prefix_sizes = vec![MAX_PREFIX_SIZE; entries.len() + 1];
+    for (index, entry) in entries.iter().enumerate().rev() {
+        let size = last.common_prefix_size(entry);
+        right_prefix_sizes[index] = size.min(right_prefix_sizes[index + 1]);
+    }
+    let compressed_size = |range: std::ops::Range<usize>, prefix_size: usize| {
+        prefix_sums[range.end] - prefix_sums[range.start] - prefix_size * range.len()
+    };
+    let capacity = page::capacity::<NodeMetadata>();
+
+    // (fits, imbalance, index) of the best split so far
+    let mut best: Option<(bool, usize, usize)> = None;
+    for split_index in 1..entries.len() {
+        let left_size = compressed_size(0..split_index, left_prefix_sizes[split_index]);
+        let right_size =
+            compressed_size(split_index..entries.len(), right_prefix_sizes[split_index]);
+        let fits = left_size <= capacity && right_size <= capacity;
+        let imbalance = if left_size > right_size {
+            left_size - right_size
+        } else {
+            right_size - left_size
+        };
+        let better = match best {
+            None => true,
+            Some((best_fits, best_imbalance, _)) => {
+                (fits && !best_fits) || (fits == best_fits && imbalance < best_imbalance)
             }
-            if bytes_so_far > split_at_byte {
-                return target_index;
+        };
+        if better {
+            best = Some((fits, imbalance, split_index));
+        }
+    }
+    best.unwrap().2
+}
+
+/// Compute the index of the pivot at which to split an overflowing internal page. Pivots before
+/// it stay on the page, and the pivot itself becomes the -inf pivot of the new page (its key goes
+/// to the parent). Like in `get_split_index`, splits after which both pages fit are preferred,
+/// then the most even ones.
+fn get_internal_split_index(pivots: &[(&[u8], PageId)]) -> usize {
+    assert!(pivots.len() >= 3, "cannot split less than three pivots");
+    let size = |key: &[u8]| pivot_tuple::size(key) + page::TUPLE_DESCRIPTOR_SIZE;
+    let total: usize = pivots.iter().map(|(key, _)| size(key)).sum();
+    let capacity = page::capacity::<NodeMetadata>();
+
+    // (fits, imbalance, index) of the best split so far
+    let mut best: Option<(bool, usize, usize)> = None;
+    let mut left_size = size(pivots[0].0);
+    for (split_index, (split_key, _)) in pivots.iter().enumerate().skip(1) {
+        let split_size = size(split_key);
+        let right_size = total - left_size - split_size + size(&[]);
+        let fits = left_size <= capacity && right_size <= capacity;
+        let imbalance = if left_size > right_size {
+            left_size - right_size
+        } else {
+            right_size - left_size
+        };
+        let better = match best {
+            None => true,
+            Some((best_fits, best_imbalance, _)) => {
+                (fits && !best_fits) || (fits == best_fits && imbalance < best_imbalance)
             }
+        };
+        if better {
+            best = Some((fits, imbalance, split_index));
         }
-        panic!("get_split_index didn't reach split_at_byte");
+        left_size += split_size;
+    }
+    best.unwrap().2
+}
+
+/// Shortest key which separates the last key of the left page from the first key of the right
+/// one (suffix truncation). It's > `left` and <= `right`, so it's also <= all keys on the right
+/// page.
+///
+/// In bytewise order this is the shortest prefix of `right` which differs from `left`. Custom
+/// comparators don't have to agree, so then we fall back to the whole `right` key.
+fn separator<'k, C: KeyComparator>(comparator: &C, left: &[u8], right: &'k [u8]) -> &'k [u8] {
+    debug_assert_eq!(comparator.compare(left, right), Ordering::Less);
+    let candidate = &right[..(common_prefix_size(left, right) + 1).min(right.len())];
+    if comparator.compare(left, candidate) == Ordering::Less
+        && comparator.compare(candidate, right) != Ordering::Greater
+    {
+        candidate
+    } else {
+        right
     }
 }
 
@@ -388,40 +1407,300 @@ mod get_split_index_tests {
     use super::*;
     use buffer_pool::disk_manager::PAGE_SIZE;
 
-    fn make_page(tuples: &[usize]) -> NodePage<Box<PageData>> {
-        let data = Box::new([0; PAGE_SIZE]);
-        let mut page = NodePage::new_leaf(data);
-        for (index, tuple_size) in tuples.iter().enumerate() {
-            page.alloc_tuple_at(index, *tuple_size).unwrap();
-        }
-        page
+    fn split_index(keys: &[&[u8]], value_sizes: &[usize]) -> usize {
+        let values: Vec<Vec<u8>> = value_sizes.iter().map(|size| vec![0; *size]).collect();
+        let entries: Vec<LeafEntryRef> = keys
+            .iter()
+            .zip(&values)
+            .map(|(key, value)| LeafEntryRef {
+                prefix: &[],
+                suffix: key,
+                flags: 0,
+                value,
+            })
+            .collect();
+        get_split_index(&entries)
     }
 
     #[test]
-    fn split_before_insert() {
-        assert_eq!(make_page(&[1]).get_split_index(1, 1), 1);
+    fn split_two() {
+        assert_eq!(split_index(&[b"a", b"b"], &[1, 1]), 1);
     }
+
     #[test]
-    fn split_after_insert() {
-        assert_eq!(make_page(&[1]).get_split_index(0, 1), 1);
+    fn split_evenly() {
+        assert_eq!(
+            split_index(&[b"a", b"b", b"c", b"d", b"e"], &[10, 10, 10, 10, 10]),
+            2
+        );
+        assert_eq!(split_index(&[b"a", b"b", b"c", b"d"], &[30, 1, 1, 1]), 1);
     }
 
     // In both cases below we can't fit both tuples on a single page.
     #[test]
     fn split_large_tuple_1() {
         assert_eq!(
-            make_page(&[PAGE_SIZE / 2]).get_split_index(0, PAGE_SIZE / 2 - 100),
+            split_index(&[b"a", b"b"], &[PAGE_SIZE / 2, PAGE_SIZE / 2 - 100]),
             1
         );
     }
     #[test]
-    #[ignore = "case not handled yet"]
     fn split_large_tuple_2() {
         assert_eq!(
-            make_page(&[PAGE_SIZE / 2 - 100]).get_split_index(0, PAGE_SIZE / 2),
+            split_index(
+                &[b"a", b"b", b"c"],
+                &[PAGE_SIZE / 2 - 100, PAGE_SIZE / 2, 100]
+            ),
             1
         );
     }
+
+    #[test]
+    fn split_with_prefixes() {
+        // The right half compresses much better, so it can take more entries.
+        let keys: Vec<Vec<u8>> = vec![
+            b"a".to_vec(),
+            b"b".to_vec(),
+            [&[b'c'; 60][..], b"1"].concat(),
+            [&[b'c'; 60][..], b"2"].concat(),
+            [&[b'c'; 60][..], b"3"].concat(),
+        ];
+        let key_refs: Vec<&[u8]> = keys.iter().map(|key| &key[..]).collect();
+        assert_eq!(split_index(&key_refs, &[70, 70, 10, 10, 10]), 2);
+    }
+
+    #[test]
+    fn test_separator() {
+        assert_eq!(separator(&Bytewise, b"abc", b"abd"), b"abd");
+        assert_eq!(separator(&Bytewise, b"abc", b"b"), b"b");
+        assert_eq!(separator(&Bytewise, b"ab", b"abcd"), b"abc");
+        assert_eq!(separator(&Bytewise, b"abcxx", b"abd"), b"abd");
+    }
+}
+
+#[cfg(test)]
+mod max_key_size_tests {
+    use super::*;
+    use buffer_pool::disk_manager_mem::DiskManagerMem;
+
+    async fn max_key_size(btree: &BTree<'_>) -> Result<u32> {
+        let meta_page = btree.buffer_pool.get_page(btree.meta_page_id).await?;
+        let meta = MetaPage::from_existing(meta_page.read().await);
+        Ok(meta.metadata().max_key_size)
+    }
+
+    #[tokio::test]
+    async fn tracked_on_insert_and_bulk_load() -> Result<()> {
+        let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+        let btree = BTree::new(&buffer_pool).await?;
+        assert_eq!(max_key_size(&btree).await?, 0);
+        btree.insert(&[1; 300], b"v").await?;
+        btree.insert(&[2; 10], b"v").await?;
+        assert_eq!(max_key_size(&btree).await?, 300);
+
+        let entries = vec![
+            (vec![1; 20], b"v"),
+            (vec![2; 200], b"v"),
+            (vec![3; 5], b"v"),
+        ];
+        let btree = BTree::bulk_load(&buffer_pool, 90, entries).await?;
+        assert_eq!(max_key_size(&btree).await?, 200);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn room_for_pivot_depends_on_key_size() -> Result<()> {
+        let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+        let mut page = NodePage::new_internal(
+            buffer_pool.allocate_page().await?.write().await,
+            1,
+            PageId(100),
+        );
+        while page.has_room_for_pivot(MAX_KEY_SIZE) {
+            let index = page.tuple_count();
+            let key = [index as u8; 100];
+            let tuple = page.alloc_tuple_at(index, pivot_tuple::size(&key)).unwrap();
+            pivot_tuple::write(tuple, PageId(101), &key);
+        }
+        assert!(page.has_room_for_pivot(100));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod verify_tests {
+    use super::*;
+    use buffer_pool::disk_manager_mem::DiskManagerMem;
+
+    fn entry(key: &[u8]) -> LeafEntry {
+        LeafEntry {
+            key: key.to_vec(),
+            flags: 0,
+            value: vec![],
+        }
+    }
+
+    /// A tree of a root and two leaves, with pivot "m". Returns the tree and the leaf ids.
+    async fn two_level_tree(buffer_pool: &BufferPool) -> Result<(BTree<'_>, PageId, PageId)> {
+        let btree = BTree::new(buffer_pool).await?;
+        let root_page_id = btree.get_root_page().await?.id();
+        let left = NodePage::new_leaf(buffer_pool.allocate_page().await?.write().await);
+        let right = NodePage::new_leaf(buffer_pool.allocate_page().await?.write().await);
+        let (left_id, right_id) = (left.id(), right.id());
+        drop((left, right));
+
+        let mut root = NodePage::new_internal(
+            buffer_pool.get_page(root_page_id).await?.write().await,
+            1,
+            left_id,
+        );
+        let tuple = root.alloc_tuple_at(1, pivot_tuple::size(b"m")).unwrap();
+        pivot_tuple::write(tuple, right_id, b"m");
+        Ok((btree, left_id, right_id))
+    }
+
+    async fn write_leaf(buffer_pool: &BufferPool, page_id: PageId, keys: &[&[u8]]) -> Result<()> {
+        let entries: Vec<LeafEntry> = keys.iter().map(|key| entry(key)).collect();
+        let entries: Vec<LeafEntryRef> = entries.iter().map(LeafEntry::as_ref).collect();
+        NodePage::from_existing(buffer_pool.get_page(page_id).await?.write().await)
+            .write_leaf(&entries);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn valid_tree() -> Result<()> {
+        let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+        let (btree, left, right) = two_level_tree(&buffer_pool).await?;
+        write_leaf(&buffer_pool, left, &[b"a", b"b"]).await?;
+        write_leaf(&buffer_pool, right, &[b"m", b"z"]).await?;
+        assert_eq!(btree.verify().await?, vec![]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn key_order() -> Result<()> {
+        let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+        let (btree, left, _) = two_level_tree(&buffer_pool).await?;
+        write_leaf(&buffer_pool, left, &[b"b", b"a", b"a"]).await?;
+        assert_eq!(
+            btree.verify().await?,
+            vec![
+                Violation::KeyOrder {
+                    page_id: left,
+                    index: 1
+                },
+                Violation::KeyOrder {
+                    page_id: left,
+                    index: 2
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn key_out_of_bounds() -> Result<()> {
+        let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+        let (btree, left, right) = two_level_tree(&buffer_pool).await?;
+        write_leaf(&buffer_pool, left, &[b"a", b"m"]).await?;
+        write_leaf(&buffer_pool, right, &[b"l", b"z"]).await?;
+        assert_eq!(
+            btree.verify().await?,
+            vec![
+                Violation::KeyOutOfBounds {
+                    page_id: left,
+                    index: 1
+                },
+                Violation::KeyOutOfBounds {
+                    page_id: right,
+                    index: 0
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn level_mismatch() -> Result<()> {
+        let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+        let (btree, left, _) = two_level_tree(&buffer_pool).await?;
+        let other_leaf = NodePage::new_leaf(buffer_pool.allocate_page().await?.write().await).id();
+        NodePage::new_internal(
+            buffer_pool.get_page(left).await?.write().await,
+            2,
+            other_leaf,
+        );
+        assert_eq!(
+            btree.verify().await?,
+            vec![
+                Violation::LevelMismatch {
+                    page_id: left,
+                    expected: 0,
+                    actual: 2,
+                },
+                Violation::LevelMismatch {
+                    page_id: other_leaf,
+                    expected: 1,
+                    actual: 0,
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn shared_child() -> Result<()> {
+        let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+        let (btree, left, _) = two_level_tree(&buffer_pool).await?;
+        let root_page_id = btree.get_root_page().await?.id();
+        let mut root = NodePage::new_internal(
+            buffer_pool.get_page(root_page_id).await?.write().await,
+            1,
+            left,
+        );
+        let tuple = root.alloc_tuple_at(1, pivot_tuple::size(b"m")).unwrap();
+        pivot_tuple::write(tuple, left, b"m");
+        drop(root);
+        assert_eq!(
+            btree.verify().await?,
+            vec![Violation::MultipleParents { page_id: left }]
+        );
+
+        // a cycle
+        let mut root = NodePage::new_internal(
+            buffer_pool.get_page(root_page_id).await?.write().await,
+            1,
+            left,
+        );
+        let tuple = root.alloc_tuple_at(1, pivot_tuple::size(b"m")).unwrap();
+        pivot_tuple::write(tuple, root_page_id, b"m");
+        drop(root);
+        assert_eq!(
+            btree.verify().await?,
+            vec![Violation::MultipleParents {
+                page_id: root_page_id
+            }]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn bad_free_space_pointer() -> Result<()> {
+        let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+        let (btree, left, _) = two_level_tree(&buffer_pool).await?;
+        write_leaf(&buffer_pool, left, &[b"a", b"b"]).await?;
+        let mut page = NodePage::from_existing(buffer_pool.get_page(left).await?.write().await);
+        unsafe { page.header_mut() }.free_space_pointer = 4;
+        drop(page);
+        assert_eq!(
+            btree.verify().await?,
+            vec![Violation::PageLayout {
+                page_id: left,
+                error: LayoutError::FreeSpacePointerOutOfRange(4),
+            }]
+        );
+        Ok(())
+    }
 }
 
 enum SearchResult {
@@ -432,18 +1711,148 @@ enum SearchResult {
 impl<T: DerefMut<Target = PageData>> NodePage<T> {
     fn new_leaf(data: T) -> Self {
         Self {
-            page: TupleBlockPage::new(data, &NodeMetadata { level: 0 }),
+            page: TupleBlockPage::new(data, &NodeMetadata::new(0)),
         }
     }
 
     fn new_internal(data: T, level: u8, first_child: PageId) -> Self {
-        let mut page = TupleBlockPage::new(data, &NodeMetadata { level });
+        let mut page = TupleBlockPage::new(data, &NodeMetadata::new(level));
         let tuple = page
             .alloc_tuple_at(0, pivot_tuple::size(&[]))
             .expect("no space for -inf tuple");
         pivot_tuple::write(tuple, first_child, &[]);
         Self { page }
     }
+
+    /// Replace the contents of a leaf page with the given entries, stripping their common prefix.
+    /// Panics if they don't fit.
+    fn write_leaf(&mut self, entries: &[LeafEntryRef]) {
+        let prefix = leaf_prefix(entries);
+        self.page.clear();
+        self.page.metadata_mut().set_prefix(&prefix);
+        for (index, entry) in entries.iter().enumerate() {
+            let tuple = self
+                .page
+                .alloc_tuple_at(index, entry.tuple_size(prefix.len()))
+                .expect("leaf entries don't fit on a page");
+            entry.write(tuple, prefix.len());
+        }
+    }
+
+    /// Rewrite a leaf page in place, so that it holds only the first `keep` entries of its
+    /// tuples with the new entry (`key`) inserted at `index`, or replacing the tuple there if
+    /// `found`. The tuples are converted to the new `prefix`, which must be common to all kept
+    /// keys. Panics if they don't fit.
+    fn rewrite_leaf_front(
+        &mut self,
+        keep: usize,
+        prefix: &[u8],
+        (index, found): (usize, bool),
+        key: &[u8],
+        flags: u16,
+        value: &[u8],
+    ) {
+        // Drop the tuples which were moved to the sibling, and the replaced one.
+        let kept_tuples = if !found && index < keep {
+            keep - 1
+        } else {
+            keep
+        };
+        for tuple_index in (kept_tuples..self.page.tuple_count()).rev() {
+            self.page.delete_tuple(tuple_index);
+        }
+        if found && index < keep {
+            self.page.delete_tuple(index);
+        }
+
+        let old_prefix = self.metadata().prefix().to_vec();
+        if old_prefix != prefix {
+            let mut buffer = vec![];
+            for tuple_index in 0..self.page.tuple_count() {
+                let tuple = self.page.get_tuple(tuple_index).expect("dead tuple");
+                let entry = LeafEntryRef {
+                    prefix: &old_prefix,
+                    suffix: leaf_tuple::get_key(tuple),
+                    flags: leaf_tuple::get_header(tuple).flags,
+                    value: leaf_tuple::get_value(tuple),
+                };
+                buffer.resize(entry.tuple_size(prefix.len()), 0);
+                entry.write(&mut buffer, prefix.len());
+                // Compacts the page if the tuple grows, which the final contents fit after.
+                self.page
+                    .update_tuple(tuple_index, &buffer)
+                    .expect("leaf entries don't fit on a page");
+            }
+            self.page.metadata_mut().set_prefix(prefix);
+        }
+
+        if index < keep {
+            let suffix = &key[prefix.len()..];
+            let tuple = self
+                .page
+                .alloc_tuple_at(index, leaf_tuple::size(suffix, value))
+                .expect("leaf entries don't fit on a page");
+            leaf_tuple::write(tuple, suffix, flags, value);
+        }
+    }
+}
+
+impl<T: DerefMut<Target = PageData>> NodePage<T> {
+    /// Split a full internal page while inserting the pivot (`key`, `downlink`) at `index`. The
+    /// page keeps the pivots before the split point, and the rest go to a new internal page
+    /// created in `sibling_data`. The pivot at the split point becomes the -inf pivot of the new
+    /// page, and its key is returned, to be inserted into the parent.
+    fn split_internal<S: DerefMut<Target = PageData>>(
+        &mut self,
+        index: usize,
+        key: &[u8],
+        downlink: PageId,
+        sibling_data: S,
+    ) -> (NodePage<S>, Vec<u8>) {
+        let level = self.metadata().level;
+        let (split_index, new_sibling, split_key) = {
+            let mut pivots: Vec<(&[u8], PageId)> = (0..self.page.tuple_count())
+                .map(|index| {
+                    let tuple = self.page.get_tuple(index).expect("dead tuple");
+                    (
+                        pivot_tuple::get_key(tuple),
+                        pivot_tuple::get_header(tuple).downlink_pointer,
+                    )
+                })
+                .collect();
+            pivots.insert(index, (key, downlink));
+            let split_index = get_internal_split_index(&pivots);
+
+            let (split_key, first_child) = pivots[split_index];
+            let mut new_sibling = NodePage::new_internal(sibling_data, level, first_child);
+            for (key, downlink) in &pivots[split_index + 1..] {
+                let index = new_sibling.tuple_count();
+                let tuple = new_sibling
+                    .alloc_tuple_at(index, pivot_tuple::size(key))
+                    .expect("pivots don't fit on a page");
+                pivot_tuple::write(tuple, *downlink, key);
+            }
+            (split_index, new_sibling, split_key.to_vec())
+        };
+
+        // The page keeps the pivots before the split point, which may include the new one.
+        let kept_tuples = if index < split_index {
+            split_index - 1
+        } else {
+            split_index
+        };
+        for tuple_index in (kept_tuples..self.page.tuple_count()).rev() {
+            self.page.delete_tuple(tuple_index);
+        }
+        if index < split_index {
+            let tuple = self
+                .page
+                .alloc_tuple_at(index, pivot_tuple::size(key))
+                .expect("pivots don't fit on a page");
+            pivot_tuple::write(tuple, downlink, key);
+        }
+        (new_sibling, split_key)
+    }
 }
 
 /// Interpret the slice bytes as a data structure.
@@ -464,24 +1873,43 @@ mod leaf_tuple {
     #[derive(Debug, Clone, Copy)]
     pub struct Header {
         pub key_size: u16,
+        pub flags: u16,
     }
 
     impl Header {
         pub const SIZE: usize = mem::size_of::<Header>();
     }
 
+    /// The value part of the tuple is an `OverflowPointer`, not the actual value.
+    pub const FLAG_OVERFLOW: u16 = 1;
+
     pub fn size(key: &[u8], value: &[u8]) -> usize {
         Header::SIZE + key.len() + value.len()
     }
 
     /// Write a leaf tuple into the provided slice.
     /// The slice must have size at least that returned by `size()`.
-    pub fn write(tuple: &mut [u8], key: &[u8], value: &[u8]) {
+    pub fn write(tuple: &mut [u8], key: &[u8], flags: u16, value: &[u8]) {
+        write_parts(tuple, key, &[], flags, value);
+    }
+
+    /// Like `write`, with the key given as `key_start + key_end`.
+    pub fn write_parts(
+        tuple: &mut [u8],
+        key_start: &[u8],
+        key_end: &[u8],
+        flags: u16,
+        value: &[u8],
+    ) {
+        let key_size = key_start.len() + key_end.len();
         *unsafe { slice_to_struct_mut(tuple) } = Header {
-            key_size: key.len() as u16,
+            key_size: key_size as u16,
+            flags,
         };
-        tuple[Header::SIZE..Header::SIZE + key.len()].copy_from_slice(key);
-        tuple[Header::SIZE + key.len()..].copy_from_slice(value);
+        let (key, rest) = tuple[Header::SIZE..].split_at_mut(key_size);
+        key[..key_start.len()].copy_from_slice(key_start);
+        key[key_start.len()..].copy_from_slice(key_end);
+        rest.copy_from_slice(value);
     }
 
     pub fn get_header(tuple: &[u8]) -> &Header {
@@ -497,6 +1925,31 @@ mod leaf_tuple {
         let key_len = get_header(tuple).key_size as usize;
         &tuple[Header::SIZE + key_len..]
     }
+
+    pub fn is_overflow(tuple: &[u8]) -> bool {
+        get_header(tuple).flags & FLAG_OVERFLOW != 0
+    }
+
+    /// Whether the tuple is long enough for its header, key and (if any) overflow pointer.
+    pub fn is_well_formed(tuple: &[u8]) -> bool {
+        tuple.len() >= Header::SIZE
+            && tuple.len() >= Header::SIZE + get_header(tuple).key_size as usize
+            && (!is_overflow(tuple) || get_value(tuple).len() == OverflowPointer::SIZE)
+    }
+
+    pub fn get_overflow_pointer(tuple: &[u8]) -> &OverflowPointer {
+        assert!(is_overflow(tuple));
+        unsafe { slice_to_struct(get_value(tuple)) }
+    }
+
+    /// The overflow pointer of the tuple, if its value is stored in overflow pages.
+    pub fn overflow_pointer(tuple: &[u8]) -> Option<OverflowPointer> {
+        if is_overflow(tuple) {
+            Some(*get_overflow_pointer(tuple))
+        } else {
+            None
+        }
+    }
 }
 
 mod pivot_tuple {
@@ -529,15 +1982,43 @@ mod pivot_tuple {
     pub fn get_key(tuple: &[u8]) -> &[u8] {
         &tuple[Header::SIZE..]
     }
+
+    pub fn is_well_formed(tuple: &[u8]) -> bool {
+        tuple.len() >= Header::SIZE
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Maximum size of the key prefix stripped from leaf tuples.
+const MAX_PREFIX_SIZE: usize = 64;
+
+#[derive(Clone, Copy)]
 struct NodeMetadata {
     level: u8,
+    /// Leaf pages store the prefix common to all their keys here, and strip it from the keys in
+    /// the tuples.
+    prefix_size: u8,
+    prefix: [u8; MAX_PREFIX_SIZE],
 }
 
 impl NodeMetadata {
+    fn new(level: u8) -> Self {
+        Self {
+            level,
+            prefix_size: 0,
+            prefix: [0; MAX_PREFIX_SIZE],
+        }
+    }
+
     fn is_leaf(&self) -> bool {
         self.level == 0
     }
+
+    fn prefix(&self) -> &[u8] {
+        &self.prefix[..self.prefix_size as usize]
+    }
+
+    fn set_prefix(&mut self, prefix: &[u8]) {
+        self.prefix_size = prefix.len() as u8;
+        self.prefix[..prefix.len()].copy_from_slice(prefix);
+    }
 }
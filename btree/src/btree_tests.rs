@@ -1,8 +1,15 @@
-use crate::btree::{BTree, NodeDump};
+use crate::btree::{
+    BTree, BulkLoader, Bytewise, Error, KeyComparator, NodeDump, Result, MAX_KEY_SIZE,
+};
 
-use buffer_pool::buffer_pool::{BufferPool, Result};
+use buffer_pool::buffer_pool::BufferPool;
 use buffer_pool::disk_manager::PAGE_SIZE;
+use buffer_pool::disk_manager_file::DiskManagerFile;
 use buffer_pool::disk_manager_mem::DiskManagerMem;
+use proptest::prelude::*;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::Bound;
 
 #[tokio::test]
 async fn test_new() -> Result<()> {
@@ -101,7 +108,8 @@ async fn test_page_split() -> Result<()> {
                 NodeDump::Leaf(vec![(vec![1; PAGE_SIZE / 2], vec![101])])
             ),
             (
-                vec![2; PAGE_SIZE / 2],
+                // Pivot keys are truncated to the shortest separator.
+                vec![2],
                 NodeDump::Leaf(vec![(vec![2; PAGE_SIZE / 2], vec![102])])
             )
         ])
@@ -127,10 +135,628 @@ async fn test_insert_into_internal_node_left() -> Result<()> {
                 ])
             ),
             (
-                vec![3; PAGE_SIZE / 2],
+                vec![3],
                 NodeDump::Leaf(vec![(vec![3; PAGE_SIZE / 2], vec![103])])
             )
         ])
     );
     Ok(())
 }
+
+#[tokio::test]
+async fn test_internal_page_split() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let btree = BTree::new(&buffer_pool).await?;
+    // Keys differ only at the end, so that pivots are long and internal pages fill up quickly.
+    let key = |i: u32| [&[b'k'; 1000][..], &i.to_be_bytes()].concat();
+    const N: u32 = 300;
+    for i in 0..N {
+        // shuffled, so that pages split in the middle too
+        let i = i * 7 % N;
+        btree.insert(&key(i), &i.to_be_bytes()).await?;
+        assert_eq!(btree.verify().await?, vec![]);
+    }
+    for i in 0..N {
+        assert_eq!(btree.get(&key(i)).await?, Some(i.to_be_bytes().to_vec()));
+    }
+    assert!(btree.stats().await?.height >= 4);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let btree = BTree::new(&buffer_pool).await?;
+    btree.insert(&[1; PAGE_SIZE / 2], &[101]).await?;
+    btree.insert(&[3; PAGE_SIZE / 2], &[103]).await?;
+    btree.insert(&[2], &[102]).await?;
+    assert_eq!(btree.get(&[1; PAGE_SIZE / 2]).await?, Some(vec![101]));
+    assert_eq!(btree.get(&[2]).await?, Some(vec![102]));
+    assert_eq!(btree.get(&[3; PAGE_SIZE / 2]).await?, Some(vec![103]));
+    assert_eq!(btree.get(&[3]).await?, None);
+    assert_eq!(btree.get(&[]).await?, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_large_values() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let btree = BTree::new(&buffer_pool).await?;
+    let values: Vec<Vec<u8>> = (0..10u8)
+        .map(|i| {
+            (0..PAGE_SIZE * (i as usize) / 2)
+                .map(|j| j as u8 ^ i)
+                .collect()
+        })
+        .collect();
+    for (i, value) in values.iter().enumerate() {
+        btree.insert(&[i as u8], value).await?;
+    }
+    for (i, value) in values.iter().enumerate() {
+        assert_eq!(btree.get(&[i as u8]).await?.as_ref(), Some(value));
+    }
+    // Values were moved out of line, so everything fits in a single leaf.
+    match btree.dump_tree().await? {
+        NodeDump::Leaf(tuples) => {
+            assert_eq!(tuples.len(), 10);
+            assert_eq!(tuples[9], (vec![9], values[9].clone()));
+        }
+        dump => panic!("expected a single leaf, got {:?}", dump),
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_overwrite() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let btree = BTree::new(&buffer_pool).await?;
+    for i in 0..100u32 {
+        btree.insert(&i.to_be_bytes(), &[1; 10]).await?;
+    }
+    // Shrinking values are replaced in place, growing ones make the leaves split.
+    let value = |i: u32| vec![2; if i % 2 == 0 { 5 } else { 500 }];
+    for i in 0..100u32 {
+        btree.insert(&i.to_be_bytes(), &value(i)).await?;
+    }
+    for i in 0..100u32 {
+        assert_eq!(btree.get(&i.to_be_bytes()).await?, Some(value(i)));
+    }
+    assert_eq!(btree.stats().await?.tuples, 100);
+    assert_eq!(btree.verify().await?, vec![]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_overflow_pages_are_reused() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let btree = BTree::new(&buffer_pool).await?;
+    let large = vec![7; PAGE_SIZE * 3];
+    btree.insert(b"a", &large).await?;
+    let last_page_id = buffer_pool.allocate_page().await?.id();
+
+    // Overwriting frees the old chain, and the next large value takes its pages.
+    btree.insert(b"a", b"small").await?;
+    btree.insert(b"b", &large).await?;
+    assert_eq!(
+        buffer_pool.allocate_page().await?.id().0,
+        last_page_id.0 + 1
+    );
+
+    // Same for deleting.
+    assert!(btree.delete(b"b").await?);
+    btree.insert(b"c", &large).await?;
+    assert_eq!(
+        buffer_pool.allocate_page().await?.id().0,
+        last_page_id.0 + 2
+    );
+
+    assert_eq!(btree.get(b"a").await?, Some(b"small".to_vec()));
+    assert_eq!(btree.get(b"c").await?, Some(large));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_key_too_large() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let btree = BTree::new(&buffer_pool).await?;
+    btree.insert(&[1; MAX_KEY_SIZE], &[1; PAGE_SIZE]).await?;
+    match btree.insert(&[2; MAX_KEY_SIZE + 1], &[]).await {
+        Err(Error::KeyTooLarge(size)) => assert_eq!(size, MAX_KEY_SIZE + 1),
+        result => panic!("expected KeyTooLarge, got {:?}", result),
+    }
+    assert_eq!(
+        btree.get(&[1; MAX_KEY_SIZE]).await?,
+        Some(vec![1; PAGE_SIZE])
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_prefix_compression() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let btree = BTree::new(&buffer_pool).await?;
+    // Keys of a secondary index typically share a long prefix.
+    const PREFIX: &str = "orders/customer_email/";
+    const N: usize = 2000;
+    let key = |i: usize| format!("{}{:06}", PREFIX, i).into_bytes();
+    for i in 0..N {
+        btree.insert(&key(i * 7919 % N), &[]).await?;
+    }
+    for i in 0..N {
+        assert_eq!(btree.get(&key(i)).await?, Some(vec![]));
+    }
+
+    let stats = btree.stats().await?;
+    assert_eq!(stats.height, 2);
+    assert_eq!(stats.tuples, N);
+    assert!(stats.prefix_bytes_saved >= N * PREFIX.len());
+    // Without prefix compression, even perfectly packed leaves couldn't hold all the keys in this
+    // many pages.
+    // (4 bytes is the leaf tuple header)
+    let uncompressed_tuple_size = 4 + key(0).len() + page::TUPLE_DESCRIPTOR_SIZE;
+    let min_uncompressed_leaf_pages = N * uncompressed_tuple_size / PAGE_SIZE;
+    assert!(
+        stats.leaf_pages < min_uncompressed_leaf_pages,
+        "{:?}",
+        stats
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bulk_load() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    const N: usize = 2000;
+    let key = |i: usize| format!("key {:06}", i).into_bytes();
+    let value = |i: usize| format!("value {}", i).into_bytes();
+    let btree = BTree::bulk_load(&buffer_pool, 90, (0..N).map(|i| (key(i), value(i)))).await?;
+    for i in 0..N {
+        assert_eq!(btree.get(&key(i)).await?, Some(value(i)));
+    }
+
+    // Inserting the same keys one by one leaves half-empty pages behind.
+    let inserted = BTree::new(&buffer_pool).await?;
+    for i in 0..N {
+        inserted.insert(&key(i), &value(i)).await?;
+    }
+    let bulk_stats = btree.stats().await?;
+    let inserted_stats = inserted.stats().await?;
+    assert_eq!(bulk_stats.tuples, N);
+    assert!(
+        bulk_stats.leaf_pages < inserted_stats.leaf_pages,
+        "{:?} {:?}",
+        bulk_stats,
+        inserted_stats
+    );
+
+    assert_eq!(btree.verify().await?, vec![]);
+    assert_eq!(inserted.verify().await?, vec![]);
+
+    // The tree can be modified afterwards.
+    btree.insert(b"key 000100a", b"new").await?;
+    assert_eq!(btree.get(b"key 000100a").await?, Some(b"new".to_vec()));
+    assert_eq!(btree.verify().await?, vec![]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bulk_load_multiple_levels() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    // Long keys differing only at the end make long pivots, so internal pages fill up quickly.
+    let key = |i: usize| format!("{}{:06}", "x".repeat(500), i).into_bytes();
+    let btree = BTree::bulk_load(&buffer_pool, 100, (0..1000).map(|i| (key(i), vec![]))).await?;
+    assert!(btree.stats().await?.height >= 3);
+    for i in 0..1000 {
+        assert_eq!(btree.get(&key(i)).await?, Some(vec![]));
+    }
+    assert_eq!(btree.get(&key(1000)).await?, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_insert_after_bulk_load() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let key = |i: usize| format!("{}{:06}", "x".repeat(500), i).into_bytes();
+    // All pages are full, so every insert below splits a leaf, and the splits reach the root.
+    let btree = BTree::bulk_load(&buffer_pool, 100, (0..200).map(|i| (key(i), vec![]))).await?;
+    let height = btree.stats().await?.height;
+    assert!(height >= 2);
+    for i in 0..200 {
+        btree
+            .insert(&[key(i), b"a".to_vec()].concat(), &[1])
+            .await?;
+    }
+    assert_eq!(btree.verify().await?, vec![]);
+    assert!(btree.stats().await?.height > height);
+    for i in 0..200 {
+        assert_eq!(btree.get(&key(i)).await?, Some(vec![]));
+        assert_eq!(
+            btree.get(&[key(i), b"a".to_vec()].concat()).await?,
+            Some(vec![1])
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bulk_load_empty() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let btree = BTree::bulk_load(&buffer_pool, 90, Vec::<(Vec<u8>, Vec<u8>)>::new()).await?;
+    assert_eq!(btree.dump_tree().await?, NodeDump::Leaf(vec![]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bulk_load_unsorted() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let unsorted = vec![(&[1u8][..], &[][..]), (&[3], &[]), (&[2], &[])];
+    match BTree::bulk_load(&buffer_pool, 90, unsorted).await {
+        Err(Error::UnsortedInput) => {}
+        result => panic!("expected UnsortedInput, got {:?}", result.map(|_| ())),
+    }
+    let duplicates = vec![(&[1u8][..], &[][..]), (&[1], &[])];
+    match BTree::bulk_load(&buffer_pool, 90, duplicates).await {
+        Err(Error::UnsortedInput) => {}
+        result => panic!("expected UnsortedInput, got {:?}", result.map(|_| ())),
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bulk_loader() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let key = |i: usize| format!("key {:06}", i).into_bytes();
+    let mut loader = BTree::bulk_loader(&buffer_pool, 90).await?;
+    for i in 0..2000 {
+        loader.push(&key(i), &key(i)).await?;
+    }
+    match loader.push(&key(1999), &[]).await {
+        Err(Error::UnsortedInput) => {}
+        result => panic!("expected UnsortedInput, got {:?}", result),
+    }
+    let btree = loader.finish().await?;
+
+    // Same tree as built from an iterator.
+    let expected = BTree::bulk_load(&buffer_pool, 90, (0..2000).map(|i| (key(i), key(i)))).await?;
+    assert_eq!(btree.dump_tree().await?, expected.dump_tree().await?);
+    assert_eq!(btree.verify().await?, vec![]);
+    Ok(())
+}
+
+/// Push 2000 entries and a large value to the loader.
+async fn push_entries(loader: &mut BulkLoader<'_>) -> Result<()> {
+    for i in 0..2000 {
+        let key = format!("key {:06}", i).into_bytes();
+        loader.push(&key, &key).await?;
+    }
+    loader.push(b"large", &vec![7; PAGE_SIZE * 2]).await
+}
+
+/// Bulk load the entries of `push_entries`, and return the number of pages allocated. Before that,
+/// `failed_loads` loaders fail, and `dropped_loads` loaders are dropped before finishing.
+async fn pages_of_bulk_load(failed_loads: usize, dropped_loads: usize) -> Result<u32> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let start = buffer_pool.allocate_page().await?.id();
+    for _ in 0..failed_loads {
+        let mut loader = BTree::bulk_loader(&buffer_pool, 90).await?;
+        push_entries(&mut loader).await?;
+        match loader.push(b"a", &[]).await {
+            Err(Error::UnsortedInput) => {}
+            result => panic!("expected UnsortedInput, got {:?}", result),
+        }
+    }
+    for _ in 0..dropped_loads {
+        let mut loader = BTree::bulk_loader(&buffer_pool, 90).await?;
+        push_entries(&mut loader).await?;
+    }
+    let mut loader = BTree::bulk_loader(&buffer_pool, 90).await?;
+    push_entries(&mut loader).await?;
+    loader.finish().await?;
+    let end = buffer_pool.allocate_page().await?.id();
+    Ok(end.0 - start.0)
+}
+
+#[tokio::test]
+async fn test_bulk_loader_frees_pages() -> Result<()> {
+    let expected = pages_of_bulk_load(0, 0).await?;
+    assert_eq!(pages_of_bulk_load(2, 0).await?, expected);
+    assert_eq!(pages_of_bulk_load(0, 2).await?, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reopen() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("btree_reopen_{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let (meta_page_id, expected) = {
+        let buffer_pool = BufferPool::new(Box::new(DiskManagerFile::open(&path).await?), 20);
+        let btree = BTree::new(&buffer_pool).await?;
+        for i in 0..200u32 {
+            btree.insert(&i.to_be_bytes(), &[i as u8; 100]).await?;
+        }
+        buffer_pool.flush_all().await?;
+        (btree.meta_page_id(), btree.dump_tree().await?)
+    };
+
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerFile::open(&path).await?), 20);
+    let btree = BTree::open(&buffer_pool, meta_page_id).await?;
+    assert_eq!(btree.dump_tree().await?, expected);
+    assert_eq!(btree.get(&7u32.to_be_bytes()).await?, Some(vec![7; 100]));
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_open_invalid_meta_page() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let page_id = buffer_pool.allocate_page().await?.id();
+    match BTree::open(&buffer_pool, page_id).await {
+        Err(Error::InvalidMetaPage(id)) => assert_eq!(id, page_id),
+        other => panic!("expected InvalidMetaPage, got {:?}", other.map(|_| ())),
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_delete() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let btree = BTree::new(&buffer_pool).await?;
+    for i in 0..100u32 {
+        btree.insert(&i.to_be_bytes(), &[0; 100]).await?;
+    }
+    for i in (0..100u32).step_by(2) {
+        assert!(btree.delete(&i.to_be_bytes()).await?);
+    }
+    assert!(!btree.delete(&0u32.to_be_bytes()).await?);
+    assert!(!btree.delete(b"missing").await?);
+    for i in 0..100u32 {
+        let expected = if i % 2 == 0 { None } else { Some(vec![0; 100]) };
+        assert_eq!(btree.get(&i.to_be_bytes()).await?, expected);
+    }
+    assert_eq!(btree.stats().await?.tuples, 50);
+    assert_eq!(btree.verify().await?, vec![]);
+
+    // deleted keys can be inserted again
+    btree.insert(&0u32.to_be_bytes(), b"again").await?;
+    assert_eq!(
+        btree.get(&0u32.to_be_bytes()).await?,
+        Some(b"again".to_vec())
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_range() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let key = |i: u32| format!("key {:04}", i).into_bytes();
+    let btree = BTree::bulk_load(&buffer_pool, 100, (0..1000).map(|i| (key(i), vec![]))).await?;
+    let range_keys = |entries: Vec<(Vec<u8>, Vec<u8>)>| -> Vec<Vec<u8>> {
+        entries.into_iter().map(|(key, _)| key).collect()
+    };
+
+    assert_eq!(
+        range_keys(
+            btree
+                .range(Bound::Included(&key(10)), Bound::Excluded(&key(20)))
+                .await?
+        ),
+        (10..20).map(key).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        range_keys(
+            btree
+                .range(Bound::Excluded(&key(10)), Bound::Included(&key(20)))
+                .await?
+        ),
+        (11..=20).map(key).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        range_keys(
+            btree
+                .range(Bound::Unbounded, Bound::Excluded(b"key 0003"))
+                .await?
+        ),
+        (0..3).map(key).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        range_keys(
+            btree
+                .range(Bound::Included(b"key 0997"), Bound::Unbounded)
+                .await?
+        ),
+        (997..1000).map(key).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        btree.range(Bound::Unbounded, Bound::Unbounded).await?.len(),
+        1000
+    );
+    assert_eq!(
+        btree
+            .range(Bound::Included(b"zzz"), Bound::Unbounded)
+            .await?,
+        vec![]
+    );
+    Ok(())
+}
+
+/// Shorter keys first, then bytewise. Keys sharing a prefix are not adjacent in this order.
+struct ShortLex;
+
+impl KeyComparator for ShortLex {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+    }
+}
+
+/// Reverse bytewise order.
+struct Reverse;
+
+impl KeyComparator for Reverse {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        b.cmp(a)
+    }
+}
+
+#[tokio::test]
+async fn test_custom_comparator() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let btree = BTree::with_comparator(&buffer_pool, Reverse).await?;
+    let key = |i: u32| format!("key {:04}", i).into_bytes();
+    for i in 0..500 {
+        btree.insert(&key(i), &[]).await?;
+    }
+    assert_eq!(btree.verify().await?, vec![]);
+    assert!(btree.stats().await?.height > 1);
+    assert_eq!(btree.get(&key(123)).await?, Some(vec![]));
+
+    let keys: Vec<Vec<u8>> = btree
+        .range(Bound::Included(&key(20)), Bound::Excluded(&key(15)))
+        .await?
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    assert_eq!(keys, (16..=20).rev().map(key).collect::<Vec<_>>());
+
+    // reopening needs the same comparator
+    let reopened = BTree::open_with_comparator(&buffer_pool, btree.meta_page_id(), Reverse).await?;
+    assert_eq!(reopened.get(&key(321)).await?, Some(vec![]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bulk_load_with_comparator() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let key = |i: u32| format!("key {:04}", i).into_bytes();
+    let btree = BTree::bulk_load_with_comparator(
+        &buffer_pool,
+        Reverse,
+        100,
+        (0..1000).rev().map(|i| (key(i), vec![])),
+    )
+    .await?;
+    assert_eq!(btree.verify().await?, vec![]);
+    assert_eq!(btree.get(&key(500)).await?, Some(vec![]));
+
+    // ascending input is unsorted for this comparator
+    match BTree::bulk_load_with_comparator(
+        &buffer_pool,
+        Reverse,
+        100,
+        (0..2).map(|i| (key(i), vec![])),
+    )
+    .await
+    {
+        Err(Error::UnsortedInput) => {}
+        other => panic!("expected UnsortedInput, got {:?}", other.map(|_| ())),
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+enum Op {
+    Insert(Vec<u8>, Vec<u8>),
+    Get(Vec<u8>),
+    Delete(Vec<u8>),
+    Range(Vec<u8>, Vec<u8>),
+}
+
+/// Keys from a small alphabet, so that they often share prefixes.
+fn key_strategy() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(0u8..4, 0..24)
+}
+
+fn op_strategy() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => (key_strategy(), prop::collection::vec(any::<u8>(), 0..200))
+            .prop_map(|(key, value)| Op::Insert(key, value)),
+        // large enough to go to overflow pages
+        1 => (key_strategy(), prop::collection::vec(any::<u8>(), 1000..5000))
+            .prop_map(|(key, value)| Op::Insert(key, value)),
+        1 => key_strategy().prop_map(Op::Get),
+        1 => key_strategy().prop_map(Op::Delete),
+        1 => (key_strategy(), key_strategy()).prop_map(|(start, end)| Op::Range(start, end)),
+    ]
+}
+
+/// Run the operations on a tree and on a model, checking the results and tree structure after
+/// each one.
+async fn run_operations<C: KeyComparator>(comparator: C, ops: Vec<Op>) -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let btree = BTree::with_comparator(&buffer_pool, comparator).await?;
+    let comparator = |a: &[u8], b: &[u8]| btree.comparator().compare(a, b);
+    let mut model = BTreeMap::new();
+    for op in ops {
+        match op {
+            Op::Insert(key, value) => {
+                btree.insert(&key, &value).await?;
+                model.insert(key, value);
+            }
+            Op::Get(key) => {
+                assert_eq!(btree.get(&key).await?, model.get(&key).cloned());
+            }
+            Op::Delete(key) => {
+                assert_eq!(btree.delete(&key).await?, model.remove(&key).is_some());
+            }
+            Op::Range(start, end) => {
+                let mut expected: Vec<_> = model
+                    .iter()
+                    .filter(|(key, _)| {
+                        comparator(&start, key) != Ordering::Greater
+                            && comparator(key, &end) == Ordering::Less
+                    })
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect();
+                expected.sort_by(|(a, _), (b, _)| comparator(a, b));
+                let actual = btree
+                    .range(Bound::Included(&start), Bound::Excluded(&end))
+                    .await?;
+                assert_eq!(actual, expected);
+            }
+        }
+        assert_eq!(btree.verify().await?, vec![]);
+    }
+    assert_eq!(btree.stats().await?.tuples, model.len());
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 100, ..ProptestConfig::default()
+    })]
+    #[test]
+    fn random_operations_keep_tree_valid(ops in prop::collection::vec(op_strategy(), 1..200)) {
+        tokio::runtime::Runtime::new().unwrap().block_on(run_operations(Bytewise, ops)).unwrap();
+    }
+
+    #[test]
+    fn random_operations_with_comparator(ops in prop::collection::vec(op_strategy(), 1..200)) {
+        tokio::runtime::Runtime::new().unwrap().block_on(run_operations(ShortLex, ops)).unwrap();
+    }
+
+    #[test]
+    fn bulk_load_builds_valid_tree(
+        fill_factor in 10..=100usize,
+        entries in prop::collection::btree_map(
+            key_strategy(),
+            prop::collection::vec(any::<u8>(), 0..100),
+            0..500,
+        ),
+    ) {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+            let btree = BTree::bulk_load(&buffer_pool, fill_factor, entries.clone()).await?;
+            assert_eq!(btree.verify().await?, vec![]);
+            for (key, value) in &entries {
+                assert_eq!(btree.get(key).await?.as_ref(), Some(value));
+            }
+            // Pages may be full, so inserting afterwards splits them.
+            for key in entries.keys() {
+                btree.insert(&[&key[..], &[9]].concat(), &[]).await?;
+            }
+            assert_eq!(btree.verify().await?, vec![]);
+            Ok::<(), Error>(())
+        }).unwrap();
+    }
+}
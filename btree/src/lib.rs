@@ -3,11 +3,7 @@
 extern crate insta;
 
 pub mod btree;
-mod page;
+mod overflow;
 
 #[cfg(test)]
 mod btree_tests;
-#[cfg(test)]
-mod hexdump;
-#[cfg(test)]
-mod page_tests;
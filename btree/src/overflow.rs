@@ -0,0 +1,108 @@
+//! Overflow pages hold values too big to be stored inline in a leaf tuple (similar to TOAST in
+//! Postgres).
+//!
+//! The value is cut into chunks, each stored as the only tuple of a `TupleBlockPage`. Pages are
+//! linked into a chain through their metadata. The leaf tuple keeps only an `OverflowPointer`.
+
+use crate::btree::{Error, Result};
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager::{PageId, INVALID_PAGE_ID};
+use page::TupleBlockPage;
+use std::mem;
+
+#[derive(Debug, Clone, Copy)]
+pub struct OverflowPointer {
+    pub first_page_id: PageId,
+    /// Total size of the value, in bytes.
+    pub size: u32,
+}
+
+impl OverflowPointer {
+    pub const SIZE: usize = mem::size_of::<OverflowPointer>();
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OverflowMetadata {
+    next_page_id: PageId,
+}
+
+/// Maximum number of value bytes stored on a single overflow page.
+pub fn chunk_size() -> usize {
+    page::capacity::<OverflowMetadata>() - page::TUPLE_DESCRIPTOR_SIZE
+}
+
+/// Store the value in a newly allocated chain of overflow pages. The ids of the pages are added to
+/// `page_ids` as they are allocated, also if writing fails.
+pub async fn write(
+    buffer_pool: &BufferPool,
+    value: &[u8],
+    page_ids: &mut Vec<PageId>,
+) -> Result<OverflowPointer> {
+    let mut next_page_id = INVALID_PAGE_ID;
+    // Chunks are written back to front, so that each page can point to the already written next
+    // one, and we don't have to keep more than one page latched.
+    for chunk in value.chunks(chunk_size()).rev() {
+        let mut page = TupleBlockPage::new(
+            buffer_pool.allocate_page().await?.write().await,
+            &OverflowMetadata { next_page_id },
+        );
+        page.insert_tuple(chunk)
+            .expect("chunk should fit on an empty page");
+        page.dirty();
+        next_page_id = page.id();
+        page_ids.push(next_page_id);
+    }
+    Ok(OverflowPointer {
+        first_page_id: next_page_id,
+        size: value.len() as u32,
+    })
+}
+
+/// Reassemble a value stored in overflow pages.
+pub async fn read(buffer_pool: &BufferPool, pointer: OverflowPointer) -> Result<Vec<u8>> {
+    let corrupt = Error::CorruptOverflowChain(pointer.first_page_id);
+    let mut value = Vec::with_capacity(pointer.size as usize);
+    let mut page_id = pointer.first_page_id;
+    while page_id.is_valid() {
+        let page = TupleBlockPage::<_, OverflowMetadata>::from_existing(
+            buffer_pool.get_page(page_id).await?.read().await,
+        );
+        let chunk = if page.tuple_count() == 1 {
+            page.get_tuple(0)
+        } else {
+            None
+        };
+        match chunk {
+            // Checking the size on every page also stops on a cycle in the chain.
+            Some(chunk) if value.len() + chunk.len() <= pointer.size as usize => {
+                value.extend_from_slice(chunk)
+            }
+            _ => return Err(corrupt),
+        }
+        page_id = page.metadata().next_page_id;
+    }
+    if value.len() != pointer.size as usize {
+        return Err(corrupt);
+    }
+    Ok(value)
+}
+
+/// Return the pages of a value to the buffer pool. Nothing may refer to the value anymore.
+pub async fn free(buffer_pool: &BufferPool, pointer: OverflowPointer) -> Result<()> {
+    let mut page_id = pointer.first_page_id;
+    // Bounded by the page count, so that a corrupt chain can't make us free pages forever.
+    let page_count = (pointer.size as usize + chunk_size() - 1) / chunk_size();
+    for _ in 0..page_count {
+        if !page_id.is_valid() {
+            break;
+        }
+        let next_page_id = TupleBlockPage::<_, OverflowMetadata>::from_existing(
+            buffer_pool.get_page(page_id).await?.read().await,
+        )
+        .metadata()
+        .next_page_id;
+        buffer_pool.free_page(page_id).await;
+        page_id = next_page_id;
+    }
+    Ok(())
+}
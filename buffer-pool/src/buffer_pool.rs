@@ -1,5 +1,5 @@
 use crate::disk_manager::*;
-use crate::sync::{AtomicBool, AtomicUsize, Ordering::*};
+use crate::sync::{AtomicBool, AtomicUsize, Mutex, Ordering::*};
 use std::cell::UnsafeCell;
 use std::collections::HashMap;
 use std::io;
@@ -55,12 +55,17 @@ pub struct BufferPool {
     capacity: usize,
     frames: Box<[Page]>,
     lock: RwLock<BufferPoolInner>,
+    /// Pages passed to `free_page_later`, freed by the next `allocate_page`.
+    pending_free_pages: Mutex<Vec<PageId>>,
 }
 
 struct BufferPoolInner {
     disk_manager: Box<dyn DiskManager + Send>,
     page_table: HashMap<PageId, FrameId>,
     free_frames: Vec<FrameId>,
+    /// Pages returned by `free_page`, handed out again by `allocate_page`. Kept only in memory, so
+    /// pages freed before a restart are never reused.
+    free_pages: Vec<PageId>,
     ref_flag: BitVec,
     clock_hand: usize,
 }
@@ -213,9 +218,11 @@ impl BufferPool {
                 disk_manager,
                 page_table: HashMap::with_capacity(capacity),
                 free_frames,
+                free_pages: vec![],
                 ref_flag: bitvec![0; capacity],
                 clock_hand: 0,
             }),
+            pending_free_pages: Mutex::new(vec![]),
         }
     }
 
@@ -294,9 +301,17 @@ impl BufferPool {
     // TODO: decopypaste - get_page
     pub async fn allocate_page(&self) -> Result<PinnedPage<'_>> {
         let mut inner = self.lock.write().await;
+        let pending_free_pages = mem::take(&mut *self.pending_free_pages.lock().unwrap());
+        for page_id in pending_free_pages {
+            self.free_page_locked(&mut inner, page_id);
+        }
         let frame_id = self.get_free_frame(inner.deref_mut()).await?;
 
-        let page_id = inner.disk_manager.allocate_page().await?;
+        // A reused page still has its old contents on disk, so it's dirty from the start.
+        let (page_id, dirty) = match inner.free_pages.pop() {
+            Some(page_id) => (page_id, true),
+            None => (inner.disk_manager.allocate_page().await?, false),
+        };
 
         let page = &self.frames[frame_id];
         // SAFETY: We're sure nobody else is accessing this Page,
@@ -305,7 +320,7 @@ impl BufferPool {
         unsafe {
             page.id.get().write(page_id);
         }
-        page.dirty.store(false, SeqCst);
+        page.dirty.store(dirty, SeqCst);
         page.pin_count.store(1, SeqCst);
 
         // Zero-fill the newly created page
@@ -319,6 +334,55 @@ impl BufferPool {
         Ok(PinnedPage { page })
     }
 
+    /// Return a page which is no longer used, so that `allocate_page` can hand it out again.
+    ///
+    /// The page must not be pinned, and nothing may refer to it anymore. Its contents are
+    /// discarded without writing them to disk.
+    pub async fn free_page(&self, page_id: PageId) {
+        let mut inner = self.lock.write().await;
+        self.free_page_locked(&mut inner, page_id);
+    }
+
+    /// Like `free_page`, but doesn't wait for the buffer pool lock, so that it can be called where
+    /// we can't await (like in `drop`). The page is freed by the next `allocate_page`.
+    pub fn free_page_later(&self, page_id: PageId) {
+        assert!(page_id.is_valid());
+        self.pending_free_pages.lock().unwrap().push(page_id);
+    }
+
+    fn free_page_locked(&self, inner: &mut BufferPoolInner, page_id: PageId) {
+        assert!(page_id.is_valid());
+        if let Some(frame_id) = inner.page_table.remove(&page_id) {
+            let page = &self.frames[frame_id];
+            assert_eq!(page.pin_count.load(SeqCst), 0, "freeing a pinned page");
+            // SAFETY: The page is not pinned, and we're holding the buffer pool lock, so nobody
+            // else is accessing it.
+            unsafe { page.id.get().write(PageId::invalid()) }
+            page.dirty.store(false, SeqCst);
+            inner.ref_flag.set(frame_id, false);
+            inner.free_frames.push(frame_id);
+        }
+        inner.free_pages.push(page_id);
+    }
+
+    /// Write all dirty pages to disk.
+    ///
+    /// Waits for page latches, so the caller must not hold any.
+    pub async fn flush_all(&self) -> Result<()> {
+        let mut inner = self.lock.write().await;
+        for page in self.frames.iter() {
+            // SAFETY: We're holding the buffer pool lock, so the page is not being switched to a
+            // different one.
+            let page_id = unsafe { *page.id.get() };
+            if page_id.is_valid() && page.dirty.load(SeqCst) {
+                let data = page.data.read().await;
+                inner.disk_manager.write_page(page_id, &data).await?;
+                page.dirty.store(false, SeqCst);
+            }
+        }
+        Ok(())
+    }
+
     async fn get_free_frame(&self, inner: &mut BufferPoolInner) -> Result<FrameId> {
         match inner.free_frames.pop() {
             Some(frame_id) => Ok(frame_id),
@@ -9,6 +9,7 @@ extern crate log;
 
 use ::buffer_pool::buffer_pool::*;
 use ::buffer_pool::disk_manager::*;
+use ::buffer_pool::disk_manager_file::*;
 use ::buffer_pool::disk_manager_mem::*;
 
 use rand::{Rng, SeedableRng};
@@ -64,6 +65,75 @@ async fn test_write_and_read_evicted_page() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_free_and_reuse_page() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 1);
+
+    let page = buffer_pool.allocate_page().await?;
+    page.data().write().await[0] = 5;
+    page.dirty();
+    drop(page);
+    buffer_pool.free_page(PageId(0)).await;
+    assert!(!buffer_pool.is_page_in_memory(PageId(0)).await);
+
+    // The freed page is handed out again, zero-filled
+    let page = buffer_pool.allocate_page().await?;
+    assert_eq!(page.id(), PageId(0));
+    assert_eq!(page.data().read().await[0], 0);
+    drop(page);
+
+    // Evict it, and check that the old contents didn't come back from disk
+    let page = buffer_pool.allocate_page().await?;
+    assert_eq!(page.id(), PageId(1));
+    drop(page);
+    let page = buffer_pool.get_page(PageId(0)).await?;
+    assert_eq!(page.data().read().await[0], 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_free_page_later() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 2);
+    for _ in 0..2 {
+        buffer_pool.allocate_page().await?;
+    }
+    buffer_pool.free_page_later(PageId(1));
+    assert!(buffer_pool.is_page_in_memory(PageId(1)).await);
+
+    // The page is freed by the next allocation, and handed out by it
+    let page = buffer_pool.allocate_page().await?;
+    assert_eq!(page.id(), PageId(1));
+    drop(page);
+    assert_eq!(buffer_pool.allocate_page().await?.id(), PageId(2));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_flush_all_and_reopen() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("buffer_pool_flush_{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerFile::open(&path).await?), 10);
+    for i in 0..3 {
+        let page = buffer_pool.allocate_page().await?;
+        page.data().write().await[0] = i + 1;
+        page.dirty();
+    }
+    buffer_pool.flush_all().await?;
+    drop(buffer_pool);
+
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerFile::open(&path).await?), 10);
+    for i in 0..3 {
+        let page = buffer_pool.get_page(PageId(i as u32)).await?;
+        assert_eq!(page.data().read().await[0], i + 1);
+    }
+    assert_eq!(buffer_pool.allocate_page().await?.id(), PageId(3));
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
 #[tokio::test]
 async fn random_multi_pin_test() -> Result<()> {
     const buffer_pool_size: usize = 2;
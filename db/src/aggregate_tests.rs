@@ -0,0 +1,251 @@
+use crate::catalog::Catalog;
+use crate::executor::{collect, Executor, HashAggregate, Row, Sort, SortAggregate};
+use crate::logical_plan::{AggregateCall, AggregateFunction, SortKey};
+use crate::test_utils::{column, run_all, values};
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager_mem::DiskManagerMem;
+use proptest::prelude::*;
+use std::collections::BTreeMap;
+use table::datum::{Datum, Type};
+
+#[tokio::test]
+async fn sql() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    assert_snapshot!(run_all(&catalog, &[
+        "create table t (g text, i int, b bigint, n decimal, f double precision)",
+        "select count(*), count(i), sum(i), min(g), avg(n) from t",
+        "select g, count(*) from t group by g",
+        "insert into t values ('a', 1, 9223372036854775807, 1.5, 0.5), ('a', 2, 1, 2, 1.5), \
+         ('b', null, null, null, null), (null, 3, 3, 0.25, 2), (null, 4, 4, -1, 4), \
+         ('c', 5, 5, 1, 8)",
+        "select count(*), count(i), sum(i), min(g), max(g), avg(i), avg(n), avg(f), sum(f) from t",
+        "select g, count(*), count(i), sum(i), min(i), max(n), avg(i) from t group by g order by g",
+        "select g, sum(n) from t group by g having count(i) > 1 order by sum(n) desc",
+        "select i > 2 as big, count(*) from t where i is not null group by i > 2 order by big",
+        "select count(*) from t where false",
+        "select g from t where false group by g",
+        "select sum(b) from t",
+        "select sum(b) from t where g is null",
+        "select avg(b) from t where g = 'a'",
+    ]).await, @r###"
+    create table t (g text, i int, b bigint, n decimal, f double precision)
+    select count(*), count(i), sum(i), min(g), avg(n) from t
+    0 | 0 | NULL | NULL | NULL
+    select g, count(*) from t group by g
+    insert into t values ('a', 1, 9223372036854775807, 1.5, 0.5), ('a', 2, 1, 2, 1.5), ('b', null, null, null, null), (null, 3, 3, 0.25, 2), (null, 4, 4, -1, 4), ('c', 5, 5, 1, 8)
+    6
+    select count(*), count(i), sum(i), min(g), max(g), avg(i), avg(n), avg(f), sum(f) from t
+    6 | 5 | 15 | a | c | 3 | 0.75 | 3.2 | 16
+    select g, count(*), count(i), sum(i), min(i), max(n), avg(i) from t group by g order by g
+    a | 2 | 2 | 3 | 1 | 2 | 1.5
+    b | 1 | 0 | NULL | NULL | NULL | NULL
+    c | 1 | 1 | 5 | 5 | 1 | 5
+    NULL | 2 | 2 | 7 | 3 | 0.25 | 3.5
+    select g, sum(n) from t group by g having count(i) > 1 order by sum(n) desc
+    a | 3.5
+    NULL | -0.75
+    select i > 2 as big, count(*) from t where i is not null group by i > 2 order by big
+    false | 2
+    true | 3
+    select count(*) from t where false
+    0
+    select g from t where false group by g
+    select sum(b) from t
+    error: value out of range for type Int8
+    select sum(b) from t where g is null
+    7
+    select avg(b) from t where g = 'a'
+    4611686018427387904
+    "###);
+}
+
+/// Average of integers, which doesn't fit in a `NUMERIC` with the full scale.
+#[tokio::test]
+async fn average_precision() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    assert_snapshot!(run_all(&catalog, &[
+        "create table t (a bigint)",
+        "insert into t values (1), (2), (2)",
+        "select avg(a) from t",
+        "insert into t values (1000000000000)",
+        "select avg(a) from t",
+        "select avg(a) from t where a < 0",
+    ]).await, @r###"
+    create table t (a bigint)
+    insert into t values (1), (2), (2)
+    3
+    select avg(a) from t
+    1.666666666666666667
+    insert into t values (1000000000000)
+    1
+    select avg(a) from t
+    250000000001.25
+    select avg(a) from t where a < 0
+    NULL
+    "###);
+}
+
+/// Rows of the test inputs: a group and a value.
+type Rows = Vec<(Option<i32>, Option<i32>)>;
+
+fn input<'a>(rows: &Rows) -> Box<dyn Executor + 'a> {
+    let datum = |value: Option<i32>| value.map_or(Datum::Null, Datum::Int4);
+    values(
+        rows.iter()
+            .map(|&(group, value)| vec![datum(group), datum(value)])
+            .collect(),
+        &[Type::Int4, Type::Int4],
+    )
+}
+
+fn aggregates() -> Vec<AggregateCall> {
+    let call = |function, arg| AggregateCall { function, arg };
+    vec![
+        call(AggregateFunction::Count, None),
+        call(AggregateFunction::Count, Some(column(1, Type::Int4))),
+        call(AggregateFunction::Sum, Some(column(1, Type::Int4))),
+        call(AggregateFunction::Min, Some(column(1, Type::Int4))),
+        call(AggregateFunction::Max, Some(column(1, Type::Int4))),
+        call(AggregateFunction::Avg, Some(column(1, Type::Int4))),
+    ]
+}
+
+/// Averages are compared rounded to 9 fractional digits. With at most 100 values, an average
+/// which isn't exactly that precise is at least 1e-12 away from rounding differently.
+fn format_average(average: f64) -> Datum {
+    Datum::String(format!("{:.9}", average))
+}
+
+/// Sort the groups by their value, and format the averages.
+fn normalize(mut rows: Vec<Row>) -> Vec<Row> {
+    for row in &mut rows {
+        if let Datum::Numeric(average) = &row[6] {
+            row[6] = format_average(average.to_string().parse().unwrap());
+        }
+    }
+    rows.sort_by_key(|row| match row[0] {
+        Datum::Int4(group) => Some(group),
+        _ => None,
+    });
+    rows
+}
+
+/// The expected result, normalized.
+fn reference(rows: &Rows) -> Vec<Row> {
+    let mut groups: BTreeMap<Option<i32>, Vec<i32>> = BTreeMap::new();
+    for &(group, value) in rows {
+        groups.entry(group).or_default().extend(value);
+    }
+    groups
+        .into_iter()
+        .map(|(group, values)| {
+            let count = values.len() as i64;
+            let sum: i64 = values.iter().map(|&value| value as i64).sum();
+            let or_null = |value: Option<Datum>| value.unwrap_or(Datum::Null);
+            vec![
+                group.map_or(Datum::Null, Datum::Int4),
+                Datum::Int8(rows.iter().filter(|row| row.0 == group).count() as i64),
+                Datum::Int8(count),
+                or_null(values.first().map(|_| Datum::Int8(sum))),
+                or_null(values.iter().min().map(|&min| Datum::Int4(min))),
+                or_null(values.iter().max().map(|&max| Datum::Int4(max))),
+                or_null(
+                    values
+                        .first()
+                        .map(|_| format_average(sum as f64 / count as f64)),
+                ),
+            ]
+        })
+        .collect()
+}
+
+async fn check_aggregates(rows: Rows) {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 64);
+    let hash = |memory_budget| -> Box<dyn Executor> {
+        Box::new(HashAggregate::new(
+            &buffer_pool,
+            input(&rows),
+            vec![column(0, Type::Int4)],
+            aggregates(),
+            memory_budget,
+        ))
+    };
+    let sorted = Box::new(Sort::new(
+        &buffer_pool,
+        input(&rows),
+        vec![SortKey {
+            expr: column(0, Type::Int4),
+            descending: false,
+        }],
+        1 << 20,
+    ));
+    let executors = vec![
+        ("hash", hash(1 << 20)),
+        ("hash, spilled", hash(500)),
+        (
+            "sort",
+            Box::new(SortAggregate::new(
+                sorted,
+                vec![column(0, Type::Int4)],
+                aggregates(),
+            )),
+        ),
+    ];
+    let expected = reference(&rows);
+    for (name, executor) in executors {
+        let result = normalize(collect(executor).await.unwrap());
+        assert_eq!(result, expected, "{} aggregate", name);
+    }
+}
+
+fn rows() -> impl Strategy<Value = Rows> {
+    let value = prop::option::weighted(0.8, -100..100);
+    prop::collection::vec((prop::option::weighted(0.9, 0..30), value), 0..100)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 50, ..ProptestConfig::default()
+    })]
+    #[test]
+    fn aggregates_match_reference(rows in rows()) {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(check_aggregates(rows));
+    }
+}
+
+/// Without GROUP BY, empty input still produces a row.
+#[tokio::test]
+async fn empty_input() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let expected = vec![vec![
+        Datum::Int8(0),
+        Datum::Int8(0),
+        Datum::Null,
+        Datum::Null,
+        Datum::Null,
+        Datum::Null,
+    ]];
+    let hash = HashAggregate::new(&buffer_pool, input(&vec![]), vec![], aggregates(), 1000);
+    assert_eq!(collect(Box::new(hash)).await.unwrap(), expected);
+    let sort = SortAggregate::new(input(&vec![]), vec![], aggregates());
+    assert_eq!(collect(Box::new(sort)).await.unwrap(), expected);
+}
+
+/// Many groups, which are spilled more than once.
+#[tokio::test]
+async fn hash_aggregate_many_groups() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 64);
+    let rows: Rows = (0..5000).map(|i| (Some(i % 1500), Some(i % 7))).collect();
+    let hash = HashAggregate::new(
+        &buffer_pool,
+        input(&rows),
+        vec![column(0, Type::Int4)],
+        aggregates(),
+        2000,
+    );
+    let result = collect(Box::new(hash)).await.unwrap();
+    assert_eq!(normalize(result), reference(&rows));
+}
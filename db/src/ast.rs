@@ -0,0 +1,539 @@
+//! Syntax tree of SQL statements, as produced by `parser`.
+//!
+//! Names are not resolved, and expressions are not type-checked - that's done by the binder.
+//!
+//! `Display` prints the tree back as SQL, with every compound expression parenthesized, so that the
+//! structure is visible (and parsing the output gives the same tree).
+
+use std::fmt;
+use table::datum::Type;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Statement {
+    CreateTable(CreateTable),
+    DropTable {
+        name: String,
+        if_exists: bool,
+    },
+    CreateIndex(CreateIndex),
+    Insert(Insert),
+    Select(Select),
+    Update(Update),
+    Delete(Delete),
+    /// `EXPLAIN statement`: shows the plan chosen for the statement, instead of executing it.
+    Explain(Box<Statement>),
+    /// `ANALYZE [table]`: collects statistics of the table, or of all tables.
+    Analyze {
+        table: Option<String>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CreateTable {
+    pub name: String,
+    pub columns: Vec<ColumnDef>,
+    /// Given either as a column constraint or a table constraint.
+    pub primary_key: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ColumnDef {
+    pub name: String,
+    pub ty: Type,
+    pub not_null: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CreateIndex {
+    pub name: String,
+    pub table: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Insert {
+    pub table: String,
+    /// Target columns, if given explicitly.
+    pub columns: Option<Vec<String>>,
+    pub rows: Vec<Vec<Expr>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Select {
+    pub projection: Vec<SelectItem>,
+    pub from: Option<FromItem>,
+    pub where_clause: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    pub order_by: Vec<OrderBy>,
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SelectItem {
+    /// `*`
+    Wildcard,
+    Expr {
+        expr: Expr,
+        alias: Option<String>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TableRef {
+    pub name: String,
+    pub alias: Option<String>,
+}
+
+/// An item of the `FROM` clause: a table, or a join of two items.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum FromItem {
+    Table(TableRef),
+    /// `left [INNER] JOIN right ON condition` or `left LEFT [OUTER] JOIN right ON condition`. A
+    /// cross join (`left, right` or `left CROSS JOIN right`) is an inner join without a condition.
+    Join {
+        left: Box<FromItem>,
+        right: Box<FromItem>,
+        join_type: JoinType,
+        condition: Option<Expr>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum JoinType {
+    Inner,
+    Left,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OrderBy {
+    pub expr: Expr,
+    pub descending: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Update {
+    pub table: String,
+    pub assignments: Vec<(String, Expr)>,
+    pub where_clause: Option<Expr>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Delete {
+    pub table: String,
+    pub where_clause: Option<Expr>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Expr {
+    Literal(Literal),
+    Column {
+        table: Option<String>,
+        name: String,
+    },
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    Like {
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        negated: bool,
+    },
+    /// `CASE [operand] WHEN ... THEN ... [ELSE ...] END`. With an operand, each `WHEN` value is
+    /// compared with it; without one, each `WHEN` is a condition.
+    Case {
+        operand: Option<Box<Expr>>,
+        branches: Vec<(Expr, Expr)>,
+        else_result: Option<Box<Expr>>,
+    },
+    /// `CAST(expr AS type)`, also written as a typed literal: `DATE '2021-01-01'`.
+    Cast {
+        expr: Box<Expr>,
+        ty: Type,
+    },
+    Function {
+        name: String,
+        args: FunctionArgs,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Literal {
+    Null,
+    Bool(bool),
+    /// A number as written: digits, with optional fraction and exponent.
+    Number(String),
+    String(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UnaryOp {
+    Not,
+    Minus,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Concat,
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum FunctionArgs {
+    /// `count(*)`
+    Star,
+    List(Vec<Expr>),
+}
+
+/// Keywords which can't be used as unquoted identifiers.
+pub const RESERVED_KEYWORDS: &[&str] = &[
+    "all", "and", "as", "asc", "by", "case", "cast", "create", "cross", "delete", "desc",
+    "distinct", "drop", "else", "end", "exists", "false", "from", "group", "having", "if", "in",
+    "index", "inner", "insert", "into", "is", "join", "key", "left", "like", "limit", "not",
+    "null", "offset", "on", "or", "order", "outer", "primary", "select", "set", "table", "then",
+    "true", "unique", "update", "values", "when", "where",
+];
+
+/// SQL name of a type, as accepted by the parser.
+pub fn type_name(ty: Type) -> &'static str {
+    match ty {
+        Type::String => "TEXT",
+        Type::Int8 => "BIGINT",
+        Type::Bool => "BOOLEAN",
+        Type::Int2 => "SMALLINT",
+        Type::Int4 => "INTEGER",
+        Type::Float8 => "DOUBLE PRECISION",
+        Type::Bytea => "BYTEA",
+        Type::Date => "DATE",
+        Type::Timestamp => "TIMESTAMP",
+        Type::Numeric => "NUMERIC",
+    }
+}
+
+/// Formats an identifier, quoting it if it wouldn't be parsed back as the same name.
+pub struct Ident<'a>(pub &'a str);
+
+impl fmt::Display for Ident<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.0;
+        let plain = name
+            .chars()
+            .next()
+            .map_or(false, |c| c.is_ascii_lowercase() || c == '_')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            && !RESERVED_KEYWORDS.contains(&name);
+        if plain {
+            write!(f, "{}", name)
+        } else {
+            write!(f, "\"{}\"", name.replace('"', "\"\""))
+        }
+    }
+}
+
+/// Formats items separated by commas.
+pub(crate) struct List<'a, T>(pub &'a [T]);
+
+impl<T: fmt::Display> fmt::Display for List<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, item) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", item)?;
+        }
+        Ok(())
+    }
+}
+
+fn idents(names: &[String]) -> Vec<Ident<'_>> {
+    names.iter().map(|name| Ident(name)).collect()
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Statement::CreateTable(create) => {
+                write!(f, "CREATE TABLE {} (", Ident(&create.name))?;
+                for (index, column) in create.columns.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} {}", Ident(&column.name), type_name(column.ty))?;
+                    if column.not_null {
+                        write!(f, " NOT NULL")?;
+                    }
+                }
+                if !create.primary_key.is_empty() {
+                    write!(f, ", PRIMARY KEY ({})", List(&idents(&create.primary_key)))?;
+                }
+                write!(f, ")")
+            }
+            Statement::DropTable { name, if_exists } => {
+                write!(f, "DROP TABLE ")?;
+                if *if_exists {
+                    write!(f, "IF EXISTS ")?;
+                }
+                write!(f, "{}", Ident(name))
+            }
+            Statement::CreateIndex(create) => write!(
+                f,
+                "CREATE {}INDEX {} ON {} ({})",
+                if create.unique { "UNIQUE " } else { "" },
+                Ident(&create.name),
+                Ident(&create.table),
+                List(&idents(&create.columns))
+            ),
+            Statement::Insert(insert) => {
+                write!(f, "INSERT INTO {}", Ident(&insert.table))?;
+                if let Some(columns) = &insert.columns {
+                    write!(f, " ({})", List(&idents(columns)))?;
+                }
+                write!(f, " VALUES ")?;
+                for (index, row) in insert.rows.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "({})", List(row))?;
+                }
+                Ok(())
+            }
+            Statement::Select(select) => write!(f, "{}", select),
+            Statement::Update(update) => {
+                write!(f, "UPDATE {} SET ", Ident(&update.table))?;
+                for (index, (column, value)) in update.assignments.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} = {}", Ident(column), value)?;
+                }
+                if let Some(condition) = &update.where_clause {
+                    write!(f, " WHERE {}", condition)?;
+                }
+                Ok(())
+            }
+            Statement::Delete(delete) => {
+                write!(f, "DELETE FROM {}", Ident(&delete.table))?;
+                if let Some(condition) = &delete.where_clause {
+                    write!(f, " WHERE {}", condition)?;
+                }
+                Ok(())
+            }
+            Statement::Explain(statement) => write!(f, "EXPLAIN {}", statement),
+            Statement::Analyze { table } => {
+                write!(f, "ANALYZE")?;
+                if let Some(table) = table {
+                    write!(f, " {}", Ident(table))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Select {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SELECT {}", List(&self.projection))?;
+        if let Some(from) = &self.from {
+            write!(f, " FROM {}", from)?;
+        }
+        if let Some(condition) = &self.where_clause {
+            write!(f, " WHERE {}", condition)?;
+        }
+        if !self.group_by.is_empty() {
+            write!(f, " GROUP BY {}", List(&self.group_by))?;
+        }
+        if let Some(condition) = &self.having {
+            write!(f, " HAVING {}", condition)?;
+        }
+        if !self.order_by.is_empty() {
+            write!(f, " ORDER BY {}", List(&self.order_by))?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {}", limit)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " OFFSET {}", offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FromItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FromItem::Table(table) => {
+                write!(f, "{}", Ident(&table.name))?;
+                if let Some(alias) = &table.alias {
+                    write!(f, " AS {}", Ident(alias))?;
+                }
+                Ok(())
+            }
+            FromItem::Join {
+                left,
+                right,
+                join_type,
+                condition,
+            } => {
+                let join = match (join_type, condition) {
+                    (JoinType::Inner, None) => "CROSS JOIN",
+                    (JoinType::Inner, Some(_)) => "JOIN",
+                    (JoinType::Left, _) => "LEFT JOIN",
+                };
+                write!(f, "{} {} {}", left, join, right)?;
+                if let Some(condition) = condition {
+                    write!(f, " ON {}", condition)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelectItem::Wildcard => write!(f, "*"),
+            SelectItem::Expr { expr, alias } => {
+                write!(f, "{}", expr)?;
+                if let Some(alias) = alias {
+                    write!(f, " AS {}", Ident(alias))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for OrderBy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.expr)?;
+        if self.descending {
+            write!(f, " DESC")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Literal(literal) => write!(f, "{}", literal),
+            Expr::Column { table, name } => {
+                if let Some(table) = table {
+                    write!(f, "{}.", Ident(table))?;
+                }
+                write!(f, "{}", Ident(name))
+            }
+            Expr::Unary { op, expr } => match op {
+                UnaryOp::Not => write!(f, "(NOT {})", expr),
+                UnaryOp::Minus => write!(f, "(- {})", expr),
+            },
+            Expr::Binary { op, left, right } => write!(f, "({} {} {})", left, op, right),
+            Expr::IsNull { expr, negated } => {
+                write!(
+                    f,
+                    "({} IS {}NULL)",
+                    expr,
+                    if *negated { "NOT " } else { "" }
+                )
+            }
+            Expr::Like {
+                expr,
+                pattern,
+                negated,
+            } => write!(
+                f,
+                "({} {}LIKE {})",
+                expr,
+                if *negated { "NOT " } else { "" },
+                pattern
+            ),
+            Expr::Case {
+                operand,
+                branches,
+                else_result,
+            } => {
+                write!(f, "CASE")?;
+                if let Some(operand) = operand {
+                    write!(f, " {}", operand)?;
+                }
+                for (condition, result) in branches {
+                    write!(f, " WHEN {} THEN {}", condition, result)?;
+                }
+                if let Some(else_result) = else_result {
+                    write!(f, " ELSE {}", else_result)?;
+                }
+                write!(f, " END")
+            }
+            Expr::Cast { expr, ty } => write!(f, "CAST({} AS {})", expr, type_name(*ty)),
+            Expr::Function { name, args } => {
+                write!(f, "{}(", Ident(name))?;
+                match args {
+                    FunctionArgs::Star => write!(f, "*")?,
+                    FunctionArgs::List(args) => write!(f, "{}", List(args))?,
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Literal::Null => write!(f, "NULL"),
+            Literal::Bool(true) => write!(f, "TRUE"),
+            Literal::Bool(false) => write!(f, "FALSE"),
+            Literal::Number(number) => write!(f, "{}", number),
+            Literal::String(s) => write!(f, "'{}'", s.replace('\'', "''")),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            BinaryOp::Or => "OR",
+            BinaryOp::And => "AND",
+            BinaryOp::Eq => "=",
+            BinaryOp::NotEq => "<>",
+            BinaryOp::Lt => "<",
+            BinaryOp::LtEq => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::GtEq => ">=",
+            BinaryOp::Concat => "||",
+            BinaryOp::Plus => "+",
+            BinaryOp::Minus => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulo => "%",
+        };
+        write!(f, "{}", s)
+    }
+}
@@ -0,0 +1,1309 @@
+//! Semantic analysis: resolves the names in a parsed statement against the catalog and
+//! type-checks its expressions, producing a `LogicalPlan`.
+//!
+//! Types of expressions follow a simplified version of PostgreSQL's rules:
+//! - String literals and `NULL` have no type of their own until the context gives them one, so
+//!   `date_column = '2021-01-01'` compares dates. The literal is converted when binding, and an
+//!   invalid one is an error. Without context (e.g. `SELECT 'a'`), they are `TEXT`.
+//! - Integer literals are `INTEGER`, or `BIGINT`/`NUMERIC` if they don't fit. Literals with a
+//!   fraction are `NUMERIC`, and ones with an exponent `DOUBLE PRECISION`.
+//! - Operands of different numeric types are converted to the wider one, in the order
+//!   `SMALLINT`, `INTEGER`, `BIGINT`, `NUMERIC`, `DOUBLE PRECISION`. `DATE` is converted to
+//!   `TIMESTAMP`. Other types are never converted implicitly, except that a value assigned to a
+//!   column may be converted to any other numeric type.
+//! - `||` converts a non-string operand to a string, as long as the other one is a string.
+//!
+//! In a `SELECT` with `GROUP BY`, `HAVING` or aggregate functions, the select list, `HAVING` and
+//! `ORDER BY` are computed from the groups, so they can only use the input columns inside an
+//! aggregate, or as part of an expression which is (structurally) equal to a `GROUP BY` one.
+//!
+//! Assigning the `NULL` literal to a `NOT NULL` column is rejected here. Other values which turn
+//! out to be NULL can only be detected when executing the statement.
+
+use crate::ast::{
+    self, type_name, BinaryOp, FunctionArgs, Literal, SelectItem, Statement, UnaryOp,
+};
+use crate::catalog::{Catalog, Error as CatalogError, TableDef};
+use crate::logical_plan::{
+    AggregateCall, AggregateFunction, Expr, Function, JoinType, LogicalPlan, SortKey,
+};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+use table::datum::text::ParseError as DatumParseError;
+use table::datum::{Datum, Nullability, Numeric, Type};
+use table::row::Column;
+
+/// Maximum number of tables in a `FROM` clause. The planner represents sets of joined tables as
+/// bitmasks of a `u32`.
+pub const MAX_FROM_TABLES: usize = 32;
+
+#[derive(Debug)]
+pub enum Error {
+    Catalog(CatalogError),
+    TableExists(String),
+    TableNotFound(String),
+    IndexExists(String),
+    ColumnNotFound(String),
+    /// A column name matches more than one column.
+    AmbiguousColumn(String),
+    /// A column is named more than once in a column list.
+    DuplicateColumn(String),
+    /// A qualified column name refers to a table which is not in the `FROM` clause.
+    MissingFromEntry(String),
+    /// Two tables of the `FROM` clause have the same name (or alias).
+    DuplicateTableName(String),
+    /// The `FROM` clause has more than `MAX_FROM_TABLES` tables.
+    TooManyTables(usize),
+    /// `SELECT *` without a `FROM` clause.
+    WildcardWithoutFrom,
+    /// An expression has the wrong type for its context, described by `context`.
+    TypeMismatch {
+        context: String,
+        expected: Type,
+        found: Type,
+    },
+    /// There's no operator for the given operand types. `left` is None for unary operators.
+    OperatorMismatch {
+        op: String,
+        left: Option<Type>,
+        right: Type,
+    },
+    CannotCast {
+        from: Type,
+        to: Type,
+    },
+    InvalidLiteral(DatumParseError),
+    NumberOutOfRange(String),
+    /// A `NULL` value is assigned to a `NOT NULL` column.
+    NotNullViolation(String),
+    /// There's no function with the given name, or taking arguments of the given types (as in
+    /// `abs(TEXT)`).
+    UnknownFunction(String),
+    WrongArgumentCount {
+        function: String,
+        found: usize,
+    },
+    /// An `INSERT` row has a different number of values than the number of target columns.
+    WrongValueCount {
+        expected: usize,
+        found: usize,
+    },
+    /// `ORDER BY n` where n is not a position in the select list.
+    InvalidOrderByPosition(u64),
+    /// A column is used outside of an aggregate in a query with aggregates, without being grouped.
+    UngroupedColumn(String),
+    /// An aggregate function is used where its argument can't be computed, for example in `WHERE`,
+    /// or in the argument of another aggregate.
+    AggregateNotAllowed,
+    /// A statement the parser doesn't produce, but which can be built directly, like a nested
+    /// `EXPLAIN`.
+    Unsupported(&'static str),
+}
+
+impl From<CatalogError> for Error {
+    fn from(err: CatalogError) -> Self {
+        Error::Catalog(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Catalog(err) => write!(f, "catalog error: {:?}", err),
+            Error::TableExists(name) => write!(f, "table {:?} already exists", name),
+            Error::TableNotFound(name) => write!(f, "table {:?} does not exist", name),
+            Error::IndexExists(name) => write!(f, "index {:?} already exists", name),
+            Error::ColumnNotFound(name) => write!(f, "column {:?} does not exist", name),
+            Error::AmbiguousColumn(name) => write!(f, "column reference {:?} is ambiguous", name),
+            Error::DuplicateColumn(name) => write!(f, "column {:?} specified more than once", name),
+            Error::MissingFromEntry(name) => {
+                write!(f, "missing FROM-clause entry for table {:?}", name)
+            }
+            Error::DuplicateTableName(name) => {
+                write!(f, "table name {:?} specified more than once", name)
+            }
+            Error::TooManyTables(count) => write!(
+                f,
+                "FROM clause has {} tables, at most {} are supported",
+                count, MAX_FROM_TABLES
+            ),
+            Error::WildcardWithoutFrom => write!(f, "SELECT * with no tables specified"),
+            Error::TypeMismatch {
+                context,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} must be of type {}, not {}",
+                context,
+                type_name(*expected),
+                type_name(*found)
+            ),
+            Error::OperatorMismatch { op, left, right } => {
+                write!(f, "operator does not exist: ")?;
+                if let Some(left) = left {
+                    write!(f, "{} ", type_name(*left))?;
+                }
+                write!(f, "{} {}", op, type_name(*right))
+            }
+            Error::CannotCast { from, to } => write!(
+                f,
+                "cannot cast type {} to {}",
+                type_name(*from),
+                type_name(*to)
+            ),
+            Error::InvalidLiteral(err) => write!(f, "{}", err),
+            Error::NumberOutOfRange(number) => write!(f, "number out of range: {}", number),
+            Error::NotNullViolation(column) => write!(
+                f,
+                "null value in column {:?} violates not-null constraint",
+                column
+            ),
+            Error::UnknownFunction(name) => write!(f, "function {} does not exist", name),
+            Error::WrongArgumentCount { function, found } => {
+                write!(f, "function {} does not take {} arguments", function, found)
+            }
+            Error::WrongValueCount { expected, found } => write!(
+                f,
+                "INSERT has {} values for {} target columns",
+                found, expected
+            ),
+            Error::InvalidOrderByPosition(position) => {
+                write!(f, "ORDER BY position {} is not in select list", position)
+            }
+            Error::UngroupedColumn(name) => write!(
+                f,
+                "column {:?} must appear in the GROUP BY clause or be used in an aggregate function",
+                name
+            ),
+            Error::AggregateNotAllowed => write!(f, "aggregate functions are not allowed here"),
+            Error::Unsupported(feature) => write!(f, "{} is not supported", feature),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub struct Binder<'a, 'b> {
+    catalog: &'a Catalog<'b>,
+}
+
+impl<'a, 'b> Binder<'a, 'b> {
+    pub fn new(catalog: &'a Catalog<'b>) -> Self {
+        Binder { catalog }
+    }
+
+    pub async fn bind(&self, statement: &Statement) -> Result<LogicalPlan> {
+        match statement {
+            Statement::Explain(statement) => Ok(LogicalPlan::Explain {
+                input: Box::new(self.bind_statement(statement).await?),
+            }),
+            statement => self.bind_statement(statement).await,
+        }
+    }
+
+    async fn bind_statement(&self, statement: &Statement) -> Result<LogicalPlan> {
+        match statement {
+            Statement::CreateTable(create) => self.bind_create_table(create).await,
+            Statement::DropTable { name, if_exists } => {
+                if !if_exists && self.catalog.get_table(name).await?.is_none() {
+                    return Err(Error::TableNotFound(name.clone()));
+                }
+                Ok(LogicalPlan::DropTable {
+                    name: name.clone(),
+                    if_exists: *if_exists,
+                })
+            }
+            Statement::CreateIndex(create) => {
+                if self.catalog.get_index(&create.name).await?.is_some() {
+                    return Err(Error::IndexExists(create.name.clone()));
+                }
+                let table = self.table(&create.table).await?;
+                Ok(LogicalPlan::CreateIndex {
+                    name: create.name.clone(),
+                    table: table.name.clone(),
+                    columns: column_indexes(table.schema.columns(), &create.columns)?,
+                    unique: create.unique,
+                })
+            }
+            Statement::Insert(insert) => self.bind_insert(insert).await,
+            Statement::Select(select) => self.bind_select(select).await,
+            Statement::Update(update) => self.bind_update(update).await,
+            Statement::Delete(delete) => {
+                let table = self.table(&delete.table).await?;
+                let scope = Scope::table(&table, &table.name);
+                let input = filter(
+                    &scope,
+                    LogicalPlan::Scan {
+                        table: table.clone(),
+                    },
+                    &delete.where_clause,
+                )?;
+                Ok(LogicalPlan::Delete {
+                    table,
+                    input: Box::new(input),
+                })
+            }
+            Statement::Analyze { table } => {
+                let tables = match table {
+                    Some(name) => vec![self.table(name).await?.name],
+                    None => self
+                        .catalog
+                        .list_tables()
+                        .await?
+                        .into_iter()
+                        .map(|table| table.name)
+                        .collect(),
+                };
+                Ok(LogicalPlan::Analyze { tables })
+            }
+            Statement::Explain(_) => Err(Error::Unsupported("nested EXPLAIN")),
+        }
+    }
+
+    async fn table(&self, name: &str) -> Result<TableDef> {
+        match self.catalog.get_table(name).await? {
+            Some(table) => Ok(table),
+            None => Err(Error::TableNotFound(name.to_string())),
+        }
+    }
+
+    async fn bind_create_table(&self, create: &ast::CreateTable) -> Result<LogicalPlan> {
+        if self.catalog.get_table(&create.name).await?.is_some() {
+            return Err(Error::TableExists(create.name.clone()));
+        }
+        let mut columns: Vec<Column> = create
+            .columns
+            .iter()
+            .map(|column| Column {
+                name: column.name.clone(),
+                ty: column.ty,
+                nullability: if column.not_null {
+                    Nullability::NotNull
+                } else {
+                    Nullability::Nullable
+                },
+            })
+            .collect();
+        let mut names = HashSet::new();
+        for column in &columns {
+            if !names.insert(&column.name) {
+                return Err(Error::DuplicateColumn(column.name.clone()));
+            }
+        }
+        let primary_key = column_indexes(&columns, &create.primary_key)?;
+        // Primary key columns are implicitly NOT NULL.
+        for &index in &primary_key {
+            columns[index].nullability = Nullability::NotNull;
+        }
+        Ok(LogicalPlan::CreateTable {
+            name: create.name.clone(),
+            columns,
+            primary_key,
+        })
+    }
+
+    async fn bind_insert(&self, insert: &ast::Insert) -> Result<LogicalPlan> {
+        let table = self.table(&insert.table).await?;
+        let columns = table.schema.columns();
+        let targets = match &insert.columns {
+            Some(names) => column_indexes(columns, names)?,
+            None => (0..columns.len()).collect(),
+        };
+        let scope = Scope::default();
+        let mut rows = vec![];
+        for values in &insert.rows {
+            if values.len() != targets.len() {
+                return Err(Error::WrongValueCount {
+                    expected: targets.len(),
+                    found: values.len(),
+                });
+            }
+            let mut row: Vec<Expr> = columns
+                .iter()
+                .map(|column| Expr::Constant {
+                    value: Datum::Null,
+                    ty: column.ty,
+                })
+                .collect();
+            for (&index, value) in targets.iter().zip(values) {
+                row[index] = assign(&columns[index], bind_expr(&scope, value)?)?;
+            }
+            for (column, value) in columns.iter().zip(&row) {
+                check_not_null(column, value)?;
+            }
+            rows.push(row);
+        }
+        Ok(LogicalPlan::Insert {
+            input: Box::new(LogicalPlan::Values {
+                columns: columns.to_vec(),
+                rows,
+            }),
+            table,
+        })
+    }
+
+    async fn bind_select(&self, select: &ast::Select) -> Result<LogicalPlan> {
+        let (scope, input) = match &select.from {
+            Some(from) => self.bind_from(from).await?,
+            None => (
+                Scope::default(),
+                LogicalPlan::Values {
+                    columns: vec![],
+                    rows: vec![vec![]],
+                },
+            ),
+        };
+        let mut plan = filter(&scope, input, &select.where_clause)?;
+
+        let is_aggregate = !select.group_by.is_empty()
+            || select.having.is_some()
+            || select.projection.iter().any(|item| match item {
+                SelectItem::Expr { expr, .. } => contains_aggregate(expr),
+                SelectItem::Wildcard => false,
+            })
+            || select
+                .order_by
+                .iter()
+                .any(|order_by| contains_aggregate(&order_by.expr));
+        let scope = if is_aggregate {
+            let group_by = select
+                .group_by
+                .iter()
+                .map(|expr| bind_expr(&scope, expr))
+                .collect::<Result<_>>()?;
+            Scope::aggregation(scope, group_by)
+        } else {
+            scope
+        };
+
+        let mut exprs = vec![];
+        let mut columns = vec![];
+        for item in &select.projection {
+            match item {
+                SelectItem::Wildcard => {
+                    if select.from.is_none() {
+                        return Err(Error::WildcardWithoutFrom);
+                    }
+                    let input = scope
+                        .aggregation
+                        .as_ref()
+                        .map_or(&scope, |aggregation| &aggregation.input);
+                    for (index, column) in input.columns.iter().enumerate() {
+                        let expr = input.column_expr(index);
+                        exprs.push(match &scope.aggregation {
+                            Some(aggregation) => match aggregation.group_column(&expr) {
+                                Some(expr) => expr,
+                                None => {
+                                    return Err(Error::UngroupedColumn(column.column.name.clone()))
+                                }
+                            },
+                            None => expr,
+                        });
+                        columns.push(column.column.clone());
+                    }
+                }
+                SelectItem::Expr { expr, alias } => {
+                    let bound = bind_expr(&scope, expr)?;
+                    columns.push(Column {
+                        name: alias.clone().unwrap_or_else(|| output_name(expr)),
+                        ty: bound.ty(),
+                        nullability: if bound.nullable() {
+                            Nullability::Nullable
+                        } else {
+                            Nullability::NotNull
+                        },
+                    });
+                    exprs.push(bound);
+                }
+            }
+        }
+
+        let having = match &select.having {
+            Some(condition) => Some(coerce(
+                bind_expr(&scope, condition)?,
+                Type::Bool,
+                "argument of HAVING",
+            )?),
+            None => None,
+        };
+
+        // Sort keys are computed from the input rows, so they can refer to columns which are not
+        // in the select list. A key can also name a column of the select list (by its output name,
+        // or position), in which case the corresponding expression is used.
+        let mut sort_keys = None;
+        if !select.order_by.is_empty() {
+            let mut keys = vec![];
+            for order_by in &select.order_by {
+                let expr = match &order_by.expr {
+                    ast::Expr::Literal(Literal::Number(number)) => {
+                        let position = number
+                            .parse::<u64>()
+                            .map_err(|_| Error::NumberOutOfRange(number.clone()))?;
+                        if position == 0 || position > exprs.len() as u64 {
+                            return Err(Error::InvalidOrderByPosition(position));
+                        }
+                        exprs[position as usize - 1].clone()
+                    }
+                    ast::Expr::Column { table: None, name } => {
+                        let mut matches =
+                            columns.iter().enumerate().filter(|(_, c)| c.name == *name);
+                        match (matches.next(), matches.next()) {
+                            (Some((index, _)), None) => exprs[index].clone(),
+                            (Some(_), Some(_)) => return Err(Error::AmbiguousColumn(name.clone())),
+                            (None, _) => bind_expr(&scope, &order_by.expr)?,
+                        }
+                    }
+                    expr => bind_expr(&scope, expr)?,
+                };
+                keys.push(SortKey {
+                    expr,
+                    descending: order_by.descending,
+                });
+            }
+            sort_keys = Some(keys);
+        }
+
+        // All aggregates are known once all the expressions using them are bound.
+        if let Some(aggregation) = scope.aggregation {
+            plan = LogicalPlan::Aggregate {
+                input: Box::new(plan),
+                group_by: aggregation.group_by,
+                aggregates: aggregation.aggregates.into_inner(),
+            };
+        }
+        if let Some(predicate) = having {
+            plan = LogicalPlan::Filter {
+                input: Box::new(plan),
+                predicate,
+            };
+        }
+        if let Some(keys) = sort_keys {
+            plan = LogicalPlan::Sort {
+                input: Box::new(plan),
+                keys,
+            };
+        }
+
+        plan = LogicalPlan::Project {
+            input: Box::new(plan),
+            exprs,
+            columns,
+        };
+        if select.limit.is_some() || select.offset.is_some() {
+            plan = LogicalPlan::Limit {
+                input: Box::new(plan),
+                limit: select.limit,
+                offset: select.offset.unwrap_or(0),
+            };
+        }
+        Ok(plan)
+    }
+
+    /// Bind the tables of the `FROM` clause, joined from left to right.
+    async fn bind_from(&self, from: &ast::FromItem) -> Result<(Scope, LogicalPlan)> {
+        let mut tables = vec![];
+        let (first, joins) = split_joins(from);
+        if joins.len() + 1 > MAX_FROM_TABLES {
+            return Err(Error::TooManyTables(joins.len() + 1));
+        }
+        let (mut scope, mut plan) = self.bind_table(first, &mut tables).await?;
+        for (right, join_type, condition) in joins {
+            let (mut right_scope, right_plan) = self.bind_table(right, &mut tables).await?;
+            let join_type = match join_type {
+                ast::JoinType::Inner => JoinType::Inner,
+                ast::JoinType::Left => {
+                    for column in &mut right_scope.columns {
+                        column.column.nullability = Nullability::Nullable;
+                    }
+                    JoinType::Left
+                }
+            };
+            scope.columns.extend(right_scope.columns);
+            let condition = match condition {
+                Some(condition) => Some(coerce(
+                    bind_expr(&scope, condition)?,
+                    Type::Bool,
+                    "argument of JOIN/ON",
+                )?),
+                None => None,
+            };
+            plan = LogicalPlan::Join {
+                left: Box::new(plan),
+                right: Box::new(right_plan),
+                join_type,
+                condition,
+            };
+        }
+        Ok((scope, plan))
+    }
+
+    /// Bind a table of the `FROM` clause. `tables` are the names (or aliases) of the tables bound
+    /// so far, which must be unique.
+    async fn bind_table(
+        &self,
+        item: &ast::FromItem,
+        tables: &mut Vec<String>,
+    ) -> Result<(Scope, LogicalPlan)> {
+        let table_ref = match item {
+            ast::FromItem::Table(table_ref) => table_ref,
+            ast::FromItem::Join { .. } => {
+                return Err(Error::Unsupported("a join on the right side of a join"))
+            }
+        };
+        let table = self.table(&table_ref.name).await?;
+        let alias = table_ref.alias.as_ref().unwrap_or(&table_ref.name);
+        if tables.contains(alias) {
+            return Err(Error::DuplicateTableName(alias.clone()));
+        }
+        tables.push(alias.clone());
+        Ok((Scope::table(&table, alias), LogicalPlan::Scan { table }))
+    }
+
+    async fn bind_update(&self, update: &ast::Update) -> Result<LogicalPlan> {
+        let table = self.table(&update.table).await?;
+        let scope = Scope::table(&table, &table.name);
+        let columns = table.schema.columns();
+        let mut assignments = vec![];
+        let mut assigned = HashSet::new();
+        for (name, value) in &update.assignments {
+            let index = match table.schema.index_of(name) {
+                Some(index) => index,
+                None => return Err(Error::ColumnNotFound(name.clone())),
+            };
+            if !assigned.insert(index) {
+                return Err(Error::DuplicateColumn(name.clone()));
+            }
+            let value = assign(&columns[index], bind_expr(&scope, value)?)?;
+            check_not_null(&columns[index], &value)?;
+            assignments.push((index, value));
+        }
+        let input = filter(
+            &scope,
+            LogicalPlan::Scan {
+                table: table.clone(),
+            },
+            &update.where_clause,
+        )?;
+        Ok(LogicalPlan::Update {
+            table,
+            input: Box::new(input),
+            assignments,
+        })
+    }
+}
+
+type Join<'s> = (&'s ast::FromItem, ast::JoinType, Option<&'s ast::Expr>);
+
+/// Split a `FROM` clause into its first item, and the joins with the following ones (the right
+/// item, join type and condition), in order.
+fn split_joins(mut item: &ast::FromItem) -> (&ast::FromItem, Vec<Join<'_>>) {
+    let mut joins = vec![];
+    while let ast::FromItem::Join {
+        left,
+        right,
+        join_type,
+        condition,
+    } = item
+    {
+        joins.push((&**right, *join_type, condition.as_ref()));
+        item = left;
+    }
+    joins.reverse();
+    (item, joins)
+}
+
+/// Indexes of the named columns.
+fn column_indexes(columns: &[Column], names: &[String]) -> Result<Vec<usize>> {
+    let mut result = vec![];
+    for name in names {
+        let index = match columns.iter().position(|column| column.name == *name) {
+            Some(index) => index,
+            None => return Err(Error::ColumnNotFound(name.clone())),
+        };
+        if result.contains(&index) {
+            return Err(Error::DuplicateColumn(name.clone()));
+        }
+        result.push(index);
+    }
+    Ok(result)
+}
+
+/// Add a filter on the `WHERE` condition, if there is one.
+fn filter(scope: &Scope, input: LogicalPlan, condition: &Option<ast::Expr>) -> Result<LogicalPlan> {
+    Ok(match condition {
+        Some(condition) => LogicalPlan::Filter {
+            input: Box::new(input),
+            predicate: coerce(
+                bind_expr(scope, condition)?,
+                Type::Bool,
+                "argument of WHERE",
+            )?,
+        },
+        None => input,
+    })
+}
+
+fn check_not_null(column: &Column, value: &Expr) -> Result<()> {
+    if column.nullability == Nullability::NotNull && value.is_null_constant() {
+        return Err(Error::NotNullViolation(column.name.clone()));
+    }
+    Ok(())
+}
+
+/// Name of a select list item without an alias.
+fn output_name(expr: &ast::Expr) -> String {
+    match expr {
+        ast::Expr::Column { name, .. } | ast::Expr::Function { name, .. } => name.clone(),
+        _ => "?column?".to_string(),
+    }
+}
+
+#[derive(Debug)]
+struct ScopeColumn {
+    /// Name (or alias) of the table the column comes from.
+    table: Option<String>,
+    column: Column,
+}
+
+/// The columns which expressions can refer to, in the order of the input row.
+#[derive(Debug, Default)]
+struct Scope {
+    columns: Vec<ScopeColumn>,
+    /// Set for expressions computed from the output of an aggregation, which has no columns of
+    /// its own in the scope.
+    aggregation: Option<Box<Aggregation>>,
+}
+
+#[derive(Debug)]
+struct Aggregation {
+    /// Scope of the aggregation's input.
+    input: Scope,
+    group_by: Vec<Expr>,
+    /// Aggregates used by the expressions bound so far.
+    aggregates: RefCell<Vec<AggregateCall>>,
+}
+
+impl Aggregation {
+    /// Bind the expression if it's an aggregate, or a `GROUP BY` expression.
+    fn bind(&self, expr: &ast::Expr) -> Result<Option<Expr>> {
+        if let ast::Expr::Function { name, args } = expr {
+            if let Some(function) = AggregateFunction::lookup(name) {
+                let call = bind_aggregate(&self.input, function, args)?;
+                let mut aggregates = self.aggregates.borrow_mut();
+                let index = match aggregates.iter().position(|other| *other == call) {
+                    Some(index) => index,
+                    None => {
+                        aggregates.push(call.clone());
+                        aggregates.len() - 1
+                    }
+                };
+                return Ok(Some(output_column(
+                    self.group_by.len() + index,
+                    call.column(),
+                )));
+            }
+        }
+        // Expressions which don't bind in the input scope on their own (because of aggregates in
+        // them) can't be grouped.
+        Ok(match bind_expr(&self.input, expr) {
+            Ok(bound) => self.group_column(&bound),
+            Err(_) => None,
+        })
+    }
+
+    fn group_column(&self, expr: &Expr) -> Option<Expr> {
+        let index = self.group_by.iter().position(|group| group == expr)?;
+        Some(output_column(index, expr.group_column()))
+    }
+}
+
+fn output_column(index: usize, column: Column) -> Expr {
+    Expr::Column {
+        index,
+        name: column.name,
+        ty: column.ty,
+        nullability: column.nullability,
+    }
+}
+
+impl Scope {
+    fn table(table: &TableDef, alias: &str) -> Scope {
+        Scope {
+            columns: table
+                .schema
+                .columns()
+                .iter()
+                .map(|column| ScopeColumn {
+                    table: Some(alias.to_string()),
+                    column: column.clone(),
+                })
+                .collect(),
+            aggregation: None,
+        }
+    }
+
+    fn aggregation(input: Scope, group_by: Vec<Expr>) -> Scope {
+        Scope {
+            columns: vec![],
+            aggregation: Some(Box::new(Aggregation {
+                input,
+                group_by,
+                aggregates: RefCell::new(vec![]),
+            })),
+        }
+    }
+
+    fn column_expr(&self, index: usize) -> Expr {
+        let column = &self.columns[index].column;
+        Expr::Column {
+            index,
+            name: column.name.clone(),
+            ty: column.ty,
+            nullability: column.nullability,
+        }
+    }
+
+    fn resolve(&self, table: Option<&str>, name: &str) -> Result<Expr> {
+        let qualified = match table {
+            Some(table) => {
+                if !self
+                    .columns
+                    .iter()
+                    .any(|c| c.table.as_deref() == Some(table))
+                {
+                    return Err(Error::MissingFromEntry(table.to_string()));
+                }
+                format!("{}.{}", table, name)
+            }
+            None => name.to_string(),
+        };
+        let mut matches = self.columns.iter().enumerate().filter(|(_, c)| {
+            c.column.name == name && (table.is_none() || c.table.as_deref() == table)
+        });
+        match (matches.next(), matches.next()) {
+            (Some((index, _)), None) => Ok(self.column_expr(index)),
+            (Some(_), Some(_)) => Err(Error::AmbiguousColumn(qualified)),
+            (None, _) => Err(Error::ColumnNotFound(qualified)),
+        }
+    }
+}
+
+fn bind_expr(scope: &Scope, expr: &ast::Expr) -> Result<Expr> {
+    if let Some(aggregation) = &scope.aggregation {
+        if let Some(bound) = aggregation.bind(expr)? {
+            return Ok(bound);
+        }
+    }
+    Ok(match expr {
+        ast::Expr::Literal(literal) => match literal {
+            Literal::Null => Expr::Constant {
+                value: Datum::Null,
+                ty: Type::String,
+            },
+            Literal::Bool(value) => Expr::Constant {
+                value: Datum::Bool(*value),
+                ty: Type::Bool,
+            },
+            Literal::Number(number) => bind_number(number)?,
+            Literal::String(s) => Expr::Constant {
+                value: Datum::String(s.clone()),
+                ty: Type::String,
+            },
+        },
+        ast::Expr::Column { table, name } => match &scope.aggregation {
+            // Grouped columns are already bound above.
+            Some(aggregation) => {
+                aggregation.input.resolve(table.as_deref(), name)?;
+                return Err(Error::UngroupedColumn(match table {
+                    Some(table) => format!("{}.{}", table, name),
+                    None => name.clone(),
+                }));
+            }
+            None => scope.resolve(table.as_deref(), name)?,
+        },
+        ast::Expr::Unary {
+            op: UnaryOp::Minus,
+            expr,
+        } => match &**expr {
+            // Bind negative numbers as constants, so that e.g. the minimal `INTEGER` is one.
+            ast::Expr::Literal(Literal::Number(number)) => bind_number(&format!("-{}", number))?,
+            expr => {
+                let expr = bind_expr(scope, expr)?;
+                if !is_numeric(expr.ty()) {
+                    return Err(Error::OperatorMismatch {
+                        op: "-".to_string(),
+                        left: None,
+                        right: expr.ty(),
+                    });
+                }
+                Expr::Unary {
+                    op: UnaryOp::Minus,
+                    expr: Box::new(expr),
+                }
+            }
+        },
+        ast::Expr::Unary {
+            op: UnaryOp::Not,
+            expr,
+        } => Expr::Unary {
+            op: UnaryOp::Not,
+            expr: Box::new(coerce(
+                bind_expr(scope, expr)?,
+                Type::Bool,
+                "argument of NOT",
+            )?),
+        },
+        ast::Expr::Binary { op, left, right } => {
+            bind_binary(*op, bind_expr(scope, left)?, bind_expr(scope, right)?)?
+        }
+        ast::Expr::IsNull { expr, negated } => Expr::IsNull {
+            expr: Box::new(bind_expr(scope, expr)?),
+            negated: *negated,
+        },
+        ast::Expr::Like {
+            expr,
+            pattern,
+            negated,
+        } => Expr::Like {
+            expr: Box::new(coerce(
+                bind_expr(scope, expr)?,
+                Type::String,
+                "argument of LIKE",
+            )?),
+            pattern: Box::new(coerce(
+                bind_expr(scope, pattern)?,
+                Type::String,
+                "argument of LIKE",
+            )?),
+            negated: *negated,
+        },
+        ast::Expr::Case {
+            operand,
+            branches,
+            else_result,
+        } => {
+            let operand = match operand {
+                Some(operand) => Some(bind_expr(scope, operand)?),
+                None => None,
+            };
+            let mut conditions = vec![];
+            let mut results = vec![];
+            for (condition, result) in branches {
+                let condition = bind_expr(scope, condition)?;
+                conditions.push(match &operand {
+                    Some(operand) => bind_binary(BinaryOp::Eq, operand.clone(), condition)?,
+                    None => coerce(condition, Type::Bool, "argument of CASE/WHEN")?,
+                });
+                results.push(bind_expr(scope, result)?);
+            }
+            results.push(match else_result {
+                Some(else_result) => bind_expr(scope, else_result)?,
+                None => Expr::Constant {
+                    value: Datum::Null,
+                    ty: Type::String,
+                },
+            });
+            let mut results = unify(results, "CASE result")?;
+            let else_result = results.pop().unwrap();
+            Expr::Case {
+                branches: conditions.into_iter().zip(results).collect(),
+                else_result: Box::new(else_result),
+            }
+        }
+        ast::Expr::Cast { expr, ty } => cast(bind_expr(scope, expr)?, *ty)?,
+        ast::Expr::Function { name, args } => bind_function(scope, name, args)?,
+    })
+}
+
+fn bind_number(number: &str) -> Result<Expr> {
+    let (value, ty) = if number.contains(&['e', 'E'][..]) {
+        match number.parse() {
+            Ok(value) => (Datum::Float8(value), Type::Float8),
+            Err(_) => return Err(Error::NumberOutOfRange(number.to_string())),
+        }
+    } else if let Ok(value) = number.parse() {
+        (Datum::Int4(value), Type::Int4)
+    } else if let Ok(value) = number.parse() {
+        (Datum::Int8(value), Type::Int8)
+    } else if let Ok(value) = Datum::parse(Type::Numeric, number) {
+        (value, Type::Numeric)
+    } else {
+        // Too many digits for a `NUMERIC`.
+        match number.parse::<f64>() {
+            Ok(value) if value.is_finite() => (Datum::Float8(value), Type::Float8),
+            _ => return Err(Error::NumberOutOfRange(number.to_string())),
+        }
+    };
+    Ok(Expr::Constant { value, ty })
+}
+
+fn bind_binary(op: BinaryOp, left: Expr, right: Expr) -> Result<Expr> {
+    let mismatch = |left: &Expr, right: &Expr| Error::OperatorMismatch {
+        op: op.to_string(),
+        left: Some(left.ty()),
+        right: right.ty(),
+    };
+    let (left, right) = match op {
+        BinaryOp::And | BinaryOp::Or => {
+            let context = format!("argument of {}", op);
+            (
+                coerce(left, Type::Bool, &context)?,
+                coerce(right, Type::Bool, &context)?,
+            )
+        }
+        BinaryOp::Concat => {
+            let is_string = |expr: &Expr| expr.ty() == Type::String;
+            if !is_string(&left) && !is_string(&right) {
+                return Err(mismatch(&left, &right));
+            }
+            (cast(left, Type::String)?, cast(right, Type::String)?)
+        }
+        _ => {
+            let ty = match common_expr_type(&left, &right) {
+                Some(ty) => ty,
+                None => return Err(mismatch(&left, &right)),
+            };
+            let valid = match op {
+                BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Multiply | BinaryOp::Divide => {
+                    is_numeric(ty)
+                }
+                BinaryOp::Modulo => is_numeric(ty) && ty != Type::Float8,
+                _ => true,
+            };
+            if !valid {
+                return Err(mismatch(&left, &right));
+            }
+            (coerce(left, ty, "operand")?, coerce(right, ty, "operand")?)
+        }
+    };
+    Ok(Expr::Binary {
+        op,
+        left: Box::new(left),
+        right: Box::new(right),
+    })
+}
+
+fn bind_function(scope: &Scope, name: &str, args: &FunctionArgs) -> Result<Expr> {
+    // Aggregates are bound by `Aggregation::bind`, when they are allowed.
+    if AggregateFunction::lookup(name).is_some() {
+        return Err(Error::AggregateNotAllowed);
+    }
+    let args = match args {
+        FunctionArgs::Star => return Err(Error::UnknownFunction(format!("{}(*)", name))),
+        FunctionArgs::List(args) => args
+            .iter()
+            .map(|arg| bind_expr(scope, arg))
+            .collect::<Result<Vec<_>>>()?,
+    };
+    let function = match Function::lookup(name) {
+        Some(function) => function,
+        None => return Err(Error::UnknownFunction(name.to_string())),
+    };
+    let signature = |args: &[Expr]| {
+        let types: Vec<_> = args.iter().map(|arg| type_name(arg.ty())).collect();
+        Error::UnknownFunction(format!("{}({})", name, types.join(", ")))
+    };
+    let args = match function {
+        Function::Coalesce => {
+            if args.is_empty() {
+                return Err(Error::WrongArgumentCount {
+                    function: name.to_string(),
+                    found: 0,
+                });
+            }
+            unify(args, "argument of coalesce")?
+        }
+        _ => {
+            let (min, max) = match function {
+                Function::Substr => (2, 3),
+                Function::Power => (2, 2),
+                Function::Replace => (3, 3),
+                _ => (1, 1),
+            };
+            if args.len() < min || args.len() > max {
+                return Err(Error::WrongArgumentCount {
+                    function: name.to_string(),
+                    found: args.len(),
+                });
+            }
+            // The type of each argument, or None for any numeric type.
+            let parameter = |index: usize| match function {
+                Function::Abs | Function::Round | Function::Floor | Function::Ceil => None,
+                Function::Sqrt | Function::Power => Some(Type::Float8),
+                Function::Substr if index > 0 => Some(Type::Int4),
+                _ => Some(Type::String),
+            };
+            let valid = args
+                .iter()
+                .enumerate()
+                .all(|(index, arg)| match parameter(index) {
+                    None => is_numeric(arg.ty()),
+                    Some(ty) => is_unknown(arg) || common_type(arg.ty(), ty) == Some(ty),
+                });
+            if !valid {
+                return Err(signature(&args));
+            }
+            let context = format!("argument of {}", name);
+            args.into_iter()
+                .enumerate()
+                .map(|(index, arg)| match parameter(index) {
+                    None => Ok(arg),
+                    Some(ty) => coerce(arg, ty, &context),
+                })
+                .collect::<Result<_>>()?
+        }
+    };
+    Ok(Expr::Function { function, args })
+}
+
+/// Bind an aggregate call, whose argument is computed from the input rows.
+fn bind_aggregate(
+    scope: &Scope,
+    function: AggregateFunction,
+    args: &FunctionArgs,
+) -> Result<AggregateCall> {
+    let name = function.name();
+    let arg = match args {
+        FunctionArgs::Star if function == AggregateFunction::Count => None,
+        FunctionArgs::Star => return Err(Error::UnknownFunction(format!("{}(*)", name))),
+        FunctionArgs::List(args) if args.len() == 1 => Some(bind_expr(scope, &args[0])?),
+        FunctionArgs::List(args) => {
+            return Err(Error::WrongArgumentCount {
+                function: name.to_string(),
+                found: args.len(),
+            })
+        }
+    };
+    if let Some(arg) = &arg {
+        let valid = match function {
+            AggregateFunction::Sum | AggregateFunction::Avg => is_numeric(arg.ty()),
+            AggregateFunction::Count | AggregateFunction::Min | AggregateFunction::Max => true,
+        };
+        if !valid {
+            return Err(Error::UnknownFunction(format!(
+                "{}({})",
+                name,
+                type_name(arg.ty())
+            )));
+        }
+    }
+    Ok(AggregateCall { function, arg })
+}
+
+/// Whether the expression calls an aggregate function.
+fn contains_aggregate(expr: &ast::Expr) -> bool {
+    match expr {
+        ast::Expr::Literal(_) | ast::Expr::Column { .. } => false,
+        ast::Expr::Unary { expr, .. }
+        | ast::Expr::IsNull { expr, .. }
+        | ast::Expr::Cast { expr, .. } => contains_aggregate(expr),
+        ast::Expr::Binary { left, right, .. } => {
+            contains_aggregate(left) || contains_aggregate(right)
+        }
+        ast::Expr::Like { expr, pattern, .. } => {
+            contains_aggregate(expr) || contains_aggregate(pattern)
+        }
+        ast::Expr::Case {
+            operand,
+            branches,
+            else_result,
+        } => {
+            operand.as_deref().map_or(false, contains_aggregate)
+                || branches.iter().any(|(condition, result)| {
+                    contains_aggregate(condition) || contains_aggregate(result)
+                })
+                || else_result.as_deref().map_or(false, contains_aggregate)
+        }
+        ast::Expr::Function { name, args } => {
+            AggregateFunction::lookup(name).is_some()
+                || match args {
+                    FunctionArgs::Star => false,
+                    FunctionArgs::List(args) => args.iter().any(contains_aggregate),
+                }
+        }
+    }
+}
+
+/// Whether the expression is a string literal or `NULL`, whose type depends on the context.
+fn is_unknown(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Constant {
+            ty: Type::String,
+            ..
+        }
+    )
+}
+
+fn is_numeric(ty: Type) -> bool {
+    numeric_rank(ty).is_some()
+}
+
+/// Position of a numeric type in the order of implicit conversions.
+fn numeric_rank(ty: Type) -> Option<u8> {
+    match ty {
+        Type::Int2 => Some(0),
+        Type::Int4 => Some(1),
+        Type::Int8 => Some(2),
+        Type::Numeric => Some(3),
+        Type::Float8 => Some(4),
+        _ => None,
+    }
+}
+
+/// The type values of both types can be implicitly converted to, if there is one.
+fn common_type(left: Type, right: Type) -> Option<Type> {
+    if left == right {
+        return Some(left);
+    }
+    if let (Some(left_rank), Some(right_rank)) = (numeric_rank(left), numeric_rank(right)) {
+        return Some(if left_rank > right_rank { left } else { right });
+    }
+    match (left, right) {
+        (Type::Date, Type::Timestamp) | (Type::Timestamp, Type::Date) => Some(Type::Timestamp),
+        _ => None,
+    }
+}
+
+/// The type both expressions can be implicitly converted to, taking literals without a type into
+/// account.
+fn common_expr_type(left: &Expr, right: &Expr) -> Option<Type> {
+    if is_unknown(left) {
+        Some(right.ty())
+    } else if is_unknown(right) {
+        Some(left.ty())
+    } else {
+        common_type(left.ty(), right.ty())
+    }
+}
+
+/// Convert the expressions to a common type.
+fn unify(exprs: Vec<Expr>, context: &str) -> Result<Vec<Expr>> {
+    let mut ty: Option<Type> = None;
+    for expr in &exprs {
+        if is_unknown(expr) {
+            continue;
+        }
+        ty = Some(match ty {
+            None => expr.ty(),
+            Some(ty) => match common_type(ty, expr.ty()) {
+                Some(ty) => ty,
+                None => {
+                    return Err(Error::TypeMismatch {
+                        context: context.to_string(),
+                        expected: ty,
+                        found: expr.ty(),
+                    })
+                }
+            },
+        });
+    }
+    let ty = ty.unwrap_or(Type::String);
+    exprs
+        .into_iter()
+        .map(|expr| coerce(expr, ty, context))
+        .collect()
+}
+
+/// Implicitly convert the expression to the given type.
+fn coerce(expr: Expr, ty: Type, context: &str) -> Result<Expr> {
+    if expr.ty() == ty {
+        return Ok(expr);
+    }
+    if is_unknown(&expr) {
+        return cast(expr, ty);
+    }
+    if common_type(expr.ty(), ty) == Some(ty) {
+        cast(expr, ty)
+    } else {
+        Err(Error::TypeMismatch {
+            context: context.to_string(),
+            expected: ty,
+            found: expr.ty(),
+        })
+    }
+}
+
+/// Convert a value assigned to the column to the column's type.
+fn assign(column: &Column, value: Expr) -> Result<Expr> {
+    if is_numeric(column.ty) && is_numeric(value.ty()) {
+        cast(value, column.ty)
+    } else {
+        coerce(
+            value,
+            column.ty,
+            &format!("value of column {:?}", column.name),
+        )
+    }
+}
+
+/// Explicitly convert the expression to the given type. Literals are converted right away.
+fn cast(expr: Expr, ty: Type) -> Result<Expr> {
+    let from = expr.ty();
+    if from == ty {
+        return Ok(expr);
+    }
+    if let Expr::Constant { value, .. } = &expr {
+        match value {
+            Datum::Null => {
+                return Ok(Expr::Constant {
+                    value: Datum::Null,
+                    ty,
+                })
+            }
+            Datum::String(s) => {
+                return Ok(Expr::Constant {
+                    value: Datum::parse(ty, s).map_err(Error::InvalidLiteral)?,
+                    ty,
+                })
+            }
+            value => {
+                if let Some(value) = convert_number(value, ty) {
+                    return Ok(Expr::Constant { value, ty });
+                }
+            }
+        }
+    }
+    let valid = from == Type::String
+        || ty == Type::String
+        || (is_numeric(from) && is_numeric(ty))
+        || matches!(
+            (from, ty),
+            (Type::Date, Type::Timestamp)
+                | (Type::Timestamp, Type::Date)
+                | (Type::Bool, Type::Int4)
+                | (Type::Int4, Type::Bool)
+        );
+    if !valid {
+        return Err(Error::CannotCast { from, to: ty });
+    }
+    Ok(Expr::Cast {
+        expr: Box::new(expr),
+        ty,
+    })
+}
+
+/// Convert a numeric constant to another numeric type, if it can be done exactly (or to the
+/// nearest `DOUBLE PRECISION`).
+fn convert_number(value: &Datum, ty: Type) -> Option<Datum> {
+    let integer = match *value {
+        Datum::Int2(value) => value as i64,
+        Datum::Int4(value) => value as i64,
+        Datum::Int8(value) => value,
+        Datum::Numeric(_) if ty == Type::Float8 => {
+            return Some(Datum::Float8(value.to_string().parse().ok()?))
+        }
+        _ => return None,
+    };
+    Some(match ty {
+        Type::Int2 => Datum::Int2(i16::try_from(integer).ok()?),
+        Type::Int4 => Datum::Int4(i32::try_from(integer).ok()?),
+        Type::Int8 => Datum::Int8(integer),
+        Type::Numeric => Datum::Numeric(Numeric::new(integer, 0)),
+        Type::Float8 => Datum::Float8(integer as f64),
+        _ => return None,
+    })
+}
@@ -0,0 +1,542 @@
+use crate::ast::{FromItem, Statement};
+use crate::binder::{Binder, Error};
+use crate::catalog::Catalog;
+use crate::logical_plan::{Expr, LogicalPlan};
+use crate::parser::parse_statement;
+use crate::test_utils::table_column;
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager_mem::DiskManagerMem;
+use table::datum::{Datum, Nullability, Type};
+
+async fn create_catalog(buffer_pool: &BufferPool) -> Catalog<'_> {
+    let catalog = Catalog::create(buffer_pool).await.unwrap();
+    catalog
+        .create_table(
+            "users",
+            vec![
+                table_column("id", Type::Int8, Nullability::NotNull),
+                table_column("name", Type::String, Nullability::Nullable),
+                table_column("email", Type::String, Nullability::NotNull),
+                table_column("age", Type::Int2, Nullability::Nullable),
+                table_column("score", Type::Float8, Nullability::Nullable),
+                table_column("balance", Type::Numeric, Nullability::Nullable),
+                table_column("birthday", Type::Date, Nullability::Nullable),
+                table_column("created", Type::Timestamp, Nullability::NotNull),
+            ],
+            vec![0],
+        )
+        .await
+        .unwrap();
+    catalog
+}
+
+/// Bind each statement and print the plan, or the error.
+async fn bind_all(statements: &[&str]) -> String {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = create_catalog(&buffer_pool).await;
+    let binder = Binder::new(&catalog);
+    let mut result = String::new();
+    for sql in statements {
+        let statement = parse_statement(sql).unwrap();
+        result += &match binder.bind(&statement).await {
+            Ok(plan) => format!("{}\n{}", sql, plan),
+            Err(err) => format!("{}\nerror: {}\n", sql, err),
+        };
+    }
+    result
+}
+
+#[tokio::test]
+async fn select() {
+    assert_snapshot!(bind_all(&[
+        "select 1, 'a', null, 2.50, 1e3, 3000000000, -2147483648",
+        "select * from users",
+        "select u.id, name as n, age + 1 from users u where age > 18 and name like 'a%'",
+        "select name from users order by age desc, 1 limit 10 offset 5",
+        "select name as n, age from users order by n, -age",
+        "select id from users offset 3",
+    ]).await, @r###"
+    select 1, 'a', null, 2.50, 1e3, 3000000000, -2147483648
+    Project: 1 AS "?column?", 'a' AS "?column?", NULL AS "?column?", 2.50 AS "?column?", DOUBLE PRECISION '1000' AS "?column?", 3000000000 AS "?column?", -2147483648 AS "?column?"
+      Values: ()
+    select * from users
+    Project: id, name, email, age, score, balance, birthday, created
+      Scan: users
+    select u.id, name as n, age + 1 from users u where age > 18 and name like 'a%'
+    Project: id, name AS n, (CAST(age AS INTEGER) + 1) AS "?column?"
+      Filter: ((CAST(age AS INTEGER) > 18) AND (name LIKE 'a%'))
+        Scan: users
+    select name from users order by age desc, 1 limit 10 offset 5
+    Limit: 10 offset 5
+      Project: name
+        Sort: age DESC, name
+          Scan: users
+    select name as n, age from users order by n, -age
+    Project: name AS n, age
+      Sort: name, (- age)
+        Scan: users
+    select id from users offset 3
+    Limit: offset 3
+      Project: id
+        Scan: users
+    "###);
+}
+
+#[tokio::test]
+async fn aggregate() {
+    assert_snapshot!(bind_all(&[
+        "select count(*), count(name), sum(age), sum(balance), avg(age), avg(score), min(name) from users",
+        "select age, count(*) from users where id > 1 group by age having sum(score) > 1 order by count(*) desc",
+        "select age + 1, max(score) * 2 from users group by age + 1, name order by age + 1",
+        "select name n, count(*) from users group by name order by n",
+        "select * from users group by id, name, email, age, score, balance, birthday, created",
+        "select count(*) from users having count(*) > 0",
+        "select sum(age) + sum(age), max(age) from users",
+    ]).await, @r###"
+    select count(*), count(name), sum(age), sum(balance), avg(age), avg(score), min(name) from users
+    Project: "count(*)" AS count, "count(name)" AS count, "sum(age)" AS sum, "sum(balance)" AS sum, "avg(age)" AS avg, "avg(score)" AS avg, "min(name)" AS min
+      Aggregate: count(*), count(name), sum(age), sum(balance), avg(age), avg(score), min(name)
+        Scan: users
+    select age, count(*) from users where id > 1 group by age having sum(score) > 1 order by count(*) desc
+    Project: age, "count(*)" AS count
+      Sort: "count(*)" DESC
+        Filter: ("sum(score)" > DOUBLE PRECISION '1')
+          Aggregate: count(*), sum(score) group by age
+            Filter: (id > 1)
+              Scan: users
+    select age + 1, max(score) * 2 from users group by age + 1, name order by age + 1
+    Project: "(CAST(age AS INTEGER) + 1)" AS "?column?", ("max(score)" * DOUBLE PRECISION '2') AS "?column?"
+      Sort: "(CAST(age AS INTEGER) + 1)"
+        Aggregate: max(score) group by (CAST(age AS INTEGER) + 1), name
+          Scan: users
+    select name n, count(*) from users group by name order by n
+    Project: name AS n, "count(*)" AS count
+      Sort: name
+        Aggregate: count(*) group by name
+          Scan: users
+    select * from users group by id, name, email, age, score, balance, birthday, created
+    Project: id, name, email, age, score, balance, birthday, created
+      Aggregate: group by id, name, email, age, score, balance, birthday, created
+        Scan: users
+    select count(*) from users having count(*) > 0
+    Project: "count(*)" AS count
+      Filter: ("count(*)" > 0)
+        Aggregate: count(*)
+          Scan: users
+    select sum(age) + sum(age), max(age) from users
+    Project: ("sum(age)" + "sum(age)") AS "?column?", "max(age)" AS max
+      Aggregate: sum(age), max(age)
+        Scan: users
+    "###);
+    assert_snapshot!(bind_all(&[
+        "select name, count(*) from users",
+        "select name from users group by age",
+        "select users.name from users group by age",
+        "select age + 1 from users group by age + 2",
+        "select * from users group by id",
+        "select id from users where count(*) > 1",
+        "select max(count(*)) from users",
+        "select id from users group by count(*)",
+        "update users set age = count(*)",
+        "select sum(name) from users",
+        "select sum(*) from users",
+        "select count(id, age) from users",
+        "select count(*) from users group by missing",
+        "select count(*) from users having 1",
+    ]).await, @r###"
+    select name, count(*) from users
+    error: column "name" must appear in the GROUP BY clause or be used in an aggregate function
+    select name from users group by age
+    error: column "name" must appear in the GROUP BY clause or be used in an aggregate function
+    select users.name from users group by age
+    error: column "users.name" must appear in the GROUP BY clause or be used in an aggregate function
+    select age + 1 from users group by age + 2
+    error: column "age" must appear in the GROUP BY clause or be used in an aggregate function
+    select * from users group by id
+    error: column "name" must appear in the GROUP BY clause or be used in an aggregate function
+    select id from users where count(*) > 1
+    error: aggregate functions are not allowed here
+    select max(count(*)) from users
+    error: aggregate functions are not allowed here
+    select id from users group by count(*)
+    error: aggregate functions are not allowed here
+    update users set age = count(*)
+    error: aggregate functions are not allowed here
+    select sum(name) from users
+    error: function sum(TEXT) does not exist
+    select sum(*) from users
+    error: function sum(*) does not exist
+    select count(id, age) from users
+    error: function count does not take 2 arguments
+    select count(*) from users group by missing
+    error: column "missing" does not exist
+    select count(*) from users having 1
+    error: argument of HAVING must be of type BOOLEAN, not INTEGER
+    "###);
+}
+
+#[tokio::test]
+async fn types() {
+    assert_snapshot!(bind_all(&[
+        "select age + id, age * 1.5, score - balance, age / 2, age % 2 from users",
+        "select birthday < created, birthday = '2000-01-01', created >= '2000-01-01 12:00:00' from users",
+        "select name || age, 'x' || 'y', name || null from users",
+        "select cast(age as text), cast('12' as integer), cast(birthday as timestamp) from users",
+        "select date '2020-02-29', timestamp '2020-01-01 10:00:00.5', cast(null as bytea)",
+        "select case when age > 18 then 'adult' end, case age when 1 then 1.5 when 2 then 2 else age end from users",
+        "select coalesce(name, email, 'none'), coalesce(age, 0), lower(name), length(email), abs(score) from users",
+        "select not (age is null), email is not null from users where null",
+    ]).await, @r###"
+    select age + id, age * 1.5, score - balance, age / 2, age % 2 from users
+    Project: (CAST(age AS BIGINT) + id) AS "?column?", (CAST(age AS NUMERIC) * 1.5) AS "?column?", (score - CAST(balance AS DOUBLE PRECISION)) AS "?column?", (CAST(age AS INTEGER) / 2) AS "?column?", (CAST(age AS INTEGER) % 2) AS "?column?"
+      Scan: users
+    select birthday < created, birthday = '2000-01-01', created >= '2000-01-01 12:00:00' from users
+    Project: (CAST(birthday AS TIMESTAMP) < created) AS "?column?", (birthday = DATE '2000-01-01') AS "?column?", (created >= TIMESTAMP '2000-01-01 12:00:00') AS "?column?"
+      Scan: users
+    select name || age, 'x' || 'y', name || null from users
+    Project: (name || CAST(age AS TEXT)) AS "?column?", ('x' || 'y') AS "?column?", (name || NULL) AS "?column?"
+      Scan: users
+    select cast(age as text), cast('12' as integer), cast(birthday as timestamp) from users
+    Project: CAST(age AS TEXT) AS "?column?", 12 AS "?column?", CAST(birthday AS TIMESTAMP) AS "?column?"
+      Scan: users
+    select date '2020-02-29', timestamp '2020-01-01 10:00:00.5', cast(null as bytea)
+    Project: DATE '2020-02-29' AS "?column?", TIMESTAMP '2020-01-01 10:00:00.5' AS "?column?", NULL AS "?column?"
+      Values: ()
+    select case when age > 18 then 'adult' end, case age when 1 then 1.5 when 2 then 2 else age end from users
+    Project: CASE WHEN (CAST(age AS INTEGER) > 18) THEN 'adult' ELSE NULL END AS "?column?", CASE WHEN (CAST(age AS INTEGER) = 1) THEN 1.5 WHEN (CAST(age AS INTEGER) = 2) THEN 2 ELSE CAST(age AS NUMERIC) END AS "?column?"
+      Scan: users
+    select coalesce(name, email, 'none'), coalesce(age, 0), lower(name), length(email), abs(score) from users
+    Project: coalesce(name, email, 'none') AS coalesce, coalesce(CAST(age AS INTEGER), 0) AS coalesce, lower(name) AS lower, length(email) AS length, abs(score) AS abs
+      Scan: users
+    select not (age is null), email is not null from users where null
+    Project: (NOT (age IS NULL)) AS "?column?", (email IS NOT NULL) AS "?column?"
+      Filter: NULL
+        Scan: users
+    "###);
+}
+
+#[tokio::test]
+async fn modification() {
+    assert_snapshot!(bind_all(&[
+        "insert into users values (1, 'a', 'a@example.com', 20, 1.5, 10, '2000-01-01', '2020-01-01 00:00:00')",
+        "insert into users (email, created, id) values ('b', '2020-01-01', 2), ('c', '2020-01-02', 3000000000)",
+        "update users set age = age + 1, score = 0 where id = 1",
+        "update users set name = null",
+        "delete from users where created < '2020-01-01'",
+        "delete from users",
+    ]).await, @r###"
+    insert into users values (1, 'a', 'a@example.com', 20, 1.5, 10, '2000-01-01', '2020-01-01 00:00:00')
+    Insert: users
+      Values: (1, 'a', 'a@example.com', 20, DOUBLE PRECISION '1.5', 10, DATE '2000-01-01', TIMESTAMP '2020-01-01 00:00:00')
+    insert into users (email, created, id) values ('b', '2020-01-01', 2), ('c', '2020-01-02', 3000000000)
+    Insert: users
+      Values: (2, NULL, 'b', NULL, NULL, NULL, NULL, TIMESTAMP '2020-01-01 00:00:00'), (3000000000, NULL, 'c', NULL, NULL, NULL, NULL, TIMESTAMP '2020-01-02 00:00:00')
+    update users set age = age + 1, score = 0 where id = 1
+    Update: users age = CAST((CAST(age AS INTEGER) + 1) AS SMALLINT), score = DOUBLE PRECISION '0'
+      Filter: (id = 1)
+        Scan: users
+    update users set name = null
+    Update: users name = NULL
+      Scan: users
+    delete from users where created < '2020-01-01'
+    Delete: users
+      Filter: (created < TIMESTAMP '2020-01-01 00:00:00')
+        Scan: users
+    delete from users
+    Delete: users
+      Scan: users
+    "###);
+}
+
+#[tokio::test]
+async fn ddl() {
+    assert_snapshot!(bind_all(&[
+        "create table t (a int, b text not null, c date primary key)",
+        "create table t (a int, b text, primary key (b, a))",
+        "drop table users",
+        "drop table if exists nope",
+        "create unique index users_by_email on users (email, id)",
+    ]).await, @r###"
+    create table t (a int, b text not null, c date primary key)
+    CreateTable: t (a INTEGER, b TEXT NOT NULL, c DATE NOT NULL) primary key (c)
+    create table t (a int, b text, primary key (b, a))
+    CreateTable: t (a INTEGER NOT NULL, b TEXT NOT NULL) primary key (b, a)
+    drop table users
+    DropTable: users
+    drop table if exists nope
+    DropTable: nope if exists
+    create unique index users_by_email on users (email, id)
+    CreateIndex: unique users_by_email on users columns [2, 0]
+    "###);
+}
+
+#[tokio::test]
+async fn joins() {
+    assert_snapshot!(bind_all(&[
+        "select a.id, b.name from users a, users b",
+        "select * from users a join users b on a.id = b.id and b.age > 1 where a.name = 'x'",
+        "select a.name, b.name, c.id from users a left join users b on a.id = b.age \
+         cross join users c",
+        "select b.age is null from users a left join users b on a.id = b.id",
+        "explain select id from users where id = 1",
+        "select * from users join users on true",
+        "select * from users a join users b on a.id",
+        "select id from users a, users b",
+        "select * from users a join users b on c.id = a.id",
+    ]).await, @r###"
+    select a.id, b.name from users a, users b
+    Project: id, name
+      Join: inner
+        Scan: users
+        Scan: users
+    select * from users a join users b on a.id = b.id and b.age > 1 where a.name = 'x'
+    Project: id, name, email, age, score, balance, birthday, created, id, name, email, age, score, balance, birthday, created
+      Filter: (name = 'x')
+        Join: inner on ((id = id) AND (CAST(age AS INTEGER) > 1))
+          Scan: users
+          Scan: users
+    select a.name, b.name, c.id from users a left join users b on a.id = b.age cross join users c
+    Project: name, name, id
+      Join: inner
+        Join: left on (id = CAST(age AS BIGINT))
+          Scan: users
+          Scan: users
+        Scan: users
+    select b.age is null from users a left join users b on a.id = b.id
+    Project: (age IS NULL) AS "?column?"
+      Join: left on (id = id)
+        Scan: users
+        Scan: users
+    explain select id from users where id = 1
+    Explain
+      Project: id
+        Filter: (id = 1)
+          Scan: users
+    select * from users join users on true
+    error: table name "users" specified more than once
+    select * from users a join users b on a.id
+    error: argument of JOIN/ON must be of type BOOLEAN, not BIGINT
+    select id from users a, users b
+    error: column reference "id" is ambiguous
+    select * from users a join users b on c.id = a.id
+    error: missing FROM-clause entry for table "c"
+    "###);
+}
+
+#[tokio::test]
+async fn errors() {
+    assert_snapshot!(bind_all(&[
+        "select * from nope",
+        "select nope from users",
+        "select x.id from users",
+        "select users.id from users u",
+        "select *",
+        "select id from users where age",
+        "select age + 'abc' from users",
+        "select name + 1 from users",
+        "select -name from users",
+        "select score % 2 from users",
+        "select 1 || 2",
+        "select not age from users",
+        "select cast(birthday as integer) from users",
+        "select 12345678901234567890123456789012345678901",
+        "select nope(1)",
+        "select lower(1)",
+        "select abs('1')",
+        "select lower(name, email) from users",
+        "select coalesce(age, name) from users",
+        "select case when true then 1 else 'a' || 'b' end",
+        "select date '2020-02-30'",
+        "select id from users order by 2",
+        "select id as x, name as x from users order by x",
+        "insert into nope values (1)",
+        "insert into users (id, nope) values (1, 2)",
+        "insert into users (id, id) values (1, 2)",
+        "insert into users (id) values (1, 2)",
+        "insert into users (id, email, created) values (1, null, '2020-01-01')",
+        "insert into users (id, email) values (1, 'a')",
+        "insert into users (id, email, created) values ('x', 'a', '2020-01-01')",
+        "insert into users (id, email, created) values (1, 'a', 5)",
+        "update users set nope = 1",
+        "update users set age = 1, age = 2",
+        "update users set email = null",
+        "delete from users where name",
+        "create table users (a int)",
+        "create table t (a int, a text)",
+        "create table t (a int, primary key (b))",
+        "drop table nope",
+        "create index users_pkey on users (id)",
+        "create index i on users (nope)",
+    ]).await, @r###"
+    select * from nope
+    error: table "nope" does not exist
+    select nope from users
+    error: column "nope" does not exist
+    select x.id from users
+    error: missing FROM-clause entry for table "x"
+    select users.id from users u
+    error: missing FROM-clause entry for table "users"
+    select *
+    error: SELECT * with no tables specified
+    select id from users where age
+    error: argument of WHERE must be of type BOOLEAN, not SMALLINT
+    select age + 'abc' from users
+    error: invalid input for type Int2: "abc"
+    select name + 1 from users
+    error: operator does not exist: TEXT + INTEGER
+    select -name from users
+    error: operator does not exist: - TEXT
+    select score % 2 from users
+    error: operator does not exist: DOUBLE PRECISION % INTEGER
+    select 1 || 2
+    error: operator does not exist: INTEGER || INTEGER
+    select not age from users
+    error: argument of NOT must be of type BOOLEAN, not SMALLINT
+    select cast(birthday as integer) from users
+    error: cannot cast type DATE to INTEGER
+    select 12345678901234567890123456789012345678901
+    Project: DOUBLE PRECISION '12345678901234568000000000000000000000000' AS "?column?"
+      Values: ()
+    select nope(1)
+    error: function nope does not exist
+    select lower(1)
+    error: function lower(INTEGER) does not exist
+    select abs('1')
+    error: function abs(TEXT) does not exist
+    select lower(name, email) from users
+    error: function lower does not take 2 arguments
+    select coalesce(age, name) from users
+    error: argument of coalesce must be of type SMALLINT, not TEXT
+    select case when true then 1 else 'a' || 'b' end
+    error: CASE result must be of type INTEGER, not TEXT
+    select date '2020-02-30'
+    error: invalid input for type Date: "2020-02-30"
+    select id from users order by 2
+    error: ORDER BY position 2 is not in select list
+    select id as x, name as x from users order by x
+    error: column reference "x" is ambiguous
+    insert into nope values (1)
+    error: table "nope" does not exist
+    insert into users (id, nope) values (1, 2)
+    error: column "nope" does not exist
+    insert into users (id, id) values (1, 2)
+    error: column "id" specified more than once
+    insert into users (id) values (1, 2)
+    error: INSERT has 2 values for 1 target columns
+    insert into users (id, email, created) values (1, null, '2020-01-01')
+    error: null value in column "email" violates not-null constraint
+    insert into users (id, email) values (1, 'a')
+    error: null value in column "created" violates not-null constraint
+    insert into users (id, email, created) values ('x', 'a', '2020-01-01')
+    error: invalid input for type Int8: "x"
+    insert into users (id, email, created) values (1, 'a', 5)
+    error: value of column "created" must be of type TIMESTAMP, not INTEGER
+    update users set nope = 1
+    error: column "nope" does not exist
+    update users set age = 1, age = 2
+    error: column "age" specified more than once
+    update users set email = null
+    error: null value in column "email" violates not-null constraint
+    delete from users where name
+    error: argument of WHERE must be of type BOOLEAN, not TEXT
+    create table users (a int)
+    error: table "users" already exists
+    create table t (a int, a text)
+    error: column "a" specified more than once
+    create table t (a int, primary key (b))
+    error: column "b" does not exist
+    drop table nope
+    error: table "nope" does not exist
+    create index users_pkey on users (id)
+    error: index "users_pkey" already exists
+    create index i on users (nope)
+    error: column "nope" does not exist
+    "###);
+}
+
+#[tokio::test]
+async fn bound_expressions() -> Result<(), Error> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = create_catalog(&buffer_pool).await;
+    let binder = Binder::new(&catalog);
+    let plan = binder
+        .bind(&parse_statement("select age + 1 as next_age, name from users").unwrap())
+        .await?;
+    assert_eq!(
+        plan.columns(),
+        vec![
+            table_column("next_age", Type::Int4, Nullability::Nullable),
+            table_column("name", Type::String, Nullability::Nullable),
+        ]
+    );
+    let exprs = match plan {
+        LogicalPlan::Project { exprs, .. } => exprs,
+        plan => panic!("unexpected plan: {:?}", plan),
+    };
+    let age = Expr::Column {
+        index: 3,
+        name: "age".to_string(),
+        ty: Type::Int2,
+        nullability: Nullability::Nullable,
+    };
+    assert_eq!(
+        exprs[0],
+        Expr::Binary {
+            op: crate::ast::BinaryOp::Plus,
+            left: Box::new(Expr::Cast {
+                expr: Box::new(age),
+                ty: Type::Int4,
+            }),
+            right: Box::new(Expr::Constant {
+                value: Datum::Int4(1),
+                ty: Type::Int4,
+            }),
+        }
+    );
+    Ok(())
+}
+
+/// Statements the parser doesn't produce.
+#[tokio::test]
+async fn unsupported_statements() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = create_catalog(&buffer_pool).await;
+    let binder = Binder::new(&catalog);
+
+    let explain = parse_statement("explain select 1").unwrap();
+    let err = binder
+        .bind(&Statement::Explain(Box::new(explain)))
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "nested EXPLAIN is not supported");
+
+    let mut select = match parse_statement("select * from users a, users b, users c").unwrap() {
+        Statement::Select(select) => select,
+        statement => panic!("unexpected statement: {:?}", statement),
+    };
+    // Nest the join of `a` and `b` on the right: `c, (a, b)`.
+    if let Some(FromItem::Join { left, right, .. }) = &mut select.from {
+        std::mem::swap(left, right);
+    }
+    let err = binder.bind(&Statement::Select(select)).await.unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "a join on the right side of a join is not supported"
+    );
+}
+
+#[tokio::test]
+async fn analyze() {
+    assert_snapshot!(bind_all(&[
+        "analyze users",
+        "analyze",
+        "analyze nope",
+    ]).await, @r###"
+    analyze users
+    Analyze: users
+    analyze
+    Analyze: users
+    analyze nope
+    error: table "nope" does not exist
+    "###);
+}
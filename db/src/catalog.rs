@@ -0,0 +1,634 @@
+//! The system catalog: definitions of tables and indexes, stored in the database itself.
+//!
+//! The catalog consists of three btrees, found through the superblock (see `superblock`):
+//! - tables: table name -> `TableDef`,
+//! - indexes: index name -> `IndexDef`,
+//! - statistics: table name -> `TableStats`, for tables which were analyzed.
+//!
+//! Definitions are encoded with `table::datum::serialize` (strings as length and UTF-8 bytes,
+//! integers little-endian):
+//! ```text
+//! TableDef:   HeapPageId (4) | ColumnCount (4) | columns... | PrimaryKeyCount (4)
+//!             | column indexes (4 each)
+//! column:     Name (string) | Type (1) | Nullability (1)
+//! IndexDef:   TableName (string) | MetaPageId (4) | Unique (1) | Primary (1) | ColumnCount (4)
+//!             | column indexes (4 each)
+//! TableStats: Rows (8) | Pages (8) | ColumnCount (4) | columns... | IndexCount (4) | indexes...
+//! column:     NullFraction (8) | Distinct (8) | MostCommonCount (4) | (value, Fraction (8))...
+//!             | BoundCount (4) | values...
+//! index:      Name (string) | Entries (8) | LeafPages (8) | Height (4)
+//! ```
+//!
+//! Values in statistics are serialized as non-NULL values of the column's type, and fractions as
+//! 64-bit floats.
+//!
+//! Creating a table also creates its heap, and for a table with a primary key, a unique index on
+//! the primary key columns named `<table>_pkey`. Dropping a table drops its indexes and statistics.
+//! Pages of dropped tables and indexes are not reclaimed: the buffer pool can free pages, but table
+//! heaps and btrees can't yet release all of their pages.
+//!
+//! DDL operations are serialized by a lock, so that e.g. two tables with the same name can't be
+//! created concurrently.
+
+use crate::statistics::{ColumnStats, IndexStats, TableStats};
+use crate::superblock::Superblock;
+use btree::btree::{BTree, Error as BTreeError};
+use buffer_pool::buffer_pool::{BufferPool, Error as BufferPoolError};
+use buffer_pool::disk_manager::PageId;
+use std::collections::HashSet;
+use std::ops::Bound;
+use table::datum::serialize::{Reader, Writer};
+use table::datum::{Datum, Nullability, Type};
+use table::row::{Column, Schema};
+use table::table_heap::{Error as TableHeapError, TableHeap};
+use tokio::sync::Mutex;
+
+#[derive(Debug)]
+pub enum Error {
+    BTree(BTreeError),
+    TableHeap(TableHeapError),
+    /// The first page of the database is not a superblock.
+    InvalidSuperblock,
+    /// The database was written in an on-disk format version we don't understand.
+    UnsupportedVersion(u32),
+    /// `Catalog::create` was called on a database which already has pages.
+    DatabaseNotEmpty,
+    TableExists(String),
+    TableNotFound(String),
+    IndexExists(String),
+    IndexNotFound(String),
+    /// Two columns of the table have the given name.
+    DuplicateColumn(String),
+    /// A column index in a primary key or index definition is out of range.
+    InvalidColumn(usize),
+    /// A primary key column is nullable.
+    NullablePrimaryKey(String),
+    /// The index implements a primary key, and can only be dropped with its table.
+    PrimaryKeyIndex(String),
+    /// The catalog entry with the given name can't be decoded.
+    CorruptEntry(String),
+}
+
+impl From<BTreeError> for Error {
+    fn from(err: BTreeError) -> Self {
+        Error::BTree(err)
+    }
+}
+
+impl From<TableHeapError> for Error {
+    fn from(err: TableHeapError) -> Self {
+        Error::TableHeap(err)
+    }
+}
+
+impl From<BufferPoolError> for Error {
+    fn from(err: BufferPoolError) -> Self {
+        Error::BTree(err.into())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TableDef {
+    pub name: String,
+    pub schema: Schema,
+    /// Indexes of the primary key columns (empty if the table has no primary key).
+    pub primary_key: Vec<usize>,
+    /// First page of the table heap.
+    pub heap_page_id: PageId,
+}
+
+impl TableDef {
+    /// Name of the index implementing the primary key, if there is one.
+    pub fn primary_key_index(&self) -> Option<String> {
+        if self.primary_key.is_empty() {
+            None
+        } else {
+            Some(primary_key_index_name(&self.name))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IndexDef {
+    pub name: String,
+    pub table_name: String,
+    /// Indexes of the indexed columns in the table.
+    pub columns: Vec<usize>,
+    pub unique: bool,
+    /// Whether the index implements the table's primary key.
+    pub primary: bool,
+    /// Meta page of the index btree (see `table::secondary_index`).
+    pub meta_page_id: PageId,
+}
+
+fn primary_key_index_name(table_name: &str) -> String {
+    format!("{}_pkey", table_name)
+}
+
+pub struct Catalog<'b> {
+    buffer_pool: &'b BufferPool,
+    tables: BTree<'b>,
+    indexes: BTree<'b>,
+    statistics: BTree<'b>,
+    ddl_lock: Mutex<()>,
+}
+
+impl<'b> Catalog<'b> {
+    /// Bootstrap a new database: write the superblock and create an empty catalog.
+    ///
+    /// The database must be empty, since the superblock has to be the first page.
+    pub async fn create(buffer_pool: &'b BufferPool) -> Result<Catalog<'b>> {
+        Superblock::allocate(buffer_pool).await?;
+        let tables = BTree::new(buffer_pool).await?;
+        let indexes = BTree::new(buffer_pool).await?;
+        let statistics = BTree::new(buffer_pool).await?;
+        Superblock {
+            tables_meta_page_id: tables.meta_page_id(),
+            indexes_meta_page_id: indexes.meta_page_id(),
+            statistics_meta_page_id: statistics.meta_page_id(),
+        }
+        .write(buffer_pool)
+        .await?;
+        Ok(Catalog {
+            buffer_pool,
+            tables,
+            indexes,
+            statistics,
+            ddl_lock: Mutex::new(()),
+        })
+    }
+
+    /// Open the catalog of an existing database.
+    pub async fn open(buffer_pool: &'b BufferPool) -> Result<Catalog<'b>> {
+        let superblock = Superblock::read(buffer_pool).await?;
+        Ok(Catalog {
+            buffer_pool,
+            tables: BTree::open(buffer_pool, superblock.tables_meta_page_id).await?,
+            indexes: BTree::open(buffer_pool, superblock.indexes_meta_page_id).await?,
+            statistics: BTree::open(buffer_pool, superblock.statistics_meta_page_id).await?,
+            ddl_lock: Mutex::new(()),
+        })
+    }
+
+    pub fn buffer_pool(&self) -> &'b BufferPool {
+        self.buffer_pool
+    }
+
+    /// Create a table with an empty heap. If `primary_key` is not empty, also creates the primary
+    /// key index (empty as well).
+    pub async fn create_table(
+        &self,
+        name: &str,
+        columns: Vec<Column>,
+        primary_key: Vec<usize>,
+    ) -> Result<TableDef> {
+        let _guard = self.ddl_lock.lock().await;
+        if self.tables.get(name.as_bytes()).await?.is_some() {
+            return Err(Error::TableExists(name.to_string()));
+        }
+        let mut names = HashSet::new();
+        for column in &columns {
+            if !names.insert(&column.name) {
+                return Err(Error::DuplicateColumn(column.name.clone()));
+            }
+        }
+        check_columns(&columns, &primary_key)?;
+        for &index in &primary_key {
+            if columns[index].nullability == Nullability::Nullable {
+                return Err(Error::NullablePrimaryKey(columns[index].name.clone()));
+            }
+        }
+        let index_name = primary_key_index_name(name);
+        if !primary_key.is_empty() && self.indexes.get(index_name.as_bytes()).await?.is_some() {
+            return Err(Error::IndexExists(index_name));
+        }
+
+        let heap = TableHeap::new(self.buffer_pool).await?;
+        let table = TableDef {
+            name: name.to_string(),
+            schema: Schema::new(columns),
+            primary_key,
+            heap_page_id: heap.first_page_id(),
+        };
+        if !table.primary_key.is_empty() {
+            let index = IndexDef {
+                name: index_name,
+                table_name: table.name.clone(),
+                columns: table.primary_key.clone(),
+                unique: true,
+                primary: true,
+                meta_page_id: BTree::new(self.buffer_pool).await?.meta_page_id(),
+            };
+            self.indexes
+                .insert(index.name.as_bytes(), &encode_index(&index))
+                .await?;
+        }
+        self.tables
+            .insert(name.as_bytes(), &encode_table(&table))
+            .await?;
+        Ok(table)
+    }
+
+    /// Drop the table, all its indexes and its statistics.
+    pub async fn drop_table(&self, name: &str) -> Result<()> {
+        let _guard = self.ddl_lock.lock().await;
+        if !self.tables.delete(name.as_bytes()).await? {
+            return Err(Error::TableNotFound(name.to_string()));
+        }
+        for index in self.list_indexes(name).await? {
+            self.indexes.delete(index.name.as_bytes()).await?;
+        }
+        self.statistics.delete(name.as_bytes()).await?;
+        Ok(())
+    }
+
+    pub async fn get_table(&self, name: &str) -> Result<Option<TableDef>> {
+        match self.tables.get(name.as_bytes()).await? {
+            Some(value) => Ok(Some(decode_table(name, &value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns all tables, ordered by name.
+    pub async fn list_tables(&self) -> Result<Vec<TableDef>> {
+        let entries = self
+            .tables
+            .range(Bound::Unbounded, Bound::Unbounded)
+            .await?;
+        entries
+            .iter()
+            .map(|(key, value)| decode_table(&decode_name(key)?, value))
+            .collect()
+    }
+
+    /// Create an (empty) index on the given columns of a table.
+    ///
+    /// Populating the index with existing rows of the table is up to the caller.
+    pub async fn create_index(
+        &self,
+        name: &str,
+        table_name: &str,
+        columns: Vec<usize>,
+        unique: bool,
+    ) -> Result<IndexDef> {
+        self.add_index(name, table_name, columns, unique, None)
+            .await
+    }
+
+    /// Add an index whose btree was already built, e.g. by bulk loading the existing rows of the
+    /// table.
+    pub async fn create_index_with_btree(
+        &self,
+        name: &str,
+        table_name: &str,
+        columns: Vec<usize>,
+        unique: bool,
+        meta_page_id: PageId,
+    ) -> Result<IndexDef> {
+        self.add_index(name, table_name, columns, unique, Some(meta_page_id))
+            .await
+    }
+
+    /// Add an index with the given btree, or a new empty one.
+    async fn add_index(
+        &self,
+        name: &str,
+        table_name: &str,
+        columns: Vec<usize>,
+        unique: bool,
+        meta_page_id: Option<PageId>,
+    ) -> Result<IndexDef> {
+        let _guard = self.ddl_lock.lock().await;
+        if self.indexes.get(name.as_bytes()).await?.is_some() {
+            return Err(Error::IndexExists(name.to_string()));
+        }
+        let table = match self.get_table(table_name).await? {
+            Some(table) => table,
+            None => return Err(Error::TableNotFound(table_name.to_string())),
+        };
+        check_columns(table.schema.columns(), &columns)?;
+        let index = IndexDef {
+            name: name.to_string(),
+            table_name: table_name.to_string(),
+            columns,
+            unique,
+            primary: false,
+            meta_page_id: match meta_page_id {
+                Some(meta_page_id) => meta_page_id,
+                None => BTree::new(self.buffer_pool).await?.meta_page_id(),
+            },
+        };
+        self.indexes
+            .insert(name.as_bytes(), &encode_index(&index))
+            .await?;
+        Ok(index)
+    }
+
+    /// Drop an index. Primary key indexes can't be dropped (other than by dropping the table).
+    pub async fn drop_index(&self, name: &str) -> Result<()> {
+        let _guard = self.ddl_lock.lock().await;
+        match self.get_index(name).await? {
+            Some(index) if index.primary => Err(Error::PrimaryKeyIndex(name.to_string())),
+            Some(_) => {
+                self.indexes.delete(name.as_bytes()).await?;
+                Ok(())
+            }
+            None => Err(Error::IndexNotFound(name.to_string())),
+        }
+    }
+
+    pub async fn get_index(&self, name: &str) -> Result<Option<IndexDef>> {
+        match self.indexes.get(name.as_bytes()).await? {
+            Some(value) => Ok(Some(decode_index(name, &value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the indexes of the given table, ordered by name.
+    pub async fn list_indexes(&self, table_name: &str) -> Result<Vec<IndexDef>> {
+        let entries = self
+            .indexes
+            .range(Bound::Unbounded, Bound::Unbounded)
+            .await?;
+        let mut result = vec![];
+        for (key, value) in entries {
+            let index = decode_index(&decode_name(&key)?, &value)?;
+            if index.table_name == table_name {
+                result.push(index);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Store the statistics of a table, replacing the previous ones. Fails with `TableNotFound` if
+    /// the table was dropped (or replaced) since `table` was read.
+    pub async fn set_statistics(&self, table: &TableDef, stats: &TableStats) -> Result<()> {
+        let _guard = self.ddl_lock.lock().await;
+        if self.get_table(&table.name).await?.as_ref() != Some(table) {
+            return Err(Error::TableNotFound(table.name.clone()));
+        }
+        let value = encode_statistics(table, stats);
+        self.statistics
+            .insert(table.name.as_bytes(), &value)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the statistics of the table, if it was analyzed.
+    pub async fn get_statistics(&self, table: &TableDef) -> Result<Option<TableStats>> {
+        match self.statistics.get(table.name.as_bytes()).await? {
+            Some(value) => match decode::statistics(table, &value) {
+                Some(stats) => Ok(Some(stats)),
+                None => Err(Error::CorruptEntry(table.name.clone())),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+/// Check that `indexes` are valid, distinct column indexes.
+fn check_columns(columns: &[Column], indexes: &[usize]) -> Result<()> {
+    let mut seen = HashSet::new();
+    for &index in indexes {
+        if index >= columns.len() {
+            return Err(Error::InvalidColumn(index));
+        }
+        if !seen.insert(index) {
+            return Err(Error::DuplicateColumn(columns[index].name.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Types in the order of their codes in catalog entries. New types may only be appended.
+pub(crate) const TYPES: [Type; 10] = [
+    Type::String,
+    Type::Int8,
+    Type::Bool,
+    Type::Int2,
+    Type::Int4,
+    Type::Float8,
+    Type::Bytea,
+    Type::Date,
+    Type::Timestamp,
+    Type::Numeric,
+];
+
+pub(crate) fn type_code(ty: Type) -> u8 {
+    TYPES.iter().position(|&t| t == ty).unwrap() as u8
+}
+
+fn write_string(w: &mut Writer, s: &str) {
+    Datum::String(s.to_string()).serialize(w, Nullability::NotNull);
+}
+
+fn write_column_indexes(w: &mut Writer, indexes: &[usize]) {
+    w.write_u32(indexes.len() as u32);
+    for &index in indexes {
+        w.write_u32(index as u32);
+    }
+}
+
+fn encode_table(table: &TableDef) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.write_u32(table.heap_page_id.0);
+    w.write_u32(table.schema.len() as u32);
+    for column in table.schema.columns() {
+        write_string(&mut w, &column.name);
+        w.write_u8(type_code(column.ty));
+        w.write_u8(match column.nullability {
+            Nullability::Nullable => 0,
+            Nullability::NotNull => 1,
+        });
+    }
+    write_column_indexes(&mut w, &table.primary_key);
+    w.data().to_vec()
+}
+
+fn encode_index(index: &IndexDef) -> Vec<u8> {
+    let mut w = Writer::new();
+    write_string(&mut w, &index.table_name);
+    w.write_u32(index.meta_page_id.0);
+    w.write_u8(index.unique as u8);
+    w.write_u8(index.primary as u8);
+    write_column_indexes(&mut w, &index.columns);
+    w.data().to_vec()
+}
+
+fn encode_statistics(table: &TableDef, stats: &TableStats) -> Vec<u8> {
+    assert_eq!(stats.columns.len(), table.schema.len());
+    let mut w = Writer::new();
+    w.write_i64(stats.rows as i64);
+    w.write_i64(stats.pages as i64);
+    w.write_u32(stats.columns.len() as u32);
+    for (column, column_def) in stats.columns.iter().zip(table.schema.columns()) {
+        let write_value = |w: &mut Writer, value: &Datum| {
+            assert_eq!(
+                value.ty(),
+                Some(column_def.ty),
+                "invalid value in statistics"
+            );
+            value.serialize(w, Nullability::NotNull);
+        };
+        w.write_f64(column.null_fraction);
+        w.write_f64(column.distinct);
+        w.write_u32(column.most_common.len() as u32);
+        for (value, fraction) in &column.most_common {
+            write_value(&mut w, value);
+            w.write_f64(*fraction);
+        }
+        w.write_u32(column.histogram.len() as u32);
+        for value in &column.histogram {
+            write_value(&mut w, value);
+        }
+    }
+    w.write_u32(stats.indexes.len() as u32);
+    for index in &stats.indexes {
+        write_string(&mut w, &index.name);
+        w.write_i64(index.entries as i64);
+        w.write_i64(index.leaf_pages as i64);
+        w.write_u32(index.height);
+    }
+    w.data().to_vec()
+}
+
+fn decode_name(key: &[u8]) -> Result<String> {
+    String::from_utf8(key.to_vec())
+        .map_err(|_| Error::CorruptEntry(String::from_utf8_lossy(key).into_owned()))
+}
+
+/// Decodes catalog entries. `None` means the entry is corrupt.
+mod decode {
+    use super::*;
+
+    fn string(r: &mut Reader) -> Option<String> {
+        match Datum::deserialize(r, Nullability::NotNull, Type::String).ok()? {
+            Datum::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn bool(r: &mut Reader) -> Option<bool> {
+        match r.read_u8().ok()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    fn column_indexes(r: &mut Reader) -> Option<Vec<usize>> {
+        let count = r.read_u32().ok()?;
+        (0..count)
+            .map(|_| r.read_u32().ok().map(|index| index as usize))
+            .collect()
+    }
+
+    pub fn table(name: &str, data: &[u8]) -> Option<TableDef> {
+        let mut r = Reader::new(data);
+        let heap_page_id = PageId(r.read_u32().ok()?);
+        let column_count = r.read_u32().ok()?;
+        let mut columns = vec![];
+        for _ in 0..column_count {
+            let name = string(&mut r)?;
+            let ty = *TYPES.get(r.read_u8().ok()? as usize)?;
+            let nullability = match r.read_u8().ok()? {
+                0 => Nullability::Nullable,
+                1 => Nullability::NotNull,
+                _ => return None,
+            };
+            columns.push(Column {
+                name,
+                ty,
+                nullability,
+            });
+        }
+        let primary_key = column_indexes(&mut r)?;
+        if !r.data().is_empty() {
+            return None;
+        }
+        Some(TableDef {
+            name: name.to_string(),
+            schema: Schema::new(columns),
+            primary_key,
+            heap_page_id,
+        })
+    }
+
+    pub fn index(name: &str, data: &[u8]) -> Option<IndexDef> {
+        let mut r = Reader::new(data);
+        let table_name = string(&mut r)?;
+        let meta_page_id = PageId(r.read_u32().ok()?);
+        let unique = bool(&mut r)?;
+        let primary = bool(&mut r)?;
+        let columns = column_indexes(&mut r)?;
+        if !r.data().is_empty() {
+            return None;
+        }
+        Some(IndexDef {
+            name: name.to_string(),
+            table_name,
+            columns,
+            unique,
+            primary,
+            meta_page_id,
+        })
+    }
+
+    pub fn statistics(table: &TableDef, data: &[u8]) -> Option<TableStats> {
+        let mut r = Reader::new(data);
+        let rows = r.read_i64().ok()? as u64;
+        let pages = r.read_i64().ok()? as u64;
+        if r.read_u32().ok()? as usize != table.schema.len() {
+            return None;
+        }
+        let mut columns = vec![];
+        for column in table.schema.columns() {
+            let value =
+                |r: &mut Reader| Datum::deserialize(r, Nullability::NotNull, column.ty).ok();
+            let null_fraction = r.read_f64().ok()?;
+            let distinct = r.read_f64().ok()?;
+            let mut most_common = vec![];
+            for _ in 0..r.read_u32().ok()? {
+                most_common.push((value(&mut r)?, r.read_f64().ok()?));
+            }
+            let mut histogram = vec![];
+            for _ in 0..r.read_u32().ok()? {
+                histogram.push(value(&mut r)?);
+            }
+            columns.push(ColumnStats {
+                null_fraction,
+                distinct,
+                most_common,
+                histogram,
+            });
+        }
+        let mut indexes = vec![];
+        for _ in 0..r.read_u32().ok()? {
+            indexes.push(IndexStats {
+                name: string(&mut r)?,
+                entries: r.read_i64().ok()? as u64,
+                leaf_pages: r.read_i64().ok()? as u64,
+                height: r.read_u32().ok()?,
+            });
+        }
+        if !r.data().is_empty() {
+            return None;
+        }
+        Some(TableStats {
+            rows,
+            pages,
+            columns,
+            indexes,
+        })
+    }
+}
+
+fn decode_table(name: &str, data: &[u8]) -> Result<TableDef> {
+    decode::table(name, data).ok_or_else(|| Error::CorruptEntry(name.to_string()))
+}
+
+fn decode_index(name: &str, data: &[u8]) -> Result<IndexDef> {
+    decode::index(name, data).ok_or_else(|| Error::CorruptEntry(name.to_string()))
+}
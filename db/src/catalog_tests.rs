@@ -0,0 +1,287 @@
+use crate::catalog::{Catalog, Error, IndexDef, Result, TableDef};
+use crate::statistics::{ColumnStats, IndexStats, TableStats};
+use crate::superblock::{Superblock, SUPERBLOCK_PAGE_ID};
+use crate::test_utils::table_column;
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager_file::DiskManagerFile;
+use buffer_pool::disk_manager_mem::DiskManagerMem;
+use table::datum::{Datum, Nullability, Type};
+use table::row::Column;
+use table::table_heap::TableHeap;
+
+fn users_columns() -> Vec<Column> {
+    vec![
+        table_column("id", Type::Int8, Nullability::NotNull),
+        table_column("name", Type::String, Nullability::Nullable),
+        table_column("created", Type::Timestamp, Nullability::NotNull),
+    ]
+}
+
+#[tokio::test]
+async fn create_and_lookup_table() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await?;
+    assert_eq!(catalog.list_tables().await?, vec![]);
+
+    let users = catalog
+        .create_table("users", users_columns(), vec![0])
+        .await?;
+    assert_eq!(users.schema.columns(), &users_columns()[..]);
+    assert_eq!(users.primary_key_index(), Some("users_pkey".to_string()));
+    assert_eq!(catalog.get_table("users").await?, Some(users.clone()));
+    assert_eq!(catalog.get_table("nope").await?, None);
+
+    // The heap is usable.
+    let heap = TableHeap::from_existing(&buffer_pool, users.heap_page_id).await?;
+    heap.insert_tuple(b"row").await?;
+
+    let items = catalog
+        .create_table(
+            "items",
+            vec![table_column("price", Type::Numeric, Nullability::Nullable)],
+            vec![],
+        )
+        .await?;
+    assert_eq!(items.primary_key_index(), None);
+    assert_eq!(catalog.list_tables().await?, vec![items, users]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn indexes() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await?;
+    catalog
+        .create_table("users", users_columns(), vec![0])
+        .await?;
+
+    let pkey = catalog.get_index("users_pkey").await?.unwrap();
+    assert_eq!(
+        pkey,
+        IndexDef {
+            name: "users_pkey".to_string(),
+            table_name: "users".to_string(),
+            columns: vec![0],
+            unique: true,
+            primary: true,
+            meta_page_id: pkey.meta_page_id,
+        }
+    );
+    let by_name = catalog
+        .create_index("users_by_name", "users", vec![1, 2], false)
+        .await?;
+    assert_eq!(
+        catalog.get_index("users_by_name").await?,
+        Some(by_name.clone())
+    );
+    assert_eq!(
+        catalog.list_indexes("users").await?,
+        vec![by_name, pkey.clone()]
+    );
+
+    catalog.drop_index("users_by_name").await?;
+    assert_eq!(catalog.get_index("users_by_name").await?, None);
+    assert_eq!(catalog.list_indexes("users").await?, vec![pkey]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn drop_table_drops_indexes() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await?;
+    catalog
+        .create_table("users", users_columns(), vec![0])
+        .await?;
+    catalog
+        .create_index("users_by_name", "users", vec![1], false)
+        .await?;
+
+    catalog.drop_table("users").await?;
+    assert_eq!(catalog.get_table("users").await?, None);
+    assert_eq!(catalog.list_tables().await?, vec![]);
+    assert_eq!(catalog.get_index("users_pkey").await?, None);
+    assert_eq!(catalog.get_index("users_by_name").await?, None);
+
+    // The name can be reused.
+    catalog
+        .create_table("users", users_columns(), vec![])
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn statistics() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await?;
+    let table = catalog
+        .create_table("users", users_columns(), vec![0])
+        .await?;
+    assert_eq!(catalog.get_statistics(&table).await?, None);
+
+    let column = |null_fraction, most_common, histogram| ColumnStats {
+        null_fraction,
+        distinct: 42.5,
+        most_common,
+        histogram,
+    };
+    let mut stats = TableStats {
+        rows: 1000,
+        pages: 7,
+        columns: vec![
+            column(0.0, vec![], vec![Datum::Int8(1), Datum::Int8(500)]),
+            column(
+                0.25,
+                vec![(Datum::String("bob".to_string()), 0.125)],
+                vec![],
+            ),
+            column(0.0, vec![(Datum::Timestamp(-1), 0.5)], vec![]),
+        ],
+        indexes: vec![IndexStats {
+            name: "users_pkey".to_string(),
+            entries: 1000,
+            leaf_pages: 3,
+            height: 2,
+        }],
+    };
+    catalog.set_statistics(&table, &stats).await?;
+    assert_eq!(catalog.get_statistics(&table).await?, Some(stats.clone()));
+    stats.rows = 2000;
+    catalog.set_statistics(&table, &stats).await?;
+    assert_eq!(catalog.get_statistics(&table).await?, Some(stats.clone()));
+
+    // Statistics are dropped with the table, and can't be set with an outdated definition.
+    catalog.drop_table("users").await?;
+    let new_table = catalog
+        .create_table("users", users_columns(), vec![])
+        .await?;
+    assert_eq!(catalog.get_statistics(&new_table).await?, None);
+    match catalog.set_statistics(&table, &stats).await {
+        Err(Error::TableNotFound(name)) => assert_eq!(name, "users"),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await?;
+    catalog
+        .create_table("users", users_columns(), vec![0])
+        .await?;
+
+    macro_rules! assert_error {
+        ($expr:expr, $pattern:pat) => {
+            match $expr.await {
+                Err($pattern) => {}
+                result => panic!("unexpected result: {:?}", result),
+            }
+        };
+    }
+
+    assert_error!(
+        catalog.create_table("users", users_columns(), vec![]),
+        Error::TableExists(_)
+    );
+    assert_error!(
+        catalog.create_table(
+            "t",
+            vec![
+                table_column("a", Type::Int8, Nullability::NotNull),
+                table_column("a", Type::Int8, Nullability::NotNull),
+            ],
+            vec![],
+        ),
+        Error::DuplicateColumn(_)
+    );
+    assert_error!(
+        catalog.create_table("t", users_columns(), vec![3]),
+        Error::InvalidColumn(3)
+    );
+    assert_error!(
+        catalog.create_table("t", users_columns(), vec![0, 0]),
+        Error::DuplicateColumn(_)
+    );
+    assert_error!(
+        catalog.create_table("t", users_columns(), vec![1]),
+        Error::NullablePrimaryKey(_)
+    );
+    assert_error!(catalog.drop_table("t"), Error::TableNotFound(_));
+    assert_eq!(catalog.get_table("t").await?, None);
+
+    assert_error!(
+        catalog.create_index("users_pkey", "users", vec![1], false),
+        Error::IndexExists(_)
+    );
+    assert_error!(
+        catalog.create_index("i", "t", vec![0], false),
+        Error::TableNotFound(_)
+    );
+    assert_error!(
+        catalog.create_index("i", "users", vec![5], false),
+        Error::InvalidColumn(5)
+    );
+    assert_error!(catalog.drop_index("i"), Error::IndexNotFound(_));
+    assert_error!(catalog.drop_index("users_pkey"), Error::PrimaryKeyIndex(_));
+    Ok(())
+}
+
+#[tokio::test]
+async fn superblock() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    buffer_pool.allocate_page().await?;
+    match Catalog::open(&buffer_pool).await {
+        Err(Error::InvalidSuperblock) => {}
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+    match Catalog::create(&buffer_pool).await {
+        Err(Error::DatabaseNotEmpty) => {}
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    Catalog::create(&buffer_pool).await?;
+    let superblock = Superblock::read(&buffer_pool).await?;
+    {
+        let page = buffer_pool.get_page(SUPERBLOCK_PAGE_ID).await?;
+        let mut data = page.write().await;
+        data[4] = 3;
+        data.dirty();
+    }
+    match Catalog::open(&buffer_pool).await {
+        Err(Error::UnsupportedVersion(3)) => {}
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+    superblock.write(&buffer_pool).await?;
+    Catalog::open(&buffer_pool).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn reopen() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("catalog_reopen_{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let expected: Vec<TableDef> = {
+        let disk_manager = DiskManagerFile::open(&path).await.unwrap();
+        let buffer_pool = BufferPool::new(Box::new(disk_manager), 20);
+        let catalog = Catalog::create(&buffer_pool).await?;
+        for i in 0..50 {
+            catalog
+                .create_table(&format!("table_{:02}", i), users_columns(), vec![0, 2])
+                .await?;
+        }
+        buffer_pool.flush_all().await?;
+        catalog.list_tables().await?
+    };
+
+    let disk_manager = DiskManagerFile::open(&path).await.unwrap();
+    let buffer_pool = BufferPool::new(Box::new(disk_manager), 20);
+    let catalog = Catalog::open(&buffer_pool).await?;
+    assert_eq!(catalog.list_tables().await?, expected);
+    let index = catalog.get_index("table_07_pkey").await?.unwrap();
+    assert_eq!(index.columns, vec![0, 2]);
+
+    std::fs::remove_file(&path).unwrap();
+    Ok(())
+}
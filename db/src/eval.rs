@@ -0,0 +1,547 @@
+//! Evaluation of bound expressions (`logical_plan::Expr`) over rows of `Datum`s.
+//!
+//! NULL follows SQL semantics: comparisons with NULL are NULL, and `AND`, `OR` and `NOT` use
+//! three-valued logic (`NULL AND FALSE` is `FALSE`, `NULL OR TRUE` is `TRUE`). Other operators
+//! and functions are NULL if any argument is, except `coalesce`.
+//!
+//! The binder has already checked types and inserted casts, so both operands of an operator have
+//! the same type. Evaluation can still fail, on values which don't fit the result type, division
+//! by zero, text which can't be cast, and invalid function arguments:
+//! - integer arithmetic is checked, in the type of its operands,
+//! - `DOUBLE PRECISION` results are out of range if they're infinite but the operands aren't,
+//! - `NUMERIC` results are rounded to as many fractional digits as fit, up to
+//!   `MAX_NUMERIC_SCALE`, halves away from zero. So are casts to integer types.
+
+use crate::ast::{BinaryOp, UnaryOp};
+use crate::logical_plan::{Expr, Function};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use table::datum::text::ParseError;
+use table::datum::{Datum, Numeric, Type, MAX_NUMERIC_SCALE, MICROS_PER_DAY};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Error {
+    /// The value doesn't fit in the result type.
+    OutOfRange(Type),
+    DivisionByZero,
+    /// Text cast to another type isn't a valid value of it.
+    InvalidText(ParseError),
+    /// A `LIKE` pattern ends with the escape character.
+    InvalidLikePattern(String),
+    InvalidArgument {
+        function: Function,
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::OutOfRange(ty) => write!(f, "value out of range for type {:?}", ty),
+            Error::DivisionByZero => write!(f, "division by zero"),
+            Error::InvalidText(err) => write!(f, "{}", err),
+            Error::InvalidLikePattern(pattern) => write!(
+                f,
+                "LIKE pattern {:?} must not end with escape character",
+                pattern
+            ),
+            Error::InvalidArgument { function, reason } => {
+                write!(f, "invalid argument of {}: {}", function.name(), reason)
+            }
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub fn eval(expr: &Expr, row: &[Datum]) -> Result<Datum> {
+    Ok(match expr {
+        Expr::Constant { value, .. } => value.clone(),
+        Expr::Column { index, .. } => row[*index].clone(),
+        Expr::Unary {
+            op: UnaryOp::Not,
+            expr,
+        } => match eval(expr, row)? {
+            Datum::Bool(value) => Datum::Bool(!value),
+            _ => Datum::Null,
+        },
+        Expr::Unary {
+            op: UnaryOp::Minus,
+            expr,
+        } => negate(eval(expr, row)?)?,
+        Expr::Binary {
+            op: BinaryOp::And,
+            left,
+            right,
+        } => match eval(left, row)? {
+            Datum::Bool(false) => Datum::Bool(false),
+            left => match (left, eval(right, row)?) {
+                (_, Datum::Bool(false)) => Datum::Bool(false),
+                (Datum::Bool(true), Datum::Bool(true)) => Datum::Bool(true),
+                _ => Datum::Null,
+            },
+        },
+        Expr::Binary {
+            op: BinaryOp::Or,
+            left,
+            right,
+        } => match eval(left, row)? {
+            Datum::Bool(true) => Datum::Bool(true),
+            left => match (left, eval(right, row)?) {
+                (_, Datum::Bool(true)) => Datum::Bool(true),
+                (Datum::Bool(false), Datum::Bool(false)) => Datum::Bool(false),
+                _ => Datum::Null,
+            },
+        },
+        Expr::Binary { op, left, right } => {
+            let left = eval(left, row)?;
+            let right = eval(right, row)?;
+            if left == Datum::Null || right == Datum::Null {
+                return Ok(Datum::Null);
+            }
+            match op {
+                BinaryOp::Concat => Datum::String(format!("{}{}", left, right)),
+                BinaryOp::Plus
+                | BinaryOp::Minus
+                | BinaryOp::Multiply
+                | BinaryOp::Divide
+                | BinaryOp::Modulo => arithmetic(*op, left, right)?,
+                _ => compare(*op, &left, &right),
+            }
+        }
+        Expr::IsNull { expr, negated } => {
+            Datum::Bool((eval(expr, row)? == Datum::Null) != *negated)
+        }
+        Expr::Like {
+            expr,
+            pattern,
+            negated,
+        } => match (eval(expr, row)?, eval(pattern, row)?) {
+            (Datum::String(text), Datum::String(pattern)) => {
+                Datum::Bool(like(&text, &pattern)? != *negated)
+            }
+            _ => Datum::Null,
+        },
+        Expr::Case {
+            branches,
+            else_result,
+        } => {
+            for (condition, result) in branches {
+                if eval_predicate(condition, row)? {
+                    return eval(result, row);
+                }
+            }
+            eval(else_result, row)?
+        }
+        Expr::Cast { expr, ty } => cast(eval(expr, row)?, *ty)?,
+        Expr::Function {
+            function: Function::Coalesce,
+            args,
+        } => {
+            for arg in args {
+                let value = eval(arg, row)?;
+                if value != Datum::Null {
+                    return Ok(value);
+                }
+            }
+            Datum::Null
+        }
+        Expr::Function { function, args } => {
+            let args = args
+                .iter()
+                .map(|arg| eval(arg, row))
+                .collect::<Result<Vec<_>>>()?;
+            if args.contains(&Datum::Null) {
+                return Ok(Datum::Null);
+            }
+            call(*function, &args)?
+        }
+    })
+}
+
+/// Evaluate a condition, as in `WHERE`: rows for which it's NULL don't match.
+pub fn eval_predicate(expr: &Expr, row: &[Datum]) -> Result<bool> {
+    Ok(eval(expr, row)? == Datum::Bool(true))
+}
+
+/// Compare two non-NULL values of the same type.
+fn compare(op: BinaryOp, left: &Datum, right: &Datum) -> Datum {
+    let ordering = match left.compare(right) {
+        Some(ordering) => ordering,
+        None => panic!("comparison of {:?} and {:?}", left, right),
+    };
+    Datum::Bool(match op {
+        BinaryOp::Eq => ordering == Ordering::Equal,
+        BinaryOp::NotEq => ordering != Ordering::Equal,
+        BinaryOp::Lt => ordering == Ordering::Less,
+        BinaryOp::LtEq => ordering != Ordering::Greater,
+        BinaryOp::Gt => ordering == Ordering::Greater,
+        BinaryOp::GtEq => ordering != Ordering::Less,
+        _ => unreachable!("not a comparison: {:?}", op),
+    })
+}
+
+fn negate(value: Datum) -> Result<Datum> {
+    Ok(match value {
+        Datum::Null => Datum::Null,
+        Datum::Float8(value) => Datum::Float8(-value),
+        Datum::Numeric(value) => Datum::Numeric(Numeric::new(
+            value
+                .mantissa
+                .checked_neg()
+                .ok_or(Error::OutOfRange(Type::Numeric))?,
+            value.scale,
+        )),
+        value => {
+            let ty = value.ty().unwrap();
+            integer(
+                ty,
+                integer_arithmetic(BinaryOp::Minus, 0, as_integer(&value), ty)?,
+            )?
+        }
+    })
+}
+
+/// Arithmetic on two non-NULL numbers of the same type.
+fn arithmetic(op: BinaryOp, left: Datum, right: Datum) -> Result<Datum> {
+    Ok(match (left, right) {
+        (Datum::Float8(a), Datum::Float8(b)) => Datum::Float8(float_arithmetic(op, a, b)?),
+        (Datum::Numeric(a), Datum::Numeric(b)) => Datum::Numeric(numeric_arithmetic(op, a, b)?),
+        (left, right) => {
+            let ty = left.ty().unwrap();
+            assert_eq!(right.ty(), Some(ty), "arithmetic on different types");
+            integer(
+                ty,
+                integer_arithmetic(op, as_integer(&left), as_integer(&right), ty)?,
+            )?
+        }
+    })
+}
+
+/// `a op b` for integers of type `ty`, computed as `i64`. The result may not fit in `ty`.
+fn integer_arithmetic(op: BinaryOp, a: i64, b: i64, ty: Type) -> Result<i64> {
+    let result = match op {
+        BinaryOp::Plus => a.checked_add(b),
+        BinaryOp::Minus => a.checked_sub(b),
+        BinaryOp::Multiply => a.checked_mul(b),
+        BinaryOp::Divide | BinaryOp::Modulo if b == 0 => return Err(Error::DivisionByZero),
+        BinaryOp::Divide => a.checked_div(b),
+        // `i64::MIN % -1` overflows, but the remainder is 0 anyway.
+        BinaryOp::Modulo if b == -1 => Some(0),
+        BinaryOp::Modulo => Some(a % b),
+        _ => unreachable!("not an arithmetic operator: {:?}", op),
+    };
+    result.ok_or(Error::OutOfRange(ty))
+}
+
+fn float_arithmetic(op: BinaryOp, a: f64, b: f64) -> Result<f64> {
+    let result = match op {
+        BinaryOp::Plus => a + b,
+        BinaryOp::Minus => a - b,
+        BinaryOp::Multiply => a * b,
+        BinaryOp::Divide if b == 0.0 => return Err(Error::DivisionByZero),
+        BinaryOp::Divide => a / b,
+        _ => unreachable!("not a DOUBLE PRECISION operator: {:?}", op),
+    };
+    check_float(result, &[a, b])
+}
+
+/// Fail if the result of an operation overflowed, i.e. it's infinite but its operands aren't.
+fn check_float(result: f64, operands: &[f64]) -> Result<f64> {
+    if result.is_infinite() && operands.iter().all(|operand| operand.is_finite()) {
+        Err(Error::OutOfRange(Type::Float8))
+    } else {
+        Ok(result)
+    }
+}
+
+fn numeric_arithmetic(op: BinaryOp, a: Numeric, b: Numeric) -> Result<Numeric> {
+    // Normalized values are less than 10^37, so their sums fit in an i128.
+    let (a_normalized, b_normalized) = (a.normalized(), b.normalized());
+    let result = match op {
+        BinaryOp::Plus => Numeric::from_normalized(a_normalized + b_normalized),
+        BinaryOp::Minus => Numeric::from_normalized(a_normalized - b_normalized),
+        BinaryOp::Multiply => divide_numeric(
+            a.mantissa as i128 * b.mantissa as i128,
+            10i128.pow(a.scale as u32 + b.scale as u32),
+        ),
+        BinaryOp::Divide | BinaryOp::Modulo if b_normalized == 0 => {
+            return Err(Error::DivisionByZero)
+        }
+        BinaryOp::Divide => divide_numeric(a_normalized, b_normalized),
+        BinaryOp::Modulo => Numeric::from_normalized(a_normalized % b_normalized),
+        _ => unreachable!("not a NUMERIC operator: {:?}", op),
+    };
+    result.ok_or(Error::OutOfRange(Type::Numeric))
+}
+
+/// `numerator / denominator`, rounded to as many fractional digits as fit in a `Numeric`, up to
+/// `MAX_NUMERIC_SCALE`, halves away from zero. Returns None if the integer part doesn't fit.
+///
+/// The denominator must not be zero, and its absolute value must be less than 10^37.
+pub(crate) fn divide_numeric(numerator: i128, denominator: i128) -> Option<Numeric> {
+    let negative = (numerator < 0) != (denominator < 0);
+    let (numerator, denominator) = (numerator.checked_abs()?, denominator.checked_abs()?);
+    let (numerator, denominator) = (numerator as u128, denominator as u128);
+    let quotient = numerator / denominator;
+    if quotient > i64::MAX as u128 {
+        return None;
+    }
+    // The quotient truncated to `MAX_NUMERIC_SCALE` fractional digits, by long division.
+    let unit = 10u128.pow(MAX_NUMERIC_SCALE as u32);
+    let mut truncated = quotient * unit;
+    let mut remainder = numerator % denominator;
+    let mut digit_unit = unit;
+    while digit_unit > 1 {
+        digit_unit /= 10;
+        remainder *= 10;
+        truncated += remainder / denominator * digit_unit;
+        remainder %= denominator;
+    }
+    // Dropping digits worth less than `dropped` rounds up if they're at least half of it. The
+    // remainder only matters for the last digit: it's less than one.
+    let mut dropped = 1;
+    loop {
+        let round_up = if dropped == 1 {
+            remainder * 2 >= denominator
+        } else {
+            truncated % dropped >= dropped / 2
+        };
+        let mantissa = truncated / dropped + round_up as u128;
+        if mantissa <= i64::MAX as u128 {
+            let normalized = (mantissa * dropped) as i128;
+            return Numeric::from_normalized(if negative { -normalized } else { normalized });
+        }
+        if dropped == unit {
+            return None;
+        }
+        dropped *= 10;
+    }
+}
+
+/// The value of an integer datum.
+fn as_integer(value: &Datum) -> i64 {
+    match *value {
+        Datum::Int2(value) => value as i64,
+        Datum::Int4(value) => value as i64,
+        Datum::Int8(value) => value,
+        ref value => panic!("not an integer: {:?}", value),
+    }
+}
+
+/// The value of a numeric rounded to an integer, halves away from zero.
+fn round_numeric(value: Numeric) -> i64 {
+    let unit = 10i64.pow(value.scale as u32);
+    let (quotient, remainder) = (value.mantissa / unit, value.mantissa % unit);
+    if remainder.abs() * 2 >= unit {
+        quotient + value.mantissa.signum()
+    } else {
+        quotient
+    }
+}
+
+/// An integer of type `ty`, if the value fits.
+fn integer(ty: Type, value: i64) -> Result<Datum> {
+    let out_of_range = |_| Error::OutOfRange(ty);
+    Ok(match ty {
+        Type::Int2 => Datum::Int2(i16::try_from(value).map_err(out_of_range)?),
+        Type::Int4 => Datum::Int4(i32::try_from(value).map_err(out_of_range)?),
+        Type::Int8 => Datum::Int8(value),
+        _ => panic!("not an integer type: {:?}", ty),
+    })
+}
+
+/// Whether the text matches the `LIKE` pattern: `%` matches any sequence of characters, `_` any
+/// character, and `\` escapes the next character.
+fn like(text: &str, pattern: &str) -> Result<bool> {
+    #[derive(PartialEq)]
+    enum Token {
+        Many,
+        One,
+        Char(char),
+    }
+    let mut tokens = vec![];
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '%' => Token::Many,
+            '_' => Token::One,
+            '\\' => match chars.next() {
+                Some(c) => Token::Char(c),
+                None => return Err(Error::InvalidLikePattern(pattern.to_string())),
+            },
+            c => Token::Char(c),
+        });
+    }
+
+    let text: Vec<char> = text.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    // Positions after the last `%` in the pattern, and of the text it matches up to.
+    let mut backtrack = None;
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(Token::Many) => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(Token::One) => {
+                p += 1;
+                t += 1;
+            }
+            Some(Token::Char(c)) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            // Let the last `%` match one more character.
+            _ => match backtrack {
+                Some((after, end)) => {
+                    backtrack = Some((after, end + 1));
+                    p = after;
+                    t = end + 1;
+                }
+                None => return Ok(false),
+            },
+        }
+    }
+    Ok(tokens[p..].iter().all(|token| *token == Token::Many))
+}
+
+/// Convert a value to another type, as in `CAST`. The binder only allows conversions from and to
+/// text, between numeric types, between dates and timestamps, and between booleans and integers.
+fn cast(value: Datum, ty: Type) -> Result<Datum> {
+    let out_of_range = Error::OutOfRange(ty);
+    Ok(match (value, ty) {
+        (Datum::Null, _) => Datum::Null,
+        (value, _) if value.ty() == Some(ty) => value,
+        (value, Type::String) => Datum::String(value.to_string()),
+        (Datum::String(s), _) => Datum::parse(ty, &s).map_err(Error::InvalidText)?,
+        (Datum::Date(days), Type::Timestamp) => Datum::Timestamp(
+            (days as i64)
+                .checked_mul(MICROS_PER_DAY)
+                .ok_or(out_of_range)?,
+        ),
+        (Datum::Timestamp(micros), Type::Date) => {
+            Datum::Date(i32::try_from(micros.div_euclid(MICROS_PER_DAY)).map_err(|_| out_of_range)?)
+        }
+        (Datum::Bool(value), Type::Int4) => Datum::Int4(value as i32),
+        (Datum::Int4(value), Type::Bool) => Datum::Bool(value != 0),
+        (Datum::Float8(value), Type::Numeric) => {
+            // The shortest representation which parses back to the same float, unless it has
+            // too many fractional digits.
+            let mut text = value.to_string();
+            let fraction_digits = text.find('.').map_or(0, |dot| text.len() - dot - 1);
+            if fraction_digits > MAX_NUMERIC_SCALE as usize {
+                text = format!("{:.*}", MAX_NUMERIC_SCALE as usize, value);
+            }
+            // Rounded values may have trailing zeros.
+            match Datum::parse(ty, &text) {
+                Ok(Datum::Numeric(value)) => {
+                    Datum::Numeric(Numeric::from_normalized(value.normalized()).unwrap())
+                }
+                _ => return Err(out_of_range),
+            }
+        }
+        (Datum::Float8(value), _) => {
+            let rounded = value.round();
+            // `i64::MAX as f64` is 2^63, which doesn't fit.
+            if rounded.is_nan() || rounded < i64::MIN as f64 || rounded >= i64::MAX as f64 {
+                return Err(out_of_range);
+            }
+            integer(ty, rounded as i64)?
+        }
+        (Datum::Numeric(value), Type::Float8) => Datum::Float8(value.to_string().parse().unwrap()),
+        (Datum::Numeric(value), _) => integer(ty, round_numeric(value))?,
+        (value, Type::Float8) => Datum::Float8(as_integer(&value) as f64),
+        (value, Type::Numeric) => Datum::Numeric(Numeric::new(as_integer(&value), 0)),
+        (value, _) => integer(ty, as_integer(&value))?,
+    })
+}
+
+/// Call a function other than `coalesce`, with non-NULL arguments.
+fn call(function: Function, args: &[Datum]) -> Result<Datum> {
+    let invalid = |reason| Error::InvalidArgument { function, reason };
+    let string = |index: usize| match &args[index] {
+        Datum::String(s) => s.as_str(),
+        value => panic!("{} argument is not a string: {:?}", function.name(), value),
+    };
+    let float = |index: usize| match args[index] {
+        Datum::Float8(value) => value,
+        ref value => panic!("{} argument is not a float: {:?}", function.name(), value),
+    };
+    Ok(match function {
+        Function::Lower => Datum::String(string(0).to_lowercase()),
+        Function::Upper => Datum::String(string(0).to_uppercase()),
+        Function::Length => Datum::Int4(string(0).chars().count() as i32),
+        Function::Substr => {
+            // Positions of characters are 1-based, and may be out of the string.
+            let start = as_integer(&args[1]);
+            let end = match args.get(2) {
+                Some(count) if as_integer(count) < 0 => {
+                    return Err(invalid("negative substring length"))
+                }
+                Some(count) => start.saturating_add(as_integer(count)),
+                None => i64::MAX,
+            };
+            let s = string(0);
+            Datum::String(
+                s.chars()
+                    .zip(1..)
+                    .filter(|&(_, position)| position >= start && position < end)
+                    .map(|(c, _)| c)
+                    .collect(),
+            )
+        }
+        Function::Trim => Datum::String(string(0).trim_matches(' ').to_string()),
+        Function::Replace => Datum::String(if string(1).is_empty() {
+            string(0).to_string()
+        } else {
+            string(0).replace(string(1), string(2))
+        }),
+        Function::Abs => {
+            let negative = match args[0] {
+                Datum::Float8(value) => value < 0.0,
+                Datum::Numeric(value) => value.mantissa < 0,
+                ref value => as_integer(value) < 0,
+            };
+            if negative {
+                negate(args[0].clone())?
+            } else {
+                args[0].clone()
+            }
+        }
+        Function::Round | Function::Floor | Function::Ceil => match args[0] {
+            Datum::Float8(value) => Datum::Float8(match function {
+                Function::Round => value.round(),
+                Function::Floor => value.floor(),
+                _ => value.ceil(),
+            }),
+            Datum::Numeric(value) => {
+                let unit = 10i64.pow(value.scale as u32);
+                let floor = value.mantissa.div_euclid(unit);
+                Datum::Numeric(Numeric::new(
+                    match function {
+                        Function::Round => round_numeric(value),
+                        Function::Floor => floor,
+                        _ => floor + (value.mantissa.rem_euclid(unit) != 0) as i64,
+                    },
+                    0,
+                ))
+            }
+            ref value => value.clone(),
+        },
+        Function::Sqrt if float(0) < 0.0 => return Err(invalid("negative number")),
+        Function::Sqrt => Datum::Float8(float(0).sqrt()),
+        Function::Power => {
+            let (base, exponent) = (float(0), float(1));
+            if base == 0.0 && exponent < 0.0 {
+                return Err(invalid("zero raised to a negative power"));
+            }
+            if base < 0.0 && exponent.is_finite() && exponent.fract() != 0.0 {
+                return Err(invalid("negative number raised to a fractional power"));
+            }
+            Datum::Float8(check_float(base.powf(exponent), &[base, exponent])?)
+        }
+        Function::Coalesce => unreachable!("coalesce is evaluated lazily"),
+    })
+}
@@ -0,0 +1,396 @@
+use crate::ast::{BinaryOp, UnaryOp};
+use crate::catalog::Catalog;
+use crate::eval::eval;
+use crate::logical_plan::Expr;
+use crate::test_utils::run_all;
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager_mem::DiskManagerMem;
+use proptest::prelude::*;
+use table::datum::{Datum, Type};
+
+async fn create_values(catalog: &Catalog<'_>) {
+    run_all(
+        catalog,
+        &[
+            "create table v (id int primary key, i int, b bigint, s smallint, n numeric, \
+             f double precision, t text, d date)",
+            "insert into v values \
+             (1, 7, 9223372036854775807, 32767, 2.50, 1.5, 'Hello', '2024-02-29'), \
+             (2, -2147483648, -9223372036854775808, -32768, -0.005, -2.5, 'a_b%c', '1970-01-01'), \
+             (3, null, null, null, null, null, null, null)",
+        ],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn arithmetic() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    create_values(&catalog).await;
+    assert_snapshot!(run_all(&catalog, &[
+        "select i + 1, i - 1, i * 3, i / 2, i % 4, -i from v where id = 1",
+        "select i / 2, i % 3, -7 / 2, -7 % 2 from v where id = 2",
+        "select i - 1 from v where id = 2",
+        "select -i from v where id = 2",
+        "select i / -1 from v where id = 2",
+        "select i % -1 from v where id = 2",
+        "select b + 1 from v where id = 1",
+        "select b * 2 from v where id = 2",
+        "select s + 1 from v where id = 1",
+        "select s + 1 from v where id = 2",
+        "select i / 0 from v where id = 1",
+        "select i % 0 from v where id = 1",
+        "select n + 1, n - 3, n * n, n / 3, n % 1, -n from v where id = 1",
+        "select n * n, n / 3, n * 1000000000000 from v where id = 2",
+        "select n / 0 from v where id = 1",
+        "select 1 / 3.0, 2 / 3.0, 1000000000000000000 / 3.0, 1 / 0.000000000000000007",
+        "select 9223372036854775807 * 10.0",
+        "select f + 1, f * f, f / 4, -f from v where id = 1",
+        "select f / 0 from v where id = 1",
+        "select f * 1e308 from v where id = 2",
+        "select i + n, i + f, i - b from v where id = 1",
+        "select i + 1, n * 2, t || '!', -f from v where id = 3",
+        "select t || i, i || '' from v where id = 1",
+    ]).await, @r###"
+    select i + 1, i - 1, i * 3, i / 2, i % 4, -i from v where id = 1
+    8 | 6 | 21 | 3 | 3 | -7
+    select i / 2, i % 3, -7 / 2, -7 % 2 from v where id = 2
+    -1073741824 | -2 | -3 | -1
+    select i - 1 from v where id = 2
+    error: value out of range for type Int4
+    select -i from v where id = 2
+    error: value out of range for type Int4
+    select i / -1 from v where id = 2
+    error: value out of range for type Int4
+    select i % -1 from v where id = 2
+    0
+    select b + 1 from v where id = 1
+    error: value out of range for type Int8
+    select b * 2 from v where id = 2
+    error: value out of range for type Int8
+    select s + 1 from v where id = 1
+    32768
+    select s + 1 from v where id = 2
+    -32767
+    select i / 0 from v where id = 1
+    error: division by zero
+    select i % 0 from v where id = 1
+    error: division by zero
+    select n + 1, n - 3, n * n, n / 3, n % 1, -n from v where id = 1
+    3.5 | -0.5 | 6.25 | 0.833333333333333333 | 0.5 | -2.50
+    select n * n, n / 3, n * 1000000000000 from v where id = 2
+    0.000025 | -0.001666666666666667 | -5000000000
+    select n / 0 from v where id = 1
+    error: division by zero
+    select 1 / 3.0, 2 / 3.0, 1000000000000000000 / 3.0, 1 / 0.000000000000000007
+    0.333333333333333333 | 0.666666666666666667 | 333333333333333333.3 | 142857142857142857.1
+    select 9223372036854775807 * 10.0
+    error: value out of range for type Numeric
+    select f + 1, f * f, f / 4, -f from v where id = 1
+    2.5 | 2.25 | 0.375 | -1.5
+    select f / 0 from v where id = 1
+    error: division by zero
+    select f * 1e308 from v where id = 2
+    error: value out of range for type Float8
+    select i + n, i + f, i - b from v where id = 1
+    9.5 | 8.5 | -9223372036854775800
+    select i + 1, n * 2, t || '!', -f from v where id = 3
+    NULL | NULL | NULL | NULL
+    select t || i, i || '' from v where id = 1
+    Hello7 | 7
+    "###);
+}
+
+#[tokio::test]
+async fn like_and_case() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    create_values(&catalog).await;
+    assert_snapshot!(run_all(&catalog, &[
+        "select id, t like 'H%', t like '_ello', t like '%l_o', t not like '%L%' from v",
+        "select t like 'a\\_b\\%c', t like 'a\\_b\\%', t like '%\\%%', t like 'a%%c' from v where id = 2",
+        "select 'ab' like '', '' like '%', 'abc' like 'a%b%c%', 'aXbXc' like 'a%b%c', 'ab' like 'a%b%c'",
+        "select 'ä' like '_', 'a' like 'A'",
+        "select t like 'a\\' from v where id = 1",
+        "select t like null from v where id = 1",
+        "select id, case when i > 0 then 'positive' when i < 0 then 'negative' end from v",
+        "select id, case i when 7 then 1 else 2 end, case when i is null then f else n end from v",
+        "select case when true then 1 else 1 / 0 end",
+    ]).await, @r###"
+    select id, t like 'H%', t like '_ello', t like '%l_o', t not like '%L%' from v
+    1 | true | true | true | true
+    2 | false | false | false | true
+    3 | NULL | NULL | NULL | NULL
+    select t like 'a\_b\%c', t like 'a\_b\%', t like '%\%%', t like 'a%%c' from v where id = 2
+    true | false | true | true
+    select 'ab' like '', '' like '%', 'abc' like 'a%b%c%', 'aXbXc' like 'a%b%c', 'ab' like 'a%b%c'
+    false | true | true | true | false
+    select 'ä' like '_', 'a' like 'A'
+    true | false
+    select t like 'a\' from v where id = 1
+    error: LIKE pattern "a\\" must not end with escape character
+    select t like null from v where id = 1
+    NULL
+    select id, case when i > 0 then 'positive' when i < 0 then 'negative' end from v
+    1 | positive
+    2 | negative
+    3 | NULL
+    select id, case i when 7 then 1 else 2 end, case when i is null then f else n end from v
+    1 | 1 | 2.5
+    2 | 2 | -0.005
+    3 | 2 | NULL
+    select case when true then 1 else 1 / 0 end
+    1
+    "###);
+}
+
+#[tokio::test]
+async fn cast() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    create_values(&catalog).await;
+    run_all(
+        &catalog,
+        &[
+            "create table texts (t text)",
+            "insert into texts values ('12'), ('1.25'), ('true'), ('2024-01-31'), ('x')",
+        ],
+    )
+    .await;
+    assert_snapshot!(run_all(&catalog, &[
+        "select cast(i as text), cast(n as text), cast(f as text), cast(d as text) from v",
+        "select cast(n as int), cast(f as int), cast(n as double precision), cast(f as numeric) from v",
+        "select cast(i as smallint) from v where id = 1",
+        "select cast(i as smallint) from v where id = 2",
+        "select cast(b as int) from v where id = 1",
+        "select cast(f * 1e18 as bigint) from v where id = 1",
+        "select cast(f * 1e300 as numeric) from v where id = 1",
+        "select cast(f / 3 as numeric), cast(f / 1e19 as numeric) from v where id = 1",
+        "select cast(cast(d as timestamp) as date), cast(i > 0 as int), cast(i as boolean) from v",
+        "select cast(t as int) from texts where t = '12'",
+        "select cast(t as numeric), cast(t as double precision) from texts where t = '1.25'",
+        "select cast(t as boolean) from texts where t = 'true'",
+        "select cast(t as date) from texts where t = '2024-01-31'",
+        "select cast(t as int) from texts where t = 'x'",
+    ]).await, @r###"
+    select cast(i as text), cast(n as text), cast(f as text), cast(d as text) from v
+    7 | 2.50 | 1.5 | 2024-02-29
+    -2147483648 | -0.005 | -2.5 | 1970-01-01
+    NULL | NULL | NULL | NULL
+    select cast(n as int), cast(f as int), cast(n as double precision), cast(f as numeric) from v
+    3 | 2 | 2.5 | 1.5
+    0 | -3 | -0.005 | -2.5
+    NULL | NULL | NULL | NULL
+    select cast(i as smallint) from v where id = 1
+    7
+    select cast(i as smallint) from v where id = 2
+    error: value out of range for type Int2
+    select cast(b as int) from v where id = 1
+    error: value out of range for type Int4
+    select cast(f * 1e18 as bigint) from v where id = 1
+    1500000000000000000
+    select cast(f * 1e300 as numeric) from v where id = 1
+    error: value out of range for type Numeric
+    select cast(f / 3 as numeric), cast(f / 1e19 as numeric) from v where id = 1
+    0.5 | 0
+    select cast(cast(d as timestamp) as date), cast(i > 0 as int), cast(i as boolean) from v
+    2024-02-29 | 1 | true
+    1970-01-01 | 0 | true
+    NULL | NULL | NULL
+    select cast(t as int) from texts where t = '12'
+    12
+    select cast(t as numeric), cast(t as double precision) from texts where t = '1.25'
+    1.25 | 1.25
+    select cast(t as boolean) from texts where t = 'true'
+    true
+    select cast(t as date) from texts where t = '2024-01-31'
+    2024-01-31
+    select cast(t as int) from texts where t = 'x'
+    error: invalid input for type Int4: "x"
+    "###);
+}
+
+#[tokio::test]
+async fn functions() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    create_values(&catalog).await;
+    assert_snapshot!(run_all(&catalog, &[
+        "select lower(t), upper(t), length(t), length('äbc') from v",
+        "select substr(t, 2), substr(t, 2, 3), substr(t, 0, 2), substr(t, 10), substr(t, -1, 3) from v",
+        "select substr(t, 1, -1) from v where id = 1",
+        "select trim('  a b  '), replace(t, 'l', 'L'), replace(t, '', 'x') from v where id = 1",
+        "select abs(i), abs(n), abs(f), abs(s) from v where id <> 2",
+        "select abs(n), abs(f) from v where id = 2",
+        "select abs(s) from v where id = 2",
+        "select round(n), floor(n), ceil(n), round(f), floor(f), ceil(f), round(i) from v",
+        "select round(-2.5), floor(-0.5), ceil(-0.5), ceil(0.001)",
+        "select sqrt(16), sqrt(2), power(2, 10), power(2, 0.5), power(-8, 3)",
+        "select sqrt(-1)",
+        "select power(0, -1)",
+        "select power(-8, 0.5)",
+        "select power(10, 400)",
+        "select coalesce(i, b, 0), coalesce(t, 'none'), coalesce(null, null, n) from v",
+        "select coalesce(1, 1 / 0)",
+        "select substr('abc')",
+        "select replace('abc', 'b')",
+        "select round('x')",
+        "select substr('abc', 'x')",
+        "select power(2)",
+    ]).await, @r###"
+    select lower(t), upper(t), length(t), length('äbc') from v
+    hello | HELLO | 5 | 3
+    a_b%c | A_B%C | 5 | 3
+    NULL | NULL | NULL | 3
+    select substr(t, 2), substr(t, 2, 3), substr(t, 0, 2), substr(t, 10), substr(t, -1, 3) from v
+    ello | ell | H |  | H
+    _b%c | _b% | a |  | a
+    NULL | NULL | NULL | NULL | NULL
+    select substr(t, 1, -1) from v where id = 1
+    error: invalid argument of substr: negative substring length
+    select trim('  a b  '), replace(t, 'l', 'L'), replace(t, '', 'x') from v where id = 1
+    a b | HeLLo | Hello
+    select abs(i), abs(n), abs(f), abs(s) from v where id <> 2
+    7 | 2.50 | 1.5 | 32767
+    NULL | NULL | NULL | NULL
+    select abs(n), abs(f) from v where id = 2
+    0.005 | 2.5
+    select abs(s) from v where id = 2
+    error: value out of range for type Int2
+    select round(n), floor(n), ceil(n), round(f), floor(f), ceil(f), round(i) from v
+    3 | 2 | 3 | 2 | 1 | 2 | 7
+    0 | -1 | 0 | -3 | -3 | -2 | -2147483648
+    NULL | NULL | NULL | NULL | NULL | NULL | NULL
+    select round(-2.5), floor(-0.5), ceil(-0.5), ceil(0.001)
+    -3 | -1 | 0 | 1
+    select sqrt(16), sqrt(2), power(2, 10), power(2, 0.5), power(-8, 3)
+    4 | 1.4142135623730951 | 1024 | 1.4142135623730951 | -512
+    select sqrt(-1)
+    error: invalid argument of sqrt: negative number
+    select power(0, -1)
+    error: invalid argument of power: zero raised to a negative power
+    select power(-8, 0.5)
+    error: invalid argument of power: negative number raised to a fractional power
+    select power(10, 400)
+    error: value out of range for type Float8
+    select coalesce(i, b, 0), coalesce(t, 'none'), coalesce(null, null, n) from v
+    7 | Hello | 2.50
+    -2147483648 | a_b%c | -0.005
+    0 | none | NULL
+    select coalesce(1, 1 / 0)
+    1
+    select substr('abc')
+    error: function substr does not take 1 arguments
+    select replace('abc', 'b')
+    error: function replace does not take 2 arguments
+    select round('x')
+    error: function round(TEXT) does not exist
+    select substr('abc', 'x')
+    error: invalid input for type Int4: "x"
+    select power(2)
+    error: function power does not take 1 arguments
+    "###);
+}
+
+/// A constant of type `BOOLEAN`, NULL if `value` is None.
+fn boolean(value: Option<bool>) -> Expr {
+    Expr::Constant {
+        value: value.map_or(Datum::Null, Datum::Bool),
+        ty: Type::Bool,
+    }
+}
+
+fn not(expr: Expr) -> Expr {
+    Expr::Unary {
+        op: UnaryOp::Not,
+        expr: Box::new(expr),
+    }
+}
+
+fn binary(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+    Expr::Binary {
+        op,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+fn and(left: Expr, right: Expr) -> Expr {
+    binary(BinaryOp::And, left, right)
+}
+
+fn or(left: Expr, right: Expr) -> Expr {
+    binary(BinaryOp::Or, left, right)
+}
+
+fn value(expr: &Expr) -> Datum {
+    eval(expr, &[]).unwrap()
+}
+
+fn check_equivalent(a: Expr, b: Expr) -> Result<(), TestCaseError> {
+    prop_assert_eq!(value(&a), value(&b), "{} is not equivalent to {}", a, b);
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 50, ..ProptestConfig::default()
+    })]
+    #[test]
+    fn three_valued_logic(
+        x in any::<Option<bool>>(),
+        y in any::<Option<bool>>(),
+        z in any::<Option<bool>>(),
+    ) {
+        let (x, y, z) = (boolean(x), boolean(y), boolean(z));
+        // Double negation, De Morgan.
+        check_equivalent(not(not(x.clone())), x.clone())?;
+        check_equivalent(not(and(x.clone(), y.clone())), or(not(x.clone()), not(y.clone())))?;
+        check_equivalent(not(or(x.clone(), y.clone())), and(not(x.clone()), not(y.clone())))?;
+        // Commutativity, associativity.
+        check_equivalent(and(x.clone(), y.clone()), and(y.clone(), x.clone()))?;
+        check_equivalent(or(x.clone(), y.clone()), or(y.clone(), x.clone()))?;
+        check_equivalent(
+            and(and(x.clone(), y.clone()), z.clone()),
+            and(x.clone(), and(y.clone(), z.clone())),
+        )?;
+        check_equivalent(
+            or(or(x.clone(), y.clone()), z.clone()),
+            or(x.clone(), or(y.clone(), z.clone())),
+        )?;
+        // Distributivity, absorption, idempotence.
+        check_equivalent(
+            and(x.clone(), or(y.clone(), z.clone())),
+            or(and(x.clone(), y.clone()), and(x.clone(), z.clone())),
+        )?;
+        check_equivalent(
+            or(x.clone(), and(y.clone(), z.clone())),
+            and(or(x.clone(), y.clone()), or(x.clone(), z.clone())),
+        )?;
+        check_equivalent(and(x.clone(), or(x.clone(), y.clone())), x.clone())?;
+        check_equivalent(or(x.clone(), and(x.clone(), y.clone())), x.clone())?;
+        check_equivalent(and(x.clone(), x.clone()), x.clone())?;
+        // Identities and annihilators.
+        check_equivalent(and(x.clone(), boolean(Some(true))), x.clone())?;
+        check_equivalent(or(x.clone(), boolean(Some(false))), x.clone())?;
+        check_equivalent(and(x.clone(), boolean(Some(false))), boolean(Some(false)))?;
+        check_equivalent(or(x.clone(), boolean(Some(true))), boolean(Some(true)))?;
+
+        // The excluded middle only holds if `x` isn't NULL.
+        let known = value(&x) != Datum::Null;
+        let expected = if known { Some(true) } else { None };
+        check_equivalent(or(x.clone(), not(x.clone())), boolean(expected))?;
+        check_equivalent(not(and(x.clone(), not(x.clone()))), boolean(expected))?;
+        // Comparisons with NULL are NULL, even of NULL with itself.
+        for &op in &[BinaryOp::Eq, BinaryOp::NotEq, BinaryOp::Lt, BinaryOp::GtEq] {
+            let compared = value(&binary(op, x.clone(), y.clone()));
+            let any_null = !known || value(&y) == Datum::Null;
+            prop_assert_eq!(compared == Datum::Null, any_null);
+        }
+        check_equivalent(
+            Expr::IsNull { expr: Box::new(x.clone()), negated: false },
+            boolean(Some(!known)),
+        )?;
+    }
+}
@@ -0,0 +1,358 @@
+//! Aggregation operators.
+//!
+//! Both compute the output of `LogicalPlan::Aggregate`: a row for each group of input rows with
+//! equal values of the `group_by` expressions, with the group values followed by the results of
+//! the aggregates. Group values are compared by their key encoding, so NULLs are equal to each
+//! other, and e.g. numerics of different scales but the same value are in the same group.
+
+use super::spill::{row_size, Partitions, SpillFile, SpillReader, MAX_PARTITION_DEPTH};
+use super::{Executor, Result, Row};
+use crate::eval::{self, divide_numeric, eval};
+use crate::logical_plan::{AggregateCall, AggregateFunction, Expr};
+use async_trait::async_trait;
+use buffer_pool::buffer_pool::BufferPool;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::mem::size_of;
+use table::datum::{Datum, Numeric, Type, MAX_NUMERIC_SCALE};
+use table::key_encoding::{encode_key, KeyColumn, NullsOrder, SortOrder};
+
+/// State of an aggregate function for one group.
+enum Accumulator {
+    Count(i64),
+    /// For `sum` and `avg`: the sum of integers and numerics (normalized) in `int`, of floats in
+    /// `float`, and the number of values.
+    Sum {
+        int: i128,
+        float: f64,
+        count: i64,
+    },
+    Min(Datum),
+    Max(Datum),
+}
+
+impl Accumulator {
+    fn new(function: AggregateFunction) -> Self {
+        match function {
+            AggregateFunction::Count => Accumulator::Count(0),
+            AggregateFunction::Sum | AggregateFunction::Avg => Accumulator::Sum {
+                int: 0,
+                float: 0.0,
+                count: 0,
+            },
+            AggregateFunction::Min => Accumulator::Min(Datum::Null),
+            AggregateFunction::Max => Accumulator::Max(Datum::Null),
+        }
+    }
+
+    fn update(&mut self, call: &AggregateCall, row: &[Datum]) -> Result<()> {
+        let value = match &call.arg {
+            Some(arg) => eval(arg, row)?,
+            // `count(*)` counts all rows.
+            None => Datum::Bool(true),
+        };
+        if value == Datum::Null {
+            return Ok(());
+        }
+        match self {
+            Accumulator::Count(count) => *count += 1,
+            Accumulator::Sum { int, float, count } => {
+                match value {
+                    Datum::Int2(value) => *int += value as i128,
+                    Datum::Int4(value) => *int += value as i128,
+                    Datum::Int8(value) => *int += value as i128,
+                    Datum::Numeric(value) => {
+                        *int = int
+                            .checked_add(value.normalized())
+                            .ok_or(eval::Error::OutOfRange(Type::Numeric))?
+                    }
+                    Datum::Float8(value) => *float += value,
+                    value => panic!("sum of a non-numeric value: {:?}", value),
+                }
+                *count += 1;
+            }
+            Accumulator::Min(min) => {
+                if *min == Datum::Null || value.compare(min) == Some(Ordering::Less) {
+                    *min = value;
+                }
+            }
+            Accumulator::Max(max) => {
+                if *max == Datum::Null || value.compare(max) == Some(Ordering::Greater) {
+                    *max = value;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self, call: &AggregateCall) -> Result<Datum> {
+        let (int, float, count) = match self {
+            Accumulator::Count(count) => return Ok(Datum::Int8(count)),
+            Accumulator::Min(value) | Accumulator::Max(value) => return Ok(value),
+            Accumulator::Sum { count: 0, .. } => return Ok(Datum::Null),
+            Accumulator::Sum { int, float, count } => (int, float, count),
+        };
+        let out_of_range = |ty| eval::Error::OutOfRange(ty);
+        Ok(match (call.function, call.arg.as_ref().map(Expr::ty)) {
+            (AggregateFunction::Sum, Some(Type::Float8)) => Datum::Float8(float),
+            (AggregateFunction::Sum, Some(Type::Numeric)) => Datum::Numeric(
+                Numeric::from_normalized(int).ok_or_else(|| out_of_range(Type::Numeric))?,
+            ),
+            (AggregateFunction::Sum, _) => {
+                Datum::Int8(i64::try_from(int).map_err(|_| out_of_range(Type::Int8))?)
+            }
+            (_, Some(Type::Float8)) => Datum::Float8(float / count as f64),
+            // The sum of numerics is normalized.
+            (_, Some(Type::Numeric)) => Datum::Numeric(
+                divide_numeric(int, count as i128 * 10i128.pow(MAX_NUMERIC_SCALE as u32))
+                    .ok_or_else(|| out_of_range(Type::Numeric))?,
+            ),
+            _ => Datum::Numeric(
+                divide_numeric(int, count as i128).ok_or_else(|| out_of_range(Type::Numeric))?,
+            ),
+        })
+    }
+}
+
+fn new_accumulators(aggregates: &[AggregateCall]) -> Vec<Accumulator> {
+    aggregates
+        .iter()
+        .map(|call| Accumulator::new(call.function))
+        .collect()
+}
+
+fn update(
+    aggregates: &[AggregateCall],
+    accumulators: &mut [Accumulator],
+    row: &[Datum],
+) -> Result<()> {
+    for (call, accumulator) in aggregates.iter().zip(accumulators) {
+        accumulator.update(call, row)?;
+    }
+    Ok(())
+}
+
+/// The output row of a group.
+fn finish(
+    aggregates: &[AggregateCall],
+    mut values: Row,
+    accumulators: Vec<Accumulator>,
+) -> Result<Row> {
+    for (call, accumulator) in aggregates.iter().zip(accumulators) {
+        values.push(accumulator.finish(call)?);
+    }
+    Ok(values)
+}
+
+fn key_columns(group_by: &[Expr]) -> Vec<KeyColumn> {
+    group_by
+        .iter()
+        .map(|expr| KeyColumn {
+            ty: expr.ty(),
+            order: SortOrder::Ascending,
+            nulls: NullsOrder::NullsLast,
+        })
+        .collect()
+}
+
+fn eval_group(group_by: &[Expr], row: &[Datum]) -> Result<Row> {
+    Ok(group_by
+        .iter()
+        .map(|expr| eval(expr, row))
+        .collect::<std::result::Result<_, _>>()?)
+}
+
+/// Rows being aggregated by `HashAggregate`: its input, or a spilled partition of it.
+enum Source<'a> {
+    Input(Box<dyn Executor + 'a>),
+    Spilled(SpillReader<'a>),
+}
+
+impl Source<'_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        match self {
+            Source::Input(input) => input.next().await,
+            Source::Spilled(reader) => reader.next().await,
+        }
+    }
+}
+
+/// Aggregates the groups in a hash table.
+///
+/// Once the groups take more than `memory_budget` bytes, the groups in memory are still updated,
+/// but input rows of other groups are split into partitions by the hash of their group values and
+/// written to spill files. The partitions are then aggregated one by one, the same way.
+pub struct HashAggregate<'a> {
+    buffer_pool: &'a BufferPool,
+    input: Option<Box<dyn Executor + 'a>>,
+    group_by: Vec<Expr>,
+    aggregates: Vec<AggregateCall>,
+    memory_budget: usize,
+    /// Partitions waiting to be aggregated, with the number of times their rows were split.
+    partitions: Vec<(SpillFile<'a>, u32)>,
+    output: std::vec::IntoIter<Row>,
+}
+
+impl<'a> HashAggregate<'a> {
+    pub fn new(
+        buffer_pool: &'a BufferPool,
+        input: Box<dyn Executor + 'a>,
+        group_by: Vec<Expr>,
+        aggregates: Vec<AggregateCall>,
+        memory_budget: usize,
+    ) -> Self {
+        HashAggregate {
+            buffer_pool,
+            input: Some(input),
+            group_by,
+            aggregates,
+            memory_budget,
+            partitions: vec![],
+            output: vec![].into_iter(),
+        }
+    }
+
+    /// Aggregate the rows of the source into `output`, spilling the ones which don't fit.
+    async fn aggregate(&mut self, mut source: Source<'a>, depth: u32) -> Result<()> {
+        let key_columns = key_columns(&self.group_by);
+        let mut groups: HashMap<Vec<u8>, (Row, Vec<Accumulator>)> = HashMap::new();
+        let mut size = 0;
+        let mut partitions = None;
+        while let Some(row) = source.next().await? {
+            let values = eval_group(&self.group_by, &row)?;
+            let key = encode_key(&key_columns, &values);
+            if !groups.contains_key(&key) {
+                let group_size = key.len()
+                    + row_size(&values)
+                    + self.aggregates.len() * size_of::<Accumulator>();
+                // Past the maximum depth, the partition most likely has a few huge groups,
+                // which are all in memory already.
+                let fits = groups.is_empty()
+                    || size + group_size <= self.memory_budget
+                    || depth >= MAX_PARTITION_DEPTH;
+                if !fits {
+                    if partitions.is_none() {
+                        partitions = Some(Partitions::new(self.buffer_pool, depth).await?);
+                    }
+                    partitions.as_mut().unwrap().write(&key, &row).await?;
+                    continue;
+                }
+                size += group_size;
+            }
+            let aggregates = &self.aggregates;
+            let (_, accumulators) = groups
+                .entry(key)
+                .or_insert_with(|| (values, new_accumulators(aggregates)));
+            update(&self.aggregates, accumulators, &row)?;
+        }
+        drop(source);
+
+        let mut rows = vec![];
+        // Without GROUP BY, there's a group even if there are no rows.
+        if groups.is_empty() && self.group_by.is_empty() {
+            rows.push(finish(
+                &self.aggregates,
+                vec![],
+                new_accumulators(&self.aggregates),
+            )?);
+        }
+        for (_, (values, accumulators)) in groups {
+            rows.push(finish(&self.aggregates, values, accumulators)?);
+        }
+        self.output = rows.into_iter();
+        if let Some(partitions) = partitions {
+            for file in partitions.finish().await? {
+                if !file.is_empty() {
+                    self.partitions.push((file, depth + 1));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Executor for HashAggregate<'_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        loop {
+            if let Some(row) = self.output.next() {
+                return Ok(Some(row));
+            }
+            if let Some(input) = self.input.take() {
+                self.aggregate(Source::Input(input), 0).await?;
+            } else if let Some((file, depth)) = self.partitions.pop() {
+                let reader = file.reader();
+                self.aggregate(Source::Spilled(reader), depth).await?;
+            } else {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Aggregates input sorted by the `group_by` expressions (in any direction), one group at a time.
+pub struct SortAggregate<'a> {
+    input: Box<dyn Executor + 'a>,
+    group_by: Vec<Expr>,
+    aggregates: Vec<AggregateCall>,
+    key_columns: Vec<KeyColumn>,
+    /// The key, values and accumulators of the current group.
+    group: Option<(Vec<u8>, Row, Vec<Accumulator>)>,
+    done: bool,
+}
+
+impl<'a> SortAggregate<'a> {
+    pub fn new(
+        input: Box<dyn Executor + 'a>,
+        group_by: Vec<Expr>,
+        aggregates: Vec<AggregateCall>,
+    ) -> Self {
+        SortAggregate {
+            input,
+            key_columns: key_columns(&group_by),
+            group_by,
+            aggregates,
+            group: None,
+            done: false,
+        }
+    }
+}
+
+#[async_trait]
+impl Executor for SortAggregate<'_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        if self.done {
+            return Ok(None);
+        }
+        while let Some(row) = self.input.next().await? {
+            let values = eval_group(&self.group_by, &row)?;
+            let key = encode_key(&self.key_columns, &values);
+            if let Some((group_key, _, accumulators)) = &mut self.group {
+                if *group_key == key {
+                    update(&self.aggregates, accumulators, &row)?;
+                    continue;
+                }
+            }
+            let mut accumulators = new_accumulators(&self.aggregates);
+            update(&self.aggregates, &mut accumulators, &row)?;
+            if let Some((_, values, accumulators)) = self.group.replace((key, values, accumulators))
+            {
+                return Ok(Some(finish(&self.aggregates, values, accumulators)?));
+            }
+        }
+        self.done = true;
+        match self.group.take() {
+            Some((_, values, accumulators)) => {
+                Ok(Some(finish(&self.aggregates, values, accumulators)?))
+            }
+            // Without GROUP BY, there's a group even if there are no rows.
+            None if self.group_by.is_empty() => Ok(Some(finish(
+                &self.aggregates,
+                vec![],
+                new_accumulators(&self.aggregates),
+            )?)),
+            None => Ok(None),
+        }
+    }
+}
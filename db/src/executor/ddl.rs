@@ -0,0 +1,200 @@
+//! Operators changing the schema. They don't return any rows.
+
+use super::{Error, Executor, ExternalSort, Result, Row, MEMORY_BUDGET};
+use crate::catalog::{Catalog, Error as CatalogError, TableDef};
+use crate::index;
+use crate::logical_plan::{Expr, LogicalPlan, SortKey};
+use crate::statistics::{Analyzer, IndexStats, TableStats, DEFAULT_SAMPLE_ROWS};
+use async_trait::async_trait;
+use buffer_pool::disk_manager::PageId;
+use table::datum::{Datum, Nullability, Type};
+use table::row::Row as RowData;
+use table::secondary_index::SecondaryIndex;
+use table::table_heap::TableHeap;
+
+/// Fill factor (in percent) of the pages of indexes built by `CREATE INDEX`. Leaves some room for
+/// rows inserted later.
+const INDEX_FILL_FACTOR: usize = 90;
+
+/// Executes a `CreateTable`, `DropTable`, `CreateIndex` or `Analyze` plan.
+pub struct Ddl<'a, 'b> {
+    catalog: &'a Catalog<'b>,
+    plan: LogicalPlan,
+    done: bool,
+}
+
+impl<'a, 'b> Ddl<'a, 'b> {
+    pub fn new(catalog: &'a Catalog<'b>, plan: LogicalPlan) -> Self {
+        Ddl {
+            catalog,
+            plan,
+            done: false,
+        }
+    }
+
+    async fn run(&self) -> Result<()> {
+        match &self.plan {
+            LogicalPlan::CreateTable {
+                name,
+                columns,
+                primary_key,
+            } => {
+                self.catalog
+                    .create_table(name, columns.clone(), primary_key.clone())
+                    .await?;
+            }
+            LogicalPlan::DropTable { name, if_exists } => {
+                match self.catalog.drop_table(name).await {
+                    Err(CatalogError::TableNotFound(_)) if *if_exists => {}
+                    result => result?,
+                }
+            }
+            LogicalPlan::CreateIndex {
+                name,
+                table,
+                columns,
+                unique,
+            } => {
+                // Checked before building the index; `create_index_with_btree` checks again.
+                if self.catalog.get_index(name).await?.is_some() {
+                    return Err(CatalogError::IndexExists(name.clone()).into());
+                }
+                let table_def = match self.catalog.get_table(table).await? {
+                    Some(table) => table,
+                    None => return Err(CatalogError::TableNotFound(table.clone()).into()),
+                };
+                let meta_page_id = self.build_index(name, &table_def, columns, *unique).await?;
+                self.catalog
+                    .create_index_with_btree(name, table, columns.clone(), *unique, meta_page_id)
+                    .await?;
+            }
+            LogicalPlan::Analyze { tables } => {
+                for name in tables {
+                    let table = match self.catalog.get_table(name).await? {
+                        Some(table) => table,
+                        None => return Err(CatalogError::TableNotFound(name.clone()).into()),
+                    };
+                    let stats = self.analyze(&table).await?;
+                    self.catalog.set_statistics(&table, &stats).await?;
+                }
+            }
+            _ => panic!("not a DDL plan: {}", self.plan),
+        }
+        Ok(())
+    }
+
+    /// Build the btree of a new index from the existing rows of the table, and return its meta
+    /// page id. The entries are sorted by key and `TupleId` with an external sort, then bulk
+    /// loaded.
+    ///
+    /// If building fails, the pages written so far are freed.
+    async fn build_index(
+        &self,
+        name: &str,
+        table: &TableDef,
+        columns: &[usize],
+        unique: bool,
+    ) -> Result<PageId> {
+        let buffer_pool = self.catalog.buffer_pool();
+        let sort_key = |index, name: &str, ty| SortKey {
+            expr: Expr::Column {
+                index,
+                name: name.to_string(),
+                ty,
+                nullability: Nullability::NotNull,
+            },
+            descending: false,
+        };
+        let mut sort = ExternalSort::new(
+            buffer_pool,
+            vec![
+                sort_key(0, "key", Type::Bytea),
+                sort_key(1, "page_id", Type::Int8),
+                sort_key(2, "slot_index", Type::Int4),
+            ],
+            MEMORY_BUDGET,
+        );
+        let key_columns = index::key_columns(table, columns);
+        let heap = TableHeap::from_existing(buffer_pool, table.heap_page_id).await?;
+        let mut iter = heap.iter().await?;
+        while let Some(((page_id, slot_index), data)) = iter.next().await? {
+            let values = RowData::new(&table.schema, data).values()?;
+            sort.push(vec![
+                Datum::Bytea(index::row_key(&key_columns, columns, &values)),
+                Datum::Int8(page_id.0 as i64),
+                Datum::Int4(slot_index as i32),
+                Datum::Bool(index::has_null_key(columns, &values)),
+            ])
+            .await?;
+        }
+        drop(iter);
+
+        let mut rows = sort.finish().await?;
+        let mut loader = SecondaryIndex::bulk_loader(buffer_pool, unique, INDEX_FILL_FACTOR)
+            .await
+            .map_err(|err| Error::from_index(name, err))?;
+        while let Some(row) = rows.next().await? {
+            let (key, tid, has_null) = match &row[..] {
+                [Datum::Bytea(key), Datum::Int8(page), Datum::Int4(slot), Datum::Bool(null)] => {
+                    (key, (PageId(*page as u32), *slot as usize), *null)
+                }
+                _ => panic!("invalid index entry row"),
+            };
+            // Keys with NULLs don't have to be unique.
+            if has_null {
+                loader.push_unchecked(key, tid).await
+            } else {
+                loader.push(key, tid).await
+            }
+            .map_err(|err| Error::from_index(name, err))?;
+        }
+        let index = loader
+            .finish()
+            .await
+            .map_err(|err| Error::from_index(name, err))?;
+        Ok(index.meta_page_id())
+    }
+
+    /// Collect the statistics of a table, reading all of its rows (see `statistics`).
+    async fn analyze(&self, table: &TableDef) -> Result<TableStats> {
+        let buffer_pool = self.catalog.buffer_pool();
+        let mut analyzer = Analyzer::new(&table.schema, DEFAULT_SAMPLE_ROWS);
+        let heap = TableHeap::from_existing(buffer_pool, table.heap_page_id).await?;
+        let mut iter = heap.iter().await?;
+        while let Some((_, data)) = iter.next().await? {
+            analyzer.add(RowData::new(&table.schema, data).values()?);
+        }
+        drop(iter);
+        let pages = heap.page_count().await? as u64;
+
+        let mut indexes = vec![];
+        for index in self.catalog.list_indexes(&table.name).await? {
+            let stats = async {
+                SecondaryIndex::open(buffer_pool, index.meta_page_id, index.unique)
+                    .await?
+                    .stats()
+                    .await
+            }
+            .await
+            .map_err(|err| Error::from_index(&index.name, err))?;
+            indexes.push(IndexStats {
+                name: index.name,
+                entries: stats.tuples as u64,
+                leaf_pages: stats.leaf_pages as u64,
+                height: stats.height as u32,
+            });
+        }
+        Ok(analyzer.finish(pages, indexes))
+    }
+}
+
+#[async_trait]
+impl Executor for Ddl<'_, '_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        if !self.done {
+            self.done = true;
+            self.run().await?;
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,605 @@
+//! Join operators.
+//!
+//! All of them support inner, left outer and semi joins. Inner and left joins return the left row
+//! followed by the right one (all NULL for a left row without matches in a left join). Semi joins
+//! return each left row which has a match, once.
+//!
+//! Apart from `NestedLoopJoin`, the joins are equi-joins: rows match when the values of
+//! `left_keys` (evaluated on the left row) are equal to the values of `right_keys` (evaluated on
+//! the right row), and the `condition`, if any, is true for the joined row. Keys containing NULL
+//! don't match anything. The key expressions on both sides must have the same types.
+
+use super::spill::{row_size, Partitions, SpillFile, SpillReader, MAX_PARTITION_DEPTH};
+use super::{Error, Executor, Result, Row};
+use crate::catalog::{IndexDef, TableDef};
+use crate::eval::{eval, eval_predicate};
+use crate::index::TableIndex;
+use crate::logical_plan::{Expr, JoinType};
+use async_trait::async_trait;
+use buffer_pool::buffer_pool::BufferPool;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Bound;
+use table::datum::Datum;
+use table::key_encoding::{encode_key, KeyColumn, NullsOrder, SortOrder};
+use table::row::Row as RowData;
+use table::table_heap::TableHeap;
+
+/// Joins the left row with the candidate right rows, appending the results to `output`.
+fn join_row<'r>(
+    join_type: JoinType,
+    condition: Option<&Expr>,
+    right_width: usize,
+    left: &[Datum],
+    candidates: impl IntoIterator<Item = &'r Row>,
+    output: &mut VecDeque<Row>,
+) -> Result<()> {
+    let mut matched = false;
+    for right in candidates {
+        let mut row = left.to_vec();
+        row.extend_from_slice(right);
+        if let Some(condition) = condition {
+            if !eval_predicate(condition, &row)? {
+                continue;
+            }
+        }
+        matched = true;
+        if join_type == JoinType::Semi {
+            break;
+        }
+        output.push_back(row);
+    }
+    match join_type {
+        JoinType::Semi if matched => output.push_back(left.to_vec()),
+        JoinType::Left if !matched => {
+            let mut row = left.to_vec();
+            row.resize(left.len() + right_width, Datum::Null);
+            output.push_back(row);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn eval_keys(keys: &[Expr], row: &[Datum]) -> Result<Vec<Datum>> {
+    Ok(keys
+        .iter()
+        .map(|key| eval(key, row))
+        .collect::<std::result::Result<_, _>>()?)
+}
+
+/// Encoding of key values in which values are equal exactly when they compare equal, or None if
+/// there's a NULL.
+fn hash_key(values: &[Datum]) -> Option<Vec<u8>> {
+    let columns = values
+        .iter()
+        .map(|value| {
+            value.ty().map(|ty| KeyColumn {
+                ty,
+                order: SortOrder::Ascending,
+                nulls: NullsOrder::NullsLast,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(encode_key(&columns, values))
+}
+
+/// For each left row, scans all rows of the right input, which is read into memory once.
+pub struct NestedLoopJoin<'a> {
+    left: Box<dyn Executor + 'a>,
+    right: Option<Box<dyn Executor + 'a>>,
+    right_rows: Vec<Row>,
+    right_width: usize,
+    join_type: JoinType,
+    condition: Option<Expr>,
+    output: VecDeque<Row>,
+}
+
+impl<'a> NestedLoopJoin<'a> {
+    /// Without a condition, all pairs of rows match.
+    pub fn new(
+        left: Box<dyn Executor + 'a>,
+        right: Box<dyn Executor + 'a>,
+        right_width: usize,
+        join_type: JoinType,
+        condition: Option<Expr>,
+    ) -> Self {
+        NestedLoopJoin {
+            left,
+            right: Some(right),
+            right_rows: vec![],
+            right_width,
+            join_type,
+            condition,
+            output: VecDeque::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Executor for NestedLoopJoin<'_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        if let Some(mut right) = self.right.take() {
+            while let Some(row) = right.next().await? {
+                self.right_rows.push(row);
+            }
+        }
+        loop {
+            if let Some(row) = self.output.pop_front() {
+                return Ok(Some(row));
+            }
+            let left = match self.left.next().await? {
+                Some(row) => row,
+                None => return Ok(None),
+            };
+            join_row(
+                self.join_type,
+                self.condition.as_ref(),
+                self.right_width,
+                &left,
+                &self.right_rows,
+                &mut self.output,
+            )?;
+        }
+    }
+}
+
+/// For each left row, looks up the matching rows of a table in an index.
+///
+/// The left keys are matched with the first `left_keys.len()` columns of the index.
+pub struct IndexNestedLoopJoin<'a> {
+    buffer_pool: &'a BufferPool,
+    left: Box<dyn Executor + 'a>,
+    table: TableDef,
+    index: IndexDef,
+    left_keys: Vec<Expr>,
+    join_type: JoinType,
+    condition: Option<Expr>,
+    state: Option<(TableIndex<'a>, TableHeap<'a>)>,
+    output: VecDeque<Row>,
+}
+
+impl<'a> IndexNestedLoopJoin<'a> {
+    pub fn new(
+        buffer_pool: &'a BufferPool,
+        left: Box<dyn Executor + 'a>,
+        table: TableDef,
+        index: IndexDef,
+        left_keys: Vec<Expr>,
+        join_type: JoinType,
+        condition: Option<Expr>,
+    ) -> Self {
+        IndexNestedLoopJoin {
+            buffer_pool,
+            left,
+            table,
+            index,
+            left_keys,
+            join_type,
+            condition,
+            state: None,
+            output: VecDeque::new(),
+        }
+    }
+
+    /// Returns the table rows with the given key.
+    async fn lookup(&mut self, key: &[Datum]) -> Result<Vec<Row>> {
+        if self.state.is_none() {
+            let index = TableIndex::open(self.buffer_pool, &self.table, self.index.clone())
+                .await
+                .map_err(|err| Error::from_index(&self.index.name, err))?;
+            let heap = TableHeap::from_existing(self.buffer_pool, self.table.heap_page_id).await?;
+            self.state = Some((index, heap));
+        }
+        let (index, heap) = self.state.as_ref().unwrap();
+        let prefix = index.key_prefix(key);
+        let entries = index
+            .index
+            .prefix_range(Bound::Included(&prefix), Bound::Included(&prefix))
+            .await
+            .map_err(|err| Error::from_index(&self.index.name, err))?;
+        let mut rows = vec![];
+        for (_, tid) in entries {
+            if let Some(data) = heap.get_tuple(tid).await? {
+                rows.push(RowData::new(&self.table.schema, &data).values()?);
+            }
+        }
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl Executor for IndexNestedLoopJoin<'_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        loop {
+            if let Some(row) = self.output.pop_front() {
+                return Ok(Some(row));
+            }
+            let left = match self.left.next().await? {
+                Some(row) => row,
+                None => return Ok(None),
+            };
+            let key = eval_keys(&self.left_keys, &left)?;
+            let candidates = if key.contains(&Datum::Null) {
+                vec![]
+            } else {
+                self.lookup(&key).await?
+            };
+            join_row(
+                self.join_type,
+                self.condition.as_ref(),
+                self.table.schema.len(),
+                &left,
+                &candidates,
+                &mut self.output,
+            )?;
+        }
+    }
+}
+
+type HashTable = HashMap<Vec<u8>, Vec<Row>>;
+
+/// Builds a hash table of the right input, and probes it with the left rows.
+///
+/// If the right rows take more than `memory_budget` bytes, both inputs are split into partitions
+/// by the hash of the key, written to spill files, and then joined partition by partition.
+pub struct HashJoin<'a> {
+    buffer_pool: &'a BufferPool,
+    left: Box<dyn Executor + 'a>,
+    right: Option<Box<dyn Executor + 'a>>,
+    left_keys: Vec<Expr>,
+    right_keys: Vec<Expr>,
+    right_width: usize,
+    join_type: JoinType,
+    condition: Option<Expr>,
+    memory_budget: usize,
+    table: HashTable,
+    /// Partitions (right and left file, and the number of times they were split) waiting to be
+    /// joined, if the inputs were partitioned.
+    partitions: Option<Vec<(SpillFile<'a>, SpillFile<'a>, u32)>>,
+    /// The left rows of the partition being joined.
+    probe: Option<SpillReader<'a>>,
+    output: VecDeque<Row>,
+}
+
+impl<'a> HashJoin<'a> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        buffer_pool: &'a BufferPool,
+        left: Box<dyn Executor + 'a>,
+        right: Box<dyn Executor + 'a>,
+        left_keys: Vec<Expr>,
+        right_keys: Vec<Expr>,
+        right_width: usize,
+        join_type: JoinType,
+        condition: Option<Expr>,
+        memory_budget: usize,
+    ) -> Self {
+        HashJoin {
+            buffer_pool,
+            left,
+            right: Some(right),
+            left_keys,
+            right_keys,
+            right_width,
+            join_type,
+            condition,
+            memory_budget,
+            table: HashMap::new(),
+            partitions: None,
+            probe: None,
+            output: VecDeque::new(),
+        }
+    }
+
+    /// Read the right input. Builds the hash table if it fits in memory, otherwise partitions both
+    /// inputs.
+    async fn build(&mut self, mut right: Box<dyn Executor + 'a>) -> Result<()> {
+        let mut rows = vec![];
+        let mut size = 0;
+        while let Some(row) = right.next().await? {
+            size += row_size(&row);
+            rows.push(row);
+            if size > self.memory_budget {
+                let mut partitions = Partitions::new(self.buffer_pool, 0).await?;
+                for row in rows {
+                    write_right(&mut partitions, &self.right_keys, row).await?;
+                }
+                while let Some(row) = right.next().await? {
+                    write_right(&mut partitions, &self.right_keys, row).await?;
+                }
+                let right_files = partitions.finish().await?;
+                let mut partitions = Partitions::new(self.buffer_pool, 0).await?;
+                while let Some(row) = self.left.next().await? {
+                    let key = hash_key(&eval_keys(&self.left_keys, &row)?);
+                    partitions
+                        .write(key.as_deref().unwrap_or(&[]), &row)
+                        .await?;
+                }
+                let left_files = partitions.finish().await?;
+                self.partitions = Some(
+                    right_files
+                        .into_iter()
+                        .zip(left_files)
+                        .map(|(right, left)| (right, left, 0))
+                        .collect(),
+                );
+                return Ok(());
+            }
+        }
+        self.table = self.hash_table(rows)?;
+        Ok(())
+    }
+
+    fn hash_table(&self, rows: Vec<Row>) -> Result<HashTable> {
+        let mut table = HashTable::new();
+        for row in rows {
+            if let Some(key) = hash_key(&eval_keys(&self.right_keys, &row)?) {
+                table.entry(key).or_default().push(row);
+            }
+        }
+        Ok(table)
+    }
+
+    /// Start joining the next partition. Returns false if there are no more.
+    async fn next_partition(&mut self) -> Result<bool> {
+        loop {
+            let (right, left, depth) = match self.partitions.as_mut().unwrap().pop() {
+                Some(partition) => partition,
+                None => return Ok(false),
+            };
+            if left.is_empty() {
+                continue;
+            }
+            let mut rows = vec![];
+            let mut size = 0;
+            let mut reader = right.reader();
+            while let Some(row) = reader.next().await? {
+                size += row_size(&row);
+                rows.push(row);
+                if size > self.memory_budget && depth < MAX_PARTITION_DEPTH {
+                    break;
+                }
+            }
+            if size > self.memory_budget && depth < MAX_PARTITION_DEPTH {
+                drop(rows);
+                let right_files =
+                    repartition(self.buffer_pool, right, &self.right_keys, depth + 1).await?;
+                let left_files =
+                    repartition(self.buffer_pool, left, &self.left_keys, depth + 1).await?;
+                let partitions = self.partitions.as_mut().unwrap();
+                for (right, left) in right_files.into_iter().zip(left_files) {
+                    partitions.push((right, left, depth + 1));
+                }
+                continue;
+            }
+            self.table = self.hash_table(rows)?;
+            self.probe = Some(left.reader());
+            return Ok(true);
+        }
+    }
+}
+
+#[async_trait]
+impl Executor for HashJoin<'_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        if let Some(right) = self.right.take() {
+            self.build(right).await?;
+        }
+        loop {
+            if let Some(row) = self.output.pop_front() {
+                return Ok(Some(row));
+            }
+            let left = if let Some(probe) = &mut self.probe {
+                probe.next().await?
+            } else if self.partitions.is_none() {
+                self.left.next().await?
+            } else {
+                None
+            };
+            match left {
+                Some(left) => {
+                    let key = hash_key(&eval_keys(&self.left_keys, &left)?);
+                    let table = &self.table;
+                    let candidates = key.and_then(|key| table.get(&key));
+                    join_row(
+                        self.join_type,
+                        self.condition.as_ref(),
+                        self.right_width,
+                        &left,
+                        candidates.into_iter().flatten(),
+                        &mut self.output,
+                    )?;
+                }
+                None if self.partitions.is_none() => return Ok(None),
+                None => {
+                    self.probe = None;
+                    self.table.clear();
+                    if !self.next_partition().await? {
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn write_right(partitions: &mut Partitions<'_>, right_keys: &[Expr], row: Row) -> Result<()> {
+    let key = hash_key(&eval_keys(right_keys, &row)?);
+    // Right rows with a NULL key can't match anything.
+    if let Some(key) = key {
+        partitions.write(&key, &row).await?;
+    }
+    Ok(())
+}
+
+/// Split a partition which doesn't fit in memory. Rows with a NULL key (only on the left side) go
+/// to the same partition as rows with an empty key, which they don't match anyway.
+async fn repartition<'b>(
+    buffer_pool: &'b BufferPool,
+    file: SpillFile<'b>,
+    keys: &[Expr],
+    depth: u32,
+) -> Result<Vec<SpillFile<'b>>> {
+    let mut partitions = Partitions::new(buffer_pool, depth).await?;
+    let mut reader = file.reader();
+    while let Some(row) = reader.next().await? {
+        let key = hash_key(&eval_keys(keys, &row)?);
+        partitions
+            .write(key.as_deref().unwrap_or(&[]), &row)
+            .await?;
+    }
+    partitions.finish().await
+}
+
+/// Compares key values, none of which is NULL.
+fn compare_keys(a: &[Datum], b: &[Datum]) -> Ordering {
+    for (a, b) in a.iter().zip(b) {
+        match a.compare(b).expect("join key values of different types") {
+            Ordering::Equal => {}
+            ordering => return ordering,
+        }
+    }
+    Ordering::Equal
+}
+
+/// Joins inputs sorted by their keys (ascending, NULLs last), by walking through both at once.
+///
+/// The right rows with the same key are kept in memory while the left rows with that key are
+/// joined with them.
+pub struct MergeJoin<'a> {
+    left: Box<dyn Executor + 'a>,
+    right: Box<dyn Executor + 'a>,
+    left_keys: Vec<Expr>,
+    right_keys: Vec<Expr>,
+    right_width: usize,
+    join_type: JoinType,
+    condition: Option<Expr>,
+    /// Right rows with the key `group_key`.
+    group: Vec<Row>,
+    group_key: Option<Vec<Datum>>,
+    /// The first right row after the group, with its key.
+    next_right: Option<(Vec<Datum>, Row)>,
+    right_done: bool,
+    output: VecDeque<Row>,
+}
+
+impl<'a> MergeJoin<'a> {
+    pub fn new(
+        left: Box<dyn Executor + 'a>,
+        right: Box<dyn Executor + 'a>,
+        left_keys: Vec<Expr>,
+        right_keys: Vec<Expr>,
+        right_width: usize,
+        join_type: JoinType,
+        condition: Option<Expr>,
+    ) -> Self {
+        MergeJoin {
+            left,
+            right,
+            left_keys,
+            right_keys,
+            right_width,
+            join_type,
+            condition,
+            group: vec![],
+            group_key: None,
+            next_right: None,
+            right_done: false,
+            output: VecDeque::new(),
+        }
+    }
+
+    async fn read_right(&mut self) -> Result<Option<(Vec<Datum>, Row)>> {
+        match self.right.next().await? {
+            Some(row) => Ok(Some((eval_keys(&self.right_keys, &row)?, row))),
+            None => Ok(None),
+        }
+    }
+
+    /// Replace the group with the next right rows with equal keys.
+    async fn next_group(&mut self) -> Result<()> {
+        self.group.clear();
+        self.group_key = None;
+        let (key, row) = loop {
+            let (key, row) = match self.next_right.take() {
+                Some(next) => next,
+                None => match self.read_right().await? {
+                    Some(next) => next,
+                    None => {
+                        self.right_done = true;
+                        return Ok(());
+                    }
+                },
+            };
+            // Rows with a NULL key don't match anything. NULLs sort last in each column, so once
+            // the leading column is NULL, the remaining rows all have NULL keys.
+            if key.first() == Some(&Datum::Null) {
+                self.right_done = true;
+                return Ok(());
+            }
+            if !key.contains(&Datum::Null) {
+                break (key, row);
+            }
+        };
+        self.group.push(row);
+        loop {
+            match self.read_right().await? {
+                Some((next_key, row))
+                    if !next_key.contains(&Datum::Null)
+                        && compare_keys(&next_key, &key) == Ordering::Equal =>
+                {
+                    self.group.push(row)
+                }
+                next => {
+                    self.next_right = next;
+                    break;
+                }
+            }
+        }
+        self.group_key = Some(key);
+        Ok(())
+    }
+
+    /// Returns whether the group has the key, after skipping right rows with smaller keys.
+    async fn seek(&mut self, key: &[Datum]) -> Result<bool> {
+        loop {
+            let ordering = match &self.group_key {
+                Some(group_key) => compare_keys(group_key, key),
+                None if self.right_done => return Ok(false),
+                None => Ordering::Less,
+            };
+            match ordering {
+                Ordering::Less => self.next_group().await?,
+                Ordering::Equal => return Ok(true),
+                Ordering::Greater => return Ok(false),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Executor for MergeJoin<'_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        loop {
+            if let Some(row) = self.output.pop_front() {
+                return Ok(Some(row));
+            }
+            let left = match self.left.next().await? {
+                Some(row) => row,
+                None => return Ok(None),
+            };
+            let key = eval_keys(&self.left_keys, &left)?;
+            let matches = !key.contains(&Datum::Null) && self.seek(&key).await?;
+            let candidates = if matches { &self.group[..] } else { &[] };
+            join_row(
+                self.join_type,
+                self.condition.as_ref(),
+                self.right_width,
+                &left,
+                candidates,
+                &mut self.output,
+            )?;
+        }
+    }
+}
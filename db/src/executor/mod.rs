@@ -0,0 +1,292 @@
+//! Pull-based ("Volcano") query execution.
+//!
+//! A plan is executed by a tree of operators implementing `Executor`. Each call to `next` returns
+//! the next row of the operator's output, pulling rows from its inputs as needed. Rows are vectors
+//! of `Datum`s, with the columns described by the plan's `columns()`.
+//!
+//! Operators don't do any work until `next` is first called, so building an executor is cheap and
+//! synchronous.
+
+mod aggregate;
+mod ddl;
+mod join;
+mod modify;
+mod pipeline;
+mod scan;
+mod sort;
+pub mod spill;
+
+pub use aggregate::{HashAggregate, SortAggregate};
+pub use ddl::Ddl;
+pub use join::{HashJoin, IndexNestedLoopJoin, MergeJoin, NestedLoopJoin};
+pub use modify::{Delete, Insert, Update};
+pub use pipeline::{Filter, Limit, Project};
+pub use scan::{IndexScan, SeqScan, Values};
+pub use sort::{compare_sort_keys, ExternalSort, Sort, SortedRows};
+
+pub use crate::logical_plan::JoinType;
+
+use crate::catalog::{Catalog, Error as CatalogError};
+use crate::eval;
+use crate::logical_plan::LogicalPlan;
+use crate::physical_plan::PhysicalPlan;
+use crate::planner;
+use async_trait::async_trait;
+use btree::btree::Error as BTreeError;
+use buffer_pool::buffer_pool::Error as BufferPoolError;
+use std::fmt;
+use table::datum::serialize::DecodeError;
+use table::datum::Datum;
+use table::secondary_index::Error as IndexError;
+use table::table_heap::{Error as TableHeapError, TupleId};
+
+#[derive(Debug)]
+pub enum Error {
+    BufferPool(BufferPoolError),
+    BTree(BTreeError),
+    Catalog(CatalogError),
+    Eval(eval::Error),
+    /// A stored row can't be decoded.
+    Decode(DecodeError),
+    /// A NULL value for a `NOT NULL` column.
+    NotNullViolation(String),
+    /// A duplicate key in the given unique index.
+    UniqueViolation(String),
+    /// A table has as many pages as it can have.
+    TableFull,
+}
+
+impl From<BufferPoolError> for Error {
+    fn from(err: BufferPoolError) -> Self {
+        Error::BufferPool(err)
+    }
+}
+
+impl From<BTreeError> for Error {
+    fn from(err: BTreeError) -> Self {
+        Error::BTree(err)
+    }
+}
+
+impl From<TableHeapError> for Error {
+    fn from(err: TableHeapError) -> Self {
+        match err {
+            TableHeapError::BufferPool(err) => Error::BufferPool(err),
+            TableHeapError::TableFull => Error::TableFull,
+        }
+    }
+}
+
+impl From<CatalogError> for Error {
+    fn from(err: CatalogError) -> Self {
+        Error::Catalog(err)
+    }
+}
+
+impl From<eval::Error> for Error {
+    fn from(err: eval::Error) -> Self {
+        Error::Eval(err)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(err: DecodeError) -> Self {
+        Error::Decode(err)
+    }
+}
+
+impl Error {
+    /// Convert an error of the given index.
+    fn from_index(index_name: &str, err: IndexError) -> Self {
+        match err {
+            IndexError::BTree(err) => Error::BTree(err),
+            IndexError::UniqueViolation(_) => Error::UniqueViolation(index_name.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::BufferPool(err) => write!(f, "buffer pool error: {:?}", err),
+            Error::BTree(err) => write!(f, "btree error: {:?}", err),
+            Error::Catalog(err) => write!(f, "catalog error: {:?}", err),
+            Error::Eval(err) => write!(f, "{}", err),
+            Error::Decode(err) => write!(f, "corrupt row: {}", err),
+            Error::NotNullViolation(column) => write!(
+                f,
+                "null value in column {:?} violates not-null constraint",
+                column
+            ),
+            Error::UniqueViolation(index) => write!(
+                f,
+                "duplicate key value violates unique constraint {:?}",
+                index
+            ),
+            Error::TableFull => write!(f, "table has reached its maximum size"),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Memory (in bytes) an operator can use for its data, before spilling it to temporary pages.
+pub const MEMORY_BUDGET: usize = 4 << 20;
+
+pub type Row = Vec<Datum>;
+
+#[async_trait]
+pub trait Executor: Send {
+    /// Returns the next row, or None when there are no more.
+    async fn next(&mut self) -> Result<Option<Row>>;
+
+    /// The `TupleId` of the row last returned by `next`, if it's a row of a table passed through
+    /// unchanged. `Update` and `Delete` need it to find the rows to modify.
+    fn tid(&self) -> Option<TupleId> {
+        None
+    }
+}
+
+/// Build the operators executing a plan.
+pub fn build<'a, 'b: 'a>(catalog: &'a Catalog<'b>, plan: &PhysicalPlan) -> Box<dyn Executor + 'a> {
+    let buffer_pool = catalog.buffer_pool();
+    let right_width = |right: &PhysicalPlan| right.columns().len();
+    match plan {
+        PhysicalPlan::Values { rows, .. } => Box::new(Values::new(rows.clone())),
+        PhysicalPlan::SeqScan { table } => Box::new(SeqScan::new(buffer_pool, table.clone())),
+        PhysicalPlan::IndexScan {
+            table,
+            index,
+            start,
+            end,
+        } => Box::new(IndexScan::new(
+            buffer_pool,
+            table.clone(),
+            index.clone(),
+            start.clone(),
+            end.clone(),
+        )),
+        PhysicalPlan::Filter { input, predicate } => {
+            Box::new(Filter::new(build(catalog, input), predicate.clone()))
+        }
+        PhysicalPlan::Project { input, exprs, .. } => {
+            Box::new(Project::new(build(catalog, input), exprs.clone()))
+        }
+        PhysicalPlan::HashAggregate {
+            input,
+            group_by,
+            aggregates,
+        } => Box::new(HashAggregate::new(
+            buffer_pool,
+            build(catalog, input),
+            group_by.clone(),
+            aggregates.clone(),
+            MEMORY_BUDGET,
+        )),
+        PhysicalPlan::Sort { input, keys } => Box::new(Sort::new(
+            buffer_pool,
+            build(catalog, input),
+            keys.clone(),
+            MEMORY_BUDGET,
+        )),
+        PhysicalPlan::Limit {
+            input,
+            limit,
+            offset,
+        } => Box::new(Limit::new(build(catalog, input), *limit, *offset)),
+        PhysicalPlan::NestedLoopJoin {
+            left,
+            right,
+            join_type,
+            condition,
+        } => Box::new(NestedLoopJoin::new(
+            build(catalog, left),
+            build(catalog, right),
+            right_width(right),
+            *join_type,
+            condition.clone(),
+        )),
+        PhysicalPlan::IndexNestedLoopJoin {
+            left,
+            table,
+            index,
+            left_keys,
+            join_type,
+            condition,
+        } => Box::new(IndexNestedLoopJoin::new(
+            buffer_pool,
+            build(catalog, left),
+            table.clone(),
+            index.clone(),
+            left_keys.clone(),
+            *join_type,
+            condition.clone(),
+        )),
+        PhysicalPlan::HashJoin {
+            left,
+            right,
+            left_keys,
+            right_keys,
+            join_type,
+            condition,
+        } => Box::new(HashJoin::new(
+            buffer_pool,
+            build(catalog, left),
+            build(catalog, right),
+            left_keys.clone(),
+            right_keys.clone(),
+            right_width(right),
+            *join_type,
+            condition.clone(),
+            MEMORY_BUDGET,
+        )),
+        PhysicalPlan::MergeJoin {
+            left,
+            right,
+            left_keys,
+            right_keys,
+            join_type,
+            condition,
+        } => Box::new(MergeJoin::new(
+            build(catalog, left),
+            build(catalog, right),
+            left_keys.clone(),
+            right_keys.clone(),
+            right_width(right),
+            *join_type,
+            condition.clone(),
+        )),
+        PhysicalPlan::Insert { table, input } => {
+            Box::new(Insert::new(catalog, table.clone(), build(catalog, input)))
+        }
+        PhysicalPlan::Update {
+            table,
+            input,
+            assignments,
+        } => Box::new(Update::new(
+            catalog,
+            table.clone(),
+            build(catalog, input),
+            assignments.clone(),
+        )),
+        PhysicalPlan::Delete { table, input } => {
+            Box::new(Delete::new(catalog, table.clone(), build(catalog, input)))
+        }
+        PhysicalPlan::Ddl(plan) => Box::new(Ddl::new(catalog, plan.clone())),
+    }
+}
+
+/// Plan and execute a statement, returning all its rows.
+pub async fn execute<'a, 'b: 'a>(catalog: &'a Catalog<'b>, plan: &LogicalPlan) -> Result<Vec<Row>> {
+    let plan = planner::plan(catalog, plan).await?;
+    collect(build(catalog, &plan)).await
+}
+
+/// Read all rows of an executor.
+pub async fn collect(mut executor: Box<dyn Executor + '_>) -> Result<Vec<Row>> {
+    let mut rows = vec![];
+    while let Some(row) = executor.next().await? {
+        rows.push(row);
+    }
+    Ok(rows)
+}
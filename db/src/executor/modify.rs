@@ -0,0 +1,256 @@
+//! Operators modifying tables. Each of them returns a single row with the number of affected rows.
+//!
+//! All indexes of the table are maintained along with the heap. Constraints are checked row by row,
+//! so when a row violates one, the rows before it stay modified.
+
+use super::{Error, Executor, Result, Row};
+use crate::catalog::{Catalog, TableDef};
+use crate::eval::eval;
+use crate::index::TableIndex;
+use crate::logical_plan::Expr;
+use async_trait::async_trait;
+use table::datum::{Datum, Nullability};
+use table::row::Row as RowData;
+use table::table_heap::{TableHeap, TupleId};
+
+/// Open all indexes of the table.
+async fn open_indexes<'b>(catalog: &Catalog<'b>, table: &TableDef) -> Result<Vec<TableIndex<'b>>> {
+    let mut indexes = vec![];
+    for def in catalog.list_indexes(&table.name).await? {
+        let name = def.name.clone();
+        indexes.push(
+            TableIndex::open(catalog.buffer_pool(), table, def)
+                .await
+                .map_err(|err| Error::from_index(&name, err))?,
+        );
+    }
+    Ok(indexes)
+}
+
+fn check_not_null(table: &TableDef, row: &[Datum]) -> Result<()> {
+    for (column, value) in table.schema.columns().iter().zip(row) {
+        if column.nullability == Nullability::NotNull && *value == Datum::Null {
+            return Err(Error::NotNullViolation(column.name.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Check that the row's key isn't already in the index, if it has to be unique.
+async fn check_unique(index: &TableIndex<'_>, row: &[Datum], key: &[u8]) -> Result<()> {
+    if !index.is_unique_key(row) {
+        return Ok(());
+    }
+    let existing = index
+        .index
+        .lookup(key)
+        .await
+        .map_err(|err| Error::from_index(&index.def.name, err))?;
+    if !existing.is_empty() {
+        return Err(Error::UniqueViolation(index.def.name.clone()));
+    }
+    Ok(())
+}
+
+async fn insert_key(index: &TableIndex<'_>, row: &[Datum], key: &[u8], tid: TupleId) -> Result<()> {
+    if index.is_unique_key(row) {
+        index.index.insert(key, tid).await
+    } else {
+        index.index.insert_unchecked(key, tid).await
+    }
+    .map_err(|err| Error::from_index(&index.def.name, err))
+}
+
+async fn delete_key(index: &TableIndex<'_>, key: &[u8], tid: TupleId) -> Result<()> {
+    index
+        .index
+        .delete(key, tid)
+        .await
+        .map_err(|err| Error::from_index(&index.def.name, err))?;
+    Ok(())
+}
+
+/// Read all rows of the input, with their `TupleId`s.
+///
+/// Rows are read before modifying any of them, so that the modifications can't affect which rows
+/// the input returns. The input is dropped afterwards, releasing any pages it still has latched.
+async fn read_targets(mut input: Box<dyn Executor + '_>) -> Result<Vec<(TupleId, Row)>> {
+    let mut targets = vec![];
+    while let Some(row) = input.next().await? {
+        let tid = input
+            .tid()
+            .expect("input of a table modification doesn't return table rows");
+        targets.push((tid, row));
+    }
+    Ok(targets)
+}
+
+/// Inserts the input rows, which have the table's columns, into the table.
+pub struct Insert<'a, 'b> {
+    catalog: &'a Catalog<'b>,
+    table: TableDef,
+    input: Box<dyn Executor + 'a>,
+    done: bool,
+}
+
+impl<'a, 'b> Insert<'a, 'b> {
+    pub fn new(catalog: &'a Catalog<'b>, table: TableDef, input: Box<dyn Executor + 'a>) -> Self {
+        Insert {
+            catalog,
+            table,
+            input,
+            done: false,
+        }
+    }
+
+    async fn run(&mut self) -> Result<i64> {
+        let heap =
+            TableHeap::from_existing(self.catalog.buffer_pool(), self.table.heap_page_id).await?;
+        let indexes = open_indexes(self.catalog, &self.table).await?;
+        let mut count = 0;
+        while let Some(row) = self.input.next().await? {
+            check_not_null(&self.table, &row)?;
+            let keys: Vec<Vec<u8>> = indexes.iter().map(|index| index.row_key(&row)).collect();
+            for (index, key) in indexes.iter().zip(&keys) {
+                check_unique(index, &row, key).await?;
+            }
+            let tid = heap
+                .insert_tuple(&RowData::encode(&self.table.schema, &row))
+                .await?;
+            for (index, key) in indexes.iter().zip(&keys) {
+                insert_key(index, &row, key, tid).await?;
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+#[async_trait]
+impl Executor for Insert<'_, '_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        Ok(Some(vec![Datum::Int8(self.run().await?)]))
+    }
+}
+
+/// Updates the input rows of the table, setting the columns to the values of the expressions
+/// (evaluated on the old row).
+pub struct Update<'a, 'b> {
+    catalog: &'a Catalog<'b>,
+    table: TableDef,
+    input: Option<Box<dyn Executor + 'a>>,
+    assignments: Vec<(usize, Expr)>,
+    done: bool,
+}
+
+impl<'a, 'b> Update<'a, 'b> {
+    pub fn new(
+        catalog: &'a Catalog<'b>,
+        table: TableDef,
+        input: Box<dyn Executor + 'a>,
+        assignments: Vec<(usize, Expr)>,
+    ) -> Self {
+        Update {
+            catalog,
+            table,
+            input: Some(input),
+            assignments,
+            done: false,
+        }
+    }
+
+    async fn run(&mut self) -> Result<i64> {
+        let targets = read_targets(self.input.take().unwrap()).await?;
+        let heap =
+            TableHeap::from_existing(self.catalog.buffer_pool(), self.table.heap_page_id).await?;
+        let indexes = open_indexes(self.catalog, &self.table).await?;
+        let mut count = 0;
+        for (tid, old_row) in targets {
+            let mut new_row = old_row.clone();
+            for (column, expr) in &self.assignments {
+                new_row[*column] = eval(expr, &old_row)?;
+            }
+            check_not_null(&self.table, &new_row)?;
+            let mut changed_keys = vec![];
+            for index in &indexes {
+                let old_key = index.row_key(&old_row);
+                let new_key = index.row_key(&new_row);
+                if old_key != new_key {
+                    check_unique(index, &new_row, &new_key).await?;
+                    changed_keys.push((index, old_key, new_key));
+                }
+            }
+            heap.update_tuple(tid, &RowData::encode(&self.table.schema, &new_row))
+                .await?;
+            for (index, old_key, new_key) in changed_keys {
+                delete_key(index, &old_key, tid).await?;
+                insert_key(index, &new_row, &new_key, tid).await?;
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+#[async_trait]
+impl Executor for Update<'_, '_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        Ok(Some(vec![Datum::Int8(self.run().await?)]))
+    }
+}
+
+/// Deletes the input rows from the table.
+pub struct Delete<'a, 'b> {
+    catalog: &'a Catalog<'b>,
+    table: TableDef,
+    input: Option<Box<dyn Executor + 'a>>,
+    done: bool,
+}
+
+impl<'a, 'b> Delete<'a, 'b> {
+    pub fn new(catalog: &'a Catalog<'b>, table: TableDef, input: Box<dyn Executor + 'a>) -> Self {
+        Delete {
+            catalog,
+            table,
+            input: Some(input),
+            done: false,
+        }
+    }
+
+    async fn run(&mut self) -> Result<i64> {
+        let targets = read_targets(self.input.take().unwrap()).await?;
+        let heap =
+            TableHeap::from_existing(self.catalog.buffer_pool(), self.table.heap_page_id).await?;
+        let indexes = open_indexes(self.catalog, &self.table).await?;
+        let mut count = 0;
+        for (tid, row) in targets {
+            if !heap.delete_tuple(tid).await? {
+                continue;
+            }
+            for index in &indexes {
+                delete_key(index, &index.row_key(&row), tid).await?;
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+#[async_trait]
+impl Executor for Delete<'_, '_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        Ok(Some(vec![Datum::Int8(self.run().await?)]))
+    }
+}
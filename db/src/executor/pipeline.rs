@@ -0,0 +1,107 @@
+//! Operators which process one input row at a time.
+
+use super::{Executor, Result, Row};
+use crate::eval::{eval, eval_predicate};
+use crate::logical_plan::Expr;
+use async_trait::async_trait;
+use table::table_heap::TupleId;
+
+/// Rows of the input for which the predicate is true.
+pub struct Filter<'a> {
+    input: Box<dyn Executor + 'a>,
+    predicate: Expr,
+}
+
+impl<'a> Filter<'a> {
+    pub fn new(input: Box<dyn Executor + 'a>, predicate: Expr) -> Self {
+        Filter { input, predicate }
+    }
+}
+
+#[async_trait]
+impl Executor for Filter<'_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        while let Some(row) = self.input.next().await? {
+            if eval_predicate(&self.predicate, &row)? {
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+
+    fn tid(&self) -> Option<TupleId> {
+        self.input.tid()
+    }
+}
+
+/// Computes the expressions for each input row.
+pub struct Project<'a> {
+    input: Box<dyn Executor + 'a>,
+    exprs: Vec<Expr>,
+}
+
+impl<'a> Project<'a> {
+    pub fn new(input: Box<dyn Executor + 'a>, exprs: Vec<Expr>) -> Self {
+        Project { input, exprs }
+    }
+}
+
+#[async_trait]
+impl Executor for Project<'_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        match self.input.next().await? {
+            Some(row) => Ok(Some(
+                self.exprs
+                    .iter()
+                    .map(|expr| eval(expr, &row))
+                    .collect::<std::result::Result<_, _>>()?,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Skips the first `offset` rows of the input, and returns at most `limit` of the following ones.
+pub struct Limit<'a> {
+    input: Box<dyn Executor + 'a>,
+    /// Number of rows left to return.
+    remaining: Option<u64>,
+    /// Number of rows left to skip.
+    offset: u64,
+}
+
+impl<'a> Limit<'a> {
+    pub fn new(input: Box<dyn Executor + 'a>, limit: Option<u64>, offset: u64) -> Self {
+        Limit {
+            input,
+            remaining: limit,
+            offset,
+        }
+    }
+}
+
+#[async_trait]
+impl Executor for Limit<'_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        if self.remaining == Some(0) {
+            return Ok(None);
+        }
+        while self.offset > 0 {
+            if self.input.next().await?.is_none() {
+                return Ok(None);
+            }
+            self.offset -= 1;
+        }
+        let row = self.input.next().await?;
+        if row.is_some() {
+            if let Some(remaining) = &mut self.remaining {
+                *remaining -= 1;
+            }
+        }
+        Ok(row)
+    }
+
+    fn tid(&self) -> Option<TupleId> {
+        self.input.tid()
+    }
+}
@@ -0,0 +1,182 @@
+//! Leaf operators, producing rows from tables, indexes or constants.
+
+use super::{Error, Executor, Result, Row};
+use crate::catalog::{IndexDef, TableDef};
+use crate::eval::eval;
+use crate::index::TableIndex;
+use crate::logical_plan::Expr;
+use async_trait::async_trait;
+use buffer_pool::buffer_pool::BufferPool;
+use std::ops::Bound;
+use table::datum::Datum;
+use table::row::Row as RowData;
+use table::table_heap::{TableHeap, TableIter, TupleId};
+
+/// All rows of a table, in physical order.
+pub struct SeqScan<'b> {
+    buffer_pool: &'b BufferPool,
+    table: TableDef,
+    state: ScanState<'b>,
+    tid: Option<TupleId>,
+}
+
+enum ScanState<'b> {
+    NotStarted,
+    Scanning(TableIter<'b>),
+    /// The iterator is dropped at the end, so that it doesn't keep the last page latched.
+    Done,
+}
+
+impl<'b> SeqScan<'b> {
+    pub fn new(buffer_pool: &'b BufferPool, table: TableDef) -> Self {
+        SeqScan {
+            buffer_pool,
+            table,
+            state: ScanState::NotStarted,
+            tid: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Executor for SeqScan<'_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        if let ScanState::NotStarted = self.state {
+            let heap = TableHeap::from_existing(self.buffer_pool, self.table.heap_page_id).await?;
+            self.state = ScanState::Scanning(heap.iter().await?);
+        }
+        self.tid = None;
+        let iter = match &mut self.state {
+            ScanState::Scanning(iter) => iter,
+            _ => return Ok(None),
+        };
+        match iter.next().await? {
+            Some((tid, data)) => {
+                self.tid = Some(tid);
+                Ok(Some(RowData::new(&self.table.schema, data).values()?))
+            }
+            None => {
+                self.state = ScanState::Done;
+                Ok(None)
+            }
+        }
+    }
+
+    fn tid(&self) -> Option<TupleId> {
+        self.tid
+    }
+}
+
+/// Rows of a table found through an index, in index order.
+///
+/// The bounds are values of the first indexed columns, and select rows by comparing them with the
+/// same number of leading columns of the row's key. For example, on an index on `(a, b)`, the
+/// bounds `Excluded([1, 2])` and `Included([1])` select rows with `a = 1 AND b > 2`.
+pub struct IndexScan<'b> {
+    buffer_pool: &'b BufferPool,
+    table: TableDef,
+    index: IndexDef,
+    start: Bound<Vec<Datum>>,
+    end: Bound<Vec<Datum>>,
+    state: Option<(TableHeap<'b>, std::vec::IntoIter<TupleId>)>,
+    tid: Option<TupleId>,
+}
+
+impl<'b> IndexScan<'b> {
+    pub fn new(
+        buffer_pool: &'b BufferPool,
+        table: TableDef,
+        index: IndexDef,
+        start: Bound<Vec<Datum>>,
+        end: Bound<Vec<Datum>>,
+    ) -> Self {
+        IndexScan {
+            buffer_pool,
+            table,
+            index,
+            start,
+            end,
+            state: None,
+            tid: None,
+        }
+    }
+
+    async fn lookup(&self) -> Result<Vec<TupleId>> {
+        let index = TableIndex::open(self.buffer_pool, &self.table, self.index.clone())
+            .await
+            .map_err(|err| Error::from_index(&self.index.name, err))?;
+        let encode = |bound: &Bound<Vec<Datum>>| match bound {
+            Bound::Included(values) => Bound::Included(index.key_prefix(values)),
+            Bound::Excluded(values) => Bound::Excluded(index.key_prefix(values)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let (start, end) = (encode(&self.start), encode(&self.end));
+        let entries = index
+            .index
+            .prefix_range(as_slice_bound(&start), as_slice_bound(&end))
+            .await
+            .map_err(|err| Error::from_index(&self.index.name, err))?;
+        Ok(entries.into_iter().map(|(_, tid)| tid).collect())
+    }
+}
+
+fn as_slice_bound(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key),
+        Bound::Excluded(key) => Bound::Excluded(key),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+#[async_trait]
+impl Executor for IndexScan<'_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        if self.state.is_none() {
+            let tids = self.lookup().await?;
+            let heap = TableHeap::from_existing(self.buffer_pool, self.table.heap_page_id).await?;
+            self.state = Some((heap, tids.into_iter()));
+        }
+        let (heap, tids) = self.state.as_mut().unwrap();
+        self.tid = None;
+        for tid in tids {
+            // The index can only point to a missing tuple if the tuple is deleted concurrently.
+            if let Some(data) = heap.get_tuple(tid).await? {
+                self.tid = Some(tid);
+                return Ok(Some(RowData::new(&self.table.schema, &data).values()?));
+            }
+        }
+        Ok(None)
+    }
+
+    fn tid(&self) -> Option<TupleId> {
+        self.tid
+    }
+}
+
+/// Constant rows.
+pub struct Values {
+    rows: std::vec::IntoIter<Vec<Expr>>,
+}
+
+impl Values {
+    pub fn new(rows: Vec<Vec<Expr>>) -> Self {
+        Values {
+            rows: rows.into_iter(),
+        }
+    }
+}
+
+#[async_trait]
+impl Executor for Values {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        match self.rows.next() {
+            Some(exprs) => Ok(Some(
+                exprs
+                    .iter()
+                    .map(|expr| eval(expr, &[]))
+                    .collect::<std::result::Result<_, _>>()?,
+            )),
+            None => Ok(None),
+        }
+    }
+}
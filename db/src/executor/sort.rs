@@ -0,0 +1,260 @@
+//! Sorting, in memory or with an external merge sort.
+//!
+//! Rows are ordered by the key encoding of their sort key values (see `key_encoding`), which
+//! orders them the same way as `compare_sort_keys`. Rows are buffered in memory until they take
+//! more than the memory budget; then they are sorted and written to a spill file as a sorted run.
+//! At the end, runs are merged `fan_in` at a time until there are few enough to be merged while
+//! returning the rows.
+//!
+//! Runs are written and merged in input order, with ties resolved by taking the row of the earlier
+//! run, so the sort is stable.
+
+use super::spill::{row_size, SpillFile, SpillReader, SpillWriter};
+use super::{Executor, Result, Row};
+use crate::eval::eval;
+use crate::logical_plan::SortKey;
+use async_trait::async_trait;
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager::PAGE_SIZE;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use table::datum::Datum;
+use table::key_encoding::{encode_key, KeyColumn, NullsOrder, SortOrder};
+
+/// Sorts rows pushed one at a time by the keys, spilling sorted runs to temporary pages when they
+/// don't fit in `memory_budget` bytes.
+pub struct ExternalSort<'b> {
+    buffer_pool: &'b BufferPool,
+    keys: Vec<SortKey>,
+    key_columns: Vec<KeyColumn>,
+    memory_budget: usize,
+    /// Rows not written to a run yet, with their encoded keys.
+    rows: Vec<(Vec<u8>, Row)>,
+    size: usize,
+    runs: Vec<SpillFile<'b>>,
+}
+
+impl<'b> ExternalSort<'b> {
+    pub fn new(buffer_pool: &'b BufferPool, keys: Vec<SortKey>, memory_budget: usize) -> Self {
+        let key_columns = keys
+            .iter()
+            .map(|key| {
+                if key.descending {
+                    KeyColumn {
+                        ty: key.expr.ty(),
+                        order: SortOrder::Descending,
+                        nulls: NullsOrder::NullsFirst,
+                    }
+                } else {
+                    KeyColumn {
+                        ty: key.expr.ty(),
+                        order: SortOrder::Ascending,
+                        nulls: NullsOrder::NullsLast,
+                    }
+                }
+            })
+            .collect();
+        ExternalSort {
+            buffer_pool,
+            keys,
+            key_columns,
+            memory_budget,
+            rows: vec![],
+            size: 0,
+            runs: vec![],
+        }
+    }
+
+    pub async fn push(&mut self, row: Row) -> Result<()> {
+        let values = self
+            .keys
+            .iter()
+            .map(|key| eval(&key.expr, &row))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let key = encode_key(&self.key_columns, &values);
+        self.size += key.len() + row_size(&row);
+        self.rows.push((key, row));
+        if self.size > self.memory_budget {
+            self.write_run().await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the rows in sorted order.
+    pub async fn finish(mut self) -> Result<SortedRows<'b>> {
+        if self.runs.is_empty() {
+            self.rows.sort_by(|(a, _), (b, _)| a.cmp(b));
+            return Ok(SortedRows(State::InMemory(self.rows.into_iter())));
+        }
+        if !self.rows.is_empty() {
+            self.write_run().await?;
+        }
+        // Each run being merged needs about a page of memory.
+        let fan_in = (self.memory_budget / PAGE_SIZE).max(2);
+        let mut runs = self.runs;
+        while runs.len() > fan_in {
+            let mut merged = vec![];
+            for chunk in runs.chunks(fan_in) {
+                let mut merge = Merge::new(chunk).await?;
+                let mut writer = SpillWriter::new(self.buffer_pool).await?;
+                while let Some((key, row)) = merge.next().await? {
+                    writer.write(&with_key(row, key)).await?;
+                }
+                merged.push(writer.finish().await?);
+            }
+            runs = merged;
+        }
+        Ok(SortedRows(State::Merge(Merge::new(&runs).await?)))
+    }
+
+    /// Sort the rows in memory and write them to a new run.
+    async fn write_run(&mut self) -> Result<()> {
+        self.rows.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut writer = SpillWriter::new(self.buffer_pool).await?;
+        for (key, row) in self.rows.drain(..) {
+            writer.write(&with_key(row, key)).await?;
+        }
+        self.runs.push(writer.finish().await?);
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Rows of runs are stored with their encoded key appended, so that it's not computed again when
+/// merging.
+fn with_key(mut row: Row, key: Vec<u8>) -> Row {
+    row.push(Datum::Bytea(key));
+    row
+}
+
+/// The output of `ExternalSort`.
+pub struct SortedRows<'b>(State<'b>);
+
+enum State<'b> {
+    InMemory(std::vec::IntoIter<(Vec<u8>, Row)>),
+    Merge(Merge<'b>),
+}
+
+impl SortedRows<'_> {
+    pub async fn next(&mut self) -> Result<Option<Row>> {
+        Ok(match &mut self.0 {
+            State::InMemory(rows) => rows.next(),
+            State::Merge(merge) => merge.next().await?,
+        }
+        .map(|(_, row)| row))
+    }
+}
+
+/// Merges sorted runs, returning the rows with their keys.
+struct Merge<'b> {
+    readers: Vec<SpillReader<'b>>,
+    /// The next row of each run.
+    heads: Vec<Option<Row>>,
+    /// Keys of the next rows, with the index of their run.
+    heap: BinaryHeap<Reverse<(Vec<u8>, usize)>>,
+}
+
+impl<'b> Merge<'b> {
+    async fn new(runs: &[SpillFile<'b>]) -> Result<Merge<'b>> {
+        let mut merge = Merge {
+            readers: runs.iter().map(SpillFile::reader).collect(),
+            heads: vec![None; runs.len()],
+            heap: BinaryHeap::new(),
+        };
+        for run in 0..runs.len() {
+            merge.advance(run).await?;
+        }
+        Ok(merge)
+    }
+
+    async fn next(&mut self) -> Result<Option<(Vec<u8>, Row)>> {
+        let Reverse((key, run)) = match self.heap.pop() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let row = self.heads[run].take().unwrap();
+        self.advance(run).await?;
+        Ok(Some((key, row)))
+    }
+
+    /// Read the next row of a run.
+    async fn advance(&mut self, run: usize) -> Result<()> {
+        if let Some(mut row) = self.readers[run].next().await? {
+            let key = match row.pop() {
+                Some(Datum::Bytea(key)) => key,
+                _ => panic!("sorted run row without a key"),
+            };
+            self.heap.push(Reverse((key, run)));
+            self.heads[run] = Some(row);
+        }
+        Ok(())
+    }
+}
+
+/// Sorts all input rows by the keys. The sort is stable.
+pub struct Sort<'a> {
+    buffer_pool: &'a BufferPool,
+    input: Option<Box<dyn Executor + 'a>>,
+    keys: Vec<SortKey>,
+    memory_budget: usize,
+    sorted: Option<SortedRows<'a>>,
+}
+
+impl<'a> Sort<'a> {
+    pub fn new(
+        buffer_pool: &'a BufferPool,
+        input: Box<dyn Executor + 'a>,
+        keys: Vec<SortKey>,
+        memory_budget: usize,
+    ) -> Self {
+        Sort {
+            buffer_pool,
+            input: Some(input),
+            keys,
+            memory_budget,
+            sorted: None,
+        }
+    }
+
+    async fn sort(&mut self) -> Result<SortedRows<'a>> {
+        let mut sort = ExternalSort::new(self.buffer_pool, self.keys.clone(), self.memory_budget);
+        // Dropped once all rows are read, so that it doesn't hold any pages.
+        let mut input = self.input.take().unwrap();
+        while let Some(row) = input.next().await? {
+            sort.push(row).await?;
+        }
+        drop(input);
+        sort.finish().await
+    }
+}
+
+#[async_trait]
+impl Executor for Sort<'_> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        if self.sorted.is_none() {
+            self.sorted = Some(self.sort().await?);
+        }
+        self.sorted.as_mut().unwrap().next().await
+    }
+}
+
+/// Compare values of sort keys. NULLs are greater than all other values.
+pub fn compare_sort_keys(keys: &[SortKey], a: &[Datum], b: &[Datum]) -> Ordering {
+    for ((key, a), b) in keys.iter().zip(a).zip(b) {
+        let ordering = match (a, b) {
+            (Datum::Null, Datum::Null) => Ordering::Equal,
+            (Datum::Null, _) => Ordering::Greater,
+            (_, Datum::Null) => Ordering::Less,
+            _ => a.compare(b).expect("sort key values of different types"),
+        };
+        let ordering = if key.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
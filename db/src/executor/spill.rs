@@ -0,0 +1,272 @@
+//! Temporary storage for operators whose data doesn't fit in memory.
+//!
+//! A spill file is a sequence of rows, written once and then read sequentially (any number of
+//! times). It's stored in pages allocated from the buffer pool, so it only reaches the disk if the
+//! pages get evicted. The list of pages is kept in memory. Each page starts with the number of data
+//! bytes on the page (2 bytes). The data of all pages is a stream of rows:
+//!
+//! - row: its length in bytes and the column count (4 bytes each), followed by the values,
+//! - value: the type code as in catalog entries, or 0xff for NULL, followed by the value serialized
+//!   as `NOT NULL`.
+//!
+//! The pages are returned to the buffer pool when the file and all of its readers are dropped, so
+//! that later spill files (or anything else) can reuse them.
+
+use super::{Result, Row};
+use crate::catalog::{type_code, TYPES};
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager::{PageId, PAGE_SIZE};
+use std::collections::hash_map::DefaultHasher;
+use std::convert::TryInto;
+use std::hash::{Hash, Hasher};
+use std::mem::size_of;
+use std::sync::Arc;
+use table::datum::serialize::{Reader, Writer};
+use table::datum::{Datum, Nullability};
+
+const HEADER_SIZE: usize = 2;
+const DATA_SIZE: usize = PAGE_SIZE - HEADER_SIZE;
+const NULL_CODE: u8 = 0xff;
+
+/// Approximate memory used by a row, for comparing with memory budgets.
+pub fn row_size(row: &[Datum]) -> usize {
+    size_of::<Row>()
+        + row
+            .iter()
+            .map(|value| {
+                size_of::<Datum>()
+                    + match value {
+                        Datum::String(s) => s.len(),
+                        Datum::Bytea(b) => b.len(),
+                        _ => 0,
+                    }
+            })
+            .sum::<usize>()
+}
+
+fn encode_row(row: &[Datum]) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.write_u32(0);
+    w.write_u32(row.len() as u32);
+    for value in row {
+        match value.ty() {
+            Some(ty) => {
+                w.write_u8(type_code(ty));
+                value.serialize(&mut w, Nullability::NotNull);
+            }
+            None => w.write_u8(NULL_CODE),
+        }
+    }
+    let mut data = w.data().to_vec();
+    let len = (data.len() - 4) as u32;
+    data[..4].copy_from_slice(&len.to_le_bytes());
+    data
+}
+
+fn decode_row(data: &[u8]) -> Row {
+    let mut r = Reader::new(data);
+    let count = r.read_u32().expect("invalid spilled row") as usize;
+    (0..count)
+        .map(|_| match r.read_u8().expect("invalid spilled row") {
+            NULL_CODE => Datum::Null,
+            code => Datum::deserialize(&mut r, Nullability::NotNull, TYPES[code as usize])
+                .expect("invalid spilled row"),
+        })
+        .collect()
+}
+
+/// Pages of a spill file, in order. They're freed when dropped.
+struct SpillPages<'b> {
+    buffer_pool: &'b BufferPool,
+    page_ids: Vec<PageId>,
+}
+
+impl Drop for SpillPages<'_> {
+    fn drop(&mut self) {
+        for &page_id in &self.page_ids {
+            self.buffer_pool.free_page_later(page_id);
+        }
+    }
+}
+
+/// A finished spill file. Clones share the pages.
+#[derive(Clone)]
+pub struct SpillFile<'b> {
+    pages: Arc<SpillPages<'b>>,
+    rows: u64,
+}
+
+impl<'b> SpillFile<'b> {
+    /// Number of rows in the file.
+    pub fn len(&self) -> u64 {
+        self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    /// A reader of the file, which keeps its pages alive even if the file is dropped.
+    pub fn reader(&self) -> SpillReader<'b> {
+        SpillReader {
+            pages: self.pages.clone(),
+            next_page: 0,
+            data: vec![],
+            offset: 0,
+            remaining: self.rows,
+        }
+    }
+}
+
+/// Writes a new spill file. The last page is kept in memory until it's full, or `finish` is called.
+///
+/// If the writer is dropped without calling `finish`, the pages written so far are freed.
+pub struct SpillWriter<'b> {
+    pages: SpillPages<'b>,
+    data: Vec<u8>,
+    rows: u64,
+}
+
+impl<'b> SpillWriter<'b> {
+    pub async fn new(buffer_pool: &'b BufferPool) -> Result<SpillWriter<'b>> {
+        let page_id = buffer_pool.allocate_page().await?.id();
+        Ok(SpillWriter {
+            pages: SpillPages {
+                buffer_pool,
+                page_ids: vec![page_id],
+            },
+            data: vec![],
+            rows: 0,
+        })
+    }
+
+    pub async fn write(&mut self, row: &[Datum]) -> Result<()> {
+        self.data.extend_from_slice(&encode_row(row));
+        self.rows += 1;
+        while self.data.len() > DATA_SIZE {
+            self.write_page(DATA_SIZE).await?;
+            self.data.drain(..DATA_SIZE);
+            let next_page_id = self.pages.buffer_pool.allocate_page().await?.id();
+            self.pages.page_ids.push(next_page_id);
+        }
+        Ok(())
+    }
+
+    pub async fn finish(self) -> Result<SpillFile<'b>> {
+        self.write_page(self.data.len()).await?;
+        Ok(SpillFile {
+            pages: Arc::new(self.pages),
+            rows: self.rows,
+        })
+    }
+
+    /// Write the first `len` bytes of `data` to the current (last) page.
+    async fn write_page(&self, len: usize) -> Result<()> {
+        let page_id = *self.pages.page_ids.last().unwrap();
+        let mut page = self
+            .pages
+            .buffer_pool
+            .get_page(page_id)
+            .await?
+            .write()
+            .await;
+        page[..HEADER_SIZE].copy_from_slice(&(len as u16).to_le_bytes());
+        page[HEADER_SIZE..HEADER_SIZE + len].copy_from_slice(&self.data[..len]);
+        page.dirty();
+        Ok(())
+    }
+}
+
+/// Reads the rows of a spill file, in the order they were written.
+///
+/// The data of the current page is copied, so that no page stays latched between calls.
+pub struct SpillReader<'b> {
+    pages: Arc<SpillPages<'b>>,
+    /// Index of the next page in `pages`.
+    next_page: usize,
+    data: Vec<u8>,
+    offset: usize,
+    remaining: u64,
+}
+
+impl SpillReader<'_> {
+    pub async fn next(&mut self) -> Result<Option<Row>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let len = u32::from_le_bytes(self.read(4).await?[..].try_into().unwrap());
+        let data = self.read(len as usize).await?;
+        Ok(Some(decode_row(&data)))
+    }
+
+    async fn read(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut result = Vec::with_capacity(len);
+        while result.len() < len {
+            if self.offset == self.data.len() {
+                self.read_page().await?;
+            }
+            let end = self.data.len().min(self.offset + len - result.len());
+            result.extend_from_slice(&self.data[self.offset..end]);
+            self.offset = end;
+        }
+        Ok(result)
+    }
+
+    async fn read_page(&mut self) -> Result<()> {
+        let page_id = *self
+            .pages
+            .page_ids
+            .get(self.next_page)
+            .expect("spill file too short");
+        self.next_page += 1;
+        let page = self.pages.buffer_pool.get_page(page_id).await?.read().await;
+        let len = u16::from_le_bytes(page[..HEADER_SIZE].try_into().unwrap()) as usize;
+        self.data = page[HEADER_SIZE..HEADER_SIZE + len].to_vec();
+        self.offset = 0;
+        Ok(())
+    }
+}
+
+/// Number of partitions the input of a hash-based operator is split into when it doesn't fit in
+/// memory.
+const PARTITIONS: usize = 16;
+
+/// How many times a partition which still doesn't fit in memory is split again. Past that (for
+/// example when most rows have the same key), the partition is processed in memory regardless of
+/// its size.
+pub(super) const MAX_PARTITION_DEPTH: u32 = 3;
+
+/// Spill files of rows split by the hash of their key. `depth` is the number of times the rows
+/// were split before, and changes the hash function, so that rows which ended up in the same
+/// partition get split again.
+pub(super) struct Partitions<'b> {
+    writers: Vec<SpillWriter<'b>>,
+    depth: u32,
+}
+
+impl<'b> Partitions<'b> {
+    pub async fn new(buffer_pool: &'b BufferPool, depth: u32) -> Result<Partitions<'b>> {
+        let mut writers = vec![];
+        for _ in 0..PARTITIONS {
+            writers.push(SpillWriter::new(buffer_pool).await?);
+        }
+        Ok(Partitions { writers, depth })
+    }
+
+    pub async fn write(&mut self, key: &[u8], row: &[Datum]) -> Result<()> {
+        let mut hasher = DefaultHasher::new();
+        self.depth.hash(&mut hasher);
+        key.hash(&mut hasher);
+        let partition = hasher.finish() as usize % PARTITIONS;
+        self.writers[partition].write(row).await
+    }
+
+    pub async fn finish(self) -> Result<Vec<SpillFile<'b>>> {
+        let mut files = vec![];
+        for writer in self.writers {
+            files.push(writer.finish().await?);
+        }
+        Ok(files)
+    }
+}
@@ -0,0 +1,384 @@
+use crate::catalog::Catalog;
+use crate::executor::{collect, IndexScan, Result, Row};
+use crate::index::TableIndex;
+use crate::test_utils::{run, run_all};
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager_mem::DiskManagerMem;
+use std::ops::Bound;
+use table::datum::Datum;
+
+async fn create_users(catalog: &Catalog<'_>) {
+    for sql in &[
+        "create table users (id bigint primary key, name text, email text not null, age smallint)",
+        "create unique index users_email on users (email)",
+        "insert into users values (1, 'alice', 'alice@example.com', 30), \
+         (2, 'bob', 'bob@example.com', null), (3, 'carol', 'carol@example.com', 25), \
+         (4, null, 'dave@example.com', 41)",
+    ] {
+        run(catalog, sql).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn select() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    create_users(&catalog).await;
+    assert_snapshot!(run_all(&catalog, &[
+        "select * from users",
+        "select name, age from users where age > 26 or name is null",
+        "select id, name from users order by age desc, id limit 3",
+        "select id from users order by id limit 2 offset 1",
+        "select id from users order by id offset 10",
+        "select 1, 'a', null, cast(2 as bigint) < 3",
+        "select age from users where not (age < 30)",
+    ]).await, @r###"
+    select * from users
+    1 | alice | alice@example.com | 30
+    2 | bob | bob@example.com | NULL
+    3 | carol | carol@example.com | 25
+    4 | NULL | dave@example.com | 41
+    select name, age from users where age > 26 or name is null
+    alice | 30
+    NULL | 41
+    select id, name from users order by age desc, id limit 3
+    2 | bob
+    4 | NULL
+    1 | alice
+    select id from users order by id limit 2 offset 1
+    2
+    3
+    select id from users order by id offset 10
+    select 1, 'a', null, cast(2 as bigint) < 3
+    1 | a | NULL | true
+    select age from users where not (age < 30)
+    30
+    41
+    "###);
+}
+
+#[tokio::test]
+async fn modification() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    create_users(&catalog).await;
+    assert_snapshot!(run_all(&catalog, &[
+        "insert into users (id, email) values (5, 'eve@example.com')",
+        "update users set age = age, email = 'bob@example.org' where id = 2",
+        "update users set id = id where age is null",
+        "delete from users where age < 30",
+        "delete from users where false",
+        "select * from users",
+    ]).await, @r###"
+    insert into users (id, email) values (5, 'eve@example.com')
+    1
+    update users set age = age, email = 'bob@example.org' where id = 2
+    1
+    update users set id = id where age is null
+    2
+    delete from users where age < 30
+    1
+    delete from users where false
+    0
+    select * from users
+    1 | alice | alice@example.com | 30
+    2 | bob | bob@example.org | NULL
+    4 | NULL | dave@example.com | 41
+    5 | NULL | eve@example.com | NULL
+    "###);
+}
+
+#[tokio::test]
+async fn constraints() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    create_users(&catalog).await;
+    assert_snapshot!(run_all(&catalog, &[
+        "insert into users values (1, 'again', 'again@example.com', 1)",
+        "insert into users values (6, 'again', 'alice@example.com', 1)",
+        "update users set email = 'carol@example.com' where id = 1",
+        "update users set id = 3 where id = 1",
+        "update users set email = name where id = 4",
+        "select * from users",
+        "update users set age = 30 where id = 3",
+        "create unique index users_age on users (age)",
+        "create unique index users_name on users (name)",
+        "insert into users values (7, 'carol', 'carol2@example.com', 1)",
+    ]).await, @r###"
+    insert into users values (1, 'again', 'again@example.com', 1)
+    error: duplicate key value violates unique constraint "users_pkey"
+    insert into users values (6, 'again', 'alice@example.com', 1)
+    error: duplicate key value violates unique constraint "users_email"
+    update users set email = 'carol@example.com' where id = 1
+    error: duplicate key value violates unique constraint "users_email"
+    update users set id = 3 where id = 1
+    error: duplicate key value violates unique constraint "users_pkey"
+    update users set email = name where id = 4
+    error: null value in column "email" violates not-null constraint
+    select * from users
+    1 | alice | alice@example.com | 30
+    2 | bob | bob@example.com | NULL
+    3 | carol | carol@example.com | 25
+    4 | NULL | dave@example.com | 41
+    update users set age = 30 where id = 3
+    1
+    create unique index users_age on users (age)
+    error: duplicate key value violates unique constraint "users_age"
+    create unique index users_name on users (name)
+    insert into users values (7, 'carol', 'carol2@example.com', 1)
+    error: duplicate key value violates unique constraint "users_name"
+    "###);
+    assert!(catalog.get_index("users_age").await.unwrap().is_none());
+    assert!(catalog.get_index("users_name").await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn unique_with_nulls() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    create_users(&catalog).await;
+    assert_snapshot!(run_all(&catalog, &[
+        "insert into users values (5, 'eve', 'eve@example.com', null)",
+        "create unique index users_age on users (age)",
+        "create unique index users_name_age on users (name, age)",
+        "insert into users values (6, null, 'frank@example.com', null)",
+        "update users set age = null where id = 1",
+        "insert into users values (7, 'grace', 'grace@example.com', 25)",
+        "insert into users values (7, 'alice', 'grace@example.com', null)",
+        "select id from users where age is null order by id",
+    ]).await, @r###"
+    insert into users values (5, 'eve', 'eve@example.com', null)
+    1
+    create unique index users_age on users (age)
+    create unique index users_name_age on users (name, age)
+    insert into users values (6, null, 'frank@example.com', null)
+    1
+    update users set age = null where id = 1
+    1
+    insert into users values (7, 'grace', 'grace@example.com', 25)
+    error: duplicate key value violates unique constraint "users_age"
+    insert into users values (7, 'alice', 'grace@example.com', null)
+    1
+    select id from users where age is null order by id
+    1
+    2
+    5
+    6
+    7
+    "###);
+}
+
+#[tokio::test]
+async fn ddl() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    create_users(&catalog).await;
+    assert_snapshot!(run_all(&catalog, &[
+        "create table t (a int)",
+        "insert into t values (1), (2)",
+        "drop table t",
+        "drop table if exists t",
+    ]).await, @r###"
+    create table t (a int)
+    insert into t values (1), (2)
+    2
+    drop table t
+    drop table if exists t
+    "###);
+    assert!(catalog.get_table("t").await.unwrap().is_none());
+}
+
+/// Index entries are kept in sync with the heap by all modifications.
+#[tokio::test]
+async fn index_maintenance() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await?;
+    create_users(&catalog).await;
+    run(
+        &catalog,
+        "update users set email = 'x@example.com' where id = 3",
+    )
+    .await?;
+    run(&catalog, "delete from users where id = 2").await?;
+    run(
+        &catalog,
+        "insert into users values (5, 'eve', 'eve@example.com', 19)",
+    )
+    .await?;
+
+    let table = catalog.get_table("users").await?.unwrap();
+    for def in catalog.list_indexes("users").await? {
+        let index = TableIndex::open(&buffer_pool, &table, def).await.unwrap();
+        let rows = collect(Box::new(IndexScan::new(
+            &buffer_pool,
+            table.clone(),
+            index.def.clone(),
+            Bound::Unbounded,
+            Bound::Unbounded,
+        )))
+        .await?;
+        let mut expected = run(&catalog, "select * from users").await?;
+        expected.sort_by_key(|row| index.row_key(row));
+        assert_eq!(rows, expected, "{}", index.def.name);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn index_scan() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await?;
+    run(&catalog, "create table t (a int not null, b int, c text)").await?;
+    run(&catalog, "create index t_a_b on t (a, b)").await?;
+    for a in 0..5 {
+        for b in &["0", "1", "2", "null"] {
+            let sql = format!("insert into t values ({}, {}, 'x')", a, b);
+            run(&catalog, &sql).await?;
+        }
+    }
+    let table = catalog.get_table("t").await?.unwrap();
+    let index = catalog.get_index("t_a_b").await?.unwrap();
+    let scan = |start: Bound<Vec<Datum>>, end: Bound<Vec<Datum>>| {
+        IndexScan::new(&buffer_pool, table.clone(), index.clone(), start, end)
+    };
+    let keys = |rows: Vec<Row>| -> Vec<String> {
+        rows.iter()
+            .map(|row| format!("{},{}", row[0], row[1]))
+            .collect()
+    };
+
+    // a = 1
+    let rows = collect(Box::new(scan(
+        Bound::Included(vec![Datum::Int4(1)]),
+        Bound::Included(vec![Datum::Int4(1)]),
+    )))
+    .await?;
+    assert_eq!(keys(rows), vec!["1,0", "1,1", "1,2", "1,NULL"]);
+
+    // a = 2 and b > 0, NULLs sort last
+    let rows = collect(Box::new(scan(
+        Bound::Excluded(vec![Datum::Int4(2), Datum::Int4(0)]),
+        Bound::Included(vec![Datum::Int4(2)]),
+    )))
+    .await?;
+    assert_eq!(keys(rows), vec!["2,1", "2,2", "2,NULL"]);
+
+    // a >= 3 and a < 4
+    let rows = collect(Box::new(scan(
+        Bound::Included(vec![Datum::Int4(3)]),
+        Bound::Excluded(vec![Datum::Int4(4)]),
+    )))
+    .await?;
+    assert_eq!(keys(rows), vec!["3,0", "3,1", "3,2", "3,NULL"]);
+
+    // a > 3
+    let rows = collect(Box::new(scan(
+        Bound::Excluded(vec![Datum::Int4(3)]),
+        Bound::Unbounded,
+    )))
+    .await?;
+    assert_eq!(keys(rows), vec!["4,0", "4,1", "4,2", "4,NULL"]);
+    Ok(())
+}
+
+/// Indexes on existing rows are built by sorting the entries and bulk loading them.
+#[tokio::test]
+async fn create_index_on_existing_rows() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 50);
+    let catalog = Catalog::create(&buffer_pool).await?;
+    run(&catalog, "create table t (a int, b text)").await?;
+    for i in 0..100 {
+        let values: Vec<String> = (0..20)
+            .map(|j| {
+                let a = (i * 20 + j) * 7919 % 1000;
+                if a % 10 == 0 {
+                    "(null, 'x')".to_string()
+                } else {
+                    format!("({}, '{}')", a, "y".repeat(a as usize % 50))
+                }
+            })
+            .collect();
+        run(
+            &catalog,
+            &format!("insert into t values {}", values.join(", ")),
+        )
+        .await?;
+    }
+    run(&catalog, "create index t_a on t (a)").await?;
+
+    let table = catalog.get_table("t").await?.unwrap();
+    let index = TableIndex::open(
+        &buffer_pool,
+        &table,
+        catalog.get_index("t_a").await?.unwrap(),
+    )
+    .await
+    .unwrap();
+    let rows = collect(Box::new(IndexScan::new(
+        &buffer_pool,
+        table.clone(),
+        index.def.clone(),
+        Bound::Unbounded,
+        Bound::Unbounded,
+    )))
+    .await?;
+    let mut expected = run(&catalog, "select * from t").await?;
+    expected.sort_by_key(|row| index.row_key(row));
+    assert_eq!(rows.len(), 2000);
+    assert_eq!(rows, expected);
+
+    // Rows inserted after creating the index are added to it.
+    run(&catalog, "insert into t values (5, 'z')").await?;
+    let rows = collect(Box::new(IndexScan::new(
+        &buffer_pool,
+        table.clone(),
+        index.def.clone(),
+        Bound::Included(vec![Datum::Int4(5)]),
+        Bound::Included(vec![Datum::Int4(5)]),
+    )))
+    .await?;
+    assert_eq!(rows.len(), 3);
+    Ok(())
+}
+
+/// Inserting into a bulk loaded index with several levels splits its full internal pages.
+#[tokio::test]
+async fn insert_into_created_index() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 50);
+    let catalog = Catalog::create(&buffer_pool).await?;
+    run(&catalog, "create table t (a text)").await?;
+    // Long keys which differ only at the end make for long pivots, so a few hundred rows are
+    // enough for several levels.
+    let insert = |range: std::ops::Range<u32>| {
+        let values: Vec<String> = range
+            .map(|i| format!("('{}{:05}')", "x".repeat(400), i * 7919 % 1000))
+            .collect();
+        format!("insert into t values {}", values.join(", "))
+    };
+    run(&catalog, &insert(0..300)).await?;
+    run(&catalog, "create index t_a on t (a)").await?;
+    run(&catalog, &insert(300..1000)).await?;
+
+    let table = catalog.get_table("t").await?.unwrap();
+    let index = TableIndex::open(
+        &buffer_pool,
+        &table,
+        catalog.get_index("t_a").await?.unwrap(),
+    )
+    .await
+    .unwrap();
+    assert!(index.index.stats().await.unwrap().height >= 3);
+    let rows = collect(Box::new(IndexScan::new(
+        &buffer_pool,
+        table.clone(),
+        index.def.clone(),
+        Bound::Unbounded,
+        Bound::Unbounded,
+    )))
+    .await?;
+    let mut expected = run(&catalog, "select * from t").await?;
+    expected.sort_by_key(|row| index.row_key(row));
+    assert_eq!(rows.len(), 1000);
+    assert_eq!(rows, expected);
+    Ok(())
+}
@@ -0,0 +1,72 @@
+//! HyperLogLog: estimates the number of distinct values in a stream, using a fixed amount of
+//! memory.
+//!
+//! Each value is hashed to 64 bits. The first `PRECISION` bits select a register, which keeps the
+//! maximum number of leading zeros (plus one) seen in the remaining bits. With `m` registers, the
+//! estimate is the harmonic mean of `2^register`, scaled by `m` and a bias correction. For small
+//! numbers of values, where many registers are still zero, linear counting is used instead. The
+//! standard error is about `1.04 / sqrt(m)`, 1.6% with 4096 registers.
+//!
+//! See Flajolet et al., "HyperLogLog: the analysis of a near-optimal cardinality estimation
+//! algorithm" (2007).
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+
+/// Number of hash bits selecting a register.
+const PRECISION: u32 = 12;
+const REGISTERS: usize = 1 << PRECISION;
+
+#[derive(Debug, Clone)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    pub fn new() -> Self {
+        HyperLogLog {
+            registers: vec![0; REGISTERS],
+        }
+    }
+
+    /// Add a value, given as bytes. Equal values must have equal bytes.
+    pub fn add(&mut self, value: &[u8]) {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(value);
+        self.add_hash(hasher.finish());
+    }
+
+    fn add_hash(&mut self, hash: u64) {
+        let register = (hash >> (64 - PRECISION)) as usize;
+        // The bit after the remaining ones bounds the rank, when they're all zero.
+        let rest = (hash << PRECISION) | (1 << (PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        if rank > self.registers[register] {
+            self.registers[register] = rank;
+        }
+    }
+
+    /// Estimated number of distinct values added.
+    pub fn estimate(&self) -> f64 {
+        let m = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-(rank as i32)))
+            .sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        }
+    }
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        HyperLogLog::new()
+    }
+}
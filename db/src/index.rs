@@ -0,0 +1,71 @@
+//! Keys of table rows in indexes.
+//!
+//! Index entries map the values of the indexed columns, encoded with `table::key_encoding` (all
+//! columns ascending, NULLs last), to the `TupleId` of the row.
+
+use crate::catalog::{IndexDef, TableDef};
+use buffer_pool::buffer_pool::BufferPool;
+use table::datum::Datum;
+use table::key_encoding::{encode_key, KeyColumn, NullsOrder, SortOrder};
+use table::secondary_index::{Result, SecondaryIndex};
+
+/// An index of a table, opened for reading or maintenance.
+pub struct TableIndex<'b> {
+    pub def: IndexDef,
+    pub index: SecondaryIndex<'b>,
+    key_columns: Vec<KeyColumn>,
+}
+
+impl<'b> TableIndex<'b> {
+    pub async fn open(
+        buffer_pool: &'b BufferPool,
+        table: &TableDef,
+        def: IndexDef,
+    ) -> Result<TableIndex<'b>> {
+        Ok(TableIndex {
+            index: SecondaryIndex::open(buffer_pool, def.meta_page_id, def.unique).await?,
+            key_columns: key_columns(table, &def.columns),
+            def,
+        })
+    }
+
+    /// The index key of a table row.
+    pub fn row_key(&self, row: &[Datum]) -> Vec<u8> {
+        row_key(&self.key_columns, &self.def.columns, row)
+    }
+
+    /// Whether the row's key has to be unique in the index: the index is unique, and none of the
+    /// key columns is NULL - NULLs are distinct from each other, so keys with them never conflict.
+    pub fn is_unique_key(&self, row: &[Datum]) -> bool {
+        self.def.unique && !has_null_key(&self.def.columns, row)
+    }
+
+    /// Encode values of the first `values.len()` indexed columns, as a prefix of keys.
+    pub fn key_prefix(&self, values: &[Datum]) -> Vec<u8> {
+        encode_key(&self.key_columns[..values.len()], values)
+    }
+}
+
+/// Key columns of an index on the given columns of a table.
+pub fn key_columns(table: &TableDef, columns: &[usize]) -> Vec<KeyColumn> {
+    let table_columns = table.schema.columns();
+    columns
+        .iter()
+        .map(|&index| KeyColumn {
+            ty: table_columns[index].ty,
+            order: SortOrder::Ascending,
+            nulls: NullsOrder::NullsLast,
+        })
+        .collect()
+}
+
+/// Whether any of the indexed columns of the row is NULL.
+pub fn has_null_key(columns: &[usize], row: &[Datum]) -> bool {
+    columns.iter().any(|&index| row[index] == Datum::Null)
+}
+
+/// The index key of a table row, for an index on `columns` with the given key columns.
+pub fn row_key(key_columns: &[KeyColumn], columns: &[usize], row: &[Datum]) -> Vec<u8> {
+    let values: Vec<Datum> = columns.iter().map(|&index| row[index].clone()).collect();
+    encode_key(key_columns, &values)
+}
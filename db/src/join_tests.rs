@@ -0,0 +1,329 @@
+use crate::ast::BinaryOp;
+use crate::binder::Binder;
+use crate::catalog::Catalog;
+use crate::executor::{
+    collect, execute, Executor, HashJoin, IndexNestedLoopJoin, JoinType, MergeJoin, NestedLoopJoin,
+    Row, Sort,
+};
+use crate::logical_plan::{Expr, SortKey};
+use crate::parser::parse_statement;
+use crate::test_utils::{column, values};
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager_mem::DiskManagerMem;
+use proptest::prelude::*;
+use table::datum::{Datum, Type};
+
+/// Rows of the test inputs: a key and a value.
+type Rows = Vec<(Option<i32>, i32)>;
+
+fn to_row(&(key, value): &(Option<i32>, i32)) -> Row {
+    vec![key.map_or(Datum::Null, Datum::Int4), Datum::Int4(value)]
+}
+
+fn input<'a>(rows: &Rows) -> Box<dyn Executor + 'a> {
+    values(rows.iter().map(to_row).collect(), &[Type::Int4, Type::Int4])
+}
+
+fn sorted_by_key<'a>(
+    buffer_pool: &'a BufferPool,
+    input: Box<dyn Executor + 'a>,
+) -> Box<dyn Executor + 'a> {
+    Box::new(Sort::new(
+        buffer_pool,
+        input,
+        vec![SortKey {
+            expr: column(0, Type::Int4),
+            descending: false,
+        }],
+        1 << 20,
+    ))
+}
+
+/// The condition `left.value < right.value`, on joined rows.
+fn condition() -> Expr {
+    Expr::Binary {
+        op: BinaryOp::Lt,
+        left: Box::new(column(1, Type::Int4)),
+        right: Box::new(column(3, Type::Int4)),
+    }
+}
+
+/// The expected result, computed by comparing all pairs of rows.
+fn reference(left: &Rows, right: &Rows, join_type: JoinType, with_condition: bool) -> Vec<Row> {
+    let mut result = vec![];
+    for l in left {
+        let matches: Vec<_> = right
+            .iter()
+            .filter(|r| l.0.is_some() && l.0 == r.0 && (!with_condition || l.1 < r.1))
+            .collect();
+        match join_type {
+            JoinType::Inner | JoinType::Left => {
+                for r in &matches {
+                    result.push([to_row(l), to_row(r)].concat());
+                }
+                if join_type == JoinType::Left && matches.is_empty() {
+                    result.push([to_row(l), vec![Datum::Null, Datum::Null]].concat());
+                }
+            }
+            JoinType::Semi => {
+                if !matches.is_empty() {
+                    result.push(to_row(l));
+                }
+            }
+        }
+    }
+    result
+}
+
+fn sorted(mut rows: Vec<Row>) -> Vec<String> {
+    let mut rows: Vec<String> = rows.drain(..).map(|row| format!("{:?}", row)).collect();
+    rows.sort();
+    rows
+}
+
+async fn check_joins(left: Rows, right: Rows) {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 64);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    for sql in &[
+        "create table r (k int, v int not null)",
+        "create index r_k on r (k)",
+    ] {
+        let plan = Binder::new(&catalog)
+            .bind(&parse_statement(sql).unwrap())
+            .await
+            .unwrap();
+        execute(&catalog, &plan).await.unwrap();
+    }
+    let table = catalog.get_table("r").await.unwrap().unwrap();
+    let index = catalog.get_index("r_k").await.unwrap().unwrap();
+    let insert = crate::executor::Insert::new(&catalog, table.clone(), input(&right));
+    collect(Box::new(insert)).await.unwrap();
+
+    for &join_type in &[JoinType::Inner, JoinType::Left, JoinType::Semi] {
+        for &with_condition in &[false, true] {
+            let expected = sorted(reference(&left, &right, join_type, with_condition));
+            let condition = if with_condition {
+                Some(condition())
+            } else {
+                None
+            };
+            let key_condition = Expr::Binary {
+                op: BinaryOp::Eq,
+                left: Box::new(column(0, Type::Int4)),
+                right: Box::new(column(2, Type::Int4)),
+            };
+            let nested_loop_condition = match &condition {
+                Some(condition) => Expr::Binary {
+                    op: BinaryOp::And,
+                    left: Box::new(key_condition),
+                    right: Box::new(condition.clone()),
+                },
+                None => key_condition,
+            };
+            let joins: Vec<(&str, Box<dyn Executor>)> = vec![
+                (
+                    "nested loop",
+                    Box::new(NestedLoopJoin::new(
+                        input(&left),
+                        input(&right),
+                        2,
+                        join_type,
+                        Some(nested_loop_condition),
+                    )),
+                ),
+                (
+                    "index nested loop",
+                    Box::new(IndexNestedLoopJoin::new(
+                        &buffer_pool,
+                        input(&left),
+                        table.clone(),
+                        index.clone(),
+                        vec![column(0, Type::Int4)],
+                        join_type,
+                        condition.clone(),
+                    )),
+                ),
+                (
+                    "hash",
+                    Box::new(HashJoin::new(
+                        &buffer_pool,
+                        input(&left),
+                        input(&right),
+                        vec![column(0, Type::Int4)],
+                        vec![column(0, Type::Int4)],
+                        2,
+                        join_type,
+                        condition.clone(),
+                        1 << 20,
+                    )),
+                ),
+                (
+                    "hash, spilled",
+                    Box::new(HashJoin::new(
+                        &buffer_pool,
+                        input(&left),
+                        input(&right),
+                        vec![column(0, Type::Int4)],
+                        vec![column(0, Type::Int4)],
+                        2,
+                        join_type,
+                        condition.clone(),
+                        500,
+                    )),
+                ),
+                (
+                    "merge",
+                    Box::new(MergeJoin::new(
+                        sorted_by_key(&buffer_pool, input(&left)),
+                        sorted_by_key(&buffer_pool, input(&right)),
+                        vec![column(0, Type::Int4)],
+                        vec![column(0, Type::Int4)],
+                        2,
+                        join_type,
+                        condition.clone(),
+                    )),
+                ),
+            ];
+            for (name, join) in joins {
+                let result = sorted(collect(join).await.unwrap());
+                assert_eq!(
+                    result, expected,
+                    "{} join, {:?}, condition: {}",
+                    name, join_type, with_condition
+                );
+            }
+        }
+    }
+}
+
+fn rows(max_key: i32) -> impl Strategy<Value = Rows> {
+    prop::collection::vec((prop::option::weighted(0.9, 0..max_key), 0..10), 0..50)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 50, ..ProptestConfig::default()
+    })]
+    #[test]
+    fn joins_match_reference(left in rows(10), right in rows(10)) {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(check_joins(left, right));
+    }
+}
+
+/// Rows of the inputs of joins on two key columns.
+type PairRows = Vec<(Option<i32>, Option<i32>)>;
+
+fn pair_input<'a>(rows: &PairRows) -> Box<dyn Executor + 'a> {
+    let datum = |value: Option<i32>| value.map_or(Datum::Null, Datum::Int4);
+    values(
+        rows.iter()
+            .map(|&(a, b)| vec![datum(a), datum(b)])
+            .collect(),
+        &[Type::Int4, Type::Int4],
+    )
+}
+
+/// Merge join on both columns. A NULL in the second column sorts before larger values of the
+/// first one, so NULL keys are spread through the inputs.
+async fn check_merge_join_pairs(left: PairRows, right: PairRows) {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 64);
+    let keys = || vec![column(0, Type::Int4), column(1, Type::Int4)];
+    let sorted_by_keys = |rows: &PairRows| {
+        let sort_keys = keys()
+            .into_iter()
+            .map(|expr| SortKey {
+                expr,
+                descending: false,
+            })
+            .collect();
+        Box::new(Sort::new(
+            &buffer_pool,
+            pair_input(rows),
+            sort_keys,
+            1 << 20,
+        ))
+    };
+    let datum = |value: Option<i32>| value.map_or(Datum::Null, Datum::Int4);
+    for &join_type in &[JoinType::Inner, JoinType::Left, JoinType::Semi] {
+        let mut expected = vec![];
+        for &(a, b) in &left {
+            let matches: Vec<_> = right
+                .iter()
+                .filter(|&&r| a.is_some() && b.is_some() && (a, b) == r)
+                .collect();
+            let row = vec![datum(a), datum(b)];
+            match join_type {
+                JoinType::Inner | JoinType::Left => {
+                    for &&(c, d) in &matches {
+                        expected.push([row.clone(), vec![datum(c), datum(d)]].concat());
+                    }
+                    if join_type == JoinType::Left && matches.is_empty() {
+                        expected.push([row, vec![Datum::Null, Datum::Null]].concat());
+                    }
+                }
+                JoinType::Semi => {
+                    if !matches.is_empty() {
+                        expected.push(row);
+                    }
+                }
+            }
+        }
+        let join = MergeJoin::new(
+            sorted_by_keys(&left),
+            sorted_by_keys(&right),
+            keys(),
+            keys(),
+            2,
+            join_type,
+            None,
+        );
+        let result = sorted(collect(Box::new(join)).await.unwrap());
+        assert_eq!(result, sorted(expected), "{:?}", join_type);
+    }
+}
+
+fn pair_rows() -> impl Strategy<Value = PairRows> {
+    prop::collection::vec(
+        (
+            prop::option::weighted(0.9, 0..4),
+            prop::option::weighted(0.7, 0..4),
+        ),
+        0..50,
+    )
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 50, ..ProptestConfig::default()
+    })]
+    #[test]
+    fn merge_join_on_two_columns(left in pair_rows(), right in pair_rows()) {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(check_merge_join_pairs(left, right));
+    }
+}
+
+/// Many rows with the same key can't be split into partitions which fit in memory.
+#[tokio::test]
+async fn hash_join_skewed() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 64);
+    let left: Rows = (0..20).map(|value| (Some(value % 2), value)).collect();
+    let right: Rows = (0..500).map(|value| (Some(value % 3), value)).collect();
+    let join = HashJoin::new(
+        &buffer_pool,
+        input(&left),
+        input(&right),
+        vec![column(0, Type::Int4)],
+        vec![column(0, Type::Int4)],
+        2,
+        JoinType::Inner,
+        None,
+        1000,
+    );
+    let result = sorted(collect(Box::new(join)).await.unwrap());
+    assert_eq!(
+        result,
+        sorted(reference(&left, &right, JoinType::Inner, false))
+    );
+}
@@ -0,0 +1,237 @@
+//! Splits SQL text into tokens.
+//!
+//! Unquoted identifiers and keywords are case-insensitive, and are lowercased. Quoted identifiers
+//! (`"Name"`) keep their case, and may contain `""` for a double quote. String literals are
+//! single-quoted, with `''` for a single quote. Comments (`-- ...` and `/* ... */`) are skipped.
+
+use crate::parser::{ParseError, Result};
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Token {
+    /// Unquoted identifier or keyword, lowercased.
+    Word(String),
+    QuotedIdent(String),
+    /// Numeric literal, as written (digits, optional fraction and exponent).
+    Number(String),
+    String(String),
+    LParen,
+    RParen,
+    Comma,
+    Semicolon,
+    Dot,
+    Star,
+    Plus,
+    Minus,
+    Slash,
+    Percent,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Concat,
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{}", word),
+            Token::QuotedIdent(ident) => write!(f, "\"{}\"", ident.replace('"', "\"\"")),
+            Token::Number(number) => write!(f, "{}", number),
+            Token::String(s) => write!(f, "'{}'", s.replace('\'', "''")),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+            Token::Semicolon => write!(f, ";"),
+            Token::Dot => write!(f, "."),
+            Token::Star => write!(f, "*"),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Slash => write!(f, "/"),
+            Token::Percent => write!(f, "%"),
+            Token::Eq => write!(f, "="),
+            Token::NotEq => write!(f, "<>"),
+            Token::Lt => write!(f, "<"),
+            Token::LtEq => write!(f, "<="),
+            Token::Gt => write!(f, ">"),
+            Token::GtEq => write!(f, ">="),
+            Token::Concat => write!(f, "||"),
+            Token::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+/// Returns the tokens of `sql` with their byte offsets, ending with `Eof`.
+pub fn tokenize(sql: &str) -> Result<Vec<(Token, usize)>> {
+    Lexer {
+        sql,
+        chars: sql.char_indices().collect(),
+        pos: 0,
+    }
+    .tokenize()
+}
+
+struct Lexer<'a> {
+    sql: &'a str,
+    chars: Vec<(usize, char)>,
+    /// Index into `chars`.
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn peek(&self) -> Option<char> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, n: usize) -> Option<char> {
+        self.chars.get(self.pos + n).map(|&(_, c)| c)
+    }
+
+    fn offset(&self) -> usize {
+        self.chars
+            .get(self.pos)
+            .map(|&(offset, _)| offset)
+            .unwrap_or_else(|| self.sql.len())
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.offset();
+        while self.peek().map_or(false, &predicate) {
+            self.pos += 1;
+        }
+        &self.sql[start..self.offset()]
+    }
+
+    fn error<T>(&self, offset: usize, message: &str) -> Result<T> {
+        Err(ParseError::new(self.sql, offset, message.to_string()))
+    }
+
+    fn tokenize(mut self) -> Result<Vec<(Token, usize)>> {
+        let mut tokens = vec![];
+        loop {
+            self.skip_whitespace_and_comments()?;
+            let offset = self.offset();
+            let c = match self.peek() {
+                Some(c) => c,
+                None => {
+                    tokens.push((Token::Eof, offset));
+                    return Ok(tokens);
+                }
+            };
+            let token = if c.is_ascii_alphabetic() || c == '_' {
+                let word = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                Token::Word(word.to_ascii_lowercase())
+            } else if c.is_ascii_digit()
+                || (c == '.' && self.peek_at(1).map_or(false, |c| c.is_ascii_digit()))
+            {
+                self.number()
+            } else if c == '\'' {
+                Token::String(self.quoted('\'', "unterminated string literal")?)
+            } else if c == '"' {
+                let ident = self.quoted('"', "unterminated quoted identifier")?;
+                if ident.is_empty() {
+                    return self.error(offset, "empty quoted identifier");
+                }
+                Token::QuotedIdent(ident)
+            } else {
+                let (token, len) = match (c, self.peek_at(1)) {
+                    ('<', Some('>')) | ('!', Some('=')) => (Token::NotEq, 2),
+                    ('<', Some('=')) => (Token::LtEq, 2),
+                    ('>', Some('=')) => (Token::GtEq, 2),
+                    ('|', Some('|')) => (Token::Concat, 2),
+                    ('(', _) => (Token::LParen, 1),
+                    (')', _) => (Token::RParen, 1),
+                    (',', _) => (Token::Comma, 1),
+                    (';', _) => (Token::Semicolon, 1),
+                    ('.', _) => (Token::Dot, 1),
+                    ('*', _) => (Token::Star, 1),
+                    ('+', _) => (Token::Plus, 1),
+                    ('-', _) => (Token::Minus, 1),
+                    ('/', _) => (Token::Slash, 1),
+                    ('%', _) => (Token::Percent, 1),
+                    ('=', _) => (Token::Eq, 1),
+                    ('<', _) => (Token::Lt, 1),
+                    ('>', _) => (Token::Gt, 1),
+                    _ => return self.error(offset, &format!("unexpected character {:?}", c)),
+                };
+                self.pos += len;
+                token
+            };
+            tokens.push((token, offset));
+        }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> Result<()> {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(c), _) if c.is_whitespace() => self.pos += 1,
+                (Some('-'), Some('-')) => {
+                    self.take_while(|c| c != '\n');
+                }
+                (Some('/'), Some('*')) => {
+                    let offset = self.offset();
+                    self.pos += 2;
+                    loop {
+                        match (self.peek(), self.peek_at(1)) {
+                            (Some('*'), Some('/')) => {
+                                self.pos += 2;
+                                break;
+                            }
+                            (Some(_), _) => self.pos += 1,
+                            (None, _) => return self.error(offset, "unterminated comment"),
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Token {
+        let start = self.offset();
+        self.take_while(|c| c.is_ascii_digit());
+        if self.peek() == Some('.') {
+            self.pos += 1;
+            self.take_while(|c| c.is_ascii_digit());
+        }
+        if let Some('e') | Some('E') = self.peek() {
+            let sign = match self.peek_at(1) {
+                Some('+') | Some('-') => 1,
+                _ => 0,
+            };
+            if self.peek_at(1 + sign).map_or(false, |c| c.is_ascii_digit()) {
+                self.pos += 1 + sign;
+                self.take_while(|c| c.is_ascii_digit());
+            }
+        }
+        Token::Number(self.sql[start..self.offset()].to_string())
+    }
+
+    /// Read a literal enclosed in `quote`, where a doubled quote stands for itself.
+    fn quoted(&mut self, quote: char, unterminated: &str) -> Result<String> {
+        let offset = self.offset();
+        self.pos += 1;
+        let mut result = String::new();
+        loop {
+            match self.peek() {
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    if self.peek() == Some(quote) {
+                        self.pos += 1;
+                        result.push(quote);
+                    } else {
+                        return Ok(result);
+                    }
+                }
+                Some(c) => {
+                    self.pos += 1;
+                    result.push(c);
+                }
+                None => return self.error(offset, unterminated),
+            }
+        }
+    }
+}
@@ -1 +1,43 @@
+#[cfg(test)]
+#[macro_use]
+extern crate insta;
 
+pub mod ast;
+pub mod binder;
+pub mod catalog;
+pub mod eval;
+pub mod executor;
+pub mod hyperloglog;
+pub mod index;
+pub mod lexer;
+pub mod logical_plan;
+pub mod parser;
+pub mod physical_plan;
+pub mod planner;
+pub mod statistics;
+pub mod superblock;
+
+#[cfg(test)]
+mod aggregate_tests;
+#[cfg(test)]
+mod binder_tests;
+#[cfg(test)]
+mod catalog_tests;
+#[cfg(test)]
+mod eval_tests;
+#[cfg(test)]
+mod executor_tests;
+#[cfg(test)]
+mod join_tests;
+#[cfg(test)]
+mod parser_tests;
+#[cfg(test)]
+mod planner_tests;
+#[cfg(test)]
+mod sort_tests;
+#[cfg(test)]
+mod spill_tests;
+#[cfg(test)]
+mod statistics_tests;
+#[cfg(test)]
+mod test_utils;
@@ -0,0 +1,788 @@
+//! Bound logical plans, as produced by the binder.
+//!
+//! All names are resolved: columns are referred to by their index in the input row, and tables by
+//! their catalog definition. Expressions are type-checked, with implicit conversions made explicit
+//! as `Cast`s, so operands of a binary operator always have the same type (except for `Concat`,
+//! which takes two strings anyway).
+//!
+//! `Display` prints the plan as an indented tree, one node per line.
+
+use crate::ast::{type_name, BinaryOp, Ident, List, UnaryOp};
+use crate::catalog::TableDef;
+use std::fmt;
+use table::datum::{Datum, Nullability, Type};
+use table::row::Column;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LogicalPlan {
+    /// Constant rows. A `SELECT` without `FROM` reads a single row without columns.
+    Values {
+        columns: Vec<Column>,
+        rows: Vec<Vec<Expr>>,
+    },
+    /// All rows of a table.
+    Scan {
+        table: TableDef,
+    },
+    Filter {
+        input: Box<LogicalPlan>,
+        predicate: Expr,
+    },
+    Project {
+        input: Box<LogicalPlan>,
+        exprs: Vec<Expr>,
+        /// Name and type of each expression.
+        columns: Vec<Column>,
+    },
+    /// One row for each group of input rows with equal values of the `group_by` expressions (with
+    /// NULLs equal to each other): the group values, followed by the results of the aggregates.
+    /// Without `group_by`, all rows form a single group, even if there are none.
+    Aggregate {
+        input: Box<LogicalPlan>,
+        group_by: Vec<Expr>,
+        aggregates: Vec<AggregateCall>,
+    },
+    /// Rows of `left` joined with rows of `right`, as described in `JoinType`. Without a
+    /// condition, all pairs of rows match.
+    Join {
+        left: Box<LogicalPlan>,
+        right: Box<LogicalPlan>,
+        join_type: JoinType,
+        condition: Option<Expr>,
+    },
+    /// Sort by the keys, in order. NULLs sort after all other values, as if they were the largest.
+    Sort {
+        input: Box<LogicalPlan>,
+        keys: Vec<SortKey>,
+    },
+    Limit {
+        input: Box<LogicalPlan>,
+        limit: Option<u64>,
+        offset: u64,
+    },
+    /// Insert the rows produced by `input`, which has the same columns as the table.
+    Insert {
+        table: TableDef,
+        input: Box<LogicalPlan>,
+    },
+    /// Update the rows produced by `input`, which scans the table. Each assignment gives a column
+    /// index and its new value, computed from the old row.
+    Update {
+        table: TableDef,
+        input: Box<LogicalPlan>,
+        assignments: Vec<(usize, Expr)>,
+    },
+    /// Delete the rows produced by `input`, which scans the table.
+    Delete {
+        table: TableDef,
+        input: Box<LogicalPlan>,
+    },
+    CreateTable {
+        name: String,
+        columns: Vec<Column>,
+        primary_key: Vec<usize>,
+    },
+    DropTable {
+        name: String,
+        if_exists: bool,
+    },
+    CreateIndex {
+        name: String,
+        table: String,
+        columns: Vec<usize>,
+        unique: bool,
+    },
+    /// Collect and store the statistics of the tables.
+    Analyze {
+        tables: Vec<String>,
+    },
+    /// Describe the plan chosen for executing `input`, one line per row.
+    Explain {
+        input: Box<LogicalPlan>,
+    },
+}
+
+/// How rows of the two inputs of a join are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinType {
+    /// The left row followed by the right one, for each pair of matching rows.
+    Inner,
+    /// Like `Inner`, plus each left row without matches followed by NULLs.
+    Left,
+    /// Each left row which has a match, once.
+    Semi,
+}
+
+impl fmt::Display for JoinType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            JoinType::Inner => "inner",
+            JoinType::Left => "left",
+            JoinType::Semi => "semi",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SortKey {
+    pub expr: Expr,
+    pub descending: bool,
+}
+
+impl LogicalPlan {
+    /// Columns of the rows produced by the plan. Data modification statements produce a single
+    /// row with the number of affected rows, and DDL statements produce nothing.
+    pub fn columns(&self) -> Vec<Column> {
+        match self {
+            LogicalPlan::Values { columns, .. } | LogicalPlan::Project { columns, .. } => {
+                columns.clone()
+            }
+            LogicalPlan::Scan { table } => table.schema.columns().to_vec(),
+            LogicalPlan::Aggregate {
+                group_by,
+                aggregates,
+                ..
+            } => group_by
+                .iter()
+                .map(Expr::group_column)
+                .chain(aggregates.iter().map(AggregateCall::column))
+                .collect(),
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                ..
+            } => join_columns(left.columns(), right.columns(), *join_type),
+            LogicalPlan::Filter { input, .. }
+            | LogicalPlan::Sort { input, .. }
+            | LogicalPlan::Limit { input, .. } => input.columns(),
+            LogicalPlan::Insert { .. }
+            | LogicalPlan::Update { .. }
+            | LogicalPlan::Delete { .. } => vec![Column {
+                name: "count".to_string(),
+                ty: Type::Int8,
+                nullability: Nullability::NotNull,
+            }],
+            LogicalPlan::CreateTable { .. }
+            | LogicalPlan::DropTable { .. }
+            | LogicalPlan::CreateIndex { .. }
+            | LogicalPlan::Analyze { .. } => vec![],
+            LogicalPlan::Explain { .. } => vec![Column {
+                name: "QUERY PLAN".to_string(),
+                ty: Type::String,
+                nullability: Nullability::NotNull,
+            }],
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        write!(f, "{:1$}", "", depth * 2)?;
+        let input = match self {
+            LogicalPlan::Values { rows, .. } => {
+                write!(f, "Values:")?;
+                for (index, row) in rows.iter().enumerate() {
+                    write!(f, "{}({})", if index > 0 { ", " } else { " " }, List(row))?;
+                }
+                None
+            }
+            LogicalPlan::Scan { table } => {
+                write!(f, "Scan: {}", Ident(&table.name))?;
+                None
+            }
+            LogicalPlan::Filter { input, predicate } => {
+                write!(f, "Filter: {}", predicate)?;
+                Some(input)
+            }
+            LogicalPlan::Project {
+                input,
+                exprs,
+                columns,
+            } => {
+                write!(f, "Project:")?;
+                for (index, (expr, column)) in exprs.iter().zip(columns).enumerate() {
+                    write!(f, "{}{}", if index > 0 { ", " } else { " " }, expr)?;
+                    match expr {
+                        Expr::Column { name, .. } if *name == column.name => {}
+                        _ => write!(f, " AS {}", Ident(&column.name))?,
+                    }
+                }
+                Some(input)
+            }
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                aggregates,
+            } => {
+                write!(f, "Aggregate:")?;
+                if !aggregates.is_empty() {
+                    write!(f, " {}", List(aggregates))?;
+                }
+                if !group_by.is_empty() {
+                    write!(f, " group by {}", List(group_by))?;
+                }
+                Some(input)
+            }
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                condition,
+            } => {
+                write!(f, "Join: {}", join_type)?;
+                if let Some(condition) = condition {
+                    write!(f, " on {}", condition)?;
+                }
+                writeln!(f)?;
+                left.fmt_indented(f, depth + 1)?;
+                return right.fmt_indented(f, depth + 1);
+            }
+            LogicalPlan::Sort { input, keys } => {
+                write!(f, "Sort: {}", List(keys))?;
+                Some(input)
+            }
+            LogicalPlan::Limit {
+                input,
+                limit,
+                offset,
+            } => {
+                write!(f, "Limit:")?;
+                if let Some(limit) = limit {
+                    write!(f, " {}", limit)?;
+                }
+                if *offset > 0 {
+                    write!(f, " offset {}", offset)?;
+                }
+                Some(input)
+            }
+            LogicalPlan::Insert { table, input } => {
+                write!(f, "Insert: {}", Ident(&table.name))?;
+                Some(input)
+            }
+            LogicalPlan::Update {
+                table,
+                input,
+                assignments,
+            } => {
+                write!(f, "Update: {}", Ident(&table.name))?;
+                let columns = table.schema.columns();
+                for (index, (column, value)) in assignments.iter().enumerate() {
+                    write!(
+                        f,
+                        "{}{} = {}",
+                        if index > 0 { ", " } else { " " },
+                        Ident(&columns[*column].name),
+                        value
+                    )?;
+                }
+                Some(input)
+            }
+            LogicalPlan::Delete { table, input } => {
+                write!(f, "Delete: {}", Ident(&table.name))?;
+                Some(input)
+            }
+            LogicalPlan::CreateTable {
+                name,
+                columns,
+                primary_key,
+            } => {
+                write!(f, "CreateTable: {} (", Ident(name))?;
+                for (index, column) in columns.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} {}", Ident(&column.name), type_name(column.ty))?;
+                    if column.nullability == Nullability::NotNull {
+                        write!(f, " NOT NULL")?;
+                    }
+                }
+                write!(f, ")")?;
+                if !primary_key.is_empty() {
+                    let names: Vec<_> = primary_key
+                        .iter()
+                        .map(|&index| Ident(&columns[index].name))
+                        .collect();
+                    write!(f, " primary key ({})", List(&names))?;
+                }
+                None
+            }
+            LogicalPlan::DropTable { name, if_exists } => {
+                write!(f, "DropTable: {}", Ident(name))?;
+                if *if_exists {
+                    write!(f, " if exists")?;
+                }
+                None
+            }
+            LogicalPlan::CreateIndex {
+                name,
+                table,
+                columns,
+                unique,
+            } => {
+                write!(
+                    f,
+                    "CreateIndex: {}{} on {} columns {:?}",
+                    if *unique { "unique " } else { "" },
+                    Ident(name),
+                    Ident(table),
+                    columns
+                )?;
+                None
+            }
+            LogicalPlan::Analyze { tables } => {
+                let names: Vec<_> = tables.iter().map(|name| Ident(name)).collect();
+                write!(f, "Analyze: {}", List(&names))?;
+                None
+            }
+            LogicalPlan::Explain { input } => {
+                write!(f, "Explain")?;
+                Some(input)
+            }
+        };
+        writeln!(f)?;
+        match input {
+            Some(input) => input.fmt_indented(f, depth + 1),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Columns of the output of a join.
+pub fn join_columns(left: Vec<Column>, right: Vec<Column>, join_type: JoinType) -> Vec<Column> {
+    match join_type {
+        JoinType::Inner => [left, right].concat(),
+        JoinType::Left => {
+            let right = right.into_iter().map(|column| Column {
+                nullability: Nullability::Nullable,
+                ..column
+            });
+            left.into_iter().chain(right).collect()
+        }
+        JoinType::Semi => left,
+    }
+}
+
+impl fmt::Display for LogicalPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.expr)?;
+        if self.descending {
+            write!(f, " DESC")?;
+        }
+        Ok(())
+    }
+}
+
+/// Scalar functions.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Function {
+    /// `lower(text) -> text`
+    Lower,
+    /// `upper(text) -> text`
+    Upper,
+    /// `length(text) -> integer`, in characters.
+    Length,
+    /// `substr(text, start integer [, count integer]) -> text`: the characters from position
+    /// `start` (the first one is 1), up to `count` of them.
+    Substr,
+    /// `trim(text) -> text`, without leading and trailing spaces.
+    Trim,
+    /// `replace(text, from text, to text) -> text`, with all occurrences of `from` replaced.
+    Replace,
+    /// `abs(number) -> number`, of the same type.
+    Abs,
+    /// `round(number) -> number`: the nearest integer, of the same type. Halves are rounded away
+    /// from zero.
+    Round,
+    /// `floor(number) -> number`: the largest integer not greater than the argument.
+    Floor,
+    /// `ceil(number) -> number`: the smallest integer not less than the argument.
+    Ceil,
+    /// `sqrt(double precision) -> double precision`
+    Sqrt,
+    /// `power(double precision, double precision) -> double precision`
+    Power,
+    /// `coalesce(value, ...)`: the first non-NULL argument. All arguments have the same type.
+    Coalesce,
+}
+
+impl Function {
+    pub fn lookup(name: &str) -> Option<Function> {
+        Some(match name {
+            "lower" => Function::Lower,
+            "upper" => Function::Upper,
+            "length" => Function::Length,
+            "substr" => Function::Substr,
+            "trim" => Function::Trim,
+            "replace" => Function::Replace,
+            "abs" => Function::Abs,
+            "round" => Function::Round,
+            "floor" => Function::Floor,
+            "ceil" => Function::Ceil,
+            "sqrt" => Function::Sqrt,
+            "power" => Function::Power,
+            "coalesce" => Function::Coalesce,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Function::Lower => "lower",
+            Function::Upper => "upper",
+            Function::Length => "length",
+            Function::Substr => "substr",
+            Function::Trim => "trim",
+            Function::Replace => "replace",
+            Function::Abs => "abs",
+            Function::Round => "round",
+            Function::Floor => "floor",
+            Function::Ceil => "ceil",
+            Function::Sqrt => "sqrt",
+            Function::Power => "power",
+            Function::Coalesce => "coalesce",
+        }
+    }
+}
+
+/// Aggregate functions. All of them ignore NULL arguments, and except for `count`, return NULL
+/// if there are no other ones.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AggregateFunction {
+    /// `count(*) -> bigint` counts rows, `count(value) -> bigint` non-NULL values.
+    Count,
+    /// `sum(number)`: `bigint` for integers, the argument's type otherwise.
+    Sum,
+    /// `min(value)`, of the same type.
+    Min,
+    /// `max(value)`, of the same type.
+    Max,
+    /// `avg(number)`: `double precision` for `double precision`, `numeric` otherwise.
+    Avg,
+}
+
+impl AggregateFunction {
+    pub fn lookup(name: &str) -> Option<AggregateFunction> {
+        Some(match name {
+            "count" => AggregateFunction::Count,
+            "sum" => AggregateFunction::Sum,
+            "min" => AggregateFunction::Min,
+            "max" => AggregateFunction::Max,
+            "avg" => AggregateFunction::Avg,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AggregateFunction::Count => "count",
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+            AggregateFunction::Avg => "avg",
+        }
+    }
+}
+
+/// An aggregate function applied to an expression computed from each input row.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AggregateCall {
+    pub function: AggregateFunction,
+    /// None for `count(*)`.
+    pub arg: Option<Expr>,
+}
+
+impl AggregateCall {
+    pub fn ty(&self) -> Type {
+        let arg = self.arg.as_ref().map_or(Type::Int8, Expr::ty);
+        match self.function {
+            AggregateFunction::Count => Type::Int8,
+            AggregateFunction::Sum => match arg {
+                Type::Int2 | Type::Int4 | Type::Int8 => Type::Int8,
+                ty => ty,
+            },
+            AggregateFunction::Min | AggregateFunction::Max => arg,
+            AggregateFunction::Avg => match arg {
+                Type::Float8 => Type::Float8,
+                _ => Type::Numeric,
+            },
+        }
+    }
+
+    pub fn nullable(&self) -> bool {
+        self.function != AggregateFunction::Count
+    }
+
+    /// The column holding the result, in the output of an `Aggregate`.
+    pub fn column(&self) -> Column {
+        column(self.to_string(), self.ty(), self.nullable())
+    }
+}
+
+fn column(name: String, ty: Type, nullable: bool) -> Column {
+    Column {
+        name,
+        ty,
+        nullability: if nullable {
+            Nullability::Nullable
+        } else {
+            Nullability::NotNull
+        },
+    }
+}
+
+impl fmt::Display for AggregateCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.arg {
+            Some(arg) => write!(f, "{}({})", self.function.name(), arg),
+            None => write!(f, "{}(*)", self.function.name()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    /// A constant. `value` is either NULL or of type `ty`.
+    Constant {
+        value: Datum,
+        ty: Type,
+    },
+    /// A column of the input row.
+    Column {
+        index: usize,
+        name: String,
+        ty: Type,
+        nullability: Nullability,
+    },
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    Like {
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        negated: bool,
+    },
+    /// The result of the first branch whose condition is true, or `else_result`. A `CASE` with an
+    /// operand is bound as comparisons of the operand with each `WHEN` value, and a missing `ELSE`
+    /// as `ELSE NULL`.
+    Case {
+        branches: Vec<(Expr, Expr)>,
+        else_result: Box<Expr>,
+    },
+    Cast {
+        expr: Box<Expr>,
+        ty: Type,
+    },
+    Function {
+        function: Function,
+        args: Vec<Expr>,
+    },
+}
+
+impl Expr {
+    pub fn ty(&self) -> Type {
+        match self {
+            Expr::Constant { ty, .. } | Expr::Column { ty, .. } | Expr::Cast { ty, .. } => *ty,
+            Expr::Unary {
+                op: UnaryOp::Not, ..
+            }
+            | Expr::IsNull { .. }
+            | Expr::Like { .. } => Type::Bool,
+            Expr::Unary { expr, .. } => expr.ty(),
+            Expr::Binary { op, left, .. } => match op {
+                BinaryOp::Or
+                | BinaryOp::And
+                | BinaryOp::Eq
+                | BinaryOp::NotEq
+                | BinaryOp::Lt
+                | BinaryOp::LtEq
+                | BinaryOp::Gt
+                | BinaryOp::GtEq => Type::Bool,
+                BinaryOp::Concat => Type::String,
+                BinaryOp::Plus
+                | BinaryOp::Minus
+                | BinaryOp::Multiply
+                | BinaryOp::Divide
+                | BinaryOp::Modulo => left.ty(),
+            },
+            Expr::Case { else_result, .. } => else_result.ty(),
+            Expr::Function { function, args } => match function {
+                Function::Lower
+                | Function::Upper
+                | Function::Substr
+                | Function::Trim
+                | Function::Replace => Type::String,
+                Function::Length => Type::Int4,
+                Function::Sqrt | Function::Power => Type::Float8,
+                Function::Abs
+                | Function::Round
+                | Function::Floor
+                | Function::Ceil
+                | Function::Coalesce => args[0].ty(),
+            },
+        }
+    }
+
+    /// Whether the expression can evaluate to NULL.
+    pub fn nullable(&self) -> bool {
+        match self {
+            Expr::Constant { value, .. } => *value == Datum::Null,
+            Expr::Column { nullability, .. } => *nullability == Nullability::Nullable,
+            Expr::IsNull { .. } => false,
+            Expr::Unary { expr, .. } | Expr::Cast { expr, .. } => expr.nullable(),
+            Expr::Binary { left, right, .. } => left.nullable() || right.nullable(),
+            Expr::Like { expr, pattern, .. } => expr.nullable() || pattern.nullable(),
+            Expr::Case {
+                branches,
+                else_result,
+            } => else_result.nullable() || branches.iter().any(|(_, result)| result.nullable()),
+            Expr::Function {
+                function: Function::Coalesce,
+                args,
+            } => args.iter().all(Expr::nullable),
+            Expr::Function { args, .. } => args.iter().any(Expr::nullable),
+        }
+    }
+
+    /// The column holding the value of a `GROUP BY` expression, in the output of an `Aggregate`.
+    pub fn group_column(&self) -> Column {
+        let name = match self {
+            Expr::Column { name, .. } => name.clone(),
+            expr => expr.to_string(),
+        };
+        column(name, self.ty(), self.nullable())
+    }
+
+    /// Whether the expression is the constant NULL.
+    pub fn is_null_constant(&self) -> bool {
+        match self {
+            Expr::Constant { value, .. } => *value == Datum::Null,
+            _ => false,
+        }
+    }
+
+    /// Indexes of the input columns the expression refers to, in order of appearance (possibly
+    /// repeated).
+    pub fn referenced_columns(&self) -> Vec<usize> {
+        let mut result = vec![];
+        self.clone().map_columns(&mut |index| {
+            result.push(index);
+            index
+        });
+        result
+    }
+
+    /// Replace the index of each column reference with `f(index)`, e.g. to compute the expression
+    /// on rows with a different layout.
+    pub fn map_columns(mut self, f: &mut impl FnMut(usize) -> usize) -> Expr {
+        self.map_columns_in_place(f);
+        self
+    }
+
+    fn map_columns_in_place(&mut self, f: &mut impl FnMut(usize) -> usize) {
+        match self {
+            Expr::Constant { .. } => {}
+            Expr::Column { index, .. } => *index = f(*index),
+            Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } | Expr::Cast { expr, .. } => {
+                expr.map_columns_in_place(f)
+            }
+            Expr::Binary { left, right, .. } => {
+                left.map_columns_in_place(f);
+                right.map_columns_in_place(f);
+            }
+            Expr::Like { expr, pattern, .. } => {
+                expr.map_columns_in_place(f);
+                pattern.map_columns_in_place(f);
+            }
+            Expr::Case {
+                branches,
+                else_result,
+            } => {
+                for (condition, result) in branches {
+                    condition.map_columns_in_place(f);
+                    result.map_columns_in_place(f);
+                }
+                else_result.map_columns_in_place(f);
+            }
+            Expr::Function { args, .. } => {
+                for arg in args {
+                    arg.map_columns_in_place(f);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Constant { value, ty } => match value {
+                Datum::Null => write!(f, "NULL"),
+                Datum::Bool(true) => write!(f, "TRUE"),
+                Datum::Bool(false) => write!(f, "FALSE"),
+                Datum::Int2(_) | Datum::Int4(_) | Datum::Int8(_) | Datum::Numeric(_) => {
+                    write!(f, "{}", value)
+                }
+                Datum::String(s) => write!(f, "'{}'", s.replace('\'', "''")),
+                _ => write!(
+                    f,
+                    "{} '{}'",
+                    type_name(*ty),
+                    value.to_string().replace('\'', "''")
+                ),
+            },
+            Expr::Column { name, .. } => write!(f, "{}", Ident(name)),
+            Expr::Unary { op, expr } => match op {
+                UnaryOp::Not => write!(f, "(NOT {})", expr),
+                UnaryOp::Minus => write!(f, "(- {})", expr),
+            },
+            Expr::Binary { op, left, right } => write!(f, "({} {} {})", left, op, right),
+            Expr::IsNull { expr, negated } => write!(
+                f,
+                "({} IS {}NULL)",
+                expr,
+                if *negated { "NOT " } else { "" }
+            ),
+            Expr::Like {
+                expr,
+                pattern,
+                negated,
+            } => write!(
+                f,
+                "({} {}LIKE {})",
+                expr,
+                if *negated { "NOT " } else { "" },
+                pattern
+            ),
+            Expr::Case {
+                branches,
+                else_result,
+            } => {
+                write!(f, "CASE")?;
+                for (condition, result) in branches {
+                    write!(f, " WHEN {} THEN {}", condition, result)?;
+                }
+                write!(f, " ELSE {} END", else_result)
+            }
+            Expr::Cast { expr, ty } => write!(f, "CAST({} AS {})", expr, type_name(*ty)),
+            Expr::Function { function, args } => {
+                write!(f, "{}({})", function.name(), List(args))
+            }
+        }
+    }
+}
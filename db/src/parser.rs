@@ -0,0 +1,769 @@
+//! Recursive descent parser for a subset of SQL.
+//!
+//! Supported statements:
+//! - `CREATE TABLE name (column type [NOT NULL | NULL | PRIMARY KEY]..., [PRIMARY KEY (columns)])`
+//! - `DROP TABLE [IF EXISTS] name`
+//! - `CREATE [UNIQUE] INDEX name ON table (columns)`
+//! - `INSERT INTO table [(columns)] VALUES (exprs), ...`
+//! - `SELECT items [FROM from_item] [WHERE expr] [GROUP BY exprs] [HAVING expr]
+//!   [ORDER BY expr [ASC | DESC], ...] [LIMIT n] [OFFSET n]`, where `from_item` is
+//!   `table [[AS] alias]`, followed by any number of `, table [[AS] alias]`,
+//!   `CROSS JOIN table [[AS] alias]`, or `[INNER | LEFT [OUTER]] JOIN table [[AS] alias] ON expr`
+//! - `UPDATE table SET column = expr, ... [WHERE expr]`
+//! - `DELETE FROM table [WHERE expr]`
+//! - `EXPLAIN statement`
+//! - `ANALYZE [table]`
+//!
+//! Operator precedence, from lowest: `OR`, `AND`, `NOT`, comparisons (`=`, `<>`, `<`, `<=`, `>`,
+//! `>=`, `IS [NOT] NULL`, `[NOT] LIKE`), `||`, `+ -`, `* / %`, unary `-`. Comparisons don't
+//! associate: `a = b = c` is an error.
+
+use crate::ast::*;
+use crate::lexer::{tokenize, Token};
+use std::fmt;
+use table::datum::Type;
+
+/// Position in the SQL text, both 1-based. The column counts characters.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseError {
+    pub position: Position,
+    pub message: String,
+}
+
+impl ParseError {
+    /// An error at the given byte offset of `sql`.
+    pub fn new(sql: &str, offset: usize, message: String) -> Self {
+        let before = &sql[..offset];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        ParseError {
+            position: Position {
+                line: before.matches('\n').count() + 1,
+                column: before[line_start..].chars().count() + 1,
+            },
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.message, self.position.line, self.position.column
+        )
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ParseError>;
+
+/// Parse statements separated by semicolons.
+pub fn parse_statements(sql: &str) -> Result<Vec<Statement>> {
+    let mut parser = Parser::new(sql)?;
+    let mut statements = vec![];
+    loop {
+        while parser.eat(&Token::Semicolon) {}
+        if parser.peek() == &Token::Eof {
+            return Ok(statements);
+        }
+        statements.push(parser.statement()?);
+        if parser.peek() != &Token::Eof {
+            parser.expect(&Token::Semicolon)?;
+        }
+    }
+}
+
+/// Parse a single statement, optionally followed by a semicolon.
+pub fn parse_statement(sql: &str) -> Result<Statement> {
+    let mut parser = Parser::new(sql)?;
+    let statement = parser.statement()?;
+    parser.eat(&Token::Semicolon);
+    parser.expect(&Token::Eof)?;
+    Ok(statement)
+}
+
+pub fn parse_expr(sql: &str) -> Result<Expr> {
+    let mut parser = Parser::new(sql)?;
+    let expr = parser.expr()?;
+    parser.expect(&Token::Eof)?;
+    Ok(expr)
+}
+
+struct Parser<'a> {
+    sql: &'a str,
+    tokens: Vec<(Token, usize)>,
+    /// Index of the current token.
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(sql: &'a str) -> Result<Self> {
+        Ok(Parser {
+            sql,
+            tokens: tokenize(sql)?,
+            pos: 0,
+        })
+    }
+
+    fn peek(&self) -> &Token {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, n: usize) -> &Token {
+        // The last token is always `Eof`.
+        let index = (self.pos + n).min(self.tokens.len() - 1);
+        &self.tokens[index].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.peek().clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, message: String) -> Result<T> {
+        Err(ParseError::new(self.sql, self.tokens[self.pos].1, message))
+    }
+
+    fn expected<T>(&self, what: &str) -> Result<T> {
+        self.error(format!("expected {}, found {}", what, self.peek()))
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == token {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            self.expected(&token.to_string())
+        }
+    }
+
+    fn is_keyword_at(&self, n: usize, keyword: &str) -> bool {
+        match self.peek_at(n) {
+            Token::Word(word) => word == keyword,
+            _ => false,
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.is_keyword_at(0, keyword)
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            self.expected(&keyword.to_ascii_uppercase())
+        }
+    }
+
+    /// Whether the current token can be an identifier.
+    fn is_ident(&self) -> bool {
+        match self.peek() {
+            Token::Word(word) => !RESERVED_KEYWORDS.contains(&word.as_str()),
+            Token::QuotedIdent(_) => true,
+            _ => false,
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        if !self.is_ident() {
+            return self.expected("identifier");
+        }
+        match self.next() {
+            Token::Word(name) | Token::QuotedIdent(name) => Ok(name),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Parse a parenthesized, comma-separated list.
+    fn parenthesized<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T>) -> Result<Vec<T>> {
+        self.expect(&Token::LParen)?;
+        let items = self.comma_separated(&mut item)?;
+        self.expect(&Token::RParen)?;
+        Ok(items)
+    }
+
+    fn comma_separated<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let mut items = vec![item(self)?];
+        while self.eat(&Token::Comma) {
+            items.push(item(self)?);
+        }
+        Ok(items)
+    }
+
+    fn statement(&mut self) -> Result<Statement> {
+        if self.eat_keyword("create") {
+            if self.eat_keyword("table") {
+                self.create_table()
+            } else if self.is_keyword("unique") || self.is_keyword("index") {
+                self.create_index()
+            } else {
+                self.expected("TABLE or INDEX")
+            }
+        } else if self.eat_keyword("drop") {
+            self.expect_keyword("table")?;
+            let if_exists = self.eat_keyword("if");
+            if if_exists {
+                self.expect_keyword("exists")?;
+            }
+            Ok(Statement::DropTable {
+                name: self.ident()?,
+                if_exists,
+            })
+        } else if self.eat_keyword("explain") {
+            if self.is_keyword("explain") {
+                return self.expected("statement to explain");
+            }
+            Ok(Statement::Explain(Box::new(self.statement()?)))
+        } else if self.eat_keyword("analyze") {
+            let table = if self.is_ident() {
+                Some(self.ident()?)
+            } else {
+                None
+            };
+            Ok(Statement::Analyze { table })
+        } else if self.eat_keyword("insert") {
+            self.insert()
+        } else if self.eat_keyword("select") {
+            Ok(Statement::Select(self.select()?))
+        } else if self.eat_keyword("update") {
+            self.update()
+        } else if self.eat_keyword("delete") {
+            self.expect_keyword("from")?;
+            Ok(Statement::Delete(Delete {
+                table: self.ident()?,
+                where_clause: self.where_clause()?,
+            }))
+        } else {
+            self.expected("statement")
+        }
+    }
+
+    fn create_table(&mut self) -> Result<Statement> {
+        let name = self.ident()?;
+        let mut columns = vec![];
+        let mut primary_key: Option<Vec<String>> = None;
+        self.expect(&Token::LParen)?;
+        loop {
+            if self.is_keyword("primary") {
+                self.check_no_primary_key(&primary_key)?;
+                self.next();
+                self.expect_keyword("key")?;
+                primary_key = Some(self.parenthesized(Self::ident)?);
+            } else {
+                let column_name = self.ident()?;
+                let ty = self.data_type()?;
+                let mut not_null = false;
+                loop {
+                    if self.eat_keyword("not") {
+                        self.expect_keyword("null")?;
+                        not_null = true;
+                    } else if self.eat_keyword("null") {
+                    } else if self.is_keyword("primary") {
+                        self.check_no_primary_key(&primary_key)?;
+                        self.next();
+                        self.expect_keyword("key")?;
+                        primary_key = Some(vec![column_name.clone()]);
+                    } else {
+                        break;
+                    }
+                }
+                columns.push(ColumnDef {
+                    name: column_name,
+                    ty,
+                    not_null,
+                });
+            }
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(&Token::RParen)?;
+        Ok(Statement::CreateTable(CreateTable {
+            name,
+            columns,
+            primary_key: primary_key.unwrap_or_default(),
+        }))
+    }
+
+    fn check_no_primary_key(&self, primary_key: &Option<Vec<String>>) -> Result<()> {
+        match primary_key {
+            Some(_) => self.error("multiple primary keys".to_string()),
+            None => Ok(()),
+        }
+    }
+
+    fn data_type(&mut self) -> Result<Type> {
+        let word = match self.peek() {
+            Token::Word(word) => word.clone(),
+            _ => return self.expected("type"),
+        };
+        let ty = match word.as_str() {
+            "text" | "varchar" => Type::String,
+            "bigint" | "int8" => Type::Int8,
+            "integer" | "int" | "int4" => Type::Int4,
+            "smallint" | "int2" => Type::Int2,
+            "boolean" | "bool" => Type::Bool,
+            "float8" => Type::Float8,
+            "double" => {
+                self.next();
+                if !self.is_keyword("precision") {
+                    return self.expected("PRECISION");
+                }
+                Type::Float8
+            }
+            "bytea" => Type::Bytea,
+            "date" => Type::Date,
+            "timestamp" => Type::Timestamp,
+            "numeric" | "decimal" => Type::Numeric,
+            _ => return self.expected("type"),
+        };
+        self.next();
+        Ok(ty)
+    }
+
+    fn create_index(&mut self) -> Result<Statement> {
+        let unique = self.eat_keyword("unique");
+        self.expect_keyword("index")?;
+        let name = self.ident()?;
+        self.expect_keyword("on")?;
+        let table = self.ident()?;
+        let columns = self.parenthesized(Self::ident)?;
+        Ok(Statement::CreateIndex(CreateIndex {
+            name,
+            table,
+            columns,
+            unique,
+        }))
+    }
+
+    fn insert(&mut self) -> Result<Statement> {
+        self.expect_keyword("into")?;
+        let table = self.ident()?;
+        let columns = if self.peek() == &Token::LParen {
+            Some(self.parenthesized(Self::ident)?)
+        } else {
+            None
+        };
+        self.expect_keyword("values")?;
+        let rows = self.comma_separated(|parser| parser.parenthesized(Self::expr))?;
+        Ok(Statement::Insert(Insert {
+            table,
+            columns,
+            rows,
+        }))
+    }
+
+    fn select(&mut self) -> Result<Select> {
+        let projection = self.comma_separated(Self::select_item)?;
+        let from = if self.eat_keyword("from") {
+            Some(self.joined_tables()?)
+        } else {
+            None
+        };
+        let where_clause = self.where_clause()?;
+        let group_by = if self.eat_keyword("group") {
+            self.expect_keyword("by")?;
+            self.comma_separated(Self::expr)?
+        } else {
+            vec![]
+        };
+        let having = if self.eat_keyword("having") {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+        let order_by = if self.eat_keyword("order") {
+            self.expect_keyword("by")?;
+            self.comma_separated(|parser| {
+                let expr = parser.expr()?;
+                let descending = if parser.eat_keyword("desc") {
+                    true
+                } else {
+                    parser.eat_keyword("asc");
+                    false
+                };
+                Ok(OrderBy { expr, descending })
+            })?
+        } else {
+            vec![]
+        };
+        let limit = if self.eat_keyword("limit") {
+            Some(self.count()?)
+        } else {
+            None
+        };
+        let offset = if self.eat_keyword("offset") {
+            Some(self.count()?)
+        } else {
+            None
+        };
+        Ok(Select {
+            projection,
+            from,
+            where_clause,
+            group_by,
+            having,
+            order_by,
+            limit,
+            offset,
+        })
+    }
+
+    /// Tables joined from left to right.
+    fn joined_tables(&mut self) -> Result<FromItem> {
+        let mut item = self.table_ref()?;
+        loop {
+            let join_type = if self.eat(&Token::Comma) {
+                None
+            } else if self.eat_keyword("cross") {
+                self.expect_keyword("join")?;
+                None
+            } else if self.eat_keyword("join") {
+                Some(JoinType::Inner)
+            } else if self.eat_keyword("inner") {
+                self.expect_keyword("join")?;
+                Some(JoinType::Inner)
+            } else if self.eat_keyword("left") {
+                self.eat_keyword("outer");
+                self.expect_keyword("join")?;
+                Some(JoinType::Left)
+            } else {
+                return Ok(item);
+            };
+            let right = self.table_ref()?;
+            let condition = match join_type {
+                Some(_) => {
+                    self.expect_keyword("on")?;
+                    Some(self.expr()?)
+                }
+                None => None,
+            };
+            item = FromItem::Join {
+                left: Box::new(item),
+                right: Box::new(right),
+                join_type: join_type.unwrap_or(JoinType::Inner),
+                condition,
+            };
+        }
+    }
+
+    fn table_ref(&mut self) -> Result<FromItem> {
+        let name = self.ident()?;
+        Ok(FromItem::Table(TableRef {
+            name,
+            alias: self.alias()?,
+        }))
+    }
+
+    fn select_item(&mut self) -> Result<SelectItem> {
+        if self.eat(&Token::Star) {
+            return Ok(SelectItem::Wildcard);
+        }
+        let expr = self.expr()?;
+        Ok(SelectItem::Expr {
+            expr,
+            alias: self.alias()?,
+        })
+    }
+
+    /// `[AS] alias`
+    fn alias(&mut self) -> Result<Option<String>> {
+        if self.eat_keyword("as") || self.is_ident() {
+            Ok(Some(self.ident()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// A non-negative integer, for `LIMIT` and `OFFSET`.
+    fn count(&mut self) -> Result<u64> {
+        if let Token::Number(number) = self.peek() {
+            if let Ok(count) = number.parse() {
+                self.next();
+                return Ok(count);
+            }
+        }
+        self.expected("non-negative integer")
+    }
+
+    fn where_clause(&mut self) -> Result<Option<Expr>> {
+        if self.eat_keyword("where") {
+            Ok(Some(self.expr()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn update(&mut self) -> Result<Statement> {
+        let table = self.ident()?;
+        self.expect_keyword("set")?;
+        let assignments = self.comma_separated(|parser| {
+            let column = parser.ident()?;
+            parser.expect(&Token::Eq)?;
+            Ok((column, parser.expr()?))
+        })?;
+        Ok(Statement::Update(Update {
+            table,
+            assignments,
+            where_clause: self.where_clause()?,
+        }))
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let mut left = self.and_expr()?;
+        while self.eat_keyword("or") {
+            left = binary(BinaryOp::Or, left, self.and_expr()?);
+        }
+        Ok(left)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr> {
+        let mut left = self.not_expr()?;
+        while self.eat_keyword("and") {
+            left = binary(BinaryOp::And, left, self.not_expr()?);
+        }
+        Ok(left)
+    }
+
+    fn not_expr(&mut self) -> Result<Expr> {
+        if self.eat_keyword("not") {
+            Ok(Expr::Unary {
+                op: UnaryOp::Not,
+                expr: Box::new(self.not_expr()?),
+            })
+        } else {
+            self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let left = self.concat_expr()?;
+        let op = match self.peek() {
+            Token::Eq => Some(BinaryOp::Eq),
+            Token::NotEq => Some(BinaryOp::NotEq),
+            Token::Lt => Some(BinaryOp::Lt),
+            Token::LtEq => Some(BinaryOp::LtEq),
+            Token::Gt => Some(BinaryOp::Gt),
+            Token::GtEq => Some(BinaryOp::GtEq),
+            _ => None,
+        };
+        if let Some(op) = op {
+            self.next();
+            return Ok(binary(op, left, self.concat_expr()?));
+        }
+        if self.eat_keyword("is") {
+            let negated = self.eat_keyword("not");
+            self.expect_keyword("null")?;
+            return Ok(Expr::IsNull {
+                expr: Box::new(left),
+                negated,
+            });
+        }
+        let negated = self.is_keyword("not") && self.is_keyword_at(1, "like");
+        if negated {
+            self.next();
+        }
+        if self.eat_keyword("like") {
+            return Ok(Expr::Like {
+                expr: Box::new(left),
+                pattern: Box::new(self.concat_expr()?),
+                negated,
+            });
+        }
+        Ok(left)
+    }
+
+    fn concat_expr(&mut self) -> Result<Expr> {
+        let mut left = self.additive()?;
+        while self.eat(&Token::Concat) {
+            left = binary(BinaryOp::Concat, left, self.additive()?);
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> Result<Expr> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Token::Plus => BinaryOp::Plus,
+                Token::Minus => BinaryOp::Minus,
+                _ => return Ok(left),
+            };
+            self.next();
+            left = binary(op, left, self.multiplicative()?);
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Token::Star => BinaryOp::Multiply,
+                Token::Slash => BinaryOp::Divide,
+                Token::Percent => BinaryOp::Modulo,
+                _ => return Ok(left),
+            };
+            self.next();
+            left = binary(op, left, self.unary()?);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat(&Token::Minus) {
+            Ok(Expr::Unary {
+                op: UnaryOp::Minus,
+                expr: Box::new(self.unary()?),
+            })
+        } else if self.eat(&Token::Plus) {
+            self.unary()
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.peek().clone() {
+            Token::Number(number) => {
+                self.next();
+                Ok(Expr::Literal(Literal::Number(number)))
+            }
+            Token::String(s) => {
+                self.next();
+                Ok(Expr::Literal(Literal::String(s)))
+            }
+            Token::LParen => {
+                self.next();
+                let expr = self.expr()?;
+                self.expect(&Token::RParen)?;
+                Ok(expr)
+            }
+            Token::Word(word) => match word.as_str() {
+                "null" => {
+                    self.next();
+                    Ok(Expr::Literal(Literal::Null))
+                }
+                "true" | "false" => {
+                    self.next();
+                    Ok(Expr::Literal(Literal::Bool(word == "true")))
+                }
+                "case" => {
+                    self.next();
+                    self.case()
+                }
+                "cast" => {
+                    self.next();
+                    self.expect(&Token::LParen)?;
+                    let expr = self.expr()?;
+                    self.expect_keyword("as")?;
+                    let ty = self.data_type()?;
+                    self.expect(&Token::RParen)?;
+                    Ok(Expr::Cast {
+                        expr: Box::new(expr),
+                        ty,
+                    })
+                }
+                "date" | "timestamp" if matches!(self.peek_at(1), Token::String(_)) => {
+                    let ty = self.data_type()?;
+                    let expr = self.primary()?;
+                    Ok(Expr::Cast {
+                        expr: Box::new(expr),
+                        ty,
+                    })
+                }
+                _ if self.is_ident() => self.name_expr(),
+                _ => self.expected("expression"),
+            },
+            Token::QuotedIdent(_) => self.name_expr(),
+            _ => self.expected("expression"),
+        }
+    }
+
+    /// A column reference or function call.
+    fn name_expr(&mut self) -> Result<Expr> {
+        let name = self.ident()?;
+        if self.eat(&Token::LParen) {
+            let args = if self.eat(&Token::Star) {
+                FunctionArgs::Star
+            } else if self.peek() == &Token::RParen {
+                FunctionArgs::List(vec![])
+            } else {
+                FunctionArgs::List(self.comma_separated(Self::expr)?)
+            };
+            self.expect(&Token::RParen)?;
+            Ok(Expr::Function { name, args })
+        } else if self.eat(&Token::Dot) {
+            Ok(Expr::Column {
+                table: Some(name),
+                name: self.ident()?,
+            })
+        } else {
+            Ok(Expr::Column { table: None, name })
+        }
+    }
+
+    fn case(&mut self) -> Result<Expr> {
+        let operand = if self.is_keyword("when") {
+            None
+        } else {
+            Some(Box::new(self.expr()?))
+        };
+        let mut branches = vec![];
+        while self.eat_keyword("when") {
+            let condition = self.expr()?;
+            self.expect_keyword("then")?;
+            branches.push((condition, self.expr()?));
+        }
+        if branches.is_empty() {
+            return self.expected("WHEN");
+        }
+        let else_result = if self.eat_keyword("else") {
+            Some(Box::new(self.expr()?))
+        } else {
+            None
+        };
+        self.expect_keyword("end")?;
+        Ok(Expr::Case {
+            operand,
+            branches,
+            else_result,
+        })
+    }
+}
+
+fn binary(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+    Expr::Binary {
+        op,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
@@ -0,0 +1,300 @@
+use crate::ast::*;
+use crate::parser::{parse_expr, parse_statement, parse_statements, ParseError, Position};
+
+/// Parse statements and print them back, or print the error.
+fn parse(sql: &str) -> String {
+    match parse_statements(sql) {
+        Ok(statements) => statements
+            .iter()
+            .map(|statement| format!("{};\n", statement))
+            .collect(),
+        Err(err) => format!("error: {}\n", err),
+    }
+}
+
+/// Parse each expression and print it back, or print the error.
+fn exprs(exprs: &[&str]) -> String {
+    exprs
+        .iter()
+        .map(|sql| match parse_expr(sql) {
+            Ok(expr) => format!("{}\n", expr),
+            Err(err) => format!("error: {}\n", err),
+        })
+        .collect()
+}
+
+#[test]
+fn create_table() {
+    assert_snapshot!(parse("
+        create table users (
+            id bigint primary key,
+            name text not null,
+            email varchar null,
+            \"Age\" smallint,
+            score double precision,
+            balance numeric,
+            avatar bytea,
+            birthday date,
+            created timestamp not null,
+            active boolean,
+            n int
+        );
+        CREATE TABLE orders (user_id INT8, n INTEGER NOT NULL, PRIMARY KEY (user_id, n))
+    "), @r###"
+    CREATE TABLE users (id BIGINT, name TEXT NOT NULL, email TEXT, "Age" SMALLINT, score DOUBLE PRECISION, balance NUMERIC, avatar BYTEA, birthday DATE, created TIMESTAMP NOT NULL, active BOOLEAN, n INTEGER, PRIMARY KEY (id));
+    CREATE TABLE orders (user_id BIGINT, n INTEGER NOT NULL, PRIMARY KEY (user_id, n));
+    "###);
+}
+
+#[test]
+fn drop_table_and_create_index() {
+    assert_snapshot!(parse("
+        drop table users;
+        drop table if exists users;
+        create index users_by_name on users (name);
+        create unique index users_by_email on users (email, id);
+    "), @r###"
+    DROP TABLE users;
+    DROP TABLE IF EXISTS users;
+    CREATE INDEX users_by_name ON users (name);
+    CREATE UNIQUE INDEX users_by_email ON users (email, id);
+    "###);
+}
+
+#[test]
+fn insert() {
+    assert_snapshot!(parse("
+        insert into users values (1, 'alice', null);
+        insert into users (id, name) values (1, 'it''s'), (2 + 3, upper('bob'));
+    "), @r###"
+    INSERT INTO users VALUES (1, 'alice', NULL);
+    INSERT INTO users (id, name) VALUES (1, 'it''s'), ((2 + 3), upper('bob'));
+    "###);
+}
+
+#[test]
+fn select() {
+    assert_snapshot!(parse("
+        select 1;
+        select * from users;
+        select id, name as n, u.email e from users as u where id > 10 and name like 'a%';
+        select * from users u order by name desc, id asc, created limit 10 offset 20;
+        select count(*), max(id) from users where name is not null;
+        select now(), \"Select\".\"Order\" from \"Select\";
+        select age, count(*) from users group by age having count(*) > 1 order by 2 desc;
+        select a, b + 1, sum(c) from t where c > 0 group by a, b + 1;
+    "), @r###"
+    SELECT 1;
+    SELECT * FROM users;
+    SELECT id, name AS n, u.email AS e FROM users AS u WHERE ((id > 10) AND (name LIKE 'a%'));
+    SELECT * FROM users AS u ORDER BY name DESC, id, created LIMIT 10 OFFSET 20;
+    SELECT count(*), max(id) FROM users WHERE (name IS NOT NULL);
+    SELECT now(), "Select"."Order" FROM "Select";
+    SELECT age, count(*) FROM users GROUP BY age HAVING (count(*) > 1) ORDER BY 2 DESC;
+    SELECT a, (b + 1), sum(c) FROM t WHERE (c > 0) GROUP BY a, (b + 1);
+    "###);
+}
+
+#[test]
+fn joins() {
+    assert_snapshot!(parse("
+        select * from a, b;
+        select * from a cross join b c, d;
+        select * from a join b on a.x = b.x inner join c on true;
+        select * from a x left join b y on x.id = y.id and y.n > 1 left outer join c on c.id = x.id;
+        explain select * from a join b on a.x = b.x where a.y = 1;
+        explain delete from a;
+    "), @r###"
+    SELECT * FROM a CROSS JOIN b;
+    SELECT * FROM a CROSS JOIN b AS c CROSS JOIN d;
+    SELECT * FROM a JOIN b ON (a.x = b.x) JOIN c ON TRUE;
+    SELECT * FROM a AS x LEFT JOIN b AS y ON ((x.id = y.id) AND (y.n > 1)) LEFT JOIN c ON (c.id = x.id);
+    EXPLAIN SELECT * FROM a JOIN b ON (a.x = b.x) WHERE (a.y = 1);
+    EXPLAIN DELETE FROM a;
+    "###);
+    let errors: String = [
+        "select * from a join b",
+        "select * from a cross join b on true",
+        "select * from a left b",
+        "select * from a join (b)",
+        "explain explain select 1",
+    ]
+    .iter()
+    .map(|sql| parse(sql))
+    .collect();
+    assert_snapshot!(errors, @r###"
+    error: expected ON, found end of input at line 1, column 23
+    error: expected ;, found on at line 1, column 30
+    error: expected JOIN, found b at line 1, column 22
+    error: expected identifier, found ( at line 1, column 22
+    error: expected statement to explain, found explain at line 1, column 9
+    "###);
+}
+
+#[test]
+fn update_and_delete() {
+    assert_snapshot!(parse("
+        update users set name = 'x', score = score * 2 where id = 1;
+        update users set active = false;
+        delete from users where id = 1 or name is null;
+        delete from users;
+    "), @r###"
+    UPDATE users SET name = 'x', score = (score * 2) WHERE (id = 1);
+    UPDATE users SET active = FALSE;
+    DELETE FROM users WHERE ((id = 1) OR (name IS NULL));
+    DELETE FROM users;
+    "###);
+}
+
+#[test]
+fn precedence() {
+    assert_snapshot!(exprs(&[
+        "a or b and c or d",
+        "not a = b and not not c",
+        "1 + 2 * 3 - 4 / 5 % 6",
+        "-a * -(b + c)",
+        "a || b + 1 = c",
+        "a + 1 is not null or b not like 'x' || y",
+        "a <> b and a != b and a <= b and a >= b and a < b and a > b",
+    ]), @r###"
+    ((a OR (b AND c)) OR d)
+    ((NOT (a = b)) AND (NOT (NOT c)))
+    ((1 + (2 * 3)) - ((4 / 5) % 6))
+    ((- a) * (- (b + c)))
+    ((a || (b + 1)) = c)
+    (((a + 1) IS NOT NULL) OR (b NOT LIKE ('x' || y)))
+    ((((((a <> b) AND (a <> b)) AND (a <= b)) AND (a >= b)) AND (a < b)) AND (a > b))
+    "###);
+}
+
+#[test]
+fn expressions() {
+    assert_snapshot!(exprs(&[
+        "case when a then 1 when b then 2 end",
+        "CASE x WHEN 1 THEN 'one' ELSE 'many' END",
+        "cast(a as integer) + cast('1.5' as decimal)",
+        "date '2020-01-01' < timestamp '2020-01-01 12:00'",
+        "date + 1",
+        "1.5e10 + .5 + 2E-3 + 7.",
+        "true and false or null",
+        "coalesce(a, b, 'c''d')",
+        "-- comment\n a /* another\n comment */ + 1",
+    ]), @r###"
+    CASE WHEN a THEN 1 WHEN b THEN 2 END
+    CASE x WHEN 1 THEN 'one' ELSE 'many' END
+    (CAST(a AS INTEGER) + CAST('1.5' AS NUMERIC))
+    (CAST('2020-01-01' AS DATE) < CAST('2020-01-01 12:00' AS TIMESTAMP))
+    (date + 1)
+    (((1.5e10 + .5) + 2E-3) + 7.)
+    ((TRUE AND FALSE) OR NULL)
+    coalesce(a, b, 'c''d')
+    (a + 1)
+    "###);
+}
+
+#[test]
+fn errors() {
+    let errors: String = [
+        "selec 1",
+        "select from users",
+        "select 1 from select",
+        "select a\nfrom t\nwhere a = = 1",
+        "select 1 select 2",
+        "select 'abc",
+        "select \"\"",
+        "select 1 /* comment",
+        "select a # b",
+        "select * from t limit -1",
+        "create table t (a blob)",
+        "create table t (a int primary key, b int, primary key (b))",
+        "create view v",
+        "insert into t values ()",
+        "update t set a = 1 where",
+    ]
+    .iter()
+    .map(|sql| parse(sql))
+    .collect();
+    assert_snapshot!(errors, @r###"
+    error: expected statement, found selec at line 1, column 1
+    error: expected expression, found from at line 1, column 8
+    error: expected identifier, found select at line 1, column 15
+    error: expected expression, found = at line 3, column 11
+    error: expected ;, found select at line 1, column 10
+    error: unterminated string literal at line 1, column 8
+    error: empty quoted identifier at line 1, column 8
+    error: unterminated comment at line 1, column 10
+    error: unexpected character '#' at line 1, column 10
+    error: expected non-negative integer, found - at line 1, column 23
+    error: expected type, found blob at line 1, column 19
+    error: multiple primary keys at line 1, column 43
+    error: expected TABLE or INDEX, found view at line 1, column 8
+    error: expected expression, found ) at line 1, column 23
+    error: expected expression, found end of input at line 1, column 25
+    "###);
+    assert_snapshot!(exprs(&[
+        "a = b = c",
+        "case else 1 end",
+        "'zażółć' + ?",
+    ]), @r###"
+    error: expected end of input, found = at line 1, column 7
+    error: expected expression, found else at line 1, column 6
+    error: unexpected character '?' at line 1, column 12
+    "###);
+}
+
+#[test]
+fn error_position() {
+    assert_eq!(
+        parse_statement("select 1;\n  select 2"),
+        Err(ParseError {
+            position: Position { line: 2, column: 3 },
+            message: "expected end of input, found select".to_string(),
+        })
+    );
+}
+
+#[test]
+fn structure() {
+    assert_eq!(
+        parse_statement("delete from t where t.a = -1;"),
+        Ok(Statement::Delete(Delete {
+            table: "t".to_string(),
+            where_clause: Some(Expr::Binary {
+                op: BinaryOp::Eq,
+                left: Box::new(Expr::Column {
+                    table: Some("t".to_string()),
+                    name: "a".to_string(),
+                }),
+                right: Box::new(Expr::Unary {
+                    op: UnaryOp::Minus,
+                    expr: Box::new(Expr::Literal(Literal::Number("1".to_string()))),
+                }),
+            }),
+        }))
+    );
+}
+
+#[test]
+fn display_roundtrip() {
+    let sql = "
+        create table \"T\" (\"select\" text primary key, b timestamp not null);
+        drop table if exists t;
+        create unique index i on t (a, b);
+        insert into t (a) values (1), (-2), ('x' || y);
+        select *, a + 1 as b, count(*) from t x where not a is null and b not like 'x'
+            order by a desc limit 1 offset 2;
+        select a, max(b) from t group by a, c having sum(b) > 0;
+        select case when a then b end, case a when 1 then 2 else 3 end, date '2020-01-01';
+        update t set a = cast(b as double precision) where a <> 1;
+        delete from t where (a or b) and c;
+        select * from a, b x join c on x.id = c.id left join d on true;
+        explain select 1;
+        analyze;
+        analyze \"T\";
+        explain analyze t;
+    ";
+    for statement in parse_statements(sql).unwrap() {
+        let printed = statement.to_string();
+        assert_eq!(parse_statement(&printed), Ok(statement), "{}", printed);
+    }
+}
@@ -0,0 +1,405 @@
+//! Physical plans, as chosen by the planner (see `planner`).
+//!
+//! A physical plan computes the same rows as the logical plan it was made from, but says how: which
+//! index finds the rows of a table, which algorithm joins two inputs. Each node is executed by the
+//! operator of the same name in `executor`. Column indexes in expressions refer to the rows of the
+//! node's input, or to the left row followed by the right row for joins.
+//!
+//! `Display` prints the plan as an indented tree, one node per line. This is the output of
+//! `EXPLAIN`.
+
+use crate::ast::{Ident, List};
+use crate::catalog::{IndexDef, TableDef};
+use crate::logical_plan::{join_columns, AggregateCall, Expr, JoinType, LogicalPlan, SortKey};
+use std::fmt;
+use std::ops::Bound;
+use table::datum::{Datum, Nullability, Type};
+use table::row::Column;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PhysicalPlan {
+    Values {
+        columns: Vec<Column>,
+        rows: Vec<Vec<Expr>>,
+    },
+    SeqScan {
+        table: TableDef,
+    },
+    /// Rows of a table whose key in the index is between the bounds, in index order. Bounds are
+    /// values of the first indexed columns (see `executor::IndexScan`).
+    IndexScan {
+        table: TableDef,
+        index: IndexDef,
+        start: Bound<Vec<Datum>>,
+        end: Bound<Vec<Datum>>,
+    },
+    Filter {
+        input: Box<PhysicalPlan>,
+        predicate: Expr,
+    },
+    Project {
+        input: Box<PhysicalPlan>,
+        exprs: Vec<Expr>,
+        columns: Vec<Column>,
+    },
+    HashAggregate {
+        input: Box<PhysicalPlan>,
+        group_by: Vec<Expr>,
+        aggregates: Vec<AggregateCall>,
+    },
+    Sort {
+        input: Box<PhysicalPlan>,
+        keys: Vec<SortKey>,
+    },
+    Limit {
+        input: Box<PhysicalPlan>,
+        limit: Option<u64>,
+        offset: u64,
+    },
+    NestedLoopJoin {
+        left: Box<PhysicalPlan>,
+        right: Box<PhysicalPlan>,
+        join_type: JoinType,
+        condition: Option<Expr>,
+    },
+    /// For each left row, looks up the rows of `table` whose first indexed columns are equal to
+    /// `left_keys`. The condition is checked on the left row followed by the table row.
+    IndexNestedLoopJoin {
+        left: Box<PhysicalPlan>,
+        table: TableDef,
+        index: IndexDef,
+        left_keys: Vec<Expr>,
+        join_type: JoinType,
+        condition: Option<Expr>,
+    },
+    /// Rows match if their keys are equal (and not NULL), and the condition is true.
+    HashJoin {
+        left: Box<PhysicalPlan>,
+        right: Box<PhysicalPlan>,
+        left_keys: Vec<Expr>,
+        right_keys: Vec<Expr>,
+        join_type: JoinType,
+        condition: Option<Expr>,
+    },
+    /// Like `HashJoin`, for inputs sorted by their keys (ascending, NULLs last).
+    MergeJoin {
+        left: Box<PhysicalPlan>,
+        right: Box<PhysicalPlan>,
+        left_keys: Vec<Expr>,
+        right_keys: Vec<Expr>,
+        join_type: JoinType,
+        condition: Option<Expr>,
+    },
+    Insert {
+        table: TableDef,
+        input: Box<PhysicalPlan>,
+    },
+    Update {
+        table: TableDef,
+        input: Box<PhysicalPlan>,
+        assignments: Vec<(usize, Expr)>,
+    },
+    Delete {
+        table: TableDef,
+        input: Box<PhysicalPlan>,
+    },
+    /// A DDL statement, which is executed as is.
+    Ddl(LogicalPlan),
+}
+
+impl PhysicalPlan {
+    /// Columns of the rows produced by the plan, as for `LogicalPlan::columns`.
+    pub fn columns(&self) -> Vec<Column> {
+        match self {
+            PhysicalPlan::Values { columns, .. } | PhysicalPlan::Project { columns, .. } => {
+                columns.clone()
+            }
+            PhysicalPlan::SeqScan { table } | PhysicalPlan::IndexScan { table, .. } => {
+                table.schema.columns().to_vec()
+            }
+            PhysicalPlan::HashAggregate {
+                group_by,
+                aggregates,
+                ..
+            } => group_by
+                .iter()
+                .map(Expr::group_column)
+                .chain(aggregates.iter().map(AggregateCall::column))
+                .collect(),
+            PhysicalPlan::NestedLoopJoin {
+                left,
+                right,
+                join_type,
+                ..
+            }
+            | PhysicalPlan::HashJoin {
+                left,
+                right,
+                join_type,
+                ..
+            }
+            | PhysicalPlan::MergeJoin {
+                left,
+                right,
+                join_type,
+                ..
+            } => join_columns(left.columns(), right.columns(), *join_type),
+            PhysicalPlan::IndexNestedLoopJoin {
+                left,
+                table,
+                join_type,
+                ..
+            } => join_columns(left.columns(), table.schema.columns().to_vec(), *join_type),
+            PhysicalPlan::Filter { input, .. }
+            | PhysicalPlan::Sort { input, .. }
+            | PhysicalPlan::Limit { input, .. } => input.columns(),
+            PhysicalPlan::Insert { .. }
+            | PhysicalPlan::Update { .. }
+            | PhysicalPlan::Delete { .. } => vec![Column {
+                name: "count".to_string(),
+                ty: Type::Int8,
+                nullability: Nullability::NotNull,
+            }],
+            PhysicalPlan::Ddl(plan) => plan.columns(),
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        write!(f, "{:1$}", "", depth * 2)?;
+        let inputs: Vec<&PhysicalPlan> = match self {
+            PhysicalPlan::Values { rows, .. } => {
+                write!(f, "Values:")?;
+                for (index, row) in rows.iter().enumerate() {
+                    write!(f, "{}({})", if index > 0 { ", " } else { " " }, List(row))?;
+                }
+                vec![]
+            }
+            PhysicalPlan::SeqScan { table } => {
+                write!(f, "SeqScan: {}", Ident(&table.name))?;
+                vec![]
+            }
+            PhysicalPlan::IndexScan {
+                table,
+                index,
+                start,
+                end,
+            } => {
+                write!(
+                    f,
+                    "IndexScan: {} using {}",
+                    Ident(&table.name),
+                    Ident(&index.name)
+                )?;
+                let bound = |op: &str, values: &[Datum]| bound_condition(table, index, op, values);
+                let conditions = match (start, end) {
+                    (Bound::Included(start), Bound::Included(end)) if start == end => {
+                        vec![bound("=", start)]
+                    }
+                    _ => {
+                        let mut conditions = vec![];
+                        match start {
+                            Bound::Included(values) => conditions.push(bound(">=", values)),
+                            Bound::Excluded(values) => conditions.push(bound(">", values)),
+                            Bound::Unbounded => {}
+                        }
+                        match end {
+                            Bound::Included(values) => conditions.push(bound("<=", values)),
+                            Bound::Excluded(values) => conditions.push(bound("<", values)),
+                            Bound::Unbounded => {}
+                        }
+                        conditions
+                    }
+                };
+                if !conditions.is_empty() {
+                    write!(f, " ({})", conditions.join(" and "))?;
+                }
+                vec![]
+            }
+            PhysicalPlan::Filter { input, predicate } => {
+                write!(f, "Filter: {}", predicate)?;
+                vec![input]
+            }
+            PhysicalPlan::Project {
+                input,
+                exprs,
+                columns,
+            } => {
+                write!(f, "Project:")?;
+                for (index, (expr, column)) in exprs.iter().zip(columns).enumerate() {
+                    write!(f, "{}{}", if index > 0 { ", " } else { " " }, expr)?;
+                    match expr {
+                        Expr::Column { name, .. } if *name == column.name => {}
+                        _ => write!(f, " AS {}", Ident(&column.name))?,
+                    }
+                }
+                vec![input]
+            }
+            PhysicalPlan::HashAggregate {
+                input,
+                group_by,
+                aggregates,
+            } => {
+                write!(f, "HashAggregate:")?;
+                if !aggregates.is_empty() {
+                    write!(f, " {}", List(aggregates))?;
+                }
+                if !group_by.is_empty() {
+                    write!(f, " group by {}", List(group_by))?;
+                }
+                vec![input]
+            }
+            PhysicalPlan::Sort { input, keys } => {
+                write!(f, "Sort: {}", List(keys))?;
+                vec![input]
+            }
+            PhysicalPlan::Limit {
+                input,
+                limit,
+                offset,
+            } => {
+                write!(f, "Limit:")?;
+                if let Some(limit) = limit {
+                    write!(f, " {}", limit)?;
+                }
+                if *offset > 0 {
+                    write!(f, " offset {}", offset)?;
+                }
+                vec![input]
+            }
+            PhysicalPlan::NestedLoopJoin {
+                left,
+                right,
+                join_type,
+                condition,
+            } => {
+                write!(f, "NestedLoopJoin: {}", join_type)?;
+                if let Some(condition) = condition {
+                    write!(f, " on {}", condition)?;
+                }
+                vec![left, right]
+            }
+            PhysicalPlan::IndexNestedLoopJoin {
+                left,
+                table,
+                index,
+                left_keys,
+                join_type,
+                condition,
+            } => {
+                write!(
+                    f,
+                    "IndexNestedLoopJoin: {} {} using {} on ",
+                    join_type,
+                    Ident(&table.name),
+                    Ident(&index.name)
+                )?;
+                let columns = table.schema.columns();
+                for (position, (key, &column)) in left_keys.iter().zip(&index.columns).enumerate() {
+                    if position > 0 {
+                        write!(f, " and ")?;
+                    }
+                    write!(f, "{} = {}", key, Ident(&columns[column].name))?;
+                }
+                if let Some(condition) = condition {
+                    write!(f, " filter {}", condition)?;
+                }
+                vec![left]
+            }
+            PhysicalPlan::HashJoin {
+                left,
+                right,
+                left_keys,
+                right_keys,
+                join_type,
+                condition,
+            }
+            | PhysicalPlan::MergeJoin {
+                left,
+                right,
+                left_keys,
+                right_keys,
+                join_type,
+                condition,
+            } => {
+                let name = match self {
+                    PhysicalPlan::HashJoin { .. } => "HashJoin",
+                    _ => "MergeJoin",
+                };
+                write!(f, "{}: {} on ", name, join_type)?;
+                for (position, (left_key, right_key)) in
+                    left_keys.iter().zip(right_keys).enumerate()
+                {
+                    if position > 0 {
+                        write!(f, " and ")?;
+                    }
+                    write!(f, "{} = {}", left_key, right_key)?;
+                }
+                if let Some(condition) = condition {
+                    write!(f, " filter {}", condition)?;
+                }
+                vec![left, right]
+            }
+            PhysicalPlan::Insert { table, input } => {
+                write!(f, "Insert: {}", Ident(&table.name))?;
+                vec![input]
+            }
+            PhysicalPlan::Update {
+                table,
+                input,
+                assignments,
+            } => {
+                write!(f, "Update: {}", Ident(&table.name))?;
+                let columns = table.schema.columns();
+                for (index, (column, value)) in assignments.iter().enumerate() {
+                    write!(
+                        f,
+                        "{}{} = {}",
+                        if index > 0 { ", " } else { " " },
+                        Ident(&columns[*column].name),
+                        value
+                    )?;
+                }
+                vec![input]
+            }
+            PhysicalPlan::Delete { table, input } => {
+                write!(f, "Delete: {}", Ident(&table.name))?;
+                vec![input]
+            }
+            // The logical plan display already ends with a newline.
+            PhysicalPlan::Ddl(plan) => return write!(f, "{}", plan),
+        };
+        writeln!(f)?;
+        for input in inputs {
+            input.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// A condition comparing the first indexed columns with bound values, e.g. `(a, b) > (1, 2)`.
+fn bound_condition(table: &TableDef, index: &IndexDef, op: &str, values: &[Datum]) -> String {
+    let columns = table.schema.columns();
+    let names: Vec<_> = index.columns[..values.len()]
+        .iter()
+        .map(|&column| Ident(&columns[column].name))
+        .collect();
+    let values: Vec<_> = index.columns[..values.len()]
+        .iter()
+        .zip(values)
+        .map(|(&column, value)| Expr::Constant {
+            value: value.clone(),
+            ty: columns[column].ty,
+        })
+        .collect();
+    if values.len() == 1 {
+        format!("{} {} {}", names[0], op, values[0])
+    } else {
+        format!("({}) {} ({})", List(&names), op, List(&values))
+    }
+}
+
+impl fmt::Display for PhysicalPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
@@ -0,0 +1,1300 @@
+//! Turns bound logical plans into physical plans.
+//!
+//! Filters are split into conjuncts, which are pushed down as far as they go: through joins, and
+//! into scans. There, conjuncts comparing a prefix of an index's columns with constants (equality
+//! on the first columns, and a range on the next one) become the bounds of an index scan.
+//!
+//! Inner joins are reordered: the tables joined by a tree of inner joins are joined one at a time,
+//! in the order found by dynamic programming over sets of tables, avoiding cross joins where
+//! possible. Each join uses the cheapest of a nested loop, index nested loop, hash or merge join.
+//! A sort is left out when its input is already ordered by the keys, e.g. by an index scan.
+//!
+//! Costs are estimated from the number of rows and pages read, using the statistics collected by
+//! `ANALYZE` (see `statistics`): row and page counts, numbers of distinct values, and the most
+//! common values and histograms of columns compared with constants. Tables which weren't analyzed
+//! are assumed to have `DEFAULT_TABLE_ROWS` rows, with `DEFAULT_NUM_DISTINCT` distinct values in
+//! each column, or all distinct for columns with a unique index.
+
+use crate::ast::BinaryOp;
+use crate::catalog::{Catalog, IndexDef, Result, TableDef};
+use crate::eval::eval;
+use crate::logical_plan::{Expr, JoinType, LogicalPlan};
+use crate::physical_plan::PhysicalPlan;
+use crate::statistics::{ColumnStats, TableStats};
+use std::collections::HashMap;
+use std::ops::Bound;
+use table::datum::{Datum, Nullability, Type};
+
+const DEFAULT_TABLE_ROWS: f64 = 1000.0;
+const DEFAULT_NUM_DISTINCT: f64 = 200.0;
+const DEFAULT_ROWS_PER_PAGE: f64 = 10.0;
+const DEFAULT_INDEX_HEIGHT: u32 = 4;
+/// Fraction of rows for which a `<`, `<=`, `>` or `>=` comparison is true.
+const COMPARISON_SELECTIVITY: f64 = 1.0 / 3.0;
+/// Fraction of rows for which other predicates are true.
+const DEFAULT_SELECTIVITY: f64 = 0.5;
+
+/// Cost of reading a page in a sequential scan.
+const SEQ_PAGE_COST: f64 = 10.0;
+/// Cost of descending one level of an index, when looking up a key.
+const INDEX_LEVEL_COST: f64 = 1.0;
+/// Cost of reading a row found in an index, which is anywhere in the table.
+const INDEX_ROW_COST: f64 = 1.5;
+/// Cost of processing a row, e.g. evaluating a predicate.
+const CPU_ROW_COST: f64 = 0.1;
+/// Cost of adding a row to a hash table.
+const HASH_ROW_COST: f64 = 0.2;
+/// Cost of writing a row to a table.
+const WRITE_ROW_COST: f64 = 1.0;
+
+/// Inner joins of up to this many tables are reordered.
+const MAX_REORDERED_TABLES: usize = 10;
+
+/// Plan the execution of a bound statement.
+pub async fn plan(catalog: &Catalog<'_>, plan: &LogicalPlan) -> Result<PhysicalPlan> {
+    let mut tables = vec![];
+    referenced_tables(plan, &mut tables);
+    tables.sort_by(|a, b| a.name.cmp(&b.name));
+    tables.dedup_by(|a, b| a.name == b.name);
+    let mut indexes = HashMap::new();
+    let mut statistics = HashMap::new();
+    for table in tables {
+        indexes.insert(table.name.clone(), catalog.list_indexes(&table.name).await?);
+        if let Some(stats) = catalog.get_statistics(table).await? {
+            statistics.insert(table.name.clone(), stats);
+        }
+    }
+    Ok(Planner {
+        indexes,
+        statistics,
+    }
+    .plan(plan, &[])
+    .plan)
+}
+
+/// The tables read or modified by the plan.
+fn referenced_tables<'p>(plan: &'p LogicalPlan, tables: &mut Vec<&'p TableDef>) {
+    match plan {
+        LogicalPlan::Scan { table } => tables.push(table),
+        LogicalPlan::Insert { table, input }
+        | LogicalPlan::Update { table, input, .. }
+        | LogicalPlan::Delete { table, input } => {
+            tables.push(table);
+            referenced_tables(input, tables);
+        }
+        LogicalPlan::Join { left, right, .. } => {
+            referenced_tables(left, tables);
+            referenced_tables(right, tables);
+        }
+        LogicalPlan::Filter { input, .. }
+        | LogicalPlan::Project { input, .. }
+        | LogicalPlan::Aggregate { input, .. }
+        | LogicalPlan::Sort { input, .. }
+        | LogicalPlan::Limit { input, .. }
+        | LogicalPlan::Explain { input } => referenced_tables(input, tables),
+        LogicalPlan::Values { .. }
+        | LogicalPlan::CreateTable { .. }
+        | LogicalPlan::DropTable { .. }
+        | LogicalPlan::CreateIndex { .. }
+        | LogicalPlan::Analyze { .. } => {}
+    }
+}
+
+/// A physical plan, with estimates of its output.
+#[derive(Clone)]
+struct Planned {
+    plan: PhysicalPlan,
+    rows: f64,
+    cost: f64,
+    /// Number of distinct values of each column.
+    distinct: Vec<f64>,
+    /// Columns by which the rows are sorted (ascending, NULLs last), most significant first.
+    order: Vec<usize>,
+}
+
+impl Planned {
+    fn width(&self) -> usize {
+        self.distinct.len()
+    }
+}
+
+/// A table scan (with predicates on its columns) which is an input of a join. An index nested
+/// loop join can look up its rows in an index, and check the predicates itself.
+type ScanInput<'p> = (&'p TableDef, &'p [Expr]);
+
+/// One of the tables joined by a tree of inner joins.
+struct Relation<'p> {
+    planned: Planned,
+    scan: Option<(&'p TableDef, Vec<Expr>)>,
+    /// Index of the first column in the output of the joins, as written.
+    offset: usize,
+}
+
+/// Tables joined so far, in order.
+struct JoinState {
+    planned: Planned,
+    relations: Vec<usize>,
+    /// Bitmask of `relations`. There are at most `binder::MAX_FROM_TABLES` relations.
+    set: u32,
+}
+
+struct Planner {
+    /// Indexes of each table used in the plan.
+    indexes: HashMap<String, Vec<IndexDef>>,
+    /// Statistics of the tables used in the plan which were analyzed.
+    statistics: HashMap<String, TableStats>,
+}
+
+impl Planner {
+    /// Plan a node. `order` gives the columns by which it's useful for the output to be sorted.
+    fn plan(&self, plan: &LogicalPlan, order: &[usize]) -> Planned {
+        match plan {
+            LogicalPlan::Values { columns, rows } => Planned {
+                plan: PhysicalPlan::Values {
+                    columns: columns.clone(),
+                    rows: rows.clone(),
+                },
+                rows: rows.len() as f64,
+                cost: CPU_ROW_COST * rows.len() as f64,
+                distinct: vec![rows.len() as f64; columns.len()],
+                order: vec![],
+            },
+            LogicalPlan::Scan { .. } | LogicalPlan::Filter { .. } | LogicalPlan::Join { .. } => {
+                self.plan_filtered(plan, vec![], order)
+            }
+            LogicalPlan::Project {
+                input,
+                exprs,
+                columns,
+            } => {
+                let mut input = self.plan(input, &[]);
+                let mut exprs = exprs.clone();
+                // A projection which only reorders columns (e.g. after reordering joins) is
+                // merged into this one.
+                if let PhysicalPlan::Project {
+                    input: inner_input,
+                    exprs: inner_exprs,
+                    ..
+                } = &input.plan
+                {
+                    let inner_columns: Option<Vec<usize>> = inner_exprs
+                        .iter()
+                        .map(|expr| match expr {
+                            Expr::Column { index, .. } => Some(*index),
+                            _ => None,
+                        })
+                        .collect();
+                    if let Some(inner_columns) = inner_columns {
+                        exprs = exprs
+                            .into_iter()
+                            .map(|expr| expr.map_columns(&mut |column| inner_columns[column]))
+                            .collect();
+                        input.order =
+                            map_order(&input.order, |column| inner_columns.get(column).copied());
+                        input.distinct = (0..inner_input.columns().len())
+                            .map(|column| {
+                                inner_columns
+                                    .iter()
+                                    .position(|&c| c == column)
+                                    .map_or(DEFAULT_NUM_DISTINCT, |p| input.distinct[p])
+                            })
+                            .collect();
+                        input.plan = (**inner_input).clone();
+                    }
+                }
+                let position = |column| {
+                    exprs.iter().position(|expr| match expr {
+                        Expr::Column { index, .. } => *index == column,
+                        _ => false,
+                    })
+                };
+                let order = map_order(&input.order, position);
+                let distinct = exprs
+                    .iter()
+                    .map(|expr| match expr {
+                        Expr::Column { index, .. } => input.distinct[*index],
+                        _ => DEFAULT_NUM_DISTINCT.min(input.rows),
+                    })
+                    .collect();
+                Planned {
+                    rows: input.rows,
+                    cost: input.cost + CPU_ROW_COST * input.rows,
+                    distinct,
+                    order,
+                    plan: PhysicalPlan::Project {
+                        input: Box::new(input.plan),
+                        exprs,
+                        columns: columns.clone(),
+                    },
+                }
+            }
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                aggregates,
+            } => {
+                let input = self.plan(input, &[]);
+                let rows = if group_by.is_empty() {
+                    1.0
+                } else {
+                    DEFAULT_NUM_DISTINCT.min(input.rows)
+                };
+                Planned {
+                    rows,
+                    cost: input.cost + CPU_ROW_COST * input.rows,
+                    distinct: vec![rows; group_by.len() + aggregates.len()],
+                    order: vec![],
+                    plan: PhysicalPlan::HashAggregate {
+                        input: Box::new(input.plan),
+                        group_by: group_by.clone(),
+                        aggregates: aggregates.clone(),
+                    },
+                }
+            }
+            LogicalPlan::Sort { input, keys } => {
+                // Keys which are all input columns, ascending, can be provided by the input.
+                let key_columns: Option<Vec<usize>> = keys
+                    .iter()
+                    .map(|key| match key.expr {
+                        Expr::Column { index, .. } if !key.descending => Some(index),
+                        _ => None,
+                    })
+                    .collect();
+                let key_columns = key_columns.unwrap_or_default();
+                let input = self.plan(input, &key_columns);
+                if !key_columns.is_empty() && input.order.starts_with(&key_columns) {
+                    return input;
+                }
+                Planned {
+                    cost: input.cost + sort_cost(input.rows),
+                    order: key_columns,
+                    plan: PhysicalPlan::Sort {
+                        input: Box::new(input.plan),
+                        keys: keys.clone(),
+                    },
+                    ..input
+                }
+            }
+            LogicalPlan::Limit {
+                input,
+                limit,
+                offset,
+            } => {
+                let input = self.plan(input, order);
+                let rows = match limit {
+                    Some(limit) => input.rows.min(*limit as f64),
+                    None => input.rows,
+                };
+                Planned {
+                    rows,
+                    plan: PhysicalPlan::Limit {
+                        input: Box::new(input.plan),
+                        limit: *limit,
+                        offset: *offset,
+                    },
+                    ..input
+                }
+            }
+            LogicalPlan::Insert { table, input } => {
+                let input = self.plan(input, &[]);
+                modification(
+                    PhysicalPlan::Insert {
+                        table: table.clone(),
+                        input: Box::new(input.plan),
+                    },
+                    input.rows,
+                    input.cost,
+                )
+            }
+            LogicalPlan::Update {
+                table,
+                input,
+                assignments,
+            } => {
+                let input = self.plan(input, &[]);
+                modification(
+                    PhysicalPlan::Update {
+                        table: table.clone(),
+                        input: Box::new(input.plan),
+                        assignments: assignments.clone(),
+                    },
+                    input.rows,
+                    input.cost,
+                )
+            }
+            LogicalPlan::Delete { table, input } => {
+                let input = self.plan(input, &[]);
+                modification(
+                    PhysicalPlan::Delete {
+                        table: table.clone(),
+                        input: Box::new(input.plan),
+                    },
+                    input.rows,
+                    input.cost,
+                )
+            }
+            LogicalPlan::CreateTable { .. }
+            | LogicalPlan::DropTable { .. }
+            | LogicalPlan::CreateIndex { .. }
+            | LogicalPlan::Analyze { .. } => Planned {
+                plan: PhysicalPlan::Ddl(plan.clone()),
+                rows: 0.0,
+                cost: 0.0,
+                distinct: vec![],
+                order: vec![],
+            },
+            LogicalPlan::Explain { input } => {
+                let input = self.plan(input, &[]);
+                let rows: Vec<Vec<Expr>> = input
+                    .plan
+                    .to_string()
+                    .lines()
+                    .map(|line| {
+                        vec![Expr::Constant {
+                            value: Datum::String(line.to_string()),
+                            ty: Type::String,
+                        }]
+                    })
+                    .collect();
+                self.plan(
+                    &LogicalPlan::Values {
+                        columns: plan.columns(),
+                        rows,
+                    },
+                    &[],
+                )
+            }
+        }
+    }
+
+    /// Plan a node, filtered by the predicates (on its output columns).
+    fn plan_filtered(
+        &self,
+        plan: &LogicalPlan,
+        mut predicates: Vec<Expr>,
+        order: &[usize],
+    ) -> Planned {
+        match plan {
+            LogicalPlan::Filter { input, predicate } => {
+                predicates.extend(conjuncts(predicate.clone()));
+                self.plan_filtered(input, predicates, order)
+            }
+            LogicalPlan::Scan { table } => self.plan_scan(table, predicates, order),
+            LogicalPlan::Join {
+                join_type: JoinType::Inner,
+                ..
+            } => self.plan_inner_joins(plan, predicates),
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                condition,
+            } => self.plan_outer_join(left, right, *join_type, condition.as_ref(), predicates),
+            _ => filter(self.plan(plan, order), predicates, &[]),
+        }
+    }
+
+    /// Plan a scan of a table: a sequential scan, or an index scan.
+    fn plan_scan(&self, table: &TableDef, predicates: Vec<Expr>, order: &[usize]) -> Planned {
+        let seq_scan = Planned {
+            plan: PhysicalPlan::SeqScan {
+                table: table.clone(),
+            },
+            rows: self.table_rows(table),
+            cost: SEQ_PAGE_COST * self.table_pages(table) + CPU_ROW_COST * self.table_rows(table),
+            distinct: self.table_distinct(table),
+            order: vec![],
+        };
+        let column_stats = self.column_stats(table);
+        let mut candidates = vec![filter(seq_scan, predicates.clone(), column_stats)];
+        for index in self.table_indexes(table) {
+            if let Some(candidate) = self.plan_index_scan(table, index, &predicates, order) {
+                candidates.push(candidate);
+            }
+        }
+        // The cost of sorting the rows is added when they're not in a useful order.
+        let total_cost = |candidate: &Planned| {
+            if order.is_empty() || candidate.order.starts_with(order) {
+                candidate.cost
+            } else {
+                candidate.cost + sort_cost(candidate.rows)
+            }
+        };
+        cheapest(candidates, total_cost)
+    }
+
+    /// Plan a scan of an index, with bounds given by the predicates on a prefix of its columns.
+    /// Returns None if no predicates apply, and the index order isn't useful either.
+    fn plan_index_scan(
+        &self,
+        table: &TableDef,
+        index: &IndexDef,
+        predicates: &[Expr],
+        order: &[usize],
+    ) -> Option<Planned> {
+        let columns = table.schema.columns();
+        let mut used = vec![false; predicates.len()];
+        let find_bound = |column: usize, ops: &[BinaryOp], used: &mut Vec<bool>| {
+            let found = predicates
+                .iter()
+                .enumerate()
+                .find_map(|(position, predicate)| {
+                    if used[position] {
+                        return None;
+                    }
+                    let (op, value) = compared_constant(predicate, column)?;
+                    if ops.contains(&op) {
+                        Some((position, op, value))
+                    } else {
+                        None
+                    }
+                });
+            if let Some((position, _, _)) = found {
+                used[position] = true;
+            }
+            found
+        };
+
+        // Equality on the first columns.
+        let mut prefix = vec![];
+        for &column in &index.columns {
+            match find_bound(column, &[BinaryOp::Eq], &mut used) {
+                Some((_, _, value)) => prefix.push(value),
+                None => break,
+            }
+        }
+        // A range of the next column.
+        let mut lower = None;
+        let mut upper = None;
+        if let Some(&column) = index.columns.get(prefix.len()) {
+            lower = find_bound(column, &[BinaryOp::Gt, BinaryOp::GtEq], &mut used);
+            upper = find_bound(column, &[BinaryOp::Lt, BinaryOp::LtEq], &mut used);
+            // NULLs sort after all values, so a lower bound alone doesn't exclude them.
+            if let (Some((position, _, _)), None) = (&lower, &upper) {
+                if columns[column].nullability == Nullability::Nullable {
+                    used[*position] = false;
+                }
+            }
+        }
+        let index_order = index.columns[prefix.len()..].to_vec();
+        let bounded = !prefix.is_empty() || lower.is_some() || upper.is_some();
+        if !bounded && (order.is_empty() || !index_order.starts_with(order)) {
+            return None;
+        }
+
+        let bound = |bound: Option<(usize, BinaryOp, Datum)>, inclusive: BinaryOp| match bound {
+            Some((_, op, value)) => {
+                let values = [prefix.clone(), vec![value]].concat();
+                if op == inclusive {
+                    Bound::Included(values)
+                } else {
+                    Bound::Excluded(values)
+                }
+            }
+            None if prefix.is_empty() => Bound::Unbounded,
+            None => Bound::Included(prefix.clone()),
+        };
+        let lower_position = lower.as_ref().map(|(position, _, _)| *position);
+        let bound_predicates: Vec<usize> = (0..predicates.len())
+            .filter(|&position| used[position] || lower_position == Some(position))
+            .collect();
+        let plan = PhysicalPlan::IndexScan {
+            table: table.clone(),
+            index: index.clone(),
+            start: bound(lower.clone(), BinaryOp::GtEq),
+            end: bound(upper, BinaryOp::LtEq),
+        };
+
+        let distinct = self.table_distinct(table);
+        let column_stats = self.column_stats(table);
+        let rows = if index.unique && prefix.len() == index.columns.len() {
+            1.0
+        } else {
+            let mut rows = self.table_rows(table);
+            for &position in &bound_predicates {
+                rows *= selectivity(&predicates[position], &distinct, column_stats);
+            }
+            rows
+        };
+        let residual = predicates
+            .iter()
+            .zip(used)
+            .filter(|(_, used)| !used)
+            .map(|(predicate, _)| predicate.clone())
+            .collect();
+        let scan = Planned {
+            plan,
+            rows,
+            cost: self.index_lookup_cost(table, index) + INDEX_ROW_COST * rows,
+            distinct: cap(distinct, rows),
+            order: index_order,
+        };
+        Some(filter(scan, residual, column_stats))
+    }
+
+    /// Plan a tree of inner joins (and filters), with the predicates on its output columns.
+    fn plan_inner_joins(&self, plan: &LogicalPlan, predicates: Vec<Expr>) -> Planned {
+        let mut inputs = vec![];
+        let mut join_predicates = predicates;
+        flatten_inner_joins(plan, 0, &mut inputs, &mut join_predicates);
+        let columns = plan.columns();
+        let relation_of: Vec<usize> = (0..inputs.len())
+            .flat_map(|relation| {
+                let (input, _) = inputs[relation];
+                vec![relation; input.columns().len()]
+            })
+            .collect();
+        let relation_set = |predicate: &Expr| {
+            predicate
+                .referenced_columns()
+                .iter()
+                .fold(0u32, |set, &column| set | 1 << relation_of[column])
+        };
+
+        // Predicates on one table are pushed down to it. Ones without columns are evaluated
+        // with the first table.
+        let mut local_predicates = vec![vec![]; inputs.len()];
+        let mut predicates = vec![];
+        for predicate in join_predicates {
+            let set = relation_set(&predicate);
+            if set.count_ones() <= 1 {
+                let relation = if set == 0 {
+                    0
+                } else {
+                    set.trailing_zeros() as usize
+                };
+                let offset = inputs[relation].1;
+                local_predicates[relation]
+                    .push(predicate.map_columns(&mut |column| column - offset));
+            } else {
+                predicates.push((set, predicate));
+            }
+        }
+        let relations: Vec<Relation> = inputs
+            .iter()
+            .zip(local_predicates)
+            .map(|(&(input, offset), predicates)| Relation {
+                planned: self.plan_filtered(input, predicates.clone(), &[]),
+                scan: match input {
+                    LogicalPlan::Scan { table } => Some((table, predicates)),
+                    _ => None,
+                },
+                offset,
+            })
+            .collect();
+
+        let count = relations.len();
+        let initial = |relation: usize| JoinState {
+            planned: relations[relation].planned.clone(),
+            relations: vec![relation],
+            set: 1 << relation,
+        };
+        let state = if count <= MAX_REORDERED_TABLES {
+            // The cheapest way to join each set of tables, from the smallest sets.
+            let mut best: HashMap<u32, JoinState> = HashMap::new();
+            for relation in 0..count {
+                best.insert(1 << relation, initial(relation));
+            }
+            for size in 2..=count as u32 {
+                for set in 1u32..1 << count {
+                    if set.count_ones() != size {
+                        continue;
+                    }
+                    // The last table is tried first, so that on ties, tables are joined in
+                    // the order they were written.
+                    let candidates: Vec<(JoinState, bool)> = (0..count)
+                        .rev()
+                        .filter(|relation| set & 1 << relation != 0)
+                        .filter_map(|relation| {
+                            let state = best.get(&(set & !(1 << relation)))?;
+                            Some(self.join_step(state, relation, &relations, &predicates))
+                        })
+                        .collect();
+                    // Cross joins are only used when there is no other way.
+                    let any_connected = candidates.iter().any(|(_, connected)| *connected);
+                    let candidates: Vec<JoinState> = candidates
+                        .into_iter()
+                        .filter(|(_, connected)| *connected || !any_connected)
+                        .map(|(state, _)| state)
+                        .collect();
+                    best.insert(set, cheapest(candidates, |state| state.planned.cost));
+                }
+            }
+            best.remove(&((1 << count) - 1)).unwrap()
+        } else {
+            (1..count).fold(initial(0), |state, relation| {
+                self.join_step(&state, relation, &relations, &predicates).0
+            })
+        };
+
+        // Restore the order of columns, if the tables were reordered.
+        if state
+            .relations
+            .iter()
+            .enumerate()
+            .all(|(i, &relation)| i == relation)
+        {
+            return state.planned;
+        }
+        let positions = column_positions(&state.relations, &relations, &relation_of);
+        let planned = state.planned;
+        let exprs = columns
+            .iter()
+            .zip(&positions)
+            .map(|(column, &position)| Expr::Column {
+                index: position,
+                name: column.name.clone(),
+                ty: column.ty,
+                nullability: column.nullability,
+            })
+            .collect();
+        let order = map_order(&planned.order, |position| {
+            positions.iter().position(|&p| p == position)
+        });
+        Planned {
+            rows: planned.rows,
+            cost: planned.cost + CPU_ROW_COST * planned.rows,
+            distinct: positions.iter().map(|&p| planned.distinct[p]).collect(),
+            order,
+            plan: PhysicalPlan::Project {
+                input: Box::new(planned.plan),
+                exprs,
+                columns,
+            },
+        }
+    }
+
+    /// Join one more table. Also returns whether any predicate connects it with the tables
+    /// joined so far.
+    fn join_step(
+        &self,
+        state: &JoinState,
+        relation: usize,
+        relations: &[Relation],
+        predicates: &[(u32, Expr)],
+    ) -> (JoinState, bool) {
+        let set = state.set | 1 << relation;
+        let mut joined = state.relations.clone();
+        joined.push(relation);
+        let relation_of = relation_columns(relations);
+        let positions = column_positions(&joined, relations, &relation_of);
+        // Predicates on the new table and the previous ones, not evaluated yet.
+        let applicable: Vec<Expr> = predicates
+            .iter()
+            .filter(|(predicate_set, _)| {
+                predicate_set & 1 << relation != 0
+                    && predicate_set & state.set != 0
+                    && predicate_set & !set == 0
+            })
+            .map(|(_, predicate)| {
+                predicate
+                    .clone()
+                    .map_columns(&mut |column| positions[column])
+            })
+            .collect();
+        let connected = !applicable.is_empty();
+        let right = &relations[relation];
+        let planned = self.plan_join(
+            state.planned.clone(),
+            right.planned.clone(),
+            right
+                .scan
+                .as_ref()
+                .map(|(table, predicates)| (*table, predicates.as_slice())),
+            JoinType::Inner,
+            applicable,
+        );
+        let state = JoinState {
+            planned,
+            relations: joined,
+            set,
+        };
+        (state, connected)
+    }
+
+    /// Plan a left (or semi) join, with the predicates on its output columns.
+    fn plan_outer_join(
+        &self,
+        left: &LogicalPlan,
+        right: &LogicalPlan,
+        join_type: JoinType,
+        condition: Option<&Expr>,
+        predicates: Vec<Expr>,
+    ) -> Planned {
+        let left_width = left.columns().len();
+        let only_left = |expr: &Expr| expr.referenced_columns().iter().all(|&c| c < left_width);
+        // Predicates on the left rows can filter them before the join, but the others would
+        // filter out rows with NULLs in the right columns, so they must be evaluated after it.
+        let (left_predicates, predicates): (Vec<Expr>, Vec<Expr>) =
+            predicates.into_iter().partition(only_left);
+        // Conditions on the right rows only can filter them before the join.
+        let (right_predicates, condition): (Vec<Expr>, Vec<Expr>) = condition
+            .cloned()
+            .map(conjuncts)
+            .unwrap_or_default()
+            .into_iter()
+            .partition(|expr| {
+                let columns = expr.referenced_columns();
+                !columns.is_empty() && columns.iter().all(|&c| c >= left_width)
+            });
+        let right_predicates: Vec<Expr> = right_predicates
+            .into_iter()
+            .map(|expr| expr.map_columns(&mut |column| column - left_width))
+            .collect();
+
+        let left = self.plan_filtered(left, left_predicates, &[]);
+        let right_scan = match right {
+            LogicalPlan::Scan { table } => Some((table, right_predicates.as_slice())),
+            _ => None,
+        };
+        let right = self.plan_filtered(right, right_predicates.clone(), &[]);
+        let joined = self.plan_join(left, right, right_scan, join_type, condition);
+        filter(joined, predicates, &[])
+    }
+
+    /// Plan a join with the cheapest algorithm. The predicates are on the left columns followed by
+    /// the right ones. `right_scan` is given if the right input is a table scan.
+    fn plan_join(
+        &self,
+        left: Planned,
+        right: Planned,
+        right_scan: Option<ScanInput>,
+        join_type: JoinType,
+        predicates: Vec<Expr>,
+    ) -> Planned {
+        let left_width = left.width();
+        let all_distinct = [left.distinct.clone(), right.distinct.clone()].concat();
+        let mut inner_rows = left.rows * right.rows;
+        for predicate in &predicates {
+            inner_rows *= selectivity(predicate, &all_distinct, &[]);
+        }
+        let rows = match join_type {
+            JoinType::Inner => inner_rows,
+            JoinType::Left => inner_rows.max(left.rows),
+            JoinType::Semi => inner_rows.min(left.rows),
+        };
+        let distinct = match join_type {
+            JoinType::Semi => left.distinct.clone(),
+            _ => all_distinct,
+        };
+
+        // Equality of a left and a right expression.
+        let key_pair = |predicate: &Expr| match predicate {
+            Expr::Binary {
+                op: BinaryOp::Eq,
+                left,
+                right,
+            } => {
+                let side = |expr: &Expr| {
+                    let columns = expr.referenced_columns();
+                    if columns.is_empty() {
+                        None
+                    } else if columns.iter().all(|&column| column < left_width) {
+                        Some(true)
+                    } else if columns.iter().all(|&column| column >= left_width) {
+                        Some(false)
+                    } else {
+                        None
+                    }
+                };
+                let to_right = |expr: &Expr| expr.clone().map_columns(&mut |c| c - left_width);
+                match (side(left), side(right)) {
+                    (Some(true), Some(false)) => Some(((**left).clone(), to_right(right))),
+                    (Some(false), Some(true)) => Some(((**right).clone(), to_right(left))),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let pairs: Vec<Option<(Expr, Expr)>> = predicates.iter().map(key_pair).collect();
+        // The predicates other than the key pairs used, as a condition.
+        let condition_without = |used: &[usize]| {
+            conjunction(
+                predicates
+                    .iter()
+                    .enumerate()
+                    .filter(|(position, _)| !used.contains(position))
+                    .map(|(_, predicate)| predicate.clone())
+                    .collect(),
+            )
+        };
+        let join = |plan, cost, order| Planned {
+            plan,
+            rows,
+            cost,
+            distinct: cap(distinct.clone(), rows),
+            order,
+        };
+
+        let mut candidates = vec![join(
+            PhysicalPlan::NestedLoopJoin {
+                left: Box::new(left.plan.clone()),
+                right: Box::new(right.plan.clone()),
+                join_type,
+                condition: conjunction(predicates.clone()),
+            },
+            left.cost + right.cost + CPU_ROW_COST * left.rows * right.rows,
+            left.order.clone(),
+        )];
+
+        let key_positions: Vec<usize> = (0..pairs.len()).filter(|&p| pairs[p].is_some()).collect();
+        if !key_positions.is_empty() {
+            let (left_keys, right_keys): (Vec<Expr>, Vec<Expr>) = key_positions
+                .iter()
+                .map(|&position| pairs[position].clone().unwrap())
+                .unzip();
+            candidates.push(join(
+                PhysicalPlan::HashJoin {
+                    left: Box::new(left.plan.clone()),
+                    right: Box::new(right.plan.clone()),
+                    left_keys,
+                    right_keys,
+                    join_type,
+                    condition: condition_without(&key_positions),
+                },
+                left.cost
+                    + right.cost
+                    + HASH_ROW_COST * right.rows
+                    + CPU_ROW_COST * (left.rows + rows),
+                vec![],
+            ));
+        }
+
+        // A merge join, if both inputs are sorted by the keys.
+        let mut merge_positions = vec![];
+        for (&left_column, &right_column) in left.order.iter().zip(&right.order) {
+            let is_column = |expr: &Expr, column| match expr {
+                Expr::Column { index, .. } => *index == column,
+                _ => false,
+            };
+            let found = pairs.iter().position(|pair| match pair {
+                Some((l, r)) => is_column(l, left_column) && is_column(r, right_column),
+                None => false,
+            });
+            match found {
+                Some(position) if !merge_positions.contains(&position) => {
+                    merge_positions.push(position)
+                }
+                _ => break,
+            }
+        }
+        if !merge_positions.is_empty() {
+            let (left_keys, right_keys): (Vec<Expr>, Vec<Expr>) = merge_positions
+                .iter()
+                .map(|&position| pairs[position].clone().unwrap())
+                .unzip();
+            candidates.push(join(
+                PhysicalPlan::MergeJoin {
+                    left: Box::new(left.plan.clone()),
+                    right: Box::new(right.plan.clone()),
+                    left_keys,
+                    right_keys,
+                    join_type,
+                    condition: condition_without(&merge_positions),
+                },
+                left.cost + right.cost + CPU_ROW_COST * (left.rows + right.rows + rows),
+                left.order.clone(),
+            ));
+        }
+
+        // An index nested loop join, looking up right rows by a prefix of an index's columns.
+        if let Some((table, table_predicates)) = right_scan {
+            let table_distinct = self.table_distinct(table);
+            for index in self.table_indexes(table) {
+                let mut used = vec![];
+                for &column in &index.columns {
+                    let found = pairs.iter().position(|pair| match pair {
+                        Some((_, Expr::Column { index, .. })) => *index == column,
+                        _ => false,
+                    });
+                    match found {
+                        Some(position) => used.push(position),
+                        None => break,
+                    }
+                }
+                if used.is_empty() {
+                    continue;
+                }
+                let rows_per_lookup = if index.unique && used.len() == index.columns.len() {
+                    1.0
+                } else {
+                    index.columns[..used.len()]
+                        .iter()
+                        .fold(self.table_rows(table), |rows, &column| {
+                            rows / table_distinct[column]
+                        })
+                };
+                let table_predicates = table_predicates
+                    .iter()
+                    .map(|predicate| predicate.clone().map_columns(&mut |c| c + left_width));
+                let condition = conjunction(
+                    condition_without(&used)
+                        .into_iter()
+                        .chain(table_predicates)
+                        .collect(),
+                );
+                candidates.push(join(
+                    PhysicalPlan::IndexNestedLoopJoin {
+                        left: Box::new(left.plan.clone()),
+                        table: table.clone(),
+                        index: index.clone(),
+                        left_keys: used
+                            .iter()
+                            .map(|&position| pairs[position].clone().unwrap().0)
+                            .collect(),
+                        join_type,
+                        condition,
+                    },
+                    left.cost
+                        + left.rows
+                            * (self.index_lookup_cost(table, index)
+                                + INDEX_ROW_COST * rows_per_lookup)
+                        + CPU_ROW_COST * rows,
+                    left.order.clone(),
+                ));
+            }
+        }
+        cheapest(candidates, |candidate| candidate.cost)
+    }
+
+    fn table_indexes(&self, table: &TableDef) -> &[IndexDef] {
+        self.indexes
+            .get(&table.name)
+            .map_or(&[], |indexes| indexes.as_slice())
+    }
+
+    fn table_rows(&self, table: &TableDef) -> f64 {
+        match self.statistics.get(&table.name) {
+            // An empty table may not stay empty; a single row is a safer guess.
+            Some(stats) => (stats.rows as f64).max(1.0),
+            None => DEFAULT_TABLE_ROWS,
+        }
+    }
+
+    fn table_pages(&self, table: &TableDef) -> f64 {
+        match self.statistics.get(&table.name) {
+            Some(stats) => stats.pages as f64,
+            None => (DEFAULT_TABLE_ROWS / DEFAULT_ROWS_PER_PAGE).ceil(),
+        }
+    }
+
+    /// Statistics of the columns of a table, or nothing if it wasn't analyzed.
+    fn column_stats(&self, table: &TableDef) -> &[ColumnStats] {
+        self.statistics
+            .get(&table.name)
+            .map_or(&[], |stats| stats.columns.as_slice())
+    }
+
+    /// Number of distinct values in each column of a table.
+    fn table_distinct(&self, table: &TableDef) -> Vec<f64> {
+        let rows = self.table_rows(table);
+        let column_stats = self.column_stats(table);
+        (0..table.schema.len())
+            .map(|column| {
+                let unique = self
+                    .table_indexes(table)
+                    .iter()
+                    .any(|index| index.unique && index.columns == [column]);
+                if unique {
+                    rows
+                } else if let Some(stats) = column_stats.get(column) {
+                    stats.distinct.min(rows).max(1.0)
+                } else {
+                    DEFAULT_NUM_DISTINCT.min(rows)
+                }
+            })
+            .collect()
+    }
+
+    /// Cost of finding a key in an index of the table, from the height of its btree.
+    fn index_lookup_cost(&self, table: &TableDef, index: &IndexDef) -> f64 {
+        let height = self
+            .statistics
+            .get(&table.name)
+            .and_then(|stats| stats.index(&index.name))
+            .map_or(DEFAULT_INDEX_HEIGHT, |stats| stats.height);
+        INDEX_LEVEL_COST * height as f64
+    }
+}
+
+/// Split a tree of inner joins into its inputs (with the index of their first column in the
+/// output) and the predicates on the output.
+fn flatten_inner_joins<'p>(
+    plan: &'p LogicalPlan,
+    offset: usize,
+    inputs: &mut Vec<(&'p LogicalPlan, usize)>,
+    predicates: &mut Vec<Expr>,
+) {
+    let shifted = |expr: &Expr| {
+        conjuncts(expr.clone())
+            .into_iter()
+            .map(|expr| expr.map_columns(&mut |column| column + offset))
+            .collect::<Vec<_>>()
+    };
+    match plan {
+        LogicalPlan::Join {
+            left,
+            right,
+            join_type: JoinType::Inner,
+            condition,
+        } => {
+            flatten_inner_joins(left, offset, inputs, predicates);
+            flatten_inner_joins(right, offset + left.columns().len(), inputs, predicates);
+            if let Some(condition) = condition {
+                predicates.extend(shifted(condition));
+            }
+        }
+        LogicalPlan::Filter { input, predicate } => {
+            flatten_inner_joins(input, offset, inputs, predicates);
+            predicates.extend(shifted(predicate));
+        }
+        _ => inputs.push((plan, offset)),
+    }
+}
+
+/// The table each column of the joined tables (as written) belongs to.
+fn relation_columns(relations: &[Relation]) -> Vec<usize> {
+    (0..relations.len())
+        .flat_map(|relation| vec![relation; relations[relation].planned.width()])
+        .collect()
+}
+
+/// Positions of the columns of the joined tables (as written), when joined in the given order.
+/// Columns of other tables are left at 0.
+fn column_positions(order: &[usize], relations: &[Relation], relation_of: &[usize]) -> Vec<usize> {
+    let mut start = vec![None; relations.len()];
+    let mut width = 0;
+    for &relation in order {
+        start[relation] = Some(width);
+        width += relations[relation].planned.width();
+    }
+    relation_of
+        .iter()
+        .enumerate()
+        .map(|(column, &relation)| {
+            start[relation].map_or(0, |start| start + column - relations[relation].offset)
+        })
+        .collect()
+}
+
+/// The columns of `order` mapped by `f`, up to the first one without a mapping.
+fn map_order(order: &[usize], f: impl Fn(usize) -> Option<usize>) -> Vec<usize> {
+    let mut result = vec![];
+    for &column in order {
+        match f(column) {
+            Some(column) => result.push(column),
+            None => break,
+        }
+    }
+    result
+}
+
+/// The predicates applied to the input, if there are any. `column_stats` are the statistics of the
+/// input's columns, if it's a scan of an analyzed table.
+fn filter(input: Planned, predicates: Vec<Expr>, column_stats: &[ColumnStats]) -> Planned {
+    let predicate = match conjunction(predicates) {
+        Some(predicate) => predicate,
+        None => return input,
+    };
+    let rows = input.rows * selectivity(&predicate, &input.distinct, column_stats);
+    Planned {
+        plan: PhysicalPlan::Filter {
+            input: Box::new(input.plan),
+            predicate,
+        },
+        rows,
+        cost: input.cost + CPU_ROW_COST * input.rows,
+        distinct: cap(input.distinct, rows),
+        order: input.order,
+    }
+}
+
+/// A statement modifying a table, returning a single row.
+fn modification(plan: PhysicalPlan, rows: f64, cost: f64) -> Planned {
+    Planned {
+        plan,
+        rows: 1.0,
+        cost: cost + WRITE_ROW_COST * rows,
+        distinct: vec![1.0],
+        order: vec![],
+    }
+}
+
+fn sort_cost(rows: f64) -> f64 {
+    CPU_ROW_COST * rows * rows.max(2.0).log2()
+}
+
+/// Numbers of distinct values, when there are only `rows` rows.
+fn cap(distinct: Vec<f64>, rows: f64) -> Vec<f64> {
+    distinct
+        .into_iter()
+        .map(|distinct| distinct.min(rows).max(1.0))
+        .collect()
+}
+
+/// The first candidate with the lowest cost.
+fn cheapest<T>(candidates: Vec<T>, cost: impl Fn(&T) -> f64) -> T {
+    candidates
+        .into_iter()
+        .fold(None, |best: Option<(f64, T)>, candidate| {
+            let candidate_cost = cost(&candidate);
+            match best {
+                Some((best_cost, best)) if best_cost <= candidate_cost => Some((best_cost, best)),
+                _ => Some((candidate_cost, candidate)),
+            }
+        })
+        .expect("no candidate plans")
+        .1
+}
+
+/// Estimated fraction of rows for which the predicate is true, given the number of distinct
+/// values of each column, and the statistics of the columns if they're known.
+fn selectivity(predicate: &Expr, distinct: &[f64], column_stats: &[ColumnStats]) -> f64 {
+    let distinct_values = |expr: &Expr| match expr {
+        Expr::Column { index, .. } => distinct[*index].max(1.0),
+        _ => DEFAULT_NUM_DISTINCT,
+    };
+    if let Some((column, op, value)) = constant_comparison(predicate) {
+        if let Some(stats) = column_stats.get(column) {
+            return comparison_selectivity(stats, op, &value);
+        }
+    }
+    match predicate {
+        Expr::Constant {
+            value: Datum::Bool(true),
+            ..
+        } => 1.0,
+        Expr::Constant { .. } => 0.0,
+        Expr::Binary {
+            op: BinaryOp::And,
+            left,
+            right,
+        } => selectivity(left, distinct, column_stats) * selectivity(right, distinct, column_stats),
+        Expr::Binary {
+            op: BinaryOp::Or,
+            left,
+            right,
+        } => {
+            let left = selectivity(left, distinct, column_stats);
+            let right = selectivity(right, distinct, column_stats);
+            left + right - left * right
+        }
+        Expr::Binary {
+            op: BinaryOp::Eq,
+            left,
+            right,
+        } => 1.0 / distinct_values(left).max(distinct_values(right)),
+        Expr::Binary {
+            op: BinaryOp::Lt, ..
+        }
+        | Expr::Binary {
+            op: BinaryOp::LtEq, ..
+        }
+        | Expr::Binary {
+            op: BinaryOp::Gt, ..
+        }
+        | Expr::Binary {
+            op: BinaryOp::GtEq, ..
+        } => COMPARISON_SELECTIVITY,
+        Expr::IsNull { expr, negated } => match &**expr {
+            Expr::Column { index, .. } if *index < column_stats.len() => {
+                let null_fraction = column_stats[*index].null_fraction;
+                if *negated {
+                    1.0 - null_fraction
+                } else {
+                    null_fraction
+                }
+            }
+            _ => DEFAULT_SELECTIVITY,
+        },
+        _ => DEFAULT_SELECTIVITY,
+    }
+}
+
+/// Estimated fraction of rows for which comparing the column with the value is true.
+fn comparison_selectivity(stats: &ColumnStats, op: BinaryOp, value: &Datum) -> f64 {
+    let not_null = 1.0 - stats.null_fraction;
+    // Rounding errors may make the difference slightly negative.
+    let fraction = match op {
+        BinaryOp::Eq => stats.equal_fraction(value),
+        BinaryOp::Lt => stats.less_fraction(value, false),
+        BinaryOp::LtEq => stats.less_fraction(value, true),
+        BinaryOp::Gt => not_null - stats.less_fraction(value, true),
+        BinaryOp::GtEq => not_null - stats.less_fraction(value, false),
+        _ => unreachable!("not a comparison: {:?}", op),
+    };
+    fraction.max(0.0)
+}
+
+/// If the predicate compares the column with a constant, the comparison (with the column on the
+/// left) and the value. The value must have the column's type, and not be NULL.
+fn compared_constant(predicate: &Expr, column: usize) -> Option<(BinaryOp, Datum)> {
+    match constant_comparison(predicate) {
+        Some((compared, op, value)) if compared == column => Some((op, value)),
+        _ => None,
+    }
+}
+
+/// If the predicate compares a column with a constant, the column, the comparison (with the column
+/// on the left) and the value, as for `compared_constant`.
+fn constant_comparison(predicate: &Expr) -> Option<(usize, BinaryOp, Datum)> {
+    let (op, left, right) = match predicate {
+        Expr::Binary { op, left, right } => (*op, left, right),
+        _ => return None,
+    };
+    let (op, column, value) = match (&**left, &**right) {
+        (Expr::Column { .. }, value) => (op, left, value),
+        (value, Expr::Column { .. }) => {
+            let flipped = match op {
+                BinaryOp::Lt => BinaryOp::Gt,
+                BinaryOp::LtEq => BinaryOp::GtEq,
+                BinaryOp::Gt => BinaryOp::Lt,
+                BinaryOp::GtEq => BinaryOp::LtEq,
+                op => op,
+            };
+            (flipped, right, value)
+        }
+        _ => return None,
+    };
+    match op {
+        BinaryOp::Eq | BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq => {}
+        _ => return None,
+    }
+    let (column, ty) = match &**column {
+        Expr::Column { index, ty, .. } => (*index, *ty),
+        _ => unreachable!(),
+    };
+    if value.ty() != ty || !value.referenced_columns().is_empty() {
+        return None;
+    }
+    match eval(value, &[]) {
+        Ok(Datum::Null) | Err(_) => None,
+        Ok(value) => Some((column, op, value)),
+    }
+}
+
+/// Split a predicate into the conjuncts of its top-level `AND`s.
+fn conjuncts(predicate: Expr) -> Vec<Expr> {
+    match predicate {
+        Expr::Binary {
+            op: BinaryOp::And,
+            left,
+            right,
+        } => [conjuncts(*left), conjuncts(*right)].concat(),
+        predicate => vec![predicate],
+    }
+}
+
+/// The `AND` of the predicates, if there are any.
+fn conjunction(predicates: Vec<Expr>) -> Option<Expr> {
+    predicates
+        .into_iter()
+        .fold(None, |result, predicate| match result {
+            Some(left) => Some(Expr::Binary {
+                op: BinaryOp::And,
+                left: Box::new(left),
+                right: Box::new(predicate),
+            }),
+            None => Some(predicate),
+        })
+}
@@ -0,0 +1,309 @@
+use crate::binder::MAX_FROM_TABLES;
+use crate::catalog::Catalog;
+use crate::test_utils::run_all;
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager_mem::DiskManagerMem;
+
+async fn create_tables(catalog: &Catalog<'_>) {
+    run_all(
+        catalog,
+        &[
+            "create table t (id int primary key, a int, b int not null, c text)",
+            "create index t_a_b on t (a, b)",
+            "create index t_b on t (b)",
+            "create table u (id int primary key, t_id int, x int)",
+            "create index u_t_id on u (t_id)",
+            "create table v (id int primary key, u_id int, y text)",
+        ],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn scans() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    create_tables(&catalog).await;
+    assert_snapshot!(run_all(&catalog, &[
+        "explain select * from t",
+        "explain select * from t where id = 5",
+        "explain select * from t where 5 = id and c = 'x'",
+        "explain select * from t where a = 1 and b > 2",
+        "explain select * from t where a = 1 and b = 2 and id = 3",
+        "explain select * from t where b >= 1 and b < 10",
+        "explain select * from t where a > 5",
+        "explain select * from t where a < 5",
+        "explain select * from t where c = 'x'",
+        "explain select * from t where id = 1 or id = 2",
+        "explain select * from t where a + 1 = 2",
+        "explain select * from t where a = null",
+        "explain select * from t where id = 1 and false",
+        "explain update t set c = 'y' where id = 3",
+        "explain delete from t where a = 1 and b < 0",
+        "explain insert into t values (1, 2, 3, 'x')",
+        "explain create table w (a int)",
+    ]).await, @r###"
+    explain select * from t
+    Project: id, a, b, c
+      SeqScan: t
+    explain select * from t where id = 5
+    Project: id, a, b, c
+      IndexScan: t using t_pkey (id = 5)
+    explain select * from t where 5 = id and c = 'x'
+    Project: id, a, b, c
+      Filter: (c = 'x')
+        IndexScan: t using t_pkey (id = 5)
+    explain select * from t where a = 1 and b > 2
+    Project: id, a, b, c
+      IndexScan: t using t_a_b ((a, b) > (1, 2) and a <= 1)
+    explain select * from t where a = 1 and b = 2 and id = 3
+    Project: id, a, b, c
+      Filter: (id = 3)
+        IndexScan: t using t_a_b ((a, b) = (1, 2))
+    explain select * from t where b >= 1 and b < 10
+    Project: id, a, b, c
+      IndexScan: t using t_b (b >= 1 and b < 10)
+    explain select * from t where a > 5
+    Project: id, a, b, c
+      Filter: (a > 5)
+        IndexScan: t using t_a_b (a > 5)
+    explain select * from t where a < 5
+    Project: id, a, b, c
+      IndexScan: t using t_a_b (a < 5)
+    explain select * from t where c = 'x'
+    Project: id, a, b, c
+      Filter: (c = 'x')
+        SeqScan: t
+    explain select * from t where id = 1 or id = 2
+    Project: id, a, b, c
+      Filter: ((id = 1) OR (id = 2))
+        SeqScan: t
+    explain select * from t where a + 1 = 2
+    Project: id, a, b, c
+      Filter: ((a + 1) = 2)
+        SeqScan: t
+    explain select * from t where a = null
+    Project: id, a, b, c
+      Filter: (a = NULL)
+        SeqScan: t
+    explain select * from t where id = 1 and false
+    Project: id, a, b, c
+      Filter: FALSE
+        IndexScan: t using t_pkey (id = 1)
+    explain update t set c = 'y' where id = 3
+    Update: t c = 'y'
+      IndexScan: t using t_pkey (id = 3)
+    explain delete from t where a = 1 and b < 0
+    Delete: t
+      IndexScan: t using t_a_b (a >= 1 and (a, b) < (1, 0))
+    explain insert into t values (1, 2, 3, 'x')
+    Insert: t
+      Values: (1, 2, 3, 'x')
+    explain create table w (a int)
+    CreateTable: w (a INTEGER)
+    "###);
+}
+
+#[tokio::test]
+async fn sorts() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    create_tables(&catalog).await;
+    assert_snapshot!(run_all(&catalog, &[
+        "explain select b, c from t where a = 1 order by b",
+        "explain select * from t where a = 1 order by b desc",
+        "explain select * from t order by id",
+        "explain select * from t order by a, b limit 10",
+        "explain select * from t order by c",
+        "explain select a, count(*) from t group by a order by a",
+        "explain select * from t join u on u.id = t.id where t.id > 0 and u.id > 0 order by t.id",
+    ]).await, @r###"
+    explain select b, c from t where a = 1 order by b
+    Project: b, c
+      IndexScan: t using t_a_b (a = 1)
+    explain select * from t where a = 1 order by b desc
+    Project: id, a, b, c
+      Sort: b DESC
+        IndexScan: t using t_a_b (a = 1)
+    explain select * from t order by id
+    Project: id, a, b, c
+      IndexScan: t using t_pkey
+    explain select * from t order by a, b limit 10
+    Limit: 10
+      Project: id, a, b, c
+        IndexScan: t using t_a_b
+    explain select * from t order by c
+    Project: id, a, b, c
+      Sort: c
+        SeqScan: t
+    explain select a, count(*) from t group by a order by a
+    Project: a, "count(*)" AS count
+      Sort: a
+        HashAggregate: count(*) group by a
+          SeqScan: t
+    explain select * from t join u on u.id = t.id where t.id > 0 and u.id > 0 order by t.id
+    Project: id, a, b, c, id, t_id, x
+      MergeJoin: inner on id = id
+        IndexScan: t using t_pkey (id > 0)
+        IndexScan: u using u_pkey (id > 0)
+    "###);
+}
+
+#[tokio::test]
+async fn joins() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    create_tables(&catalog).await;
+    assert_snapshot!(run_all(&catalog, &[
+        "explain select * from t join u on u.t_id = t.id where t.a = 1",
+        "explain select * from u join t on u.t_id = t.id",
+        "explain select * from t, u, v where v.u_id = u.id and u.t_id = t.id and v.y = 'z'",
+        "explain select * from t, u where t.c = 'x'",
+        "explain select * from t join u on t.a < u.x",
+        "explain select * from t join u on t.id = u.id where t.id < 100 and u.id < 100",
+        "explain select * from t left join u on u.t_id = t.id and u.x > 1 where t.a = 1",
+        "explain select * from t left join u on u.t_id = t.id where u.x is null",
+        "explain select * from t left join u on t.a = 1 and u.x + 1 = t.b",
+        "explain select * from u join v on v.u_id = u.id left join t on t.id = u.t_id \
+         where v.y = 'z'",
+    ]).await, @r###"
+    explain select * from t join u on u.t_id = t.id where t.a = 1
+    Project: id, a, b, c, id, t_id, x
+      IndexNestedLoopJoin: inner u using u_t_id on id = t_id
+        IndexScan: t using t_a_b (a = 1)
+    explain select * from u join t on u.t_id = t.id
+    Project: id, t_id, x, id, a, b, c
+      HashJoin: inner on t_id = id
+        SeqScan: u
+        SeqScan: t
+    explain select * from t, u, v where v.u_id = u.id and u.t_id = t.id and v.y = 'z'
+    Project: id, a, b, c, id, t_id, x, id, u_id, y
+      IndexNestedLoopJoin: inner t using t_pkey on t_id = id
+        IndexNestedLoopJoin: inner u using u_pkey on u_id = id
+          Filter: (y = 'z')
+            SeqScan: v
+    explain select * from t, u where t.c = 'x'
+    Project: id, a, b, c, id, t_id, x
+      NestedLoopJoin: inner
+        Filter: (c = 'x')
+          SeqScan: t
+        SeqScan: u
+    explain select * from t join u on t.a < u.x
+    Project: id, a, b, c, id, t_id, x
+      NestedLoopJoin: inner on (a < x)
+        SeqScan: t
+        SeqScan: u
+    explain select * from t join u on t.id = u.id where t.id < 100 and u.id < 100
+    Project: id, a, b, c, id, t_id, x
+      MergeJoin: inner on id = id
+        IndexScan: t using t_pkey (id < 100)
+        IndexScan: u using u_pkey (id < 100)
+    explain select * from t left join u on u.t_id = t.id and u.x > 1 where t.a = 1
+    Project: id, a, b, c, id, t_id, x
+      IndexNestedLoopJoin: left u using u_t_id on id = t_id filter (x > 1)
+        IndexScan: t using t_a_b (a = 1)
+    explain select * from t left join u on u.t_id = t.id where u.x is null
+    Project: id, a, b, c, id, t_id, x
+      Filter: (x IS NULL)
+        HashJoin: left on id = t_id
+          SeqScan: t
+          SeqScan: u
+    explain select * from t left join u on t.a = 1 and u.x + 1 = t.b
+    Project: id, a, b, c, id, t_id, x
+      HashJoin: left on b = (x + 1) filter (a = 1)
+        SeqScan: t
+        SeqScan: u
+    explain select * from u join v on v.u_id = u.id left join t on t.id = u.t_id where v.y = 'z'
+    Project: id, t_id, x, id, u_id, y, id, a, b, c
+      IndexNestedLoopJoin: left t using t_pkey on t_id = id
+        Project: id, t_id, x, id, u_id, y
+          IndexNestedLoopJoin: inner u using u_pkey on u_id = id
+            Filter: (y = 'z')
+              SeqScan: v
+    "###);
+}
+
+#[tokio::test]
+async fn join_results() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    create_tables(&catalog).await;
+    assert_snapshot!(run_all(&catalog, &[
+        "insert into t values (1, 1, 10, 'x'), (2, 1, 20, 'y'), (3, null, 30, 'z')",
+        "insert into u values (1, 1, 5), (2, 1, 6), (3, 2, null), (4, null, 7)",
+        "insert into v values (1, 1, 'p'), (2, 3, 'q'), (3, 3, 'q'), (4, 9, 'q')",
+        "select t.id, u.id from t join u on u.t_id = t.id order by t.id, u.id",
+        "select t.id, u.id from t left join u on u.t_id = t.id and u.x > 5 order by t.id, u.id",
+        "select t.id, u.id from t left join u on u.t_id = t.id where u.id is null",
+        "select v.id, u.id, t.c from t, u, v where v.u_id = u.id and u.t_id = t.id and v.y = 'q'",
+        "select count(*) from t, u",
+        "select t.id, u.id from t join u on t.id = u.id where t.id < 3 and u.id < 3",
+        "select id, c from t where a = 1 order by b desc",
+        "select id from t where a > 0",
+    ]).await, @r###"
+    insert into t values (1, 1, 10, 'x'), (2, 1, 20, 'y'), (3, null, 30, 'z')
+    3
+    insert into u values (1, 1, 5), (2, 1, 6), (3, 2, null), (4, null, 7)
+    4
+    insert into v values (1, 1, 'p'), (2, 3, 'q'), (3, 3, 'q'), (4, 9, 'q')
+    4
+    select t.id, u.id from t join u on u.t_id = t.id order by t.id, u.id
+    1 | 1
+    1 | 2
+    2 | 3
+    select t.id, u.id from t left join u on u.t_id = t.id and u.x > 5 order by t.id, u.id
+    1 | 2
+    2 | NULL
+    3 | NULL
+    select t.id, u.id from t left join u on u.t_id = t.id where u.id is null
+    3 | NULL
+    select v.id, u.id, t.c from t, u, v where v.u_id = u.id and u.t_id = t.id and v.y = 'q'
+    2 | 3 | y
+    3 | 3 | y
+    select count(*) from t, u
+    12
+    select t.id, u.id from t join u on t.id = u.id where t.id < 3 and u.id < 3
+    1 | 1
+    2 | 2
+    select id, c from t where a = 1 order by b desc
+    2 | y
+    1 | x
+    select id from t where a > 0
+    1
+    2
+    "###);
+}
+
+/// Sets of joined tables are bitmasks, so the number of tables is limited.
+#[tokio::test]
+async fn many_tables() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    let select = |count: usize| {
+        let tables: Vec<String> = (0..count).map(|i| format!("w w{}", i)).collect();
+        let conditions: Vec<String> = (1..count)
+            .map(|i| format!("w{}.a = w{}.a", i - 1, i))
+            .collect();
+        format!(
+            "select count(*) from {} where {}",
+            tables.join(", "),
+            conditions.join(" and ")
+        )
+    };
+    let result = run_all(
+        &catalog,
+        &[
+            "create table w (a int)",
+            "insert into w values (1), (2)",
+            &select(MAX_FROM_TABLES),
+            &select(MAX_FROM_TABLES + 1),
+        ],
+    )
+    .await;
+    let lines: Vec<&str> = result.lines().collect();
+    assert_eq!(lines[4], "2");
+    assert_eq!(
+        lines[6],
+        "error: FROM clause has 33 tables, at most 32 are supported"
+    );
+}
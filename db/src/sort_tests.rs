@@ -0,0 +1,71 @@
+use crate::executor::{collect, compare_sort_keys, Executor, Row, Sort};
+use crate::logical_plan::SortKey;
+use crate::test_utils::{column, values};
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager_mem::DiskManagerMem;
+use proptest::prelude::*;
+use table::datum::{Datum, Type};
+
+/// Rows of the test inputs: values of the two sort key columns.
+type Rows = Vec<(Option<i32>, Option<String>)>;
+
+/// The rows, with their position as a third column to check that the sort is stable.
+fn to_rows(rows: &Rows) -> Vec<Row> {
+    rows.iter()
+        .enumerate()
+        .map(|(i, (a, b))| {
+            vec![
+                a.map_or(Datum::Null, Datum::Int4),
+                b.clone().map_or(Datum::Null, Datum::String),
+                Datum::Int4(i as i32),
+            ]
+        })
+        .collect()
+}
+
+fn input<'a>(rows: &Rows) -> Box<dyn Executor + 'a> {
+    values(to_rows(rows), &[Type::Int4, Type::String, Type::Int4])
+}
+
+async fn check_sort(rows: Rows, a_descending: bool, b_descending: bool) {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 64);
+    let keys = vec![
+        SortKey {
+            expr: column(0, Type::Int4),
+            descending: a_descending,
+        },
+        SortKey {
+            expr: column(1, Type::String),
+            descending: b_descending,
+        },
+    ];
+    let mut expected = to_rows(&rows);
+    expected.sort_by(|a, b| compare_sort_keys(&keys, &a[..2], &b[..2]));
+    // Small budgets write many runs, which need more than one merge pass.
+    for &memory_budget in &[1 << 20, 2000, 100] {
+        let sort = Sort::new(&buffer_pool, input(&rows), keys.clone(), memory_budget);
+        let result = collect(Box::new(sort)).await.unwrap();
+        assert_eq!(result, expected, "memory budget {}", memory_budget);
+    }
+}
+
+fn rows() -> impl Strategy<Value = Rows> {
+    let a = prop::option::weighted(0.9, -5..5);
+    let b = prop::option::weighted(0.9, "[ab]{0,3}");
+    prop::collection::vec((a, b), 0..200)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 50, ..ProptestConfig::default()
+    })]
+    #[test]
+    fn sort_matches_reference(
+        rows in rows(),
+        a_descending in any::<bool>(),
+        b_descending in any::<bool>(),
+    ) {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(check_sort(rows, a_descending, b_descending));
+    }
+}
@@ -0,0 +1,123 @@
+use crate::executor::spill::SpillWriter;
+use crate::executor::{collect, Error, Executor, HashJoin, JoinType, Result, Sort};
+use crate::logical_plan::SortKey;
+use crate::test_utils::{column, values};
+use async_trait::async_trait;
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager::{DiskManager, PageData, PageId};
+use buffer_pool::disk_manager_mem::DiskManagerMem;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::Arc;
+use table::datum::{Datum, Numeric, Type};
+
+#[tokio::test]
+async fn roundtrip() -> Result<()> {
+    // Smaller than the file, so that its pages get evicted.
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 4);
+    let rows: Vec<Vec<Datum>> = (0..1000)
+        .map(|i| {
+            vec![
+                Datum::Int8(i),
+                if i % 3 == 0 {
+                    Datum::Null
+                } else {
+                    Datum::String("x".repeat(i as usize % 50))
+                },
+                Datum::Numeric(Numeric::new(i, 2)),
+                Datum::Bytea(vec![i as u8; if i % 100 == 0 { 10_000 } else { 1 }]),
+            ]
+        })
+        .collect();
+    let mut writer = SpillWriter::new(&buffer_pool).await?;
+    for row in &rows {
+        writer.write(row).await?;
+    }
+    let file = writer.finish().await?;
+    assert_eq!(file.len(), 1000);
+
+    // Files can be read more than once.
+    for _ in 0..2 {
+        let mut reader = file.reader();
+        let mut read = vec![];
+        while let Some(row) = reader.next().await? {
+            read.push(row);
+        }
+        assert_eq!(read, rows);
+    }
+
+    let empty = SpillWriter::new(&buffer_pool).await?.finish().await?;
+    assert!(empty.is_empty());
+    assert_eq!(empty.reader().next().await?, None);
+    Ok(())
+}
+
+/// Counts the pages allocated on disk.
+struct CountingDiskManager {
+    inner: DiskManagerMem,
+    allocated: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl DiskManager for CountingDiskManager {
+    async fn write_page(&mut self, page_id: PageId, data: &PageData) -> io::Result<()> {
+        self.inner.write_page(page_id, data).await
+    }
+
+    async fn read_page(&mut self, page_id: PageId, data: &mut PageData) -> io::Result<()> {
+        self.inner.read_page(page_id, data).await
+    }
+
+    async fn allocate_page(&mut self) -> io::Result<PageId> {
+        self.allocated.fetch_add(1, SeqCst);
+        self.inner.allocate_page().await
+    }
+}
+
+/// The numbers `0..n`, shuffled.
+fn numbers<'a>(n: i64) -> Box<dyn Executor + 'a> {
+    values(
+        (0..n).map(|i| vec![Datum::Int8(i * 7919 % n)]).collect(),
+        &[Type::Int8],
+    )
+}
+
+#[tokio::test]
+async fn pages_are_reused() -> Result<()> {
+    let allocated = Arc::new(AtomicUsize::new(0));
+    let disk_manager = CountingDiskManager {
+        inner: DiskManagerMem::new(),
+        allocated: allocated.clone(),
+    };
+    let buffer_pool = BufferPool::new(Box::new(disk_manager), 16);
+    // Both spill with these budgets; the sort needs more than one merge pass.
+    let queries = || async {
+        let key = SortKey {
+            expr: column(0, Type::Int8),
+            descending: false,
+        };
+        let sort = Sort::new(&buffer_pool, numbers(2000), vec![key], 1000);
+        assert_eq!(collect(Box::new(sort)).await?.len(), 2000);
+        let join = HashJoin::new(
+            &buffer_pool,
+            numbers(2000),
+            numbers(2000),
+            vec![column(0, Type::Int8)],
+            vec![column(0, Type::Int8)],
+            1,
+            JoinType::Inner,
+            None,
+            1000,
+        );
+        assert_eq!(collect(Box::new(join)).await?.len(), 2000);
+        Ok::<_, Error>(())
+    };
+
+    queries().await?;
+    let pages = allocated.load(SeqCst);
+    for _ in 0..3 {
+        queries().await?;
+    }
+    assert_eq!(allocated.load(SeqCst), pages);
+    Ok(())
+}
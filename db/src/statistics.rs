@@ -0,0 +1,327 @@
+//! Statistics of the data in a table, collected by `ANALYZE` and used by the planner to estimate
+//! how many rows scans, filters and joins produce.
+//!
+//! `ANALYZE` reads all rows of the table. It counts them exactly, and estimates the number of
+//! distinct values of each column with a `HyperLogLog` sketch. The rest comes from a uniform sample
+//! of the rows (reservoir sampling), so that the memory used doesn't depend on the size of the
+//! table:
+//! - the fraction of NULLs,
+//! - the most common values, with their frequencies,
+//! - an equi-depth histogram of the other values: bounds which split them into buckets with the
+//!   same number of values.
+//!
+//! Values are grouped and ordered by their key encoding (see `table::key_encoding`), which is
+//! also what the sketches hash.
+//!
+//! Statistics are a snapshot: they're stored in the catalog (see `Catalog::set_statistics`), and
+//! not updated as rows change until the table is analyzed again.
+
+use crate::hyperloglog::HyperLogLog;
+use std::cmp::Ordering;
+use table::datum::Datum;
+use table::key_encoding::{encode_key, KeyColumn, NullsOrder, SortOrder};
+use table::row::Schema;
+
+/// Number of rows sampled by `ANALYZE`.
+pub const DEFAULT_SAMPLE_ROWS: usize = 30_000;
+
+/// Maximum number of most common values kept per column.
+const MAX_MOST_COMMON: usize = 10;
+/// A value is one of the most common if it's this much more frequent than the average value.
+const MOST_COMMON_RATIO: f64 = 1.25;
+/// Number of buckets in histograms.
+const HISTOGRAM_BUCKETS: usize = 20;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TableStats {
+    pub rows: u64,
+    /// Pages of the table heap.
+    pub pages: u64,
+    /// Statistics of each column of the table.
+    pub columns: Vec<ColumnStats>,
+    /// Statistics of the table's indexes, ordered by name.
+    pub indexes: Vec<IndexStats>,
+}
+
+impl TableStats {
+    pub fn index(&self, name: &str) -> Option<&IndexStats> {
+        self.indexes.iter().find(|index| index.name == name)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ColumnStats {
+    /// Fraction of rows where the column is NULL.
+    pub null_fraction: f64,
+    /// Estimated number of distinct values, not counting NULL.
+    pub distinct: f64,
+    /// The most common values and the fraction of rows having each, most common first.
+    pub most_common: Vec<(Datum, f64)>,
+    /// Bounds of the histogram buckets of values other than the most common ones, in ascending
+    /// order. Empty if there are less than two such values.
+    pub histogram: Vec<Datum>,
+}
+
+impl ColumnStats {
+    /// Fraction of rows where the column is neither NULL nor one of the most common values.
+    fn other_fraction(&self) -> f64 {
+        let most_common: f64 = self.most_common.iter().map(|(_, fraction)| fraction).sum();
+        (1.0 - self.null_fraction - most_common).max(0.0)
+    }
+
+    /// Estimated fraction of rows where the column is equal to the value (which isn't NULL).
+    pub fn equal_fraction(&self, value: &Datum) -> f64 {
+        let most_common = self
+            .most_common
+            .iter()
+            .find(|(common, _)| common.compare(value) == Some(Ordering::Equal));
+        match most_common {
+            Some((_, fraction)) => *fraction,
+            // The other values are assumed to be equally common.
+            None => {
+                self.other_fraction() / (self.distinct - self.most_common.len() as f64).max(1.0)
+            }
+        }
+    }
+
+    /// Estimated fraction of rows where the column is less than the value (which isn't NULL), or
+    /// equal to it if `inclusive`.
+    pub fn less_fraction(&self, value: &Datum, inclusive: bool) -> f64 {
+        let most_common: f64 = self
+            .most_common
+            .iter()
+            .filter(|(common, _)| match common.compare(value) {
+                Some(Ordering::Less) => true,
+                Some(Ordering::Equal) => inclusive,
+                _ => false,
+            })
+            .map(|(_, fraction)| fraction)
+            .sum();
+        most_common + self.other_fraction() * self.histogram_fraction(value)
+    }
+
+    /// Estimated fraction of the values in the histogram which are less than the value. Within a
+    /// bucket, values are assumed to be evenly distributed, if they're numbers.
+    fn histogram_fraction(&self, value: &Datum) -> f64 {
+        let bounds = &self.histogram;
+        if bounds.len() < 2 {
+            return 0.5;
+        }
+        let less = |bound: &Datum| bound.compare(value) == Some(Ordering::Less);
+        // Index of the first bound which isn't less than the value.
+        let index = bounds.iter().position(|bound| !less(bound));
+        let buckets = (bounds.len() - 1) as f64;
+        match index {
+            Some(0) => 0.0,
+            None => 1.0,
+            Some(index) => {
+                let (low, high) = (&bounds[index - 1], &bounds[index]);
+                let position = match (to_number(low), to_number(high), to_number(value)) {
+                    (Some(low), Some(high), Some(value)) if high > low => {
+                        (value - low) / (high - low)
+                    }
+                    _ => 0.5,
+                };
+                (index as f64 - 1.0 + position) / buckets
+            }
+        }
+    }
+}
+
+/// The value as a number, for types where that's meaningful for interpolation.
+fn to_number(value: &Datum) -> Option<f64> {
+    match value {
+        Datum::Int2(value) => Some(*value as f64),
+        Datum::Int4(value) | Datum::Date(value) => Some(*value as f64),
+        Datum::Int8(value) | Datum::Timestamp(value) => Some(*value as f64),
+        Datum::Float8(value) => Some(*value),
+        Datum::Numeric(value) => Some(value.normalized() as f64),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct IndexStats {
+    pub name: String,
+    pub entries: u64,
+    pub leaf_pages: u64,
+    /// Number of levels of the btree, 1 if the root is a leaf.
+    pub height: u32,
+}
+
+/// Collects the statistics of a table, from all of its rows.
+pub struct Analyzer {
+    key_columns: Vec<KeyColumn>,
+    sample_rows: usize,
+    rows: u64,
+    sample: Vec<Vec<Datum>>,
+    sketches: Vec<HyperLogLog>,
+    random: XorShift,
+}
+
+impl Analyzer {
+    /// Start analyzing a table with the given schema, keeping a sample of up to `sample_rows`
+    /// rows.
+    pub fn new(schema: &Schema, sample_rows: usize) -> Self {
+        Analyzer {
+            key_columns: schema
+                .columns()
+                .iter()
+                .map(|column| KeyColumn {
+                    ty: column.ty,
+                    order: SortOrder::Ascending,
+                    nulls: NullsOrder::NullsLast,
+                })
+                .collect(),
+            sample_rows,
+            rows: 0,
+            sample: vec![],
+            sketches: vec![HyperLogLog::new(); schema.len()],
+            random: XorShift::new(),
+        }
+    }
+
+    pub fn add(&mut self, row: Vec<Datum>) {
+        for ((value, key_column), sketch) in
+            row.iter().zip(&self.key_columns).zip(&mut self.sketches)
+        {
+            if *value != Datum::Null {
+                sketch.add(&encode_value(*key_column, value));
+            }
+        }
+        // Algorithm R: the n-th row replaces a random row of the sample with probability
+        // `sample_rows / n`.
+        self.rows += 1;
+        if self.sample.len() < self.sample_rows {
+            self.sample.push(row);
+        } else {
+            let position = self.random.next() % self.rows;
+            if position < self.sample_rows as u64 {
+                self.sample[position as usize] = row;
+            }
+        }
+    }
+
+    /// The statistics of the rows added, given the statistics of the table's storage.
+    pub fn finish(self, pages: u64, indexes: Vec<IndexStats>) -> TableStats {
+        let complete = self.sample.len() as u64 == self.rows;
+        let columns = self
+            .key_columns
+            .iter()
+            .zip(&self.sketches)
+            .enumerate()
+            .map(|(column, (&key_column, sketch))| {
+                let values = self.sample.iter().map(|row| &row[column]);
+                let distinct = if complete {
+                    None
+                } else {
+                    Some(sketch.estimate().round())
+                };
+                column_stats(key_column, values, self.sample.len(), distinct)
+            })
+            .collect();
+        TableStats {
+            rows: self.rows,
+            pages,
+            columns,
+            indexes,
+        }
+    }
+}
+
+/// Statistics of a column, from its values in a sample of `sample_rows` rows. `distinct` is the
+/// estimated number of distinct values in the table, or None if the sample is the whole table.
+fn column_stats<'a>(
+    key_column: KeyColumn,
+    values: impl Iterator<Item = &'a Datum>,
+    sample_rows: usize,
+    distinct: Option<f64>,
+) -> ColumnStats {
+    let mut values: Vec<(Vec<u8>, &Datum)> = values
+        .filter(|&value| *value != Datum::Null)
+        .map(|value| (encode_value(key_column, value), value))
+        .collect();
+    let non_null = values.len();
+    values.sort_by(|a, b| a.0.cmp(&b.0));
+    // Distinct values with their number of occurrences, in ascending order.
+    let mut groups: Vec<(&Datum, usize)> = vec![];
+    for (position, (key, value)) in values.iter().enumerate() {
+        if position > 0 && values[position - 1].0 == *key {
+            groups.last_mut().unwrap().1 += 1;
+        } else {
+            groups.push((value, 1));
+        }
+    }
+    let sampled_distinct = groups.len() as f64;
+    let distinct = distinct.map_or(sampled_distinct, |distinct| distinct.max(sampled_distinct));
+
+    // If the sample seems to have all the values, they're all kept as the most common ones.
+    // Otherwise, only values which are noticeably more common than the average one.
+    let mut by_count: Vec<usize> = (0..groups.len()).collect();
+    by_count.sort_by(|&a, &b| groups[b].1.cmp(&groups[a].1));
+    let all_values = groups.len() <= MAX_MOST_COMMON && distinct <= sampled_distinct;
+    let average = non_null as f64 / distinct.max(1.0);
+    let common: Vec<usize> = by_count
+        .into_iter()
+        .take(MAX_MOST_COMMON)
+        .filter(|&group| {
+            let count = groups[group].1;
+            all_values || (count >= 2 && count as f64 > MOST_COMMON_RATIO * average)
+        })
+        .collect();
+    let most_common = common
+        .iter()
+        .map(|&group| {
+            let (value, count) = groups[group];
+            (value.clone(), count as f64 / sample_rows as f64)
+        })
+        .collect();
+
+    // Bounds of equi-depth buckets of the other values.
+    let others: Vec<&Datum> = groups
+        .iter()
+        .enumerate()
+        .filter(|(group, _)| !common.contains(group))
+        .flat_map(|(_, &(value, count))| vec![value; count])
+        .collect();
+    let histogram = if others.len() < 2 {
+        vec![]
+    } else {
+        let buckets = HISTOGRAM_BUCKETS.min(others.len() - 1);
+        (0..=buckets)
+            .map(|bucket| others[bucket * (others.len() - 1) / buckets].clone())
+            .collect()
+    };
+
+    ColumnStats {
+        null_fraction: if sample_rows == 0 {
+            0.0
+        } else {
+            (sample_rows - non_null) as f64 / sample_rows as f64
+        },
+        distinct,
+        most_common,
+        histogram,
+    }
+}
+
+fn encode_value(key_column: KeyColumn, value: &Datum) -> Vec<u8> {
+    encode_key(&[key_column], std::slice::from_ref(value))
+}
+
+/// A xorshift pseudo-random number generator, with a fixed seed so that `ANALYZE` is
+/// deterministic.
+struct XorShift(u64);
+
+impl XorShift {
+    fn new() -> Self {
+        XorShift(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
@@ -0,0 +1,241 @@
+use crate::catalog::Catalog;
+use crate::hyperloglog::HyperLogLog;
+use crate::statistics::{Analyzer, ColumnStats};
+use crate::test_utils::run_all;
+use crate::test_utils::table_column;
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager_mem::DiskManagerMem;
+use table::datum::{Datum, Nullability, Type};
+use table::row::Schema;
+
+fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "{} is not within {} of {}",
+        actual,
+        tolerance,
+        expected
+    );
+}
+
+#[test]
+fn hyperloglog() {
+    for &count in &[0u32, 10, 1000, 100_000] {
+        let mut sketch = HyperLogLog::new();
+        for value in 0..count {
+            sketch.add(&value.to_le_bytes());
+            sketch.add(&value.to_le_bytes());
+        }
+        assert_close(sketch.estimate(), count as f64, count as f64 * 0.05);
+    }
+}
+
+fn schema() -> Schema {
+    Schema::new(vec![
+        table_column("a", Type::Int4, Nullability::Nullable),
+        table_column("b", Type::Int8, Nullability::NotNull),
+        table_column("c", Type::String, Nullability::Nullable),
+    ])
+}
+
+/// A table small enough to be sampled completely.
+#[test]
+fn analyze_all_rows() {
+    let mut analyzer = Analyzer::new(&schema(), 1000);
+    for i in 0..1000 {
+        analyzer.add(vec![
+            if i % 4 == 0 {
+                Datum::Null
+            } else {
+                Datum::Int4(i as i32 % 3)
+            },
+            Datum::Int8(999 - i),
+            if i < 900 {
+                Datum::String("x".to_string())
+            } else {
+                Datum::String(format!("v{}", i))
+            },
+        ]);
+    }
+    let stats = analyzer.finish(7, vec![]);
+    assert_eq!((stats.rows, stats.pages), (1000, 7));
+
+    // All values of `a` are among the most common.
+    let a = &stats.columns[0];
+    assert_eq!(a.null_fraction, 0.25);
+    assert_eq!(a.distinct, 3.0);
+    assert_eq!(
+        a.most_common,
+        vec![
+            (Datum::Int4(0), 0.25),
+            (Datum::Int4(1), 0.25),
+            (Datum::Int4(2), 0.25),
+        ]
+    );
+    assert_eq!(a.histogram, vec![]);
+
+    let b = &stats.columns[1];
+    assert_eq!(b.null_fraction, 0.0);
+    assert_eq!(b.distinct, 1000.0);
+    assert_eq!(b.most_common, vec![]);
+    assert_eq!(b.histogram.len(), 21);
+    assert_eq!(b.histogram[0], Datum::Int8(0));
+    assert_eq!(b.histogram[10], Datum::Int8(499));
+    assert_eq!(b.histogram[20], Datum::Int8(999));
+
+    let c = &stats.columns[2];
+    assert_eq!(c.distinct, 101.0);
+    assert_eq!(c.most_common, vec![(Datum::String("x".to_string()), 0.9)]);
+    assert_eq!(
+        c.histogram.first(),
+        Some(&Datum::String("v900".to_string()))
+    );
+    assert_eq!(c.histogram.last(), Some(&Datum::String("v999".to_string())));
+}
+
+/// A table larger than the sample.
+#[test]
+fn analyze_sample() {
+    let mut analyzer = Analyzer::new(&schema(), 1000);
+    for i in 0..20000 {
+        analyzer.add(vec![
+            if i % 10 == 0 {
+                Datum::Null
+            } else {
+                Datum::Int4(i as i32)
+            },
+            Datum::Int8(if i % 2 == 0 { 0 } else { i }),
+            Datum::String("x".to_string()),
+        ]);
+    }
+    let stats = analyzer.finish(100, vec![]);
+    assert_eq!(stats.rows, 20000);
+
+    let a = &stats.columns[0];
+    assert_close(a.null_fraction, 0.1, 0.03);
+    assert_close(a.distinct, 18000.0, 900.0);
+    assert_eq!(a.most_common, vec![]);
+    assert_eq!(a.histogram.len(), 21);
+
+    let b = &stats.columns[1];
+    assert_close(b.distinct, 10001.0, 500.0);
+    assert_eq!(b.most_common.len(), 1);
+    assert_eq!(b.most_common[0].0, Datum::Int8(0));
+    assert_close(b.most_common[0].1, 0.5, 0.05);
+
+    let c = &stats.columns[2];
+    assert_eq!(c.distinct, 1.0);
+    assert_eq!(c.most_common, vec![(Datum::String("x".to_string()), 1.0)]);
+}
+
+#[test]
+fn selectivity() {
+    let stats = ColumnStats {
+        null_fraction: 0.1,
+        distinct: 50.0,
+        most_common: vec![(Datum::Int4(1000), 0.2)],
+        histogram: (0..=10).map(|i| Datum::Int4(i * 10)).collect(),
+    };
+    assert_eq!(stats.equal_fraction(&Datum::Int4(1000)), 0.2);
+    assert_close(stats.equal_fraction(&Datum::Int4(5)), 0.7 / 49.0, 1e-9);
+    assert_eq!(stats.less_fraction(&Datum::Int4(-5), true), 0.0);
+    assert_close(
+        stats.less_fraction(&Datum::Int4(25), false),
+        0.7 * 0.25,
+        1e-9,
+    );
+    assert_close(
+        stats.less_fraction(&Datum::Int4(50), false),
+        0.7 * 0.5,
+        1e-9,
+    );
+    assert_close(stats.less_fraction(&Datum::Int4(1000), false), 0.7, 1e-9);
+    assert_close(stats.less_fraction(&Datum::Int4(1000), true), 0.9, 1e-9);
+
+    // Strings are assumed to be in the middle of their bucket.
+    let stats = ColumnStats {
+        null_fraction: 0.0,
+        distinct: 3.0,
+        most_common: vec![],
+        histogram: vec![
+            Datum::String("a".to_string()),
+            Datum::String("m".to_string()),
+            Datum::String("z".to_string()),
+        ],
+    };
+    assert_eq!(
+        stats.less_fraction(&Datum::String("b".to_string()), false),
+        0.25
+    );
+    assert_eq!(
+        stats.less_fraction(&Datum::String("zz".to_string()), false),
+        1.0
+    );
+}
+
+#[tokio::test]
+async fn analyze() {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+    let catalog = Catalog::create(&buffer_pool).await.unwrap();
+    let rows: Vec<String> = (0..2000)
+        .map(|i| format!("({}, {}, 'x')", i, i % 2))
+        .collect();
+    let insert = format!("insert into t values {}", rows.join(", "));
+    run_all(
+        &catalog,
+        &[
+            "create table t (id int primary key, k int, v text)",
+            "create index t_k on t (k)",
+            &insert,
+        ],
+    )
+    .await;
+    assert_snapshot!(run_all(&catalog, &[
+        "explain select * from t where k = 1",
+        "analyze t",
+        "explain select * from t where k = 1",
+        "explain select * from t where k = 3",
+        "explain select * from t where id < 10",
+        "explain select * from t where id > 10",
+        "select count(*) from t where k = 1 and id < 10",
+        "analyze",
+        "analyze u",
+        "explain analyze t",
+    ]).await, @r###"
+    explain select * from t where k = 1
+    Project: id, k, v
+      IndexScan: t using t_k (k = 1)
+    analyze t
+    explain select * from t where k = 1
+    Project: id, k, v
+      Filter: (k = 1)
+        SeqScan: t
+    explain select * from t where k = 3
+    Project: id, k, v
+      IndexScan: t using t_k (k = 3)
+    explain select * from t where id < 10
+    Project: id, k, v
+      IndexScan: t using t_pkey (id < 10)
+    explain select * from t where id > 10
+    Project: id, k, v
+      Filter: (id > 10)
+        SeqScan: t
+    select count(*) from t where k = 1 and id < 10
+    5
+    analyze
+    analyze u
+    error: table "u" does not exist
+    explain analyze t
+    Analyze: t
+    "###);
+
+    let table = catalog.get_table("t").await.unwrap().unwrap();
+    let stats = catalog.get_statistics(&table).await.unwrap().unwrap();
+    assert_eq!(stats.rows, 2000);
+    assert!(stats.pages > 1);
+    assert_eq!(stats.columns[0].distinct, 2000.0);
+    assert_eq!(stats.columns[1].distinct, 2.0);
+    let names: Vec<_> = stats.indexes.iter().map(|index| &index.name[..]).collect();
+    assert_eq!(names, vec!["t_k", "t_pkey"]);
+    assert_eq!(stats.indexes[0].entries, 2000);
+}
@@ -0,0 +1,102 @@
+//! The superblock: the first page of the database, from which everything else is found.
+//!
+//! Page format:
+//! ```text
+//! -------------------------------------------------------------------------
+//! | Magic (4) | Version (4) | TablesMetaPageId (4) | IndexesMetaPageId (4) |
+//! -------------------------------------------------------------------------
+//! | StatisticsMetaPageId (4) | ...
+//! -------------------------------------------------------------------------
+//! ```
+//!
+//! `TablesMetaPageId`, `IndexesMetaPageId` and `StatisticsMetaPageId` are the btrees of the catalog
+//! (see `catalog`).
+
+use crate::catalog::{Error, Result};
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager::{PageData, PageId};
+
+/// The superblock is always the first page.
+pub const SUPERBLOCK_PAGE_ID: PageId = PageId(0);
+
+/// Identifies a superblock ("DBSB").
+const MAGIC: u32 = 0x4442_5342;
+
+/// On-disk format version. Bump when the format of the superblock or catalog changes
+/// incompatibly.
+const FORMAT_VERSION: u32 = 2;
+
+const OFFSET_MAGIC: usize = 0x00;
+const OFFSET_VERSION: usize = 0x04;
+const OFFSET_TABLES_META_PAGE_ID: usize = 0x08;
+const OFFSET_INDEXES_META_PAGE_ID: usize = 0x0c;
+const OFFSET_STATISTICS_META_PAGE_ID: usize = 0x10;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Superblock {
+    pub tables_meta_page_id: PageId,
+    pub indexes_meta_page_id: PageId,
+    pub statistics_meta_page_id: PageId,
+}
+
+impl Superblock {
+    /// Allocate the superblock page in an empty database. Its contents are written by `write`.
+    pub async fn allocate(buffer_pool: &BufferPool) -> Result<()> {
+        let page = buffer_pool.allocate_page().await?;
+        if page.id() != SUPERBLOCK_PAGE_ID {
+            return Err(Error::DatabaseNotEmpty);
+        }
+        Ok(())
+    }
+
+    pub async fn read(buffer_pool: &BufferPool) -> Result<Superblock> {
+        let page = buffer_pool.get_page(SUPERBLOCK_PAGE_ID).await?;
+        let data = page.read().await;
+        if read_u32(&data, OFFSET_MAGIC) != MAGIC {
+            return Err(Error::InvalidSuperblock);
+        }
+        let version = read_u32(&data, OFFSET_VERSION);
+        if version != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        Ok(Superblock {
+            tables_meta_page_id: PageId(read_u32(&data, OFFSET_TABLES_META_PAGE_ID)),
+            indexes_meta_page_id: PageId(read_u32(&data, OFFSET_INDEXES_META_PAGE_ID)),
+            statistics_meta_page_id: PageId(read_u32(&data, OFFSET_STATISTICS_META_PAGE_ID)),
+        })
+    }
+
+    pub async fn write(&self, buffer_pool: &BufferPool) -> Result<()> {
+        let page = buffer_pool.get_page(SUPERBLOCK_PAGE_ID).await?;
+        let mut data = page.write().await;
+        write_u32(&mut data, OFFSET_MAGIC, MAGIC);
+        write_u32(&mut data, OFFSET_VERSION, FORMAT_VERSION);
+        write_u32(
+            &mut data,
+            OFFSET_TABLES_META_PAGE_ID,
+            self.tables_meta_page_id.0,
+        );
+        write_u32(
+            &mut data,
+            OFFSET_INDEXES_META_PAGE_ID,
+            self.indexes_meta_page_id.0,
+        );
+        write_u32(
+            &mut data,
+            OFFSET_STATISTICS_META_PAGE_ID,
+            self.statistics_meta_page_id.0,
+        );
+        data.dirty();
+        Ok(())
+    }
+}
+
+fn read_u32(data: &PageData, offset: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn write_u32(data: &mut PageData, offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
@@ -0,0 +1,82 @@
+//! Helpers shared by the tests of this crate.
+
+use crate::binder::Binder;
+use crate::catalog::Catalog;
+use crate::executor::{execute, Executor, Result, Row, Values};
+use crate::logical_plan::Expr;
+use crate::parser::parse_statement;
+use table::datum::{Nullability, Type};
+use table::row::Column;
+
+/// Bind and execute a statement. Panics if the statement doesn't parse or bind.
+pub async fn run(catalog: &Catalog<'_>, sql: &str) -> Result<Vec<Row>> {
+    let statement = parse_statement(sql).unwrap();
+    let plan = Binder::new(catalog).bind(&statement).await.unwrap();
+    execute(catalog, &plan).await
+}
+
+/// One line per row, with values separated by `|`.
+fn format_rows(rows: &[Row]) -> String {
+    let mut result = String::new();
+    for row in rows {
+        let values: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+        result += &values.join(" | ");
+        result += "\n";
+    }
+    result
+}
+
+/// Execute each statement and print the returned rows, or the error.
+pub async fn run_all(catalog: &Catalog<'_>, statements: &[&str]) -> String {
+    let mut result = String::new();
+    for sql in statements {
+        let statement = parse_statement(sql).unwrap();
+        result += sql;
+        result += "\n";
+        let plan = match Binder::new(catalog).bind(&statement).await {
+            Ok(plan) => plan,
+            Err(err) => {
+                result += &format!("error: {}\n", err);
+                continue;
+            }
+        };
+        match execute(catalog, &plan).await {
+            Ok(rows) => result += &format_rows(&rows),
+            Err(err) => result += &format!("error: {}\n", err),
+        }
+    }
+    result
+}
+
+/// A column of a table schema.
+pub fn table_column(name: &str, ty: Type, nullability: Nullability) -> Column {
+    Column {
+        name: name.to_string(),
+        ty,
+        nullability,
+    }
+}
+
+/// A nullable column of the input, named after its index.
+pub fn column(index: usize, ty: Type) -> Expr {
+    Expr::Column {
+        index,
+        name: format!("c{}", index),
+        ty,
+        nullability: Nullability::Nullable,
+    }
+}
+
+/// An executor returning the rows, whose columns have the given types.
+pub fn values<'a>(rows: Vec<Row>, types: &[Type]) -> Box<dyn Executor + 'a> {
+    Box::new(Values::new(
+        rows.into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .zip(types)
+                    .map(|(value, &ty)| Expr::Constant { value, ty })
+                    .collect()
+            })
+            .collect(),
+    ))
+}
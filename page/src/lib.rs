@@ -0,0 +1,504 @@
+//! A generic page storing some metadata (opaque sequence of bytes, specific to page type) and a sequence of tuples (opaque byte sequences).
+//!
+//! Shared by the B-tree and the table heap.
+
+#[cfg(test)]
+#[macro_use]
+extern crate insta;
+
+#[cfg(test)]
+mod hexdump;
+#[cfg(test)]
+mod page_tests;
+
+use buffer_pool::disk_manager::{PageData, PAGE_SIZE};
+use std::mem;
+use std::ops::{Deref, DerefMut};
+
+pub struct TupleBlockPage<T, Meta = ()> {
+    data: T,
+    _phantom: std::marker::PhantomData<Meta>,
+}
+
+// Page format:
+// --------------------------------------------------------
+// | lsn (4) | metadata_size (2) | free_space_pointer (2) |
+// -------------------------------------------------------------------
+// | tuple_count (2) | reserved (2) | tuple_offset[0] (2) | tuple_size[0] (2) | ... |
+// ----------------------------------------------------------------------------------
+// | tuple_offset[tuple_count-1] (2) | tuple_size[tuple_count-1] (2) |
+// -------------------------------------------------------------------
+// | FREE SPACE | TUPLES | metadata (metadata_size) |
+// --------------------------------------------------
+//              ^ free_space_pointer
+//
+// A tuple slot is in one of three states:
+// - normal: the descriptor points to the tuple data,
+// - dead: the tuple was deleted (the top bit of tuple_size is set), but its data still occupies
+//   space on the page until the next compaction,
+// - unused: tuple_offset is 0, the slot has no data.
+//
+// Dead and unused slots keep their index, so that marking a tuple as dead doesn't move the other
+// tuples. `prune` removes them.
+
+pub struct PageHeader {
+    lsn: u32,
+    metadata_size: u16,
+    pub free_space_pointer: u16,
+    pub tuple_count: u16,
+    /// Explicit padding, so that it's always zeroed.
+    #[allow(dead_code)]
+    reserved: u16,
+    // next: a sequence of TupleDescriptor structs
+}
+
+#[derive(Debug)]
+struct TupleDescriptor {
+    offset: u16,
+    size: u16,
+}
+
+impl TupleDescriptor {
+    /// Set in `size` when the tuple is dead.
+    const DEAD_FLAG: u16 = 1 << 15;
+
+    const UNUSED: TupleDescriptor = TupleDescriptor { offset: 0, size: 0 };
+
+    fn is_unused(&self) -> bool {
+        self.offset == 0
+    }
+
+    fn is_dead(&self) -> bool {
+        self.size & Self::DEAD_FLAG != 0
+    }
+
+    /// Size of the tuple data (live or dead) on the page.
+    fn tuple_size(&self) -> usize {
+        (self.size & !Self::DEAD_FLAG) as usize
+    }
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum Error {
+    PageFull,
+}
+
+pub use Error::*;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub type SlotIndex = usize;
+
+/// Inconsistency in the page header or tuple descriptors, found by `check_layout`.
+#[derive(PartialEq, Eq, Debug)]
+pub enum LayoutError {
+    /// `metadata_size` in the header doesn't match the expected metadata type.
+    MetadataSizeMismatch { expected: u16, actual: u16 },
+    /// The tuple descriptor array doesn't fit on the page.
+    TooManyTuples(u16),
+    /// Free space pointer points into the descriptor array or the metadata.
+    FreeSpacePointerOutOfRange(u16),
+    /// The tuple lies (at least partly) outside of the tuple area.
+    TupleOutOfBounds(SlotIndex),
+    /// Two tuples share some bytes.
+    TuplesOverlap(SlotIndex, SlotIndex),
+}
+
+pub const TUPLE_DESCRIPTOR_SIZE: usize = mem::size_of::<TupleDescriptor>();
+
+/// Space available for tuples (including their descriptors) on an empty page with the given
+/// metadata type.
+pub fn capacity<Meta>() -> usize {
+    PAGE_SIZE - mem::size_of::<PageHeader>() - mem::size_of::<Meta>()
+}
+
+/// Check that the page data is a sane tuple block page with the given metadata type: descriptors
+/// and tuples lie within their areas, and tuples don't overlap.
+///
+/// Done on raw data, because `TupleBlockPage::from_existing` assumes a valid header.
+pub fn check_layout<Meta>(data: &PageData) -> std::result::Result<(), LayoutError> {
+    let header: &PageHeader = unsafe { &*(data.as_ptr() as *const PageHeader) };
+    let metadata_size = mem::size_of::<Meta>() as u16;
+    if header.metadata_size != metadata_size {
+        return Err(LayoutError::MetadataSizeMismatch {
+            expected: metadata_size,
+            actual: header.metadata_size,
+        });
+    }
+    let tuple_area_end = PAGE_SIZE - metadata_size as usize;
+    let descriptors_end = mem::size_of::<PageHeader>()
+        + header.tuple_count as usize * mem::size_of::<TupleDescriptor>();
+    if descriptors_end > tuple_area_end {
+        return Err(LayoutError::TooManyTuples(header.tuple_count));
+    }
+    let free_space_pointer = header.free_space_pointer as usize;
+    if free_space_pointer < descriptors_end || free_space_pointer > tuple_area_end {
+        return Err(LayoutError::FreeSpacePointerOutOfRange(
+            header.free_space_pointer,
+        ));
+    }
+
+    // (start, end, index) of live tuples
+    let mut tuples = vec![];
+    for index in 0..header.tuple_count as usize {
+        let offset = mem::size_of::<PageHeader>() + index * mem::size_of::<TupleDescriptor>();
+        let descriptor =
+            unsafe { (data[offset..].as_ptr() as *const TupleDescriptor).read_unaligned() };
+        if descriptor.is_unused() {
+            continue;
+        }
+        let start = descriptor.offset as usize;
+        let end = start + descriptor.tuple_size();
+        if start < free_space_pointer || end > tuple_area_end {
+            return Err(LayoutError::TupleOutOfBounds(index));
+        }
+        tuples.push((start, end, index));
+    }
+    tuples.sort();
+    for pair in tuples.windows(2) {
+        if pair[0].1 > pair[1].0 {
+            return Err(LayoutError::TuplesOverlap(pair[0].2, pair[1].2));
+        }
+    }
+    Ok(())
+}
+
+impl<T, Meta> Deref for TupleBlockPage<T, Meta> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl<T: Deref<Target = PageData>, Meta> TupleBlockPage<T, Meta> {
+    pub fn from_existing(data: T) -> Self {
+        let page = Self {
+            data,
+            _phantom: std::marker::PhantomData,
+        };
+        assert!(mem::size_of::<PageHeader>() < PAGE_SIZE);
+        assert!(page.header().metadata_size == mem::size_of::<Meta>() as u16);
+        page
+    }
+
+    pub fn header(&self) -> &PageHeader {
+        unsafe { mem::transmute(self.data.as_ptr()) }
+    }
+
+    pub fn metadata(&self) -> &Meta {
+        unsafe { mem::transmute(self.data[PAGE_SIZE - mem::size_of::<Meta>()..].as_ptr()) }
+    }
+
+    /// LSN of the last log record which modified the page.
+    pub fn lsn(&self) -> u32 {
+        self.header().lsn
+    }
+
+    pub fn tuple_count(&self) -> usize {
+        self.header().tuple_count as usize
+    }
+
+    pub fn free_space(&self) -> usize {
+        let header = self.header();
+        (header.free_space_pointer as usize)
+            - mem::size_of::<PageHeader>()
+            - self.tuple_count() * mem::size_of::<TupleDescriptor>()
+    }
+
+    pub fn free_space_after_compaction(&self) -> usize {
+        PAGE_SIZE
+            - mem::size_of::<PageHeader>()
+            - mem::size_of::<Meta>()
+            - self.tuple_count() * mem::size_of::<TupleDescriptor>()
+            - self.total_tuple_size()
+    }
+
+    /// Total size of live tuples (dead tuples are not counted, since compaction reclaims them).
+    pub fn total_tuple_size(&self) -> usize {
+        let mut tuple_total = 0;
+        for index in 0..self.tuple_count() {
+            let descriptor = self.get_tuple_descriptor(index);
+            if !descriptor.is_unused() && !descriptor.is_dead() {
+                tuple_total += descriptor.tuple_size();
+            }
+        }
+        tuple_total
+    }
+
+    /// Returns the tuple in the given slot, or `None` if it's dead or unused.
+    pub fn get_tuple(&self, index: SlotIndex) -> Option<&[u8]> {
+        let descriptor = self.get_tuple_descriptor(index);
+        if descriptor.is_unused() || descriptor.is_dead() {
+            return None;
+        }
+        let offset = descriptor.offset as usize;
+        Some(&self.data[offset..offset + descriptor.tuple_size()])
+    }
+
+    pub fn is_dead(&self, index: SlotIndex) -> bool {
+        self.get_tuple_descriptor(index).is_dead()
+    }
+
+    fn get_tuple_descriptor(&self, index: usize) -> TupleDescriptor {
+        assert!(index < self.tuple_count());
+        let offset = mem::size_of::<PageHeader>() + index * mem::size_of::<TupleDescriptor>();
+        unsafe { (self.data[offset..].as_ptr() as *const TupleDescriptor).read() }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn data(&self) -> &PageData {
+        &self.data
+    }
+
+    /// Copies of the live tuples, in slot order. Dead and unused slots are skipped.
+    pub fn dump_tuples(&self) -> Vec<Vec<u8>> {
+        (0..self.tuple_count())
+            .filter_map(|index| self.get_tuple(index))
+            .map(|tuple| tuple.to_vec())
+            .collect()
+    }
+}
+
+impl<T: DerefMut<Target = PageData>, Meta: Copy> TupleBlockPage<T, Meta> {
+    /// Initialize a new page in the given storage.
+    pub fn new(data: T, metadata: &Meta) -> Self {
+        let mut page = Self {
+            data,
+            _phantom: std::marker::PhantomData,
+        };
+        let metadata_size = mem::size_of::<Meta>() as u16;
+        *unsafe { page.header_mut() } = PageHeader {
+            lsn: 0,
+            metadata_size,
+            free_space_pointer: PAGE_SIZE as u16 - metadata_size,
+            tuple_count: 0,
+            reserved: 0,
+        };
+        *page.metadata_mut() = *metadata;
+        page
+    }
+
+    /// Returns a mutable reference to the header.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because by messing with `tuple_count` or `free_space_pointer` one can cause accesses beyond the page
+    /// boundary.
+    pub unsafe fn header_mut(&mut self) -> &mut PageHeader {
+        mem::transmute(self.data.as_ptr())
+    }
+
+    pub fn set_lsn(&mut self, lsn: u32) {
+        unsafe { self.header_mut() }.lsn = lsn;
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut Meta {
+        unsafe { mem::transmute(self.data[PAGE_SIZE - mem::size_of::<Meta>()..].as_ptr()) }
+    }
+
+    /// Move all live tuples to the end of the page, so that the free space is contiguous.
+    ///
+    /// Slot indexes don't change. Dead tuples lose their data and become unused slots.
+    pub fn compact(&mut self) {
+        let data_copy = Box::new(*self.data);
+        let copy = TupleBlockPage::<&PageData, Meta>::from_existing(&data_copy);
+        let mut free_space_pointer = PAGE_SIZE - mem::size_of::<Meta>();
+        for index in 0..copy.tuple_count() {
+            let descriptor = match copy.get_tuple(index) {
+                Some(tuple) => {
+                    free_space_pointer -= tuple.len();
+                    self.data[free_space_pointer..free_space_pointer + tuple.len()]
+                        .copy_from_slice(tuple);
+                    TupleDescriptor {
+                        offset: free_space_pointer as u16,
+                        size: tuple.len() as u16,
+                    }
+                }
+                None => TupleDescriptor::UNUSED,
+            };
+            self.set_tuple_descriptor(index, descriptor);
+        }
+        unsafe { self.header_mut() }.free_space_pointer = free_space_pointer as u16;
+    }
+
+    /// Mark the tuple as dead. Other slots are not moved, and the tuple's space is reclaimed on the
+    /// next compaction.
+    pub fn mark_dead(&mut self, index: SlotIndex) {
+        let mut descriptor = self.get_tuple_descriptor(index);
+        assert!(
+            !descriptor.is_unused() && !descriptor.is_dead(),
+            "tuple is not live"
+        );
+        descriptor.size |= TupleDescriptor::DEAD_FLAG;
+        self.set_tuple_descriptor(index, descriptor);
+    }
+
+    /// Remove dead and unused slots (shifting the slots after them left) and reclaim their space.
+    ///
+    /// Returns the number of removed slots.
+    pub fn prune(&mut self) -> usize {
+        self.compact();
+        let tuple_count = self.tuple_count();
+        let mut kept = 0;
+        for index in 0..tuple_count {
+            let descriptor = self.get_tuple_descriptor(index);
+            if !descriptor.is_unused() {
+                self.set_tuple_descriptor(kept, descriptor);
+                kept += 1;
+            }
+        }
+        unsafe { self.header_mut() }.tuple_count = kept as u16;
+        tuple_count - kept
+    }
+
+    /// Remove all tuples from the page. Metadata is left intact.
+    pub fn clear(&mut self) {
+        let header = unsafe { self.header_mut() };
+        header.tuple_count = 0;
+        header.free_space_pointer = (PAGE_SIZE as u16) - header.metadata_size;
+    }
+
+    /// Delete a tuple, shifting the tuples after it one slot left. Its space is reclaimed on the
+    /// next compaction.
+    ///
+    /// See `mark_dead` for deleting without moving other slots.
+    pub fn delete_tuple(&mut self, index: SlotIndex) {
+        let tuple_count = self.tuple_count();
+        assert!(index < tuple_count);
+        self.data.copy_within(
+            Self::tuple_descriptor_offset(index + 1)..Self::tuple_descriptor_offset(tuple_count),
+            Self::tuple_descriptor_offset(index),
+        );
+        unsafe { self.header_mut() }.tuple_count = (tuple_count - 1) as u16;
+    }
+
+    /// Remove dead and unused slots from the end of the slot array.
+    pub fn truncate_free_slots(&mut self) {
+        let mut tuple_count = self.tuple_count();
+        while tuple_count > 0 && self.get_tuple(tuple_count - 1).is_none() {
+            tuple_count -= 1;
+        }
+        unsafe { self.header_mut() }.tuple_count = tuple_count as u16;
+    }
+
+    /// Allocate `size` bytes of tuple data, making sure that additionally `extra_space` bytes stay
+    /// free. Compacts the page if needed. Returns the offset of the allocated data.
+    fn alloc_space(&mut self, size: usize, extra_space: usize) -> Result<u16> {
+        let space_needed = size + extra_space;
+        if self.free_space() < space_needed && self.free_space_after_compaction() >= space_needed {
+            self.compact();
+        }
+        if self.free_space() < space_needed {
+            return Err(Error::PageFull);
+        }
+        let start = self.header().free_space_pointer - (size as u16);
+        unsafe { self.header_mut() }.free_space_pointer = start;
+        Ok(start)
+    }
+
+    pub fn alloc_tuple_at(&mut self, index: SlotIndex, size: usize) -> Result<&mut [u8]> {
+        let start = self.alloc_space(size, mem::size_of::<TupleDescriptor>())?;
+        let end = start + (size as u16);
+        // move tuple descriptors after insertion point one index to the right
+        let tuple_count = self.tuple_count();
+        self.data.copy_within(
+            Self::tuple_descriptor_offset(index)..Self::tuple_descriptor_offset(tuple_count),
+            Self::tuple_descriptor_offset(index + 1),
+        );
+
+        unsafe { self.header_mut() }.tuple_count = self.header().tuple_count + 1;
+
+        // write the new tuple descriptor
+        self.set_tuple_descriptor(
+            index as usize,
+            TupleDescriptor {
+                offset: start,
+                size: (end - start),
+            },
+        );
+        Ok(&mut self.data[start as usize..end as usize])
+    }
+
+    fn set_tuple_descriptor(&mut self, index: usize, descriptor: TupleDescriptor) {
+        assert!(index < self.tuple_count());
+        let offset = Self::tuple_descriptor_offset(index);
+        unsafe { (self.data[offset..].as_mut_ptr() as *mut TupleDescriptor).write(descriptor) }
+    }
+
+    fn tuple_descriptor_offset(index: usize) -> usize {
+        mem::size_of::<PageHeader>() + index * mem::size_of::<TupleDescriptor>()
+    }
+
+    pub fn insert_tuple<'a>(&mut self, tuple: &'a [u8]) -> Result<SlotIndex> {
+        let index = self.tuple_count();
+        self.insert_tuple_at(index, tuple)?;
+        Ok(index)
+    }
+
+    pub fn insert_tuple_at<'a>(&mut self, index: SlotIndex, tuple: &'a [u8]) -> Result<()> {
+        let new_tuple = self.alloc_tuple_at(index, tuple.len())?;
+        new_tuple.copy_from_slice(tuple);
+        Ok(())
+    }
+
+    /// Insert a tuple into the first dead or unused slot, or at the end if there's none. Unlike
+    /// `insert_tuple_at`, doesn't move any other slots.
+    pub fn insert_tuple_in_free_slot(&mut self, tuple: &[u8]) -> Result<SlotIndex> {
+        let index = match (0..self.tuple_count()).find(|&index| self.get_tuple(index).is_none()) {
+            Some(index) => index,
+            None => return self.insert_tuple(tuple),
+        };
+        // A dead tuple's data is left for compaction to reclaim.
+        self.set_tuple_descriptor(index, TupleDescriptor::UNUSED);
+        let offset = self.alloc_space(tuple.len(), 0)?;
+        self.write_tuple(index, offset, tuple);
+        Ok(index)
+    }
+
+    /// Replace a live tuple, keeping its slot index. The new tuple is written in place if it's not
+    /// larger than the old one.
+    pub fn update_tuple(&mut self, index: SlotIndex, tuple: &[u8]) -> Result<()> {
+        let descriptor = self.get_tuple_descriptor(index);
+        assert!(
+            !descriptor.is_unused() && !descriptor.is_dead(),
+            "tuple is not live"
+        );
+        let offset = if tuple.len() <= descriptor.tuple_size() {
+            descriptor.offset
+        } else {
+            // The old data may be discarded by compaction, so that its space can be reused.
+            self.set_tuple_descriptor(index, TupleDescriptor::UNUSED);
+            match self.alloc_space(tuple.len(), 0) {
+                Ok(offset) => offset,
+                Err(error) => {
+                    self.set_tuple_descriptor(index, descriptor);
+                    return Err(error);
+                }
+            }
+        };
+        self.write_tuple(index, offset, tuple);
+        Ok(())
+    }
+
+    fn write_tuple(&mut self, index: SlotIndex, offset: u16, tuple: &[u8]) {
+        let start = offset as usize;
+        self.data[start..start + tuple.len()].copy_from_slice(tuple);
+        self.set_tuple_descriptor(
+            index,
+            TupleDescriptor {
+                offset,
+                size: tuple.len() as u16,
+            },
+        );
+    }
+
+    pub fn get_tuple_mut(&mut self, index: SlotIndex) -> Option<&mut [u8]> {
+        let descriptor = self.get_tuple_descriptor(index);
+        if descriptor.is_unused() || descriptor.is_dead() {
+            return None;
+        }
+        let offset = descriptor.offset as usize;
+        Some(&mut self.data[offset..offset + descriptor.tuple_size()])
+    }
+}
@@ -2,12 +2,20 @@
 #![allow(clippy::unit_arg)]
 
 use proptest_derive::Arbitrary;
+use std::cmp::Ordering;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Arbitrary)]
 pub enum Type {
     String,
     Int8,
     Bool,
+    Int2,
+    Int4,
+    Float8,
+    Bytea,
+    Date,
+    Timestamp,
+    Numeric,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Arbitrary)]
@@ -18,11 +26,30 @@ pub enum Nullability {
 
 pub use Nullability::*;
 
-#[derive(Debug, PartialEq, Eq, Clone, Arbitrary)]
+/// Range of dates which can be represented in text (years 0 to 9999), in days since 1970-01-01.
+pub const MIN_DATE: i32 = -719_528;
+pub const MAX_DATE: i32 = 2_932_896;
+
+pub const MICROS_PER_DAY: i64 = 86_400_000_000;
+
+/// Range of timestamps which can be represented in text, in microseconds since 1970-01-01 00:00.
+pub const MIN_TIMESTAMP: i64 = MIN_DATE as i64 * MICROS_PER_DAY;
+pub const MAX_TIMESTAMP: i64 = (MAX_DATE as i64 + 1) * MICROS_PER_DAY - 1;
+
+#[derive(Debug, Clone, Arbitrary)]
 pub enum Datum {
     String(String),
     Int8(i64),
     Bool(bool),
+    Int2(i16),
+    Int4(i32),
+    Float8(f64),
+    Bytea(Vec<u8>),
+    /// Days since 1970-01-01.
+    Date(#[proptest(strategy = "MIN_DATE..=MAX_DATE")] i32),
+    /// Microseconds since 1970-01-01 00:00:00 (without time zone).
+    Timestamp(#[proptest(strategy = "MIN_TIMESTAMP..=MAX_TIMESTAMP")] i64),
+    Numeric(Numeric),
     Null,
 }
 
@@ -34,14 +61,179 @@ impl Datum {
             Datum::String(_) => Some(Type::String),
             Datum::Int8(_) => Some(Type::Int8),
             Datum::Bool(_) => Some(Type::Bool),
+            Datum::Int2(_) => Some(Type::Int2),
+            Datum::Int4(_) => Some(Type::Int4),
+            Datum::Float8(_) => Some(Type::Float8),
+            Datum::Bytea(_) => Some(Type::Bytea),
+            Datum::Date(_) => Some(Type::Date),
+            Datum::Timestamp(_) => Some(Type::Timestamp),
+            Datum::Numeric(_) => Some(Type::Numeric),
             Datum::Null => None,
         }
     }
+
+    /// Compare two non-NULL values of the same type. Returns None if either is NULL or the types
+    /// differ.
+    ///
+    /// Floats are totally ordered: NaN is equal to itself and greater than any other value, and
+    /// -0 is equal to 0. Numerics are compared by value, regardless of scale.
+    pub fn compare(&self, other: &Datum) -> Option<Ordering> {
+        Some(match (self, other) {
+            (Datum::String(a), Datum::String(b)) => a.cmp(b),
+            (Datum::Int8(a), Datum::Int8(b)) => a.cmp(b),
+            (Datum::Bool(a), Datum::Bool(b)) => a.cmp(b),
+            (Datum::Int2(a), Datum::Int2(b)) => a.cmp(b),
+            (Datum::Int4(a), Datum::Int4(b)) => a.cmp(b),
+            (Datum::Float8(a), Datum::Float8(b)) => compare_f64(*a, *b),
+            (Datum::Bytea(a), Datum::Bytea(b)) => a.cmp(b),
+            (Datum::Date(a), Datum::Date(b)) => a.cmp(b),
+            (Datum::Timestamp(a), Datum::Timestamp(b)) => a.cmp(b),
+            (Datum::Numeric(a), Datum::Numeric(b)) => a.cmp(b),
+            _ => return None,
+        })
+    }
+}
+
+/// Equality consistent with `compare`, except that NULL is equal to NULL.
+impl PartialEq for Datum {
+    fn eq(&self, other: &Datum) -> bool {
+        match (self, other) {
+            (Datum::Null, Datum::Null) => true,
+            _ => self.compare(other) == Some(Ordering::Equal),
+        }
+    }
+}
+
+impl Eq for Datum {}
+
+fn compare_f64(a: f64, b: f64) -> Ordering {
+    a.partial_cmp(&b)
+        .unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+}
+
+pub const MAX_NUMERIC_SCALE: u8 = 18;
+
+/// Fixed-point decimal number: `mantissa * 10^-scale`.
+#[derive(Debug, Clone, Copy, Arbitrary)]
+pub struct Numeric {
+    pub mantissa: i64,
+    #[proptest(strategy = "0..=MAX_NUMERIC_SCALE")]
+    pub scale: u8,
+}
+
+impl Numeric {
+    pub fn new(mantissa: i64, scale: u8) -> Self {
+        assert!(scale <= MAX_NUMERIC_SCALE, "numeric scale too large");
+        Numeric { mantissa, scale }
+    }
+
+    /// The value scaled to `MAX_NUMERIC_SCALE`. Always fits, since `i64::MAX * 10^18 < i128::MAX`.
+    pub fn normalized(&self) -> i128 {
+        self.mantissa as i128 * 10i128.pow((MAX_NUMERIC_SCALE - self.scale) as u32)
+    }
+
+    /// Inverse of `normalized`, with the smallest scale which represents the value exactly.
+    /// Returns None if the value doesn't fit.
+    pub fn from_normalized(mut value: i128) -> Option<Numeric> {
+        let mut scale = MAX_NUMERIC_SCALE;
+        while scale > 0 && value % 10 == 0 {
+            value /= 10;
+            scale -= 1;
+        }
+        if value < i64::MIN as i128 || value > i64::MAX as i128 {
+            return None;
+        }
+        Some(Numeric::new(value as i64, scale))
+    }
+}
+
+impl Ord for Numeric {
+    fn cmp(&self, other: &Numeric) -> Ordering {
+        self.normalized().cmp(&other.normalized())
+    }
+}
+
+impl PartialOrd for Numeric {
+    fn partial_cmp(&self, other: &Numeric) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Numeric {
+    fn eq(&self, other: &Numeric) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
 }
 
+impl Eq for Numeric {}
+
+/// Binary serialization of single values.
+///
+/// Format: for nullable values, a marker byte (0 for NULL, 1 otherwise); then the value, unless
+/// it's NULL. Integers (including `Date`, `Timestamp` and the `Numeric` mantissa) and float bits
+/// are little-endian, `Bool` is one byte (0 or 1), `String` and `Bytea` are the length (`u32`)
+/// followed by the bytes, and `Numeric` is the mantissa followed by the scale (one byte).
 pub mod serialize {
     use super::*;
-    use std::{mem, slice};
+    use std::fmt;
+
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    pub enum DecodeError {
+        /// The input ended, while `needed` more bytes were expected at `offset`.
+        UnexpectedEof {
+            offset: usize,
+            needed: usize,
+        },
+        InvalidNullMarker {
+            offset: usize,
+            value: u8,
+        },
+        InvalidBool {
+            offset: usize,
+            value: u8,
+        },
+        InvalidUtf8 {
+            offset: usize,
+        },
+        InvalidNumericScale {
+            offset: usize,
+            value: u8,
+        },
+        /// An offset of a variable-size value in a row (see `row`) is out of order.
+        InvalidOffset {
+            offset: usize,
+            value: u32,
+        },
+    }
+
+    impl fmt::Display for DecodeError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                DecodeError::UnexpectedEof { offset, needed } => write!(
+                    f,
+                    "unexpected end of input at offset {} (expected {} more bytes)",
+                    offset, needed
+                ),
+                DecodeError::InvalidNullMarker { offset, value } => {
+                    write!(f, "invalid null marker {} at offset {}", value, offset)
+                }
+                DecodeError::InvalidBool { offset, value } => {
+                    write!(f, "invalid bool {} at offset {}", value, offset)
+                }
+                DecodeError::InvalidUtf8 { offset } => {
+                    write!(f, "invalid UTF-8 in string at offset {}", offset)
+                }
+                DecodeError::InvalidNumericScale { offset, value } => {
+                    write!(f, "invalid numeric scale {} at offset {}", value, offset)
+                }
+                DecodeError::InvalidOffset { offset, value } => {
+                    write!(f, "invalid value offset {} at offset {}", value, offset)
+                }
+            }
+        }
+    }
+
+    pub type Result<T> = std::result::Result<T, DecodeError>;
 
     pub struct Reader<'a> {
         data: &'a [u8],
@@ -57,34 +249,52 @@ pub mod serialize {
             &self.data[self.offset..]
         }
 
-        pub fn advance(&mut self, len: usize) {
+        /// Number of bytes read so far.
+        pub fn offset(&self) -> usize {
+            self.offset
+        }
+
+        pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+            if self.data.len() - self.offset < len {
+                return Err(DecodeError::UnexpectedEof {
+                    offset: self.offset,
+                    needed: len - (self.data.len() - self.offset),
+                });
+            }
+            let bytes = &self.data[self.offset..self.offset + len];
             self.offset += len;
+            Ok(bytes)
         }
 
-        #[allow(clippy::cast_ptr_alignment)]
-        unsafe fn read<T: Copy>(&mut self) -> T {
-            let len = mem::size_of::<T>();
-            let ptr = self.data[self.offset..self.offset + len].as_ptr() as *const T;
-            self.advance(len);
-            ptr.read_unaligned()
+        fn read_array<A: AsMut<[u8]> + Default>(&mut self) -> Result<A> {
+            let mut array = A::default();
+            let len = array.as_mut().len();
+            array.as_mut().copy_from_slice(self.read_bytes(len)?);
+            Ok(array)
         }
 
-        pub fn read_u32(&mut self) -> u32 {
-            unsafe { self.read() }
+        pub fn read_u32(&mut self) -> Result<u32> {
+            Ok(u32::from_le_bytes(self.read_array()?))
         }
 
-        pub fn read_u8(&mut self) -> u8 {
-            unsafe { self.read() }
+        pub fn read_u8(&mut self) -> Result<u8> {
+            Ok(self.read_bytes(1)?[0])
         }
 
-        pub fn read_i64(&mut self) -> i64 {
-            unsafe { self.read() }
+        pub fn read_i64(&mut self) -> Result<i64> {
+            Ok(i64::from_le_bytes(self.read_array()?))
         }
 
-        pub fn read_bytes(&mut self, len: usize) -> &'a [u8] {
-            let ptr = &self.data[self.offset..self.offset + len];
-            self.advance(len);
-            ptr
+        pub fn read_i16(&mut self) -> Result<i16> {
+            Ok(i16::from_le_bytes(self.read_array()?))
+        }
+
+        pub fn read_i32(&mut self) -> Result<i32> {
+            Ok(i32::from_le_bytes(self.read_array()?))
+        }
+
+        pub fn read_f64(&mut self) -> Result<f64> {
+            Ok(f64::from_bits(u64::from_le_bytes(self.read_array()?)))
         }
     }
 
@@ -107,23 +317,28 @@ pub mod serialize {
             &self.data
         }
 
-        #[allow(clippy::cast_ptr_alignment)]
-        unsafe fn write<T: Copy>(&mut self, val: T) {
-            let len = mem::size_of::<T>();
-            let ptr = &val as *const T as *const u8;
-            self.data.extend_from_slice(slice::from_raw_parts(ptr, len));
-        }
-
         pub fn write_u32(&mut self, val: u32) {
-            unsafe { self.write(val) }
+            self.write_bytes(&val.to_le_bytes())
         }
 
         pub fn write_i64(&mut self, val: i64) {
-            unsafe { self.write(val) }
+            self.write_bytes(&val.to_le_bytes())
         }
 
         pub fn write_u8(&mut self, val: u8) {
-            unsafe { self.write(val) }
+            self.data.push(val)
+        }
+
+        pub fn write_i16(&mut self, val: i16) {
+            self.write_bytes(&val.to_le_bytes())
+        }
+
+        pub fn write_i32(&mut self, val: i32) {
+            self.write_bytes(&val.to_le_bytes())
+        }
+
+        pub fn write_f64(&mut self, val: f64) {
+            self.write_bytes(&val.to_bits().to_le_bytes())
         }
 
         pub fn write_bytes(&mut self, b: &[u8]) {
@@ -131,52 +346,367 @@ pub mod serialize {
         }
     }
 
+    const NULL: u8 = 0;
+    const NOT_NULL: u8 = 1;
+
     impl Datum {
-        pub fn deserialize(r: &mut Reader, n: Nullability, ty: Type) -> Datum {
+        /// Read a value written by `serialize` with the same nullability, and of the given type.
+        pub fn deserialize(r: &mut Reader, n: Nullability, ty: Type) -> Result<Datum> {
             if n == Nullable {
-                let is_null = r.read_u8() == 0;
-                if is_null {
-                    return Datum::Null;
+                let offset = r.offset();
+                match r.read_u8()? {
+                    NULL => return Ok(Datum::Null),
+                    NOT_NULL => {}
+                    value => return Err(DecodeError::InvalidNullMarker { offset, value }),
                 }
             }
-            match ty {
+            let offset = r.offset();
+            Ok(match ty {
                 Type::String => {
-                    let len = r.read_u32() as usize;
-                    let bytes = r.read_bytes(len);
-                    Datum::String(String::from_utf8_lossy(bytes).into_owned())
+                    let len = r.read_u32()? as usize;
+                    let bytes = r.read_bytes(len)?;
+                    let s = std::str::from_utf8(bytes)
+                        .map_err(|_| DecodeError::InvalidUtf8 { offset: offset + 4 })?;
+                    Datum::String(s.to_string())
                 }
-                Type::Int8 => Datum::Int8(r.read_i64()),
-                Type::Bool => Datum::Bool(r.read_u8() > 0),
-            }
+                Type::Int8 => Datum::Int8(r.read_i64()?),
+                Type::Bool => match r.read_u8()? {
+                    0 => Datum::Bool(false),
+                    1 => Datum::Bool(true),
+                    value => return Err(DecodeError::InvalidBool { offset, value }),
+                },
+                Type::Int2 => Datum::Int2(r.read_i16()?),
+                Type::Int4 => Datum::Int4(r.read_i32()?),
+                Type::Float8 => Datum::Float8(r.read_f64()?),
+                Type::Bytea => {
+                    let len = r.read_u32()? as usize;
+                    Datum::Bytea(r.read_bytes(len)?.to_vec())
+                }
+                Type::Date => Datum::Date(r.read_i32()?),
+                Type::Timestamp => Datum::Timestamp(r.read_i64()?),
+                Type::Numeric => {
+                    let mantissa = r.read_i64()?;
+                    match r.read_u8()? {
+                        scale if scale <= MAX_NUMERIC_SCALE => {
+                            Datum::Numeric(Numeric::new(mantissa, scale))
+                        }
+                        value => {
+                            return Err(DecodeError::InvalidNumericScale {
+                                offset: offset + 8,
+                                value,
+                            })
+                        }
+                    }
+                }
+            })
         }
 
         pub fn serialize(&self, w: &mut Writer, n: Nullability) {
-            const NOT_NULL: u8 = 1;
+            if *self == Datum::Null {
+                assert_eq!(n, Nullable);
+                w.write_u8(NULL);
+                return;
+            }
+            if n == Nullable {
+                w.write_u8(NOT_NULL);
+            }
             match self {
                 Datum::String(s) => {
-                    if n == Nullable {
-                        w.write_u8(NOT_NULL);
-                    }
                     w.write_u32(s.len() as u32);
                     w.write_bytes(s.as_bytes());
                 }
-                Datum::Int8(v) => {
-                    if n == Nullable {
-                        w.write_u8(NOT_NULL);
-                    }
-                    w.write_i64(*v);
+                Datum::Int8(v) => w.write_i64(*v),
+                Datum::Bool(v) => w.write_u8(*v as u8),
+                Datum::Int2(v) => w.write_i16(*v),
+                Datum::Int4(v) => w.write_i32(*v),
+                Datum::Float8(v) => w.write_f64(*v),
+                Datum::Bytea(b) => {
+                    w.write_u32(b.len() as u32);
+                    w.write_bytes(b);
+                }
+                Datum::Date(v) => w.write_i32(*v),
+                Datum::Timestamp(v) => w.write_i64(*v),
+                Datum::Numeric(v) => {
+                    w.write_i64(v.mantissa);
+                    w.write_u8(v.scale);
                 }
-                Datum::Bool(v) => {
-                    if n == Nullable {
-                        w.write_u8(NOT_NULL);
+                Datum::Null => unreachable!(),
+            }
+        }
+    }
+}
+
+/// Text representation of values, as used in SQL literals and query output.
+///
+/// - `Bool`: `true` or `false` (`t`, `f` and any case are accepted when parsing),
+/// - `Float8`: decimal, or `NaN`, `Infinity`, `-Infinity`,
+/// - `Bytea`: `\x` followed by two hex digits per byte,
+/// - `Date`: `YYYY-MM-DD`,
+/// - `Timestamp`: `YYYY-MM-DD HH:MM:SS`, with up to 6 fractional digits of seconds (trailing zeros
+///   are omitted). A date alone is parsed as midnight.
+/// - `Numeric`: decimal with `scale` fractional digits,
+/// - NULL is formatted as `NULL`, but never parsed.
+pub mod text {
+    use super::*;
+    use std::fmt;
+
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    pub struct ParseError {
+        pub ty: Type,
+        pub input: String,
+    }
+
+    impl fmt::Display for ParseError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "invalid input for type {:?}: {:?}", self.ty, self.input)
+        }
+    }
+
+    impl Datum {
+        pub fn parse(ty: Type, s: &str) -> Result<Datum, ParseError> {
+            parse(ty, s).ok_or_else(|| ParseError {
+                ty,
+                input: s.into(),
+            })
+        }
+    }
+
+    fn parse(ty: Type, s: &str) -> Option<Datum> {
+        Some(match ty {
+            Type::String => Datum::String(s.into()),
+            Type::Int8 => Datum::Int8(s.parse().ok()?),
+            Type::Int2 => Datum::Int2(s.parse().ok()?),
+            Type::Int4 => Datum::Int4(s.parse().ok()?),
+            Type::Bool => match s.to_ascii_lowercase().as_str() {
+                "t" | "true" => Datum::Bool(true),
+                "f" | "false" => Datum::Bool(false),
+                _ => return None,
+            },
+            Type::Float8 => Datum::Float8(match s {
+                "NaN" => f64::NAN,
+                "Infinity" => f64::INFINITY,
+                "-Infinity" => f64::NEG_INFINITY,
+                // Rust's parser also accepts "inf" and "nan", which we don't
+                _ if s
+                    .bytes()
+                    .any(|b| b.is_ascii_alphabetic() && b != b'e' && b != b'E') =>
+                {
+                    return None
+                }
+                _ => s.parse().ok()?,
+            }),
+            Type::Bytea => Datum::Bytea(parse_bytea(s)?),
+            Type::Date => Datum::Date(parse_date(s)?),
+            Type::Timestamp => Datum::Timestamp(parse_timestamp(s)?),
+            Type::Numeric => Datum::Numeric(parse_numeric(s)?),
+        })
+    }
+
+    fn parse_bytea(s: &str) -> Option<Vec<u8>> {
+        if !s.starts_with("\\x") || s.len() % 2 != 0 {
+            return None;
+        }
+        (2..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+            .collect()
+    }
+
+    /// Parse a number of exactly `len` decimal digits.
+    fn parse_digits(s: &str, len: usize) -> Option<i64> {
+        if s.len() != len || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s.parse().ok()
+    }
+
+    fn parse_date(s: &str) -> Option<i32> {
+        let mut parts = s.splitn(3, '-');
+        let year = parse_digits(parts.next()?, 4)?;
+        let month = parse_digits(parts.next()?, 2)?;
+        let day = parse_digits(parts.next()?, 2)?;
+        if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+            return None;
+        }
+        Some(days_from_civil(year, month, day) as i32)
+    }
+
+    fn parse_timestamp(s: &str) -> Option<i64> {
+        let (date, time) = match s.find(&[' ', 'T'][..]) {
+            Some(index) => (&s[..index], &s[index + 1..]),
+            None => (s, "00:00:00"),
+        };
+        let (time, fraction) = match time.find('.') {
+            Some(index) => (&time[..index], &time[index + 1..]),
+            None => (time, ""),
+        };
+        let mut parts = time.splitn(3, ':');
+        let hour = parse_digits(parts.next()?, 2)?;
+        let minute = parse_digits(parts.next()?, 2)?;
+        let second = parse_digits(parts.next()?, 2)?;
+        if hour > 23 || minute > 59 || second > 59 || fraction.len() > 6 {
+            return None;
+        }
+        let micros = if fraction.is_empty() {
+            0
+        } else {
+            parse_digits(fraction, fraction.len())? * 10i64.pow(6 - fraction.len() as u32)
+        };
+        Some(
+            parse_date(date)? as i64 * MICROS_PER_DAY
+                + ((hour * 60 + minute) * 60 + second) * 1_000_000
+                + micros,
+        )
+    }
+
+    fn parse_numeric(s: &str) -> Option<Numeric> {
+        let (integer, fraction) = match s.find('.') {
+            Some(index) => (&s[..index], &s[index + 1..]),
+            None => (s, ""),
+        };
+        let (negative, integer) = match integer.as_bytes().first() {
+            Some(b'-') => (true, &integer[1..]),
+            Some(b'+') => (false, &integer[1..]),
+            _ => (false, integer),
+        };
+        let digits = || integer.bytes().chain(fraction.bytes());
+        if fraction.len() > MAX_NUMERIC_SCALE as usize
+            || integer.is_empty() && fraction.is_empty()
+            || !digits().all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        let mut mantissa: i64 = 0;
+        for digit in digits() {
+            let digit = (digit - b'0') as i64;
+            mantissa = mantissa.checked_mul(10)?;
+            mantissa = if negative {
+                mantissa.checked_sub(digit)?
+            } else {
+                mantissa.checked_add(digit)?
+            };
+        }
+        Some(Numeric::new(mantissa, fraction.len() as u8))
+    }
+
+    fn is_leap_year(year: i64) -> bool {
+        year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+    }
+
+    fn days_in_month(year: i64, month: i64) -> i64 {
+        match month {
+            2 if is_leap_year(year) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    // Conversion between dates and day numbers, from
+    // http://howardhinnant.github.io/date_algorithms.html
+
+    /// Number of days since 1970-01-01 of the given date (in the proleptic Gregorian calendar).
+    fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = if year >= 0 { year } else { year - 399 } / 400;
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    /// Inverse of `days_from_civil`.
+    fn civil_from_days(days: i64) -> (i64, i64, i64) {
+        let days = days + 719_468;
+        let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400;
+        (if month <= 2 { year + 1 } else { year }, month, day)
+    }
+
+    fn fmt_date(f: &mut fmt::Formatter, days: i64) -> fmt::Result {
+        let (year, month, day) = civil_from_days(days);
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+
+    impl fmt::Display for Numeric {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            // i128, so that negating i64::MIN doesn't overflow
+            let abs = (self.mantissa as i128).abs();
+            let unit = 10i128.pow(self.scale as u32);
+            if self.mantissa < 0 {
+                write!(f, "-")?;
+            }
+            write!(f, "{}", abs / unit)?;
+            if self.scale > 0 {
+                write!(f, ".{:0width$}", abs % unit, width = self.scale as usize)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl fmt::Display for Datum {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Datum::String(s) => write!(f, "{}", s),
+                Datum::Int8(v) => write!(f, "{}", v),
+                Datum::Bool(v) => write!(f, "{}", v),
+                Datum::Int2(v) => write!(f, "{}", v),
+                Datum::Int4(v) => write!(f, "{}", v),
+                Datum::Float8(v) if v.is_nan() => write!(f, "NaN"),
+                Datum::Float8(v) if v.is_infinite() && *v > 0.0 => write!(f, "Infinity"),
+                Datum::Float8(v) if v.is_infinite() => write!(f, "-Infinity"),
+                Datum::Float8(v) => write!(f, "{}", v),
+                Datum::Bytea(bytes) => {
+                    write!(f, "\\x")?;
+                    for byte in bytes {
+                        write!(f, "{:02x}", byte)?;
                     }
-                    w.write_u8(*v as u8);
+                    Ok(())
                 }
-                Datum::Null => {
-                    assert_eq!(n, Nullable);
-                    w.write_u8(0);
+                Datum::Date(days) => fmt_date(f, *days as i64),
+                Datum::Timestamp(v) => {
+                    fmt_date(f, v.div_euclid(MICROS_PER_DAY))?;
+                    let micros = v.rem_euclid(MICROS_PER_DAY);
+                    let seconds = micros / 1_000_000;
+                    write!(
+                        f,
+                        " {:02}:{:02}:{:02}",
+                        seconds / 3600,
+                        seconds / 60 % 60,
+                        seconds % 60
+                    )?;
+                    let fraction = micros % 1_000_000;
+                    if fraction != 0 {
+                        let digits = format!("{:06}", fraction);
+                        write!(f, ".{}", digits.trim_end_matches('0'))?;
+                    }
+                    Ok(())
                 }
+                Datum::Numeric(v) => write!(f, "{}", v),
+                Datum::Null => write!(f, "NULL"),
             }
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn dates() {
+            assert_eq!(days_from_civil(1970, 1, 1), 0);
+            assert_eq!(days_from_civil(2000, 3, 1), 11017);
+            assert_eq!(days_from_civil(0, 1, 1), MIN_DATE as i64);
+            assert_eq!(days_from_civil(9999, 12, 31), MAX_DATE as i64);
+            assert_eq!(civil_from_days(-1), (1969, 12, 31));
+            assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        }
+    }
 }
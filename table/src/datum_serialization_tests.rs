@@ -1,6 +1,7 @@
 use crate::datum;
 use crate::hexdump::pretty_hex;
-use datum::{Datum, NotNull, Nullability, Nullable, Type};
+use datum::serialize::DecodeError;
+use datum::{Datum, NotNull, Nullability, Nullable, Numeric, Type};
 use proptest::prelude::*;
 
 #[test]
@@ -44,6 +45,13 @@ fn serialize_null() {
     assert_snapshot!(pretty_hex(&w.data()), @"0000:   00                                                   .");
 }
 
+#[test]
+fn serialize_numeric() {
+    let mut w = datum::serialize::Writer::new();
+    Datum::Numeric(Numeric::new(-12050, 3)).serialize(&mut w, Nullable);
+    assert_snapshot!(pretty_hex(&w.data()), @"0000:   01 ee d0 ff  ff ff ff ff  ff 03                      ..........");
+}
+
 proptest! {
     #![proptest_config(ProptestConfig {
         cases: 1000, ..ProptestConfig::default()
@@ -58,7 +66,7 @@ proptest! {
             n,
             d.ty().unwrap_or(Type::Bool),
         );
-        prop_assert_eq!(&d, &d2, "n={:?}, ty={:?}", n, d.ty());
+        prop_assert_eq!(Ok(d.clone()), d2, "n={:?}, ty={:?}", n, d.ty());
     }
 
     #[test]
@@ -74,11 +82,71 @@ proptest! {
             .map(|(d, n0)| {
                 let n = if *d == Datum::Null { Nullable } else { *n0 };
                 (
-                    Datum::deserialize(&mut reader, n, d.ty().unwrap_or(Type::Bool)),
+                    Datum::deserialize(&mut reader, n, d.ty().unwrap_or(Type::Bool)).unwrap(),
                     *n0,
                 )
             })
             .collect::<Vec<_>>();
         prop_assert_eq!(&tuple, &tuple2);
+        prop_assert!(reader.data().is_empty());
+    }
+
+    /// Decoding arbitrary input never panics, and whatever is decoded successfully is encoded back
+    /// to the same bytes (so there's only one valid encoding of each value).
+    #[test]
+    fn deserialize_arbitrary_bytes(data: Vec<u8>, n: Nullability, ty: Type) {
+        let mut reader = datum::serialize::Reader::new(&data);
+        if let Ok(d) = Datum::deserialize(&mut reader, n, ty) {
+            let mut w = datum::serialize::Writer::new();
+            d.serialize(&mut w, n);
+            prop_assert_eq!(w.data(), &data[..reader.offset()]);
+        }
     }
 }
+
+fn deserialize(data: &[u8], n: Nullability, ty: Type) -> Result<Datum, DecodeError> {
+    Datum::deserialize(&mut datum::serialize::Reader::new(data), n, ty)
+}
+
+#[test]
+fn deserialize_errors() {
+    assert_eq!(
+        deserialize(&[1, 2, 3], NotNull, Type::Int8),
+        Err(DecodeError::UnexpectedEof {
+            offset: 0,
+            needed: 5
+        })
+    );
+    assert_eq!(
+        deserialize(&[5, 0, 0, 0, b'a'], NotNull, Type::String),
+        Err(DecodeError::UnexpectedEof {
+            offset: 4,
+            needed: 4
+        })
+    );
+    assert_eq!(
+        deserialize(&[1, 2, 0, 0, 0, 0xc4, 0x28], Nullable, Type::String),
+        Err(DecodeError::InvalidUtf8 { offset: 5 })
+    );
+    assert_eq!(
+        deserialize(&[2], Nullable, Type::Bool),
+        Err(DecodeError::InvalidNullMarker {
+            offset: 0,
+            value: 2
+        })
+    );
+    assert_eq!(
+        deserialize(&[1, 2], Nullable, Type::Bool),
+        Err(DecodeError::InvalidBool {
+            offset: 1,
+            value: 2
+        })
+    );
+    assert_eq!(
+        deserialize(&[0, 0, 0, 0, 0, 0, 0, 0, 19], NotNull, Type::Numeric),
+        Err(DecodeError::InvalidNumericScale {
+            offset: 8,
+            value: 19
+        })
+    );
+}
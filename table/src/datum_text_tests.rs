@@ -0,0 +1,115 @@
+use crate::datum::text::ParseError;
+use crate::datum::{Datum, Numeric, Type};
+use proptest::prelude::*;
+use std::cmp::Ordering;
+
+fn parse(ty: Type, s: &str) -> Datum {
+    Datum::parse(ty, s).unwrap()
+}
+
+#[test]
+fn parse_and_format() {
+    let cases: &[(Type, &str, Datum)] = &[
+        (Type::Int2, "-32768", Datum::Int2(i16::MIN)),
+        (Type::Int4, "123", Datum::Int4(123)),
+        (Type::Bool, "true", Datum::Bool(true)),
+        (Type::Float8, "1.5", Datum::Float8(1.5)),
+        (Type::Float8, "-Infinity", Datum::Float8(f64::NEG_INFINITY)),
+        (Type::Bytea, "\\x00ff10", Datum::Bytea(vec![0, 0xff, 0x10])),
+        (Type::Date, "2020-02-29", Datum::Date(18321)),
+        (Type::Date, "1969-12-31", Datum::Date(-1)),
+        (
+            Type::Timestamp,
+            "2020-02-29 12:34:56",
+            Datum::Timestamp(18321 * 86_400_000_000 + 45_296_000_000),
+        ),
+        (
+            Type::Timestamp,
+            "1970-01-01 00:00:00.25",
+            Datum::Timestamp(250_000),
+        ),
+        (
+            Type::Numeric,
+            "-12.050",
+            Datum::Numeric(Numeric::new(-12050, 3)),
+        ),
+        (Type::Numeric, "0.5", Datum::Numeric(Numeric::new(5, 1))),
+    ];
+    for (ty, text, datum) in cases {
+        assert_eq!(&parse(*ty, text), datum, "parsing {:?}", text);
+        assert_eq!(&datum.to_string(), text);
+    }
+}
+
+#[test]
+fn parse_variants() {
+    assert_eq!(parse(Type::Bool, "F"), Datum::Bool(false));
+    assert_eq!(parse(Type::Float8, "1e3"), Datum::Float8(1000.0));
+    assert_eq!(
+        parse(Type::Timestamp, "2020-01-01"),
+        parse(Type::Timestamp, "2020-01-01T00:00:00")
+    );
+    assert_eq!(
+        parse(Type::Numeric, "+.5"),
+        Datum::Numeric(Numeric::new(5, 1))
+    );
+}
+
+#[test]
+fn parse_errors() {
+    let cases: &[(Type, &str)] = &[
+        (Type::Int2, "32768"),
+        (Type::Bool, "yes"),
+        (Type::Float8, "inf"),
+        (Type::Bytea, "\\x0"),
+        (Type::Bytea, "00"),
+        (Type::Date, "2019-02-29"),
+        (Type::Date, "2020-1-1"),
+        (Type::Timestamp, "2020-01-01 24:00:00"),
+        (Type::Timestamp, "2020-01-01 00:00:00.1234567"),
+        (Type::Numeric, "1.2.3"),
+        (Type::Numeric, "."),
+        (Type::Numeric, "99999999999999999999"),
+    ];
+    for (ty, text) in cases {
+        assert_eq!(
+            Datum::parse(*ty, text),
+            Err(ParseError {
+                ty: *ty,
+                input: text.to_string()
+            })
+        );
+    }
+}
+
+#[test]
+fn compare() {
+    let nan = Datum::Float8(f64::NAN);
+    assert_eq!(nan, nan);
+    assert_eq!(
+        Datum::Float8(f64::INFINITY).compare(&nan),
+        Some(Ordering::Less)
+    );
+    assert_eq!(Datum::Float8(-0.0), Datum::Float8(0.0));
+    assert_eq!(
+        Datum::Numeric(Numeric::new(150, 2)),
+        Datum::Numeric(Numeric::new(15, 1))
+    );
+    assert_eq!(
+        Datum::Numeric(Numeric::new(-1, 18)).compare(&Datum::Numeric(Numeric::new(0, 0))),
+        Some(Ordering::Less)
+    );
+    assert_eq!(Datum::Int4(1).compare(&Datum::Int8(1)), None);
+    assert_eq!(Datum::Null.compare(&Datum::Null), None);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 1000, ..ProptestConfig::default()
+    })]
+    #[test]
+    fn text_roundtrip(d: Datum) {
+        prop_assume!(d != Datum::Null);
+        prop_assert_eq!(Datum::parse(d.ty().unwrap(), &d.to_string()), Ok(d));
+    }
+}
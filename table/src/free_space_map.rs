@@ -0,0 +1,423 @@
+//! Free space map: records approximately how much free space each page of a table heap has, so that
+//! inserts can go straight to a page with enough room instead of trying every page.
+//!
+//! Each heap page has an ordinal - its position in the heap's list of pages - and its entry
+//! `(heap page id, category)` is stored at that position in the map, so it can be updated without
+//! a search. Entries are stored on leaf pages, `ENTRIES_PER_LEAF` per page. The root page lists the
+//! leaf pages, each with the highest category on it, so that `find` only reads leaves with enough
+//! space. It also stores the number of heap pages and the id of the last one.
+//!
+//! The highest category of a leaf is raised right after updating an entry, but it's only lowered by
+//! `find`, when it reads a leaf without enough space - so it may overestimate until then.
+//!
+//! Free space is stored as a category (one byte) in units of `PAGE_SIZE / 256` bytes, rounded
+//! down, so the map may underestimate free space, but never overestimates it (as long as it's up to
+//! date).
+//!
+//! Latching: the root page is never latched while a leaf page is write-latched, so `update` latches
+//! them one after another, and `find` can read leaves while holding the root.
+//!
+//! Page format:
+//! ```text
+//! Root page:
+//! -----------------------------------------------------------------------------------------
+//! | PageCount (4) | LastPageId (4) | LeafPageId_1 (4) | MaxCategory_1 (1) | ...
+//! -----------------------------------------------------------------------------------------
+//!
+//! Leaf page:
+//! --------------------------------------------------------------
+//! | HeapPageId_1 (4) | Category_1 (1) | HeapPageId_2 (4) | ...
+//! --------------------------------------------------------------
+//! ```
+//! Entries of heap pages not recorded yet have an invalid page id.
+
+use crate::table_heap::{Error, Result};
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager::{PageData, PageId, INVALID_PAGE_ID, PAGE_SIZE};
+use std::ops::{Deref, DerefMut};
+
+const OFFSET_PAGE_COUNT: usize = 0x00;
+const OFFSET_LAST_PAGE_ID: usize = 0x04;
+const OFFSET_LEAVES: usize = 0x08;
+const SIZE_ENTRY: usize = 5;
+
+/// Number of entries on a leaf page.
+const ENTRIES_PER_LEAF: usize = PAGE_SIZE / SIZE_ENTRY;
+
+/// Maximum number of leaf pages.
+const MAX_LEAVES: usize = (PAGE_SIZE - OFFSET_LEAVES) / SIZE_ENTRY;
+
+/// Maximum number of pages of a table heap.
+pub const MAX_PAGES: usize = MAX_LEAVES * ENTRIES_PER_LEAF;
+
+/// Number of bytes of free space per category.
+const CATEGORY_SIZE: usize = PAGE_SIZE / 256;
+
+/// Category of a page with the given amount of free space.
+fn category(free_space: usize) -> u8 {
+    (free_space / CATEGORY_SIZE).min(u8::MAX as usize) as u8
+}
+
+/// Minimum category of a page which surely has `size` bytes of free space.
+fn required_category(size: usize) -> usize {
+    (size + CATEGORY_SIZE - 1) / CATEGORY_SIZE
+}
+
+pub struct FreeSpaceMap<'b> {
+    buffer_pool: &'b BufferPool,
+    root_page_id: PageId,
+}
+
+impl<'b> FreeSpaceMap<'b> {
+    /// Create a new, empty map.
+    pub async fn new(buffer_pool: &'b BufferPool) -> Result<FreeSpaceMap<'b>> {
+        let page = buffer_pool.allocate_page().await?;
+        MapPage::new_root(page.data().write().await);
+        page.dirty();
+        Ok(FreeSpaceMap {
+            buffer_pool,
+            root_page_id: page.id(),
+        })
+    }
+
+    pub fn from_existing(buffer_pool: &'b BufferPool, root_page_id: PageId) -> Self {
+        FreeSpaceMap {
+            buffer_pool,
+            root_page_id,
+        }
+    }
+
+    pub fn root_page_id(&self) -> PageId {
+        self.root_page_id
+    }
+
+    /// Returns the first page (in heap order) with at least `size` bytes of free space, other than
+    /// `exclude`.
+    pub async fn find(&self, size: usize, exclude: Option<PageId>) -> Result<Option<PageId>> {
+        let required = required_category(size);
+        let mut stale_leaves = vec![];
+        let mut found = None;
+        {
+            let root_page = self.buffer_pool.get_page(self.root_page_id).await?;
+            let root = MapPage::from_existing(root_page.data().read().await);
+            for leaf_index in 0..root.leaf_count() {
+                let (leaf_page_id, max_category) = root.leaf(leaf_index);
+                if (max_category as usize) < required {
+                    continue;
+                }
+                let leaf_page = self.buffer_pool.get_page(leaf_page_id).await?;
+                let leaf = MapPage::from_existing(leaf_page.data().read().await);
+                found = leaf.entries().find(|&(page_id, category)| {
+                    page_id.is_valid() && category as usize >= required && Some(page_id) != exclude
+                });
+                if found.is_some() {
+                    break;
+                }
+                if leaf.max_category() < max_category {
+                    stale_leaves.push(leaf_index);
+                }
+            }
+        }
+        for leaf_index in stale_leaves {
+            self.lower_max_category(leaf_index).await?;
+        }
+        Ok(found.map(|(page_id, _)| page_id))
+    }
+
+    /// Returns the last heap page recorded in the map.
+    pub async fn last_page_id(&self) -> Result<Option<PageId>> {
+        let root_page = self.buffer_pool.get_page(self.root_page_id).await?;
+        let root = MapPage::from_existing(root_page.data().read().await);
+        Ok(if root.page_count() > 0 {
+            Some(root.last_page_id())
+        } else {
+            None
+        })
+    }
+
+    /// Record the amount of free space on the heap page with the given ordinal.
+    pub async fn update(&self, ordinal: usize, page_id: PageId, free_space: usize) -> Result<()> {
+        let category = category(free_space);
+        let leaf_index = ordinal / ENTRIES_PER_LEAF;
+        let leaf_page_id = {
+            let root_page = self.buffer_pool.get_page(self.root_page_id).await?;
+            let root = MapPage::from_existing(root_page.data().read().await);
+            if ordinal < root.page_count() {
+                Some(root.leaf(leaf_index).0)
+            } else {
+                None
+            }
+        };
+        let leaf_page_id = match leaf_page_id {
+            Some(leaf_page_id) => leaf_page_id,
+            None => self.append(ordinal, page_id).await?,
+        };
+
+        {
+            let leaf_page = self.buffer_pool.get_page(leaf_page_id).await?;
+            let mut leaf = MapPage::from_existing(leaf_page.data().write().await);
+            let index = ordinal % ENTRIES_PER_LEAF;
+            if leaf.entry(index) == (page_id, category) {
+                return Ok(());
+            }
+            leaf.set_entry(index, page_id, category);
+            leaf_page.dirty();
+        }
+
+        // The maximum is checked only after the leaf is updated, so that a concurrent `find` either
+        // sees the new entry when lowering it, or lowers it before it's raised here.
+        let needs_raise = {
+            let root_page = self.buffer_pool.get_page(self.root_page_id).await?;
+            let root = MapPage::from_existing(root_page.data().read().await);
+            root.leaf(leaf_index).1 < category
+        };
+        if needs_raise {
+            let root_page = self.buffer_pool.get_page(self.root_page_id).await?;
+            let mut root = MapPage::from_existing(root_page.data().write().await);
+            if root.leaf(leaf_index).1 < category {
+                root.set_leaf(leaf_index, leaf_page_id, category);
+                root_page.dirty();
+            }
+        }
+        Ok(())
+    }
+
+    /// Extend the map to include the heap page with the given ordinal. Returns its leaf page.
+    async fn append(&self, ordinal: usize, page_id: PageId) -> Result<PageId> {
+        let leaf_index = ordinal / ENTRIES_PER_LEAF;
+        if leaf_index >= MAX_LEAVES {
+            return Err(Error::TableFull);
+        }
+        let root_page = self.buffer_pool.get_page(self.root_page_id).await?;
+        let mut root = MapPage::from_existing(root_page.data().write().await);
+        // Leaves may have been added concurrently.
+        for new_leaf_index in root.leaf_count()..=leaf_index {
+            let new_page = self.buffer_pool.allocate_page().await?;
+            MapPage::new_leaf(new_page.data().write().await);
+            new_page.dirty();
+            root.set_leaf(new_leaf_index, new_page.id(), 0);
+        }
+        if ordinal >= root.page_count() {
+            root.set_page_count(ordinal + 1);
+            root.set_last_page_id(page_id);
+        }
+        root_page.dirty();
+        Ok(root.leaf(leaf_index).0)
+    }
+
+    /// Recompute the highest category of a leaf, after `find` found it to be too high.
+    async fn lower_max_category(&self, leaf_index: usize) -> Result<()> {
+        let root_page = self.buffer_pool.get_page(self.root_page_id).await?;
+        let mut root = MapPage::from_existing(root_page.data().write().await);
+        let (leaf_page_id, max_category) = root.leaf(leaf_index);
+        let leaf_page = self.buffer_pool.get_page(leaf_page_id).await?;
+        let new_max_category = MapPage::from_existing(leaf_page.data().read().await).max_category();
+        if new_max_category != max_category {
+            root.set_leaf(leaf_index, leaf_page_id, new_max_category);
+            root_page.dirty();
+        }
+        Ok(())
+    }
+}
+
+struct MapPage<T> {
+    data: T,
+}
+
+impl<T: Deref<Target = PageData>> MapPage<T> {
+    fn from_existing(data: T) -> Self {
+        MapPage { data }
+    }
+
+    // Root page
+
+    fn page_count(&self) -> usize {
+        self.read_u32(OFFSET_PAGE_COUNT) as usize
+    }
+
+    fn last_page_id(&self) -> PageId {
+        PageId(self.read_u32(OFFSET_LAST_PAGE_ID))
+    }
+
+    fn leaf_count(&self) -> usize {
+        (self.page_count() + ENTRIES_PER_LEAF - 1) / ENTRIES_PER_LEAF
+    }
+
+    /// Returns the leaf page id and the highest category on it.
+    fn leaf(&self, index: usize) -> (PageId, u8) {
+        self.read_entry(OFFSET_LEAVES + index * SIZE_ENTRY)
+    }
+
+    // Leaf page
+
+    fn entry(&self, index: usize) -> (PageId, u8) {
+        self.read_entry(index * SIZE_ENTRY)
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (PageId, u8)> + '_ {
+        (0..ENTRIES_PER_LEAF).map(move |index| self.entry(index))
+    }
+
+    fn max_category(&self) -> u8 {
+        self.entries()
+            .filter(|(page_id, _)| page_id.is_valid())
+            .map(|(_, category)| category)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn read_entry(&self, offset: usize) -> (PageId, u8) {
+        (PageId(self.read_u32(offset)), self.data[offset + 4])
+    }
+
+    #[allow(clippy::cast_ptr_alignment)]
+    fn read_u32(&self, offset: usize) -> u32 {
+        let ptr = self.data[offset..].as_ptr() as *const u32;
+        unsafe { ptr.read_unaligned() }
+    }
+}
+
+impl<T: DerefMut<Target = PageData>> MapPage<T> {
+    fn new_root(data: T) -> Self {
+        let mut page = MapPage { data };
+        page.set_page_count(0);
+        page.set_last_page_id(INVALID_PAGE_ID);
+        page
+    }
+
+    fn new_leaf(data: T) -> Self {
+        let mut page = MapPage { data };
+        for index in 0..ENTRIES_PER_LEAF {
+            page.set_entry(index, INVALID_PAGE_ID, 0);
+        }
+        page
+    }
+
+    fn set_page_count(&mut self, value: usize) {
+        self.write_u32(OFFSET_PAGE_COUNT, value as u32);
+    }
+
+    fn set_last_page_id(&mut self, value: PageId) {
+        self.write_u32(OFFSET_LAST_PAGE_ID, value.0);
+    }
+
+    fn set_leaf(&mut self, index: usize, page_id: PageId, max_category: u8) {
+        self.write_entry(OFFSET_LEAVES + index * SIZE_ENTRY, page_id, max_category);
+    }
+
+    fn set_entry(&mut self, index: usize, page_id: PageId, category: u8) {
+        self.write_entry(index * SIZE_ENTRY, page_id, category);
+    }
+
+    fn write_entry(&mut self, offset: usize, page_id: PageId, category: u8) {
+        self.write_u32(offset, page_id.0);
+        self.data[offset + 4] = category;
+    }
+
+    #[allow(clippy::cast_ptr_alignment)]
+    fn write_u32(&mut self, offset: usize, value: u32) {
+        let ptr = self.data[offset..].as_mut_ptr() as *mut u32;
+        unsafe {
+            ptr.write_unaligned(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use buffer_pool::disk_manager_mem::DiskManagerMem;
+
+    #[test]
+    fn categories() {
+        assert_eq!(category(0), 0);
+        assert_eq!(category(CATEGORY_SIZE - 1), 0);
+        assert_eq!(category(CATEGORY_SIZE), 1);
+        assert_eq!(category(PAGE_SIZE), u8::MAX);
+        assert_eq!(required_category(0), 0);
+        assert_eq!(required_category(1), 1);
+        assert_eq!(required_category(CATEGORY_SIZE), 1);
+        assert_eq!(required_category(CATEGORY_SIZE + 1), 2);
+    }
+
+    #[tokio::test]
+    async fn find_and_update() -> Result<()> {
+        let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+        let map = FreeSpaceMap::new(&buffer_pool).await?;
+        assert_eq!(map.find(0, None).await?, None);
+        assert_eq!(map.last_page_id().await?, None);
+
+        map.update(0, PageId(10), 100).await?;
+        map.update(1, PageId(11), 1000).await?;
+        map.update(2, PageId(12), 2000).await?;
+        assert_eq!(map.find(0, None).await?, Some(PageId(10)));
+        assert_eq!(map.find(500, None).await?, Some(PageId(11)));
+        assert_eq!(map.find(500, Some(PageId(11))).await?, Some(PageId(12)));
+        assert_eq!(map.find(3000, None).await?, None);
+        assert_eq!(map.last_page_id().await?, Some(PageId(12)));
+
+        map.update(1, PageId(11), 0).await?;
+        assert_eq!(map.find(500, None).await?, Some(PageId(12)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn many_pages() -> Result<()> {
+        let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+        let map = FreeSpaceMap::new(&buffer_pool).await?;
+        let n = ENTRIES_PER_LEAF * 2 + 10;
+        for i in 0..n {
+            map.update(i, PageId(100 + i as u32), 0).await?;
+        }
+        map.update(n - 5, PageId(100 + n as u32 - 5), 1000).await?;
+        assert_eq!(map.find(900, None).await?, Some(PageId(100 + n as u32 - 5)));
+        assert_eq!(map.last_page_id().await?, Some(PageId(100 + n as u32 - 1)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn table_full() -> Result<()> {
+        let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+        let map = FreeSpaceMap::new(&buffer_pool).await?;
+        map.update(MAX_PAGES - 1, PageId(100), 1000).await?;
+        assert!(matches!(
+            map.update(MAX_PAGES, PageId(101), 1000).await,
+            Err(Error::TableFull)
+        ));
+        assert_eq!(map.last_page_id().await?, Some(PageId(100)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn max_category_of_leaves() -> Result<()> {
+        let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+        let map = FreeSpaceMap::new(&buffer_pool).await?;
+        let n = ENTRIES_PER_LEAF * 2;
+        for i in 0..n {
+            map.update(i, PageId(100 + i as u32), 2000).await?;
+        }
+        let max_categories = || async {
+            let root_page = buffer_pool.get_page(map.root_page_id()).await?;
+            let root = MapPage::from_existing(root_page.data().read().await);
+            Ok::<_, buffer_pool::buffer_pool::Error>((root.leaf(0).1, root.leaf(1).1))
+        };
+        assert_eq!(max_categories().await?, (category(2000), category(2000)));
+
+        // Lowering entries leaves the maximum too high, until a `find` reads the leaf.
+        for i in 0..ENTRIES_PER_LEAF {
+            map.update(i, PageId(100 + i as u32), 100).await?;
+        }
+        assert_eq!(max_categories().await?, (category(2000), category(2000)));
+        assert_eq!(
+            map.find(1000, None).await?,
+            Some(PageId(100 + ENTRIES_PER_LEAF as u32))
+        );
+        assert_eq!(max_categories().await?, (category(100), category(2000)));
+
+        // Raising an entry raises the maximum right away.
+        map.update(7, PageId(107), 3000).await?;
+        assert_eq!(max_categories().await?, (category(3000), category(2000)));
+        assert_eq!(map.find(2500, None).await?, Some(PageId(107)));
+        Ok(())
+    }
+}
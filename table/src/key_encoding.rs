@@ -0,0 +1,218 @@
+//! Order-preserving ("memcomparable") encoding of `Datum` tuples, for use as btree keys.
+//!
+//! Encoded keys compare bytewise the same way as the tuples compare column by column, with each
+//! column's sort order and NULL placement taken into account.
+//!
+//! Each column is encoded as a marker byte followed by the value:
+//! - marker: `NULL_FIRST` or `NULL_LAST` for NULL (and no value), `NOT_NULL` otherwise,
+//! - integers (`Int2`, `Int4`, `Int8`, and `Date`, `Timestamp`): big-endian, with the sign bit
+//!   flipped so that negative numbers sort first,
+//! - `Bool`: one byte, 0 or 1,
+//! - `Float8`: the IEEE bits big-endian, with the sign bit flipped for positive numbers and all
+//!   bits inverted for negative ones. NaN is normalized to a positive NaN, so that it sorts last,
+//!   and -0 to 0.
+//! - `Numeric`: the value scaled to `MAX_NUMERIC_SCALE`, as a 16-byte integer. Decoding yields the
+//!   smallest scale which represents the value, so the original scale isn't preserved.
+//! - `String`, `Bytea`: the bytes with each 0 byte escaped as `00 ff`, terminated by `00 01`. The
+//!   terminator makes the encoding prefix-free, so a string sorts before its extensions and the
+//!   following columns don't affect the order.
+//!
+//! For descending columns all value bytes are inverted. The marker isn't, since NULL placement is
+//! specified independently of the direction.
+
+use crate::datum::{Datum, Numeric, Type};
+use proptest_derive::Arbitrary;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Arbitrary)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Arbitrary)]
+pub enum NullsOrder {
+    NullsFirst,
+    NullsLast,
+}
+
+/// Type and ordering of a key column.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct KeyColumn {
+    pub ty: Type,
+    pub order: SortOrder,
+    pub nulls: NullsOrder,
+}
+
+const NULL_FIRST: u8 = 0x00;
+const NOT_NULL: u8 = 0x01;
+const NULL_LAST: u8 = 0x02;
+
+const ESCAPE: u8 = 0x00;
+const ESCAPED_ZERO: u8 = 0xff;
+const TERMINATOR: u8 = 0x01;
+
+/// Encode the values of a key. `values` must have one datum (of the column's type, or NULL) per
+/// column.
+pub fn encode_key(columns: &[KeyColumn], values: &[Datum]) -> Vec<u8> {
+    assert_eq!(columns.len(), values.len(), "wrong number of key values");
+    let mut result = vec![];
+    for (column, value) in columns.iter().zip(values) {
+        if *value == Datum::Null {
+            result.push(match column.nulls {
+                NullsOrder::NullsFirst => NULL_FIRST,
+                NullsOrder::NullsLast => NULL_LAST,
+            });
+            continue;
+        }
+        assert_eq!(value.ty(), Some(column.ty), "key value of wrong type");
+        result.push(NOT_NULL);
+        let start = result.len();
+        match value {
+            Datum::Int8(v) | Datum::Timestamp(v) => {
+                result.extend_from_slice(&((*v as u64) ^ (1 << 63)).to_be_bytes())
+            }
+            Datum::Int4(v) | Datum::Date(v) => {
+                result.extend_from_slice(&((*v as u32) ^ (1 << 31)).to_be_bytes())
+            }
+            Datum::Int2(v) => result.extend_from_slice(&((*v as u16) ^ (1 << 15)).to_be_bytes()),
+            Datum::Bool(v) => result.push(*v as u8),
+            Datum::Float8(v) => result.extend_from_slice(&encode_f64(*v).to_be_bytes()),
+            Datum::Numeric(v) => {
+                result.extend_from_slice(&((v.normalized() as u128) ^ (1 << 127)).to_be_bytes())
+            }
+            Datum::String(s) => write_escaped_bytes(&mut result, s.as_bytes()),
+            Datum::Bytea(b) => write_escaped_bytes(&mut result, b),
+            Datum::Null => unreachable!(),
+        }
+        if column.order == SortOrder::Descending {
+            invert(&mut result[start..]);
+        }
+    }
+    result
+}
+
+/// Decode a key encoded by `encode_key` with the same columns.
+///
+/// Panics on malformed input.
+pub fn decode_key(columns: &[KeyColumn], data: &[u8]) -> Vec<Datum> {
+    let mut offset = 0;
+    let mut result = vec![];
+    for column in columns {
+        let marker = data[offset];
+        offset += 1;
+        if marker != NOT_NULL {
+            result.push(Datum::Null);
+            continue;
+        }
+        // Descending values are decoded from an inverted copy of the rest of the key.
+        let rest: Vec<u8> = match column.order {
+            SortOrder::Ascending => data[offset..].to_vec(),
+            SortOrder::Descending => data[offset..].iter().map(|byte| !byte).collect(),
+        };
+        let read_u64 = || {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&rest[..8]);
+            u64::from_be_bytes(bytes)
+        };
+        let read_u32 = || {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(&rest[..4]);
+            u32::from_be_bytes(bytes)
+        };
+        let (value, len) = match column.ty {
+            Type::Int8 => (Datum::Int8((read_u64() ^ (1 << 63)) as i64), 8),
+            Type::Timestamp => (Datum::Timestamp((read_u64() ^ (1 << 63)) as i64), 8),
+            Type::Int4 => (Datum::Int4((read_u32() ^ (1 << 31)) as i32), 4),
+            Type::Date => (Datum::Date((read_u32() ^ (1 << 31)) as i32), 4),
+            Type::Int2 => {
+                let bits = u16::from_be_bytes([rest[0], rest[1]]);
+                (Datum::Int2((bits ^ (1 << 15)) as i16), 2)
+            }
+            Type::Bool => (Datum::Bool(rest[0] != 0), 1),
+            Type::Float8 => (Datum::Float8(decode_f64(read_u64())), 8),
+            Type::Numeric => {
+                let mut bytes = [0; 16];
+                bytes.copy_from_slice(&rest[..16]);
+                let value = (u128::from_be_bytes(bytes) ^ (1 << 127)) as i128;
+                let numeric = Numeric::from_normalized(value).expect("numeric out of range");
+                (Datum::Numeric(numeric), 16)
+            }
+            Type::String => {
+                let (bytes, len) = read_escaped_bytes(&rest);
+                let s = String::from_utf8(bytes).expect("invalid UTF-8 in key");
+                (Datum::String(s), len)
+            }
+            Type::Bytea => {
+                let (bytes, len) = read_escaped_bytes(&rest);
+                (Datum::Bytea(bytes), len)
+            }
+        };
+        result.push(value);
+        offset += len;
+    }
+    assert_eq!(offset, data.len(), "trailing bytes in key");
+    result
+}
+
+/// Append the bytes with zeros escaped, and a terminator.
+pub(crate) fn write_escaped_bytes(result: &mut Vec<u8>, bytes: &[u8]) {
+    for &byte in bytes {
+        result.push(byte);
+        if byte == ESCAPE {
+            result.push(ESCAPED_ZERO);
+        }
+    }
+    result.push(ESCAPE);
+    result.push(TERMINATOR);
+}
+
+/// Read bytes written by `write_escaped_bytes`. Returns them, and the number of bytes consumed.
+pub(crate) fn read_escaped_bytes(data: &[u8]) -> (Vec<u8>, usize) {
+    let mut result = vec![];
+    let mut offset = 0;
+    loop {
+        let byte = data[offset];
+        offset += 1;
+        if byte != ESCAPE {
+            result.push(byte);
+            continue;
+        }
+        let next = data[offset];
+        offset += 1;
+        match next {
+            ESCAPED_ZERO => result.push(ESCAPE),
+            TERMINATOR => return (result, offset),
+            _ => panic!("invalid escape sequence in key"),
+        }
+    }
+}
+
+fn encode_f64(value: f64) -> u64 {
+    let value = if value.is_nan() {
+        f64::NAN
+    } else if value == 0.0 {
+        0.0
+    } else {
+        value
+    };
+    let bits = value.to_bits();
+    if bits & (1 << 63) != 0 {
+        !bits
+    } else {
+        bits ^ (1 << 63)
+    }
+}
+
+fn decode_f64(bits: u64) -> f64 {
+    f64::from_bits(if bits & (1 << 63) != 0 {
+        bits ^ (1 << 63)
+    } else {
+        !bits
+    })
+}
+
+fn invert(bytes: &mut [u8]) {
+    for byte in bytes {
+        *byte = !*byte;
+    }
+}
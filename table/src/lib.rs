@@ -2,12 +2,22 @@
 #[macro_use]
 extern crate insta;
 
+pub mod free_space_map;
+pub mod key_encoding;
+pub mod row;
+pub mod secondary_index;
 pub mod table_heap;
 pub mod table_page;
 
 #[cfg(test)]
 mod hexdump;
 #[cfg(test)]
+mod key_encoding_tests;
+#[cfg(test)]
+mod row_tests;
+#[cfg(test)]
+mod secondary_index_tests;
+#[cfg(test)]
 mod table_heap_tests;
 #[cfg(test)]
 mod table_page_tests;
@@ -15,3 +25,5 @@ mod table_page_tests;
 pub mod datum;
 #[cfg(test)]
 pub mod datum_serialization_tests;
+#[cfg(test)]
+mod datum_text_tests;
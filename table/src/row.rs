@@ -0,0 +1,260 @@
+//! Rows: tuples of `Datum`s described by a `Schema`, encoded so that any column can be read without
+//! decoding the columns before it.
+//!
+//! Row format:
+//! ```text
+//! -------------------------------------------------------
+//! | null bitmap | fixed-size part | variable-size data |
+//! -------------------------------------------------------
+//! ```
+//!
+//! - The null bitmap has one bit per column (bit `i % 8` of byte `i / 8`), set if the column is
+//!   NULL.
+//! - The fixed-size part has a slot for each column, at an offset computed from the schema: the
+//!   value of fixed-size columns (as in `slot_size`; `Numeric` is the mantissa followed by the
+//!   scale), and for variable-size (`String` and `Bytea`) columns the offset of the value in the
+//!   row (4 bytes). A variable-size value ends where the next one begins, or at the end of the row
+//!   for the last one. Slots of NULL columns are zero, except for variable-size ones, which are
+//!   empty.
+//!
+//! All integers are little-endian.
+
+use crate::datum::serialize::{DecodeError, Result};
+use crate::datum::{Datum, Nullability, Numeric, Type, MAX_NUMERIC_SCALE};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Column {
+    pub name: String,
+    pub ty: Type,
+    pub nullability: Nullability,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Schema {
+    columns: Vec<Column>,
+    /// Offset of each column's slot in the fixed-size part.
+    slot_offsets: Vec<usize>,
+    /// For each column, the slot offset of the next variable-size column.
+    next_variable_slots: Vec<Option<usize>>,
+    /// Offset where the variable-size data starts.
+    data_offset: usize,
+}
+
+fn slot_size(ty: Type) -> usize {
+    match ty {
+        Type::Int8 | Type::Float8 | Type::Timestamp => 8,
+        Type::Int4 | Type::Date => 4,
+        Type::Int2 => 2,
+        Type::Bool => 1,
+        Type::Numeric => 9,
+        Type::String | Type::Bytea => 4,
+    }
+}
+
+fn is_variable_size(ty: Type) -> bool {
+    ty == Type::String || ty == Type::Bytea
+}
+
+impl Schema {
+    pub fn new(columns: Vec<Column>) -> Self {
+        let mut offset = (columns.len() + 7) / 8;
+        let mut slot_offsets = vec![];
+        for column in &columns {
+            slot_offsets.push(offset);
+            offset += slot_size(column.ty);
+        }
+        let mut next_variable_slots = vec![None; columns.len()];
+        let mut next = None;
+        for (index, column) in columns.iter().enumerate().rev() {
+            next_variable_slots[index] = next;
+            if is_variable_size(column.ty) {
+                next = Some(slot_offsets[index]);
+            }
+        }
+        Schema {
+            columns,
+            slot_offsets,
+            next_variable_slots,
+            data_offset: offset,
+        }
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Index of the column with the given name.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column.name == name)
+    }
+}
+
+/// An encoded row, together with its schema.
+#[derive(Debug, Clone, Copy)]
+pub struct Row<'a> {
+    schema: &'a Schema,
+    data: &'a [u8],
+}
+
+impl<'a> Row<'a> {
+    /// Encode a row. `values` must have one datum per column, of the column's type (or NULL, if the
+    /// column is nullable).
+    pub fn encode(schema: &Schema, values: &[Datum]) -> Vec<u8> {
+        assert_eq!(schema.len(), values.len(), "wrong number of values");
+        let mut result = vec![0u8; schema.data_offset];
+        for (index, (column, value)) in schema.columns.iter().zip(values).enumerate() {
+            let slot = schema.slot_offsets[index];
+            if *value == Datum::Null {
+                assert_eq!(
+                    column.nullability,
+                    Nullability::Nullable,
+                    "NULL in a NOT NULL column {}",
+                    column.name
+                );
+                result[index / 8] |= 1 << (index % 8);
+            } else {
+                assert_eq!(
+                    value.ty(),
+                    Some(column.ty),
+                    "value of wrong type in column {}",
+                    column.name
+                );
+            }
+            if is_variable_size(column.ty) {
+                let offset = result.len() as u32;
+                result[slot..slot + 4].copy_from_slice(&offset.to_le_bytes());
+            }
+            let mut write = |bytes: &[u8]| result[slot..slot + bytes.len()].copy_from_slice(bytes);
+            match value {
+                Datum::Int8(v) | Datum::Timestamp(v) => write(&v.to_le_bytes()),
+                Datum::Int4(v) | Datum::Date(v) => write(&v.to_le_bytes()),
+                Datum::Int2(v) => write(&v.to_le_bytes()),
+                Datum::Bool(v) => write(&[*v as u8]),
+                Datum::Float8(v) => write(&v.to_bits().to_le_bytes()),
+                Datum::Numeric(v) => {
+                    write(&v.mantissa.to_le_bytes());
+                    result[slot + 8] = v.scale;
+                }
+                Datum::String(s) => result.extend_from_slice(s.as_bytes()),
+                Datum::Bytea(b) => result.extend_from_slice(b),
+                Datum::Null => {}
+            }
+        }
+        result
+    }
+
+    /// View an encoded row. `data` must have been encoded with the same schema - otherwise decoding
+    /// values returns errors (or wrong values).
+    pub fn new(schema: &'a Schema, data: &'a [u8]) -> Self {
+        Row { schema, data }
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn is_null(&self, index: usize) -> Result<bool> {
+        Ok(self.bytes(index / 8, 1)?[0] & (1 << (index % 8)) != 0)
+    }
+
+    /// Decode the value of a column. Returns an error if the row is corrupt.
+    pub fn get(&self, index: usize) -> Result<Datum> {
+        if self.is_null(index)? {
+            return Ok(Datum::Null);
+        }
+        let slot = self.schema.slot_offsets[index];
+        let ty = self.schema.columns[index].ty;
+        let data = self.bytes(slot, slot_size(ty))?;
+        Ok(match ty {
+            Type::Int8 => Datum::Int8(i64::from_le_bytes(read_array(data))),
+            Type::Timestamp => Datum::Timestamp(i64::from_le_bytes(read_array(data))),
+            Type::Int4 => Datum::Int4(i32::from_le_bytes(read_array(data))),
+            Type::Date => Datum::Date(i32::from_le_bytes(read_array(data))),
+            Type::Int2 => Datum::Int2(i16::from_le_bytes(read_array(data))),
+            Type::Bool => match data[0] {
+                0 => Datum::Bool(false),
+                1 => Datum::Bool(true),
+                value => {
+                    return Err(DecodeError::InvalidBool {
+                        offset: slot,
+                        value,
+                    })
+                }
+            },
+            Type::Float8 => Datum::Float8(f64::from_bits(u64::from_le_bytes(read_array(data)))),
+            Type::Numeric => match data[8] {
+                scale if scale <= MAX_NUMERIC_SCALE => {
+                    Datum::Numeric(Numeric::new(i64::from_le_bytes(read_array(data)), scale))
+                }
+                value => {
+                    return Err(DecodeError::InvalidNumericScale {
+                        offset: slot + 8,
+                        value,
+                    })
+                }
+            },
+            Type::String => {
+                let (offset, bytes) = self.variable_size_value(index)?;
+                let s =
+                    std::str::from_utf8(bytes).map_err(|_| DecodeError::InvalidUtf8 { offset })?;
+                Datum::String(s.to_string())
+            }
+            Type::Bytea => Datum::Bytea(self.variable_size_value(index)?.1.to_vec()),
+        })
+    }
+
+    /// Returns the offset and bytes of a variable-size value.
+    fn variable_size_value(&self, index: usize) -> Result<(usize, &'a [u8])> {
+        let slot = self.schema.slot_offsets[index];
+        let start = self.read_offset(slot)?;
+        let end = match self.schema.next_variable_slots[index] {
+            Some(next_slot) => self.read_offset(next_slot)?,
+            None => self.data.len(),
+        };
+        if start < self.schema.data_offset || start > end {
+            return Err(DecodeError::InvalidOffset {
+                offset: slot,
+                value: start as u32,
+            });
+        }
+        Ok((start, self.bytes(start, end - start)?))
+    }
+
+    /// Decode all values. Returns an error if the row is corrupt.
+    pub fn values(&self) -> Result<Vec<Datum>> {
+        (0..self.schema.len())
+            .map(|index| self.get(index))
+            .collect()
+    }
+
+    fn read_offset(&self, slot: usize) -> Result<usize> {
+        Ok(u32::from_le_bytes(read_array(self.bytes(slot, 4)?)) as usize)
+    }
+
+    /// Returns `len` bytes of the row at `offset`, or an error if the row is too short.
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8]> {
+        match self.data.get(offset..offset + len) {
+            Some(bytes) => Ok(bytes),
+            None => Err(DecodeError::UnexpectedEof {
+                offset: offset.min(self.data.len()),
+                needed: offset + len - self.data.len(),
+            }),
+        }
+    }
+}
+
+/// Read the first bytes of `data` into an array (of inferred size).
+fn read_array<A: AsMut<[u8]> + Default>(data: &[u8]) -> A {
+    let mut array = A::default();
+    let len = array.as_mut().len();
+    array.as_mut().copy_from_slice(&data[..len]);
+    array
+}
@@ -0,0 +1,181 @@
+use crate::datum::serialize::DecodeError;
+use crate::datum::{Datum, NotNull, Nullability, Nullable, Type};
+use crate::hexdump::pretty_hex;
+use crate::row::{Column, Row, Schema};
+use proptest::prelude::*;
+
+fn column(name: &str, ty: Type, nullability: Nullability) -> Column {
+    Column {
+        name: name.into(),
+        ty,
+        nullability,
+    }
+}
+
+fn example_schema() -> Schema {
+    Schema::new(vec![
+        column("id", Type::Int8, NotNull),
+        column("name", Type::String, NotNull),
+        column("email", Type::String, Nullable),
+        column("active", Type::Bool, Nullable),
+        column("bio", Type::String, Nullable),
+    ])
+}
+
+#[test]
+fn encode_row() {
+    let schema = example_schema();
+    let data = Row::encode(
+        &schema,
+        &[
+            Datum::Int8(0x1234),
+            Datum::String("alice".into()),
+            Datum::Null,
+            Datum::Bool(true),
+            Datum::String("hi".into()),
+        ],
+    );
+    assert_snapshot!(pretty_hex(&data), @r###"
+    0000:   04 34 12 00  00 00 00 00  00 16 00 00  00 1b 00 00   .4..............
+    0010:   00 01 1b 00  00 00 61 6c  69 63 65 68  69            ......alicehi
+    "###);
+}
+
+#[test]
+fn get_column() {
+    let schema = example_schema();
+    let values = vec![
+        Datum::Int8(-5),
+        Datum::String("bob".into()),
+        Datum::String("bob@example.com".into()),
+        Datum::Null,
+        Datum::Null,
+    ];
+    let data = Row::encode(&schema, &values);
+    let row = Row::new(&schema, &data);
+    assert_eq!(row.get(2), Ok(Datum::String("bob@example.com".into())));
+    assert_eq!(
+        row.get(schema.index_of("name").unwrap()),
+        Ok(values[1].clone())
+    );
+    assert_eq!(row.is_null(3), Ok(true));
+    assert_eq!(row.is_null(0), Ok(false));
+    assert_eq!(row.values(), Ok(values));
+}
+
+#[test]
+fn decode_corrupt_row() {
+    let schema = example_schema();
+    let data = Row::encode(
+        &schema,
+        &[
+            Datum::Int8(0x1234),
+            Datum::String("alice".into()),
+            Datum::Null,
+            Datum::Bool(true),
+            Datum::String("hi".into()),
+        ],
+    );
+    let corrupt = |offset: usize, value: u8| {
+        let mut data = data.clone();
+        data[offset] = value;
+        data
+    };
+
+    let truncated = &data[..20];
+    assert_eq!(Row::new(&schema, truncated).get(0), Ok(Datum::Int8(0x1234)));
+    assert_eq!(
+        Row::new(&schema, truncated).get(4),
+        Err(DecodeError::UnexpectedEof {
+            offset: 18,
+            needed: 2
+        })
+    );
+    assert!(Row::new(&schema, &[]).values().is_err());
+    assert_eq!(
+        Row::new(&schema, &corrupt(22, 0xff)).get(1),
+        Err(DecodeError::InvalidUtf8 { offset: 22 })
+    );
+    assert_eq!(
+        Row::new(&schema, &corrupt(17, 2)).get(3),
+        Err(DecodeError::InvalidBool {
+            offset: 17,
+            value: 2
+        })
+    );
+    assert_eq!(
+        Row::new(&schema, &corrupt(9, 0x30)).get(1),
+        Err(DecodeError::InvalidOffset {
+            offset: 9,
+            value: 0x30
+        })
+    );
+}
+
+#[test]
+#[should_panic(expected = "NULL in a NOT NULL column id")]
+fn null_in_not_null_column() {
+    let schema = example_schema();
+    Row::encode(
+        &schema,
+        &[
+            Datum::Null,
+            Datum::String("".into()),
+            Datum::Null,
+            Datum::Null,
+            Datum::Null,
+        ],
+    );
+}
+
+/// Schema with a column for each value (nullable if the value is NULL), and the values.
+fn schema_and_values() -> impl Strategy<Value = (Schema, Vec<Datum>)> {
+    any::<Vec<(Datum, Nullability)>>().prop_map(|columns| {
+        let schema = Schema::new(
+            columns
+                .iter()
+                .enumerate()
+                .map(|(index, (datum, nullability))| Column {
+                    name: format!("c{}", index),
+                    ty: datum.ty().unwrap_or(Type::Bool),
+                    nullability: if *datum == Datum::Null {
+                        Nullable
+                    } else {
+                        *nullability
+                    },
+                })
+                .collect(),
+        );
+        (
+            schema,
+            columns.into_iter().map(|(datum, _)| datum).collect(),
+        )
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 1000, ..ProptestConfig::default()
+    })]
+    #[test]
+    fn roundtrip((schema, values) in schema_and_values()) {
+        let data = Row::encode(&schema, &values);
+        prop_assert_eq!(Row::new(&schema, &data).values(), Ok(values));
+    }
+
+    #[test]
+    fn get_any_column((schema, values) in schema_and_values(), index: prop::sample::Index) {
+        prop_assume!(!values.is_empty());
+        let index = index.index(values.len());
+        let data = Row::encode(&schema, &values);
+        let row = Row::new(&schema, &data);
+        prop_assert_eq!(row.get(index), Ok(values[index].clone()));
+        prop_assert_eq!(row.is_null(index), Ok(values[index] == Datum::Null));
+    }
+
+    #[test]
+    fn decode_garbage((schema, _) in schema_and_values(), data: Vec<u8>) {
+        // Must not panic.
+        let _ = Row::new(&schema, &data).values();
+    }
+}
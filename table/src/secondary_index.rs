@@ -0,0 +1,306 @@
+//! Secondary indexes, mapping index keys to `TupleId`s of the table heap.
+//!
+//! Entries are stored in a `BTree` as composite keys `(index key, TupleId)` with empty values, so
+//! that the btree doesn't need duplicate support. The index key is encoded so that:
+//! - composite keys sort by index key first, then by `TupleId`,
+//! - the encoded index key is never a prefix of another one, so all entries for a given key form a
+//!   contiguous range.
+//!
+//! To do that, each 0 byte of the index key is escaped as `00 ff`, and the key is terminated with
+//! `00 01` (the same way as strings in `key_encoding`). The `TupleId` follows as a big-endian page
+//! id and slot index.
+
+use crate::key_encoding::{read_escaped_bytes, write_escaped_bytes};
+use crate::table_heap::TupleId;
+use btree::btree::{BTree, BulkLoader, Error as BTreeError, TreeStats};
+use buffer_pool::buffer_pool::BufferPool;
+use buffer_pool::disk_manager::PageId;
+use std::ops::Bound;
+
+#[derive(Debug)]
+pub enum Error {
+    BTree(BTreeError),
+    /// The index is unique, and already has an entry with the given key.
+    UniqueViolation(Vec<u8>),
+}
+
+impl From<BTreeError> for Error {
+    fn from(err: BTreeError) -> Self {
+        Error::BTree(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Size of an encoded `TupleId`.
+const TUPLE_ID_SIZE: usize = 6;
+
+/// Encodes a `TupleId` greater than any real one (page id and slot index all ones).
+const MAX_TUPLE_ID: [u8; TUPLE_ID_SIZE] = [0xff; TUPLE_ID_SIZE];
+
+pub struct SecondaryIndex<'b> {
+    btree: BTree<'b>,
+    unique: bool,
+}
+
+impl<'b> SecondaryIndex<'b> {
+    /// Create a new, empty index.
+    pub async fn new(buffer_pool: &'b BufferPool, unique: bool) -> Result<SecondaryIndex<'b>> {
+        Ok(SecondaryIndex {
+            btree: BTree::new(buffer_pool).await?,
+            unique,
+        })
+    }
+
+    /// Open an existing index, given the meta page id of its btree.
+    ///
+    /// Uniqueness is not stored in the index itself; it's part of the index definition.
+    pub async fn open(
+        buffer_pool: &'b BufferPool,
+        meta_page_id: PageId,
+        unique: bool,
+    ) -> Result<SecondaryIndex<'b>> {
+        Ok(SecondaryIndex {
+            btree: BTree::open(buffer_pool, meta_page_id).await?,
+            unique,
+        })
+    }
+
+    /// Start building a new index from entries sorted by key and `TupleId`. See
+    /// `SecondaryIndexLoader`.
+    pub async fn bulk_loader(
+        buffer_pool: &'b BufferPool,
+        unique: bool,
+        fill_factor: usize,
+    ) -> Result<SecondaryIndexLoader<'b>> {
+        Ok(SecondaryIndexLoader {
+            loader: BTree::bulk_loader(buffer_pool, fill_factor).await?,
+            unique,
+            previous_key: None,
+        })
+    }
+
+    pub fn meta_page_id(&self) -> PageId {
+        self.btree.meta_page_id()
+    }
+
+    pub fn is_unique(&self) -> bool {
+        self.unique
+    }
+
+    /// Size and shape of the index btree. Its tuples are the entries of the index.
+    pub async fn stats(&self) -> Result<TreeStats> {
+        Ok(self.btree.stats().await?)
+    }
+
+    /// Add an entry pointing from `key` to the tuple.
+    ///
+    /// For unique indexes, returns `UniqueViolation` if there's already an entry with this key.
+    /// Note that the check and the insert are not atomic, so concurrent inserts of the same key
+    /// are not detected.
+    pub async fn insert(&self, key: &[u8], tid: TupleId) -> Result<()> {
+        if self.unique && !self.lookup(key).await?.is_empty() {
+            return Err(Error::UniqueViolation(key.to_vec()));
+        }
+        self.insert_unchecked(key, tid).await
+    }
+
+    /// Add an entry pointing from `key` to the tuple, without checking uniqueness - for keys which
+    /// never conflict with other keys, like keys with NULLs in SQL.
+    pub async fn insert_unchecked(&self, key: &[u8], tid: TupleId) -> Result<()> {
+        self.btree
+            .insert(&encode_entry(key, tid), &[])
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Remove the entry pointing from `key` to the tuple. Returns whether it existed.
+    pub async fn delete(&self, key: &[u8], tid: TupleId) -> Result<bool> {
+        Ok(self.btree.delete(&encode_entry(key, tid)).await?)
+    }
+
+    /// Returns the tuples with the given key, in `TupleId` order.
+    pub async fn lookup(&self, key: &[u8]) -> Result<Vec<TupleId>> {
+        Ok(self
+            .range(Bound::Included(key), Bound::Included(key))
+            .await?
+            .into_iter()
+            .map(|(_, tid)| tid)
+            .collect())
+    }
+
+    /// Returns the entries with keys between the given bounds, ordered by key and `TupleId`.
+    pub async fn range(
+        &self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+    ) -> Result<Vec<(Vec<u8>, TupleId)>> {
+        // All composite keys for an index key `k` are between `encode_key(k)` (exclusive) and
+        // `encode_key(k) + MAX_TUPLE_ID` (exclusive).
+        let start = match start {
+            Bound::Included(key) => Bound::Included(encode_key(key)),
+            Bound::Excluded(key) => Bound::Included([&encode_key(key)[..], &MAX_TUPLE_ID].concat()),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let end = match end {
+            Bound::Included(key) => Bound::Excluded([&encode_key(key)[..], &MAX_TUPLE_ID].concat()),
+            Bound::Excluded(key) => Bound::Excluded(encode_key(key)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        Ok(self
+            .btree
+            .range(as_slice_bound(&start), as_slice_bound(&end))
+            .await?
+            .into_iter()
+            .map(|(entry, _)| decode_entry(&entry))
+            .collect())
+    }
+
+    /// Like `range`, but the bounds are key prefixes, compared with the same number of leading
+    /// bytes of each key. For example, `Included(p)` as the end bound includes all keys starting
+    /// with `p`.
+    ///
+    /// With keys from `key_encoding`, a prefix encoding the first columns of a key selects the
+    /// keys by the values of those columns.
+    pub async fn prefix_range(
+        &self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+    ) -> Result<Vec<(Vec<u8>, TupleId)>> {
+        // Escaping preserves prefixes, so entries with keys starting with `p` are exactly the
+        // entries starting with `escape(p)`.
+        let start = match start {
+            Bound::Included(prefix) => Bound::Included(escape_prefix(prefix)),
+            Bound::Excluded(prefix) => match prefix_successor(escape_prefix(prefix)) {
+                Some(successor) => Bound::Included(successor),
+                None => return Ok(vec![]),
+            },
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let end = match end {
+            Bound::Included(prefix) => match prefix_successor(escape_prefix(prefix)) {
+                Some(successor) => Bound::Excluded(successor),
+                None => Bound::Unbounded,
+            },
+            Bound::Excluded(prefix) => Bound::Excluded(escape_prefix(prefix)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        Ok(self
+            .btree
+            .range(as_slice_bound(&start), as_slice_bound(&end))
+            .await?
+            .into_iter()
+            .map(|(entry, _)| decode_entry(&entry))
+            .collect())
+    }
+}
+
+/// Builds a new index bottom-up from entries pushed in `(key, TupleId)` order, which is much
+/// faster than inserting them one by one.
+pub struct SecondaryIndexLoader<'b> {
+    loader: BulkLoader<'b>,
+    unique: bool,
+    previous_key: Option<Vec<u8>>,
+}
+
+impl<'b> SecondaryIndexLoader<'b> {
+    /// Add an entry pointing from `key` to the tuple.
+    ///
+    /// For unique indexes, returns `UniqueViolation` if the previous entry has the same key.
+    /// Returns `BTree(UnsortedInput)` if the entry is not greater than the previous one.
+    pub async fn push(&mut self, key: &[u8], tid: TupleId) -> Result<()> {
+        if self.unique && self.previous_key.as_deref() == Some(key) {
+            return Err(Error::UniqueViolation(key.to_vec()));
+        }
+        self.push_unchecked(key, tid).await
+    }
+
+    /// Add an entry without checking uniqueness (see `SecondaryIndex::insert_unchecked`).
+    pub async fn push_unchecked(&mut self, key: &[u8], tid: TupleId) -> Result<()> {
+        self.loader.push(&encode_entry(key, tid), &[]).await?;
+        self.previous_key = Some(key.to_vec());
+        Ok(())
+    }
+
+    /// Write the remaining pages, and return the index.
+    pub async fn finish(self) -> Result<SecondaryIndex<'b>> {
+        Ok(SecondaryIndex {
+            btree: self.loader.finish().await?,
+            unique: self.unique,
+        })
+    }
+}
+
+/// Escaped bytes of a key prefix, without the terminator.
+fn escape_prefix(prefix: &[u8]) -> Vec<u8> {
+    let mut result = encode_key(prefix);
+    result.truncate(result.len() - 2);
+    result
+}
+
+/// The smallest byte string greater than all strings starting with `prefix`, if there is one.
+fn prefix_successor(mut prefix: Vec<u8>) -> Option<Vec<u8>> {
+    while let Some(last) = prefix.pop() {
+        if last != 0xff {
+            prefix.push(last + 1);
+            return Some(prefix);
+        }
+    }
+    None
+}
+
+fn as_slice_bound(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key),
+        Bound::Excluded(key) => Bound::Excluded(key),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// Encode the index key part of an entry, including the terminator.
+fn encode_key(key: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(key.len() + 2);
+    write_escaped_bytes(&mut result, key);
+    result
+}
+
+fn encode_entry(key: &[u8], (page_id, slot_index): TupleId) -> Vec<u8> {
+    let mut result = encode_key(key);
+    result.extend_from_slice(&page_id.0.to_be_bytes());
+    result.extend_from_slice(&(slot_index as u16).to_be_bytes());
+    result
+}
+
+fn decode_entry(entry: &[u8]) -> (Vec<u8>, TupleId) {
+    let (key, key_size) = read_escaped_bytes(entry);
+    let tid = &entry[key_size..];
+    assert_eq!(tid.len(), TUPLE_ID_SIZE, "invalid index entry encoding");
+    let page_id = u32::from_be_bytes([tid[0], tid[1], tid[2], tid[3]]);
+    let slot_index = u16::from_be_bytes([tid[4], tid[5]]);
+    (key, (PageId(page_id), slot_index as usize))
+}
+
+#[cfg(test)]
+mod encoding_tests {
+    use super::*;
+
+    #[test]
+    fn encoding_preserves_order() {
+        let keys: Vec<&[u8]> = vec![b"", b"\0", b"\0\0", b"\0a", b"a", b"a\0", b"a\x01", b"ab"];
+        let encoded: Vec<Vec<u8>> = keys
+            .iter()
+            .map(|key| encode_entry(key, (PageId(1), 2)))
+            .collect();
+        let mut sorted = encoded.clone();
+        sorted.sort();
+        assert_eq!(encoded, sorted);
+    }
+
+    #[test]
+    fn encoding_roundtrip() {
+        for key in &[&b""[..], b"\0", b"a\0b\0\0", b"\xff\x01"] {
+            let tid = (PageId(0x01020304), 0x0506);
+            assert_eq!(decode_entry(&encode_entry(key, tid)), (key.to_vec(), tid));
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use crate::secondary_index::{Error, Result, SecondaryIndex};
+use crate::table_heap::{TableHeap, TupleId};
+use btree::btree::Error as BTreeError;
+
+use buffer_pool::{
+    buffer_pool::BufferPool, disk_manager::PageId, disk_manager_mem::DiskManagerMem,
+};
+use std::ops::Bound;
+
+#[tokio::test]
+async fn non_unique_lookup() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+    let index = SecondaryIndex::new(&buffer_pool, false).await?;
+    index.insert(b"b", (PageId(1), 0)).await?;
+    index.insert(b"a", (PageId(2), 5)).await?;
+    index.insert(b"b", (PageId(0), 3)).await?;
+    index.insert(b"ba", (PageId(0), 1)).await?;
+
+    assert_eq!(
+        index.lookup(b"b").await?,
+        vec![(PageId(0), 3), (PageId(1), 0)]
+    );
+    assert_eq!(index.lookup(b"a").await?, vec![(PageId(2), 5)]);
+    assert_eq!(index.lookup(b"c").await?, vec![]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn unique_violation() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+    let index = SecondaryIndex::new(&buffer_pool, true).await?;
+    index.insert(b"a", (PageId(0), 0)).await?;
+    index.insert(b"a\0", (PageId(0), 1)).await?;
+    match index.insert(b"a", (PageId(0), 2)).await {
+        Err(Error::UniqueViolation(key)) => assert_eq!(key, b"a"),
+        other => panic!("expected UniqueViolation, got {:?}", other),
+    }
+
+    // After deleting the entry the key can be reused.
+    assert!(index.delete(b"a", (PageId(0), 0)).await?);
+    index.insert(b"a", (PageId(0), 2)).await?;
+    assert_eq!(index.lookup(b"a").await?, vec![(PageId(0), 2)]);
+
+    index.insert_unchecked(b"a", (PageId(0), 3)).await?;
+    assert_eq!(
+        index.lookup(b"a").await?,
+        vec![(PageId(0), 2), (PageId(0), 3)]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn delete() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+    let index = SecondaryIndex::new(&buffer_pool, false).await?;
+    index.insert(b"a", (PageId(0), 0)).await?;
+    index.insert(b"a", (PageId(0), 1)).await?;
+    assert!(index.delete(b"a", (PageId(0), 0)).await?);
+    assert!(!index.delete(b"a", (PageId(0), 0)).await?);
+    assert!(!index.delete(b"b", (PageId(0), 1)).await?);
+    assert_eq!(index.lookup(b"a").await?, vec![(PageId(0), 1)]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn bulk_loader() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+    let entries: Vec<(Vec<u8>, TupleId)> = (0..2000u32)
+        .map(|i| {
+            let key = format!("key{:03}", i / 2).into_bytes();
+            (key, (PageId(i % 7), i as usize % 3))
+        })
+        .collect();
+    let mut sorted = entries.clone();
+    sorted.sort_by_key(|(key, (page_id, slot_index))| (key.clone(), page_id.0, *slot_index));
+    let mut loader = SecondaryIndex::bulk_loader(&buffer_pool, false, 90).await?;
+    for (key, tid) in &sorted {
+        loader.push(key, *tid).await?;
+    }
+    let index = loader.finish().await?;
+    assert_eq!(
+        index.range(Bound::Unbounded, Bound::Unbounded).await?,
+        sorted
+    );
+    assert_eq!(
+        index.lookup(b"key042").await?,
+        vec![(PageId(0), 0), (PageId(1), 1)]
+    );
+    let stats = index.stats().await?;
+    assert_eq!(stats.tuples, 2000);
+    assert!(stats.height > 1);
+
+    // Entries with the same key but a smaller `TupleId` are out of order.
+    let mut loader = SecondaryIndex::bulk_loader(&buffer_pool, false, 90).await?;
+    loader.push(b"a", (PageId(1), 0)).await?;
+    match loader.push(b"a", (PageId(0), 5)).await {
+        Err(Error::BTree(BTreeError::UnsortedInput)) => {}
+        other => panic!("expected UnsortedInput, got {:?}", other),
+    }
+
+    let mut loader = SecondaryIndex::bulk_loader(&buffer_pool, true, 90).await?;
+    loader.push(b"a", (PageId(0), 0)).await?;
+    loader.push(b"a\0", (PageId(0), 1)).await?;
+    match loader.push(b"a\0", (PageId(0), 2)).await {
+        Err(Error::UniqueViolation(key)) => assert_eq!(key, b"a\0"),
+        other => panic!("expected UniqueViolation, got {:?}", other),
+    }
+    loader.push_unchecked(b"a\0", (PageId(0), 2)).await?;
+    let index = loader.finish().await?;
+    assert_eq!(
+        index.lookup(b"a\0").await?,
+        vec![(PageId(0), 1), (PageId(0), 2)]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn range() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+    let index = SecondaryIndex::new(&buffer_pool, false).await?;
+    for i in 0..500usize {
+        let key = format!("{:03}", i % 100);
+        index.insert(key.as_bytes(), (PageId(i as u32), 0)).await?;
+    }
+
+    let entries = index
+        .range(Bound::Excluded(b"010"), Bound::Included(b"012"))
+        .await?;
+    assert_eq!(entries.len(), 10);
+    assert!(entries[..5].iter().all(|(key, _)| key == b"011"));
+    assert!(entries[5..].iter().all(|(key, _)| key == b"012"));
+    assert_eq!(
+        entries[..5]
+            .iter()
+            .map(|(_, tid)| tid.0)
+            .collect::<Vec<_>>(),
+        vec![
+            PageId(11),
+            PageId(111),
+            PageId(211),
+            PageId(311),
+            PageId(411)
+        ]
+    );
+
+    assert_eq!(
+        index
+            .range(Bound::Included(b"098"), Bound::Unbounded)
+            .await?
+            .len(),
+        10
+    );
+    assert_eq!(
+        index
+            .range(Bound::Unbounded, Bound::Excluded(b"001"))
+            .await?
+            .len(),
+        5
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn prefix_range() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+    let index = SecondaryIndex::new(&buffer_pool, false).await?;
+    let keys: Vec<&[u8]> = vec![
+        b"",
+        b"\0",
+        b"\0\xff",
+        b"a",
+        b"a\0",
+        b"ab",
+        b"b",
+        b"\xff",
+        b"\xff\xff",
+    ];
+    for (i, key) in keys.iter().enumerate() {
+        index.insert(key, (PageId(i as u32), 0)).await?;
+    }
+    let range = |start: Bound<&'static [u8]>, end: Bound<&'static [u8]>| {
+        let index = &index;
+        async move {
+            let entries = index.prefix_range(start, end).await?;
+            Ok::<_, Error>(entries.into_iter().map(|(key, _)| key).collect::<Vec<_>>())
+        }
+    };
+    let expected = |keys: &[&[u8]]| keys.iter().map(|key| key.to_vec()).collect::<Vec<_>>();
+
+    assert_eq!(
+        range(Bound::Included(b"a"), Bound::Included(b"a")).await?,
+        expected(&[b"a", b"a\0", b"ab"])
+    );
+    assert_eq!(
+        range(Bound::Excluded(b"a"), Bound::Unbounded).await?,
+        expected(&[b"b", b"\xff", b"\xff\xff"])
+    );
+    assert_eq!(
+        range(Bound::Unbounded, Bound::Excluded(b"a")).await?,
+        expected(&[b"", b"\0", b"\0\xff"])
+    );
+    assert_eq!(
+        range(Bound::Included(b"\0"), Bound::Included(b"\0")).await?,
+        expected(&[b"\0", b"\0\xff"])
+    );
+    assert_eq!(
+        range(Bound::Included(b"\xff"), Bound::Included(b"\xff")).await?,
+        expected(&[b"\xff", b"\xff\xff"])
+    );
+    assert_eq!(
+        range(Bound::Excluded(b"\xff"), Bound::Unbounded).await?,
+        expected(&[])
+    );
+    assert_eq!(
+        range(Bound::Unbounded, Bound::Unbounded).await?,
+        expected(&keys)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn index_table_heap() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+    let table = TableHeap::new(&buffer_pool).await.unwrap();
+    let index = SecondaryIndex::new(&buffer_pool, true).await?;
+    for name in &["alice", "bob", "carol"] {
+        let tid = table.insert_tuple(name.as_bytes()).await.unwrap();
+        index.insert(name.as_bytes(), tid).await?;
+    }
+    let tids = index.lookup(b"bob").await?;
+    assert_eq!(tids.len(), 1);
+    assert_eq!(&*table.get_tuple(tids[0]).await.unwrap().unwrap(), b"bob");
+    Ok(())
+}
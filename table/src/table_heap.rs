@@ -1,131 +1,373 @@
+use crate::free_space_map::{self, FreeSpaceMap};
 use crate::table_page;
-use buffer_pool::buffer_pool::{BufferPool, PinnedPageReadGuard, Result};
+use buffer_pool::buffer_pool::{
+    BufferPool, Error as BufferPoolError, PinnedPageReadGuard, PinnedPageWriteGuard,
+};
 use buffer_pool::disk_manager::PageId;
 use std::ops::Deref;
-use table_page::{SlotIndex, TablePage};
+use table_page::{Slot, SlotIndex, TablePage};
+
+#[derive(Debug)]
+pub enum Error {
+    BufferPool(BufferPoolError),
+    /// The table has as many pages as its free space map can record.
+    TableFull,
+}
+
+impl From<BufferPoolError> for Error {
+    fn from(err: BufferPoolError) -> Self {
+        Error::BufferPool(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
 
 pub type TupleId = (PageId, SlotIndex);
 
+type WritePage<'b> = TablePage<PinnedPageWriteGuard<'b>>;
+
+// Tuples which no longer fit on their page after an update are moved to another page, leaving a
+// forwarding pointer in the original slot, so that their `TupleId` stays valid. A moved tuple
+// always has its forwarding slot on the page of its `TupleId` - it's never forwarded twice.
+//
+// Locking: the page of the `TupleId` is locked while the moved tuple is accessed, so that the tuple
+// can't be moved concurrently. Pages are not locked in a consistent order, so concurrent updates
+// of tuples moved between the same pages can deadlock.
+//
+// Inserts find a page with enough space using the free space map, which is updated whenever a page
+// changes (while the page is still locked). Each page stores its ordinal in the list of pages,
+// which is the position of its entry in the map. If no page has enough space, a new page is
+// appended.
+// The last page is found in the map, and the list of pages is followed from there, in case another
+// insert has appended a page in the meantime.
+
 pub struct TableHeap<'b> {
     buffer_pool: &'b BufferPool,
     first_page_id: PageId,
+    free_space_map: FreeSpaceMap<'b>,
 }
 
 impl<'b> TableHeap<'b> {
-    pub fn from_existing(buffer_pool: &'b BufferPool, first_page_id: PageId) -> Self {
-        TableHeap {
+    pub async fn from_existing(
+        buffer_pool: &'b BufferPool,
+        first_page_id: PageId,
+    ) -> Result<TableHeap<'b>> {
+        let free_space_map_page_id = {
+            let first_page = TableHeap::read_page_in(buffer_pool, first_page_id).await?;
+            first_page.get_free_space_map_page_id()
+        };
+        Ok(TableHeap {
             buffer_pool,
             first_page_id,
-        }
+            free_space_map: FreeSpaceMap::from_existing(buffer_pool, free_space_map_page_id),
+        })
     }
 
     /// Initialize a new table heap in the given storage.
     pub async fn new(buffer_pool: &'b BufferPool) -> Result<TableHeap<'b>> {
+        let free_space_map = FreeSpaceMap::new(buffer_pool).await?;
         let page = buffer_pool.allocate_page().await?;
-        TablePage::new(page.data().write().await);
+        let mut table_page = TablePage::new(page.data().write().await);
+        table_page.set_free_space_map_page_id(free_space_map.root_page_id());
+        free_space_map
+            .update(0, page.id(), table_page.reclaimable_space())
+            .await?;
         page.dirty();
+        drop(table_page);
         Ok(TableHeap {
             buffer_pool,
             first_page_id: page.id(),
+            free_space_map,
         })
     }
 
+    pub fn first_page_id(&self) -> PageId {
+        self.first_page_id
+    }
+
     pub async fn insert_tuple<'a>(&self, tuple: &'a [u8]) -> Result<TupleId> {
-        let mut page_id = self.first_page_id;
+        self.insert(tuple, None).await
+    }
+
+    /// Insert the tuple on a page with enough space.
+    ///
+    /// If `moved_from` is given, the tuple is being moved from a slot on that (locked) page, and is
+    /// inserted on a different page.
+    async fn insert(
+        &self,
+        tuple: &[u8],
+        mut moved_from: Option<(&mut WritePage<'b>, SlotIndex)>,
+    ) -> Result<TupleId> {
+        let insert = |table_page: &mut WritePage<'b>, from: Option<TupleId>| match from {
+            Some(from) => table_page.insert_moved_tuple(from, tuple),
+            None => table_page.insert_tuple(tuple),
+        };
+        let from = moved_from
+            .as_ref()
+            .map(|(page, slot_index)| (page.unwrap().id(), *slot_index));
+        let exclude = from.map(|(page_id, _)| page_id);
+        let required_space = table_page::required_space(match from {
+            Some(_) => table_page::MOVED_HEADER_SIZE + tuple.len(),
+            None => tuple.len(),
+        });
+
+        while let Some(page_id) = self.free_space_map.find(required_space, exclude).await? {
+            let mut table_page = self.write_page(page_id).await?;
+            let result = insert(&mut table_page, from);
+            // If the insert failed, the map was out of date, and is corrected here - so that the
+            // page isn't found again.
+            self.update_free_space(&table_page).await?;
+            if let Ok(slot_index) = result {
+                table_page.unwrap().dirty();
+                return Ok((page_id, slot_index));
+            }
+        }
+
+        let mut page_id = match self.free_space_map.last_page_id().await? {
+            Some(page_id) => page_id,
+            None => self.first_page_id,
+        };
         loop {
-            let page = self.buffer_pool.get_page(page_id).await?;
-            let mut table_page = TablePage::from_existing(page.data().write().await);
-            match table_page.insert_tuple(tuple) {
-                Ok(slot_index) => {
-                    page.dirty();
-                    return Ok((page.id(), slot_index));
+            let mut locked_page = None;
+            let table_page = match &mut moved_from {
+                Some((page, _)) if page.unwrap().id() == page_id => &mut **page,
+                _ => {
+                    locked_page = Some(self.write_page(page_id).await?);
+                    locked_page.as_mut().unwrap()
+                }
+            };
+            if Some(page_id) != exclude {
+                let result = insert(table_page, from);
+                self.update_free_space(table_page).await?;
+                if let Ok(slot_index) = result {
+                    table_page.unwrap().dirty();
+                    return Ok((page_id, slot_index));
+                }
+            }
+
+            let next_page_id = table_page.get_next_page_id();
+            if next_page_id.is_valid() {
+                page_id = next_page_id;
+            } else {
+                let ordinal = table_page.get_ordinal() + 1;
+                if ordinal as usize >= free_space_map::MAX_PAGES {
+                    return Err(Error::TableFull);
+                }
+                let new_page = self.buffer_pool.allocate_page().await?;
+                table_page.set_next_page_id(new_page.id());
+                table_page.unwrap().dirty();
+                let new_page_data = new_page.write().await;
+
+                // Note: we can only unlock the previous page after locking the next one -
+                // otherwise we would be publishing a pointer to an uninitialized page.
+                drop(locked_page);
+
+                let mut new_table_page = TablePage::new(new_page_data);
+                new_table_page.set_prev_page_id(page_id);
+                new_table_page.set_ordinal(ordinal);
+                let slot_index =
+                    insert(&mut new_table_page, from).expect("Tuple too big to fit on a new page");
+                self.update_free_space(&new_table_page).await?;
+                new_table_page.unwrap().dirty();
+                return Ok((new_table_page.unwrap().id(), slot_index));
+            }
+        }
+    }
+
+    /// Delete the tuple. Returns whether it existed.
+    ///
+    /// The slot may be reused by a later insert, so references to the tuple (like index entries)
+    /// have to be removed too.
+    pub async fn delete_tuple(&self, tid: TupleId) -> Result<bool> {
+        let mut page = self.write_page(tid.0).await?;
+        match page.get_slot(tid.1) {
+            Slot::Free | Slot::Moved { .. } => return Ok(false),
+            Slot::Tuple(_) => {}
+            Slot::Forward(page_id, slot_index) => {
+                let mut target = self.write_page(page_id).await?;
+                target.delete_tuple(slot_index);
+                self.update_free_space(&target).await?;
+                target.unwrap().dirty();
+            }
+        }
+        page.delete_tuple(tid.1);
+        self.update_free_space(&page).await?;
+        page.unwrap().dirty();
+        Ok(true)
+    }
+
+    /// Replace the tuple's data. Returns whether it existed.
+    ///
+    /// The tuple is updated in place if it still fits on its page, otherwise it's moved to another
+    /// page. Its `TupleId` doesn't change in either case.
+    pub async fn update_tuple(&self, tid: TupleId, tuple: &[u8]) -> Result<bool> {
+        let mut page = self.write_page(tid.0).await?;
+        match page.get_slot(tid.1) {
+            Slot::Free | Slot::Moved { .. } => return Ok(false),
+            Slot::Tuple(_) => {
+                if page.update_tuple(tid.1, tuple).is_ok() {
+                    self.update_free_space(&page).await?;
+                    page.unwrap().dirty();
+                    return Ok(true);
                 }
-                Err(table_page::PageFull) => {
-                    page_id = table_page.get_next_page_id();
-
-                    if !page_id.is_valid() {
-                        let new_page = self.buffer_pool.allocate_page().await?;
-                        table_page.set_next_page_id(new_page.id());
-                        page.dirty();
-                        let new_page_data = new_page.data().write().await;
-
-                        // Note: we can only unlock the previous page after locking the next one -
-                        // otherwise we would be publishing a pointer to an uninitialized page.
-                        drop(table_page);
-                        drop(page);
-
-                        let mut new_table_page = TablePage::new(new_page_data);
-                        let slot_index = new_table_page
-                            .insert_tuple(tuple)
-                            .expect("Tuple too big to fit on a new page");
-                        new_page.dirty();
-                        return Ok((new_page.id(), slot_index));
-                    }
+            }
+            Slot::Forward(page_id, slot_index) => {
+                let mut target = self.write_page(page_id).await?;
+                if target.update_tuple(slot_index, tuple).is_ok() {
+                    self.update_free_space(&target).await?;
+                    target.unwrap().dirty();
+                    return Ok(true);
                 }
+                // The old copy is only deleted once the forward points to the new one, so that
+                // the tuple isn't lost if the insert fails. The target page may be chosen for the
+                // new copy, so it's unlocked in between.
+                drop(target);
+                self.move_tuple(&mut page, tid.1, tuple).await?;
+                let mut target = self.write_page(page_id).await?;
+                target.delete_tuple(slot_index);
+                self.update_free_space(&target).await?;
+                target.unwrap().dirty();
+                return Ok(true);
             }
         }
+        self.move_tuple(&mut page, tid.1, tuple).await?;
+        Ok(true)
     }
 
-    pub async fn iter(&self) -> Result<TableIter<'_>> {
+    /// Insert the tuple on another page, and replace the slot with a forward to it.
+    async fn move_tuple(
+        &self,
+        page: &mut WritePage<'b>,
+        slot_index: SlotIndex,
+        tuple: &[u8],
+    ) -> Result<()> {
+        let new_tid = self.insert(tuple, Some((page, slot_index))).await?;
+        page.set_forward(slot_index, new_tid);
+        self.update_free_space(page).await?;
+        page.unwrap().dirty();
+        Ok(())
+    }
+
+    /// Record the page's current free space in the free space map.
+    async fn update_free_space(&self, page: &WritePage<'b>) -> Result<()> {
+        self.free_space_map
+            .update(
+                page.get_ordinal() as usize,
+                page.unwrap().id(),
+                page.reclaimable_space(),
+            )
+            .await
+    }
+
+    /// Iterate over all tuples. The iterator only borrows the buffer pool, not the heap.
+    pub async fn iter(&self) -> Result<TableIter<'b>> {
         self.iter_at((self.first_page_id, 0)).await
     }
 
-    pub async fn get_tuple(&self, tid: TupleId) -> Result<TupleReadGuard<'_>> {
-        Ok(TupleReadGuard {
-            iter: self.iter_at(tid).await?,
-        })
+    /// Number of pages in the heap, found by following the list of pages.
+    pub async fn page_count(&self) -> Result<usize> {
+        let mut count = 1;
+        let mut page_id = self.read_page(self.first_page_id).await?.get_next_page_id();
+        while page_id.is_valid() {
+            count += 1;
+            page_id = self.read_page(page_id).await?.get_next_page_id();
+        }
+        Ok(count)
+    }
+
+    /// Returns the tuple, or `None` if it doesn't exist (for example was deleted).
+    pub async fn get_tuple(&self, tid: TupleId) -> Result<Option<TupleReadGuard<'_>>> {
+        let page = self.read_page(tid.0).await?;
+        let (page, slot_index) = match page.get_slot(tid.1) {
+            Slot::Free | Slot::Moved { .. } => return Ok(None),
+            Slot::Tuple(_) => (page, tid.1),
+            Slot::Forward(page_id, slot_index) => {
+                // Lock the target page before unlocking the forwarding one, so that the tuple
+                // can't be moved in between.
+                let target = self.read_page(page_id).await?;
+                drop(page);
+                // A dangling forward could point at a slot reused by another tuple.
+                match target.get_slot(slot_index) {
+                    Slot::Moved { from, .. } if from == tid => {}
+                    _ => return Ok(None),
+                }
+                (target, slot_index)
+            }
+        };
+        Ok(Some(TupleReadGuard { page, slot_index }))
     }
 
-    async fn iter_at(&self, tid: TupleId) -> Result<TableIter<'_>> {
+    async fn iter_at(&self, tid: TupleId) -> Result<TableIter<'b>> {
         Ok(TableIter {
-            table: self,
+            buffer_pool: self.buffer_pool,
             slot_index: tid.1,
             page: self.read_page(tid.0).await?,
         })
     }
 
     async fn read_page(&self, page_id: PageId) -> Result<TablePage<PinnedPageReadGuard<'b>>> {
-        let page = self.buffer_pool.get_page(page_id).await?;
+        TableHeap::read_page_in(self.buffer_pool, page_id).await
+    }
+
+    async fn read_page_in(
+        buffer_pool: &'b BufferPool,
+        page_id: PageId,
+    ) -> Result<TablePage<PinnedPageReadGuard<'b>>> {
+        let page = buffer_pool.get_page(page_id).await?;
         Ok(TablePage::from_existing(page.read().await))
     }
+
+    async fn write_page(&self, page_id: PageId) -> Result<WritePage<'b>> {
+        let page = self.buffer_pool.get_page(page_id).await?;
+        Ok(TablePage::from_existing(page.write().await))
+    }
 }
 
 pub struct TableIter<'b> {
-    table: &'b TableHeap<'b>,
+    buffer_pool: &'b BufferPool,
     slot_index: SlotIndex,
     page: TablePage<PinnedPageReadGuard<'b>>,
 }
 
 impl<'b> TableIter<'b> {
+    /// Returns the next tuple in physical order. Deleted slots are skipped, and moved tuples are
+    /// returned (with their original `TupleId`) where they're stored, not at the forwarding slot.
     pub async fn next<'t>(&'t mut self) -> Result<Option<(TupleId, &'t [u8])>> {
-        while self.slot_index >= self.page.get_tuple_count() {
-            let next = self.page.get_next_page_id();
-            if !next.is_valid() {
-                return Ok(None);
+        loop {
+            while self.slot_index >= self.page.get_tuple_count() {
+                let next = self.page.get_next_page_id();
+                if !next.is_valid() {
+                    return Ok(None);
+                }
+                self.page = TableHeap::read_page_in(self.buffer_pool, next).await?;
+                self.slot_index = 0;
             }
-            self.page = self.table.read_page(next).await?;
-            self.slot_index = 0;
+            let slot_index = self.slot_index;
+            self.slot_index += 1;
+            let tid = match self.page.get_slot(slot_index) {
+                Slot::Free | Slot::Forward(..) => continue,
+                Slot::Tuple(_) => (self.page.unwrap().id(), slot_index),
+                Slot::Moved { from, .. } => from,
+            };
+            return Ok(Some((
+                tid,
+                self.page.get_tuple(slot_index).expect("invalid slot index"),
+            )));
         }
-        let slot_index = self.slot_index;
-        self.slot_index += 1;
-        Ok(Some((
-            (self.page.unwrap().id(), slot_index),
-            self.page.get_tuple(slot_index).expect("invalid slot index"),
-        )))
     }
 }
 
 pub struct TupleReadGuard<'b> {
-    iter: TableIter<'b>,
+    page: TablePage<PinnedPageReadGuard<'b>>,
+    slot_index: SlotIndex,
 }
 
 impl Deref for TupleReadGuard<'_> {
     type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
-        self.iter
-            .page
-            .get_tuple(self.iter.slot_index)
+        self.page
+            .get_tuple(self.slot_index)
             .expect("invalid slot index")
     }
 }
@@ -1,16 +1,19 @@
-use crate::table_heap::TableHeap;
+use crate::table_heap::{Result, TableHeap, TupleId};
+use crate::table_page::TablePage;
 
 use buffer_pool::{
-    buffer_pool::{BufferPool, Result},
+    buffer_pool::BufferPool,
+    disk_manager::{INVALID_PAGE_ID, PAGE_SIZE},
     disk_manager_mem::DiskManagerMem,
 };
+use proptest::prelude::*;
 
 #[tokio::test]
 async fn insert_and_get() -> Result<()> {
     let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
     let table = TableHeap::new(&buffer_pool).await?;
     let tid = table.insert_tuple(b"Hello World").await?;
-    assert_eq!(&*table.get_tuple(tid).await?, b"Hello World");
+    assert_eq!(&*table.get_tuple(tid).await?.unwrap(), b"Hello World");
     Ok(())
 }
 
@@ -36,3 +39,236 @@ async fn insert_many_and_iter() -> Result<()> {
     }
     Ok(())
 }
+
+#[tokio::test]
+async fn page_links() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+    let table = TableHeap::new(&buffer_pool).await?;
+    let first_page_id = table.first_page_id();
+    let second_page_id = loop {
+        let (page_id, _) = table.insert_tuple(&[1u8; 100]).await?;
+        if page_id != first_page_id {
+            break page_id;
+        }
+    };
+
+    let first_page = buffer_pool.get_page(first_page_id).await?;
+    let first_page = TablePage::from_existing(first_page.read().await);
+    assert_eq!(first_page.get_next_page_id(), second_page_id);
+    assert_eq!(first_page.get_prev_page_id(), INVALID_PAGE_ID);
+    let second_page = buffer_pool.get_page(second_page_id).await?;
+    let second_page = TablePage::from_existing(second_page.read().await);
+    assert_eq!(second_page.get_next_page_id(), INVALID_PAGE_ID);
+    assert_eq!(second_page.get_prev_page_id(), first_page_id);
+    drop((first_page, second_page));
+    assert_eq!(table.page_count().await?, 2);
+    Ok(())
+}
+
+async fn collect(table: &TableHeap<'_>) -> Result<Vec<(TupleId, Vec<u8>)>> {
+    let mut iter = table.iter().await?;
+    let mut result = vec![];
+    while let Some((tid, tuple)) = iter.next().await? {
+        result.push((tid, tuple.to_vec()));
+    }
+    Ok(result)
+}
+
+#[tokio::test]
+async fn delete() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+    let table = TableHeap::new(&buffer_pool).await?;
+    let a = table.insert_tuple(b"a").await?;
+    let b = table.insert_tuple(b"b").await?;
+    let c = table.insert_tuple(b"c").await?;
+
+    assert!(table.delete_tuple(b).await?);
+    assert!(!table.delete_tuple(b).await?);
+    assert!(table.get_tuple(b).await?.is_none());
+    assert_eq!(
+        collect(&table).await?,
+        vec![(a, b"a".to_vec()), (c, b"c".to_vec())]
+    );
+
+    // the freed slot is reused
+    assert_eq!(table.insert_tuple(b"d").await?, b);
+    Ok(())
+}
+
+#[tokio::test]
+async fn update_in_place() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+    let table = TableHeap::new(&buffer_pool).await?;
+    let tid = table.insert_tuple(b"Hello World").await?;
+    assert!(table.update_tuple(tid, b"Hello").await?);
+    assert_eq!(&*table.get_tuple(tid).await?.unwrap(), b"Hello");
+    assert!(table.update_tuple(tid, b"Hello again, World").await?);
+    assert_eq!(
+        &*table.get_tuple(tid).await?.unwrap(),
+        b"Hello again, World"
+    );
+    assert_eq!(
+        collect(&table).await?,
+        vec![(tid, b"Hello again, World".to_vec())]
+    );
+
+    assert!(table.delete_tuple(tid).await?);
+    assert!(!table.update_tuple(tid, b"Hello").await?);
+    Ok(())
+}
+
+#[tokio::test]
+async fn update_moves_tuple() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+    let table = TableHeap::new(&buffer_pool).await?;
+    let small = table.insert_tuple(b"small").await?;
+    let big = table.insert_tuple(&[1u8; PAGE_SIZE / 2]).await?;
+    assert_eq!(small.0, big.0);
+
+    // doesn't fit on the first page anymore
+    let large = vec![2u8; PAGE_SIZE / 2];
+    assert!(table.update_tuple(small, &large).await?);
+    assert_eq!(&*table.get_tuple(small).await?.unwrap(), &large[..]);
+    assert_eq!(
+        collect(&table).await?,
+        vec![(big, vec![1u8; PAGE_SIZE / 2]), (small, large)]
+    );
+
+    // updating a moved tuple
+    assert!(table.update_tuple(small, b"small again").await?);
+    assert_eq!(&*table.get_tuple(small).await?.unwrap(), b"small again");
+
+    // a moved tuple which doesn't fit on its new page is moved again
+    table.insert_tuple(&[3u8; PAGE_SIZE / 2]).await?;
+    let larger = vec![4u8; PAGE_SIZE * 3 / 4];
+    assert!(table.update_tuple(small, &larger).await?);
+    assert_eq!(&*table.get_tuple(small).await?.unwrap(), &larger[..]);
+    assert_eq!(collect(&table).await?.len(), 3);
+
+    assert!(table.delete_tuple(small).await?);
+    assert!(table.get_tuple(small).await?.is_none());
+    assert_eq!(collect(&table).await?.len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn dangling_forward() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+    let table = TableHeap::new(&buffer_pool).await?;
+    let small = table.insert_tuple(b"small").await?;
+    table.insert_tuple(&[1u8; PAGE_SIZE / 2]).await?;
+    assert!(table.update_tuple(small, &[2u8; PAGE_SIZE / 2]).await?);
+    let other = table.insert_tuple(b"other").await?;
+
+    // a forward to a slot holding another tuple isn't followed
+    {
+        let mut page = TablePage::from_existing(buffer_pool.get_page(small.0).await?.write().await);
+        page.set_forward(small.1, other);
+        page.unwrap().dirty();
+    }
+    assert!(table.get_tuple(small).await?.is_none());
+    assert_eq!(&*table.get_tuple(other).await?.unwrap(), b"other");
+    Ok(())
+}
+
+#[tokio::test]
+async fn insert_uses_freed_space() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+    let table = TableHeap::new(&buffer_pool).await?;
+    let mut tids = vec![];
+    for i in 0..10u8 {
+        tids.push(table.insert_tuple(&[i; PAGE_SIZE / 3]).await?);
+    }
+    let first_page = tids[0].0;
+    assert_ne!(tids[9].0, first_page);
+
+    // space freed on the first page is found through the free space map
+    assert!(table.delete_tuple(tids[1]).await?);
+    assert_eq!(table.insert_tuple(&[20; PAGE_SIZE / 3]).await?, tids[1]);
+
+    // all pages are full, so a new one is appended
+    let tid = table.insert_tuple(&[21; PAGE_SIZE / 3]).await?;
+    assert!(tids.iter().all(|(page_id, _)| *page_id != tid.0));
+    assert_eq!(collect(&table).await?.last().unwrap().0, tid);
+    Ok(())
+}
+
+#[tokio::test]
+async fn reopen() -> Result<()> {
+    let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 10);
+    let first_page_id = {
+        let table = TableHeap::new(&buffer_pool).await?;
+        for i in 0..10u8 {
+            table.insert_tuple(&[i; PAGE_SIZE / 3]).await?;
+        }
+        table.first_page_id()
+    };
+    let table = TableHeap::from_existing(&buffer_pool, first_page_id).await?;
+    let tid = table.insert_tuple(b"Hello").await?;
+    assert_eq!(&*table.get_tuple(tid).await?.unwrap(), b"Hello");
+    assert_eq!(collect(&table).await?.len(), 11);
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+enum Op {
+    Insert(usize),
+    Update(usize, usize),
+    Delete(usize),
+}
+
+fn op_strategy() -> impl Strategy<Value = Op> {
+    // tuple sizes up to a third of a page, so that updates often move tuples
+    let size = 0..PAGE_SIZE / 3;
+    prop_oneof![
+        size.clone().prop_map(Op::Insert),
+        (any::<usize>(), size).prop_map(|(index, size)| Op::Update(index, size)),
+        any::<usize>().prop_map(Op::Delete),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 100, ..ProptestConfig::default()
+    })]
+    #[test]
+    fn random_operations(ops in prop::collection::vec(op_strategy(), 1..100)) {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let buffer_pool = BufferPool::new(Box::new(DiskManagerMem::new()), 20);
+            let table = TableHeap::new(&buffer_pool).await?;
+            // in insertion order
+            let mut expected: Vec<(TupleId, Vec<u8>)> = vec![];
+            for (i, op) in ops.into_iter().enumerate() {
+                let tuple = |size| vec![i as u8; size];
+                match op {
+                    Op::Insert(size) => {
+                        let tid = table.insert_tuple(&tuple(size)).await?;
+                        assert!(expected.iter().all(|(other, _)| *other != tid));
+                        expected.push((tid, tuple(size)));
+                    }
+                    Op::Update(index, size) if !expected.is_empty() => {
+                        let len = expected.len();
+                        let entry = &mut expected[index % len];
+                        assert!(table.update_tuple(entry.0, &tuple(size)).await?);
+                        entry.1 = tuple(size);
+                    }
+                    Op::Delete(index) if !expected.is_empty() => {
+                        let (tid, _) = expected.remove(index % expected.len());
+                        assert!(table.delete_tuple(tid).await?);
+                    }
+                    _ => {}
+                }
+                for (tid, tuple) in &expected {
+                    assert_eq!(&*table.get_tuple(*tid).await?.unwrap(), &tuple[..]);
+                }
+                let key = |(tid, _): &(TupleId, Vec<u8>)| ((tid.0).0, tid.1);
+                let mut actual = collect(&table).await?;
+                actual.sort_by_key(key);
+                let mut sorted = expected.clone();
+                sorted.sort_by_key(key);
+                assert_eq!(actual, sorted);
+            }
+            Result::Ok(())
+        }).unwrap();
+    }
+}
@@ -1,171 +1,270 @@
-use buffer_pool::disk_manager::{PageData, PageId, INVALID_PAGE_ID, PAGE_SIZE};
+use buffer_pool::disk_manager::{PageData, PageId, INVALID_PAGE_ID};
+use page::TupleBlockPage;
+use std::convert::TryInto;
 use std::ops::{Deref, DerefMut};
 
+pub use page::{Error, PageFull, Result, SlotIndex};
+
 pub struct TablePage<T> {
-    data: T,
+    page: TupleBlockPage<T, HeapPageMetadata>,
 }
 
 //
-// Slotted page format:
-// ---------------------------------------------------------
-// | HEADER | ... FREE SPACE ... | ... INSERTED TUPLES ... |
-// ---------------------------------------------------------
-//                               ^
-//                               free space pointer
+// A table page is a `TupleBlockPage` (see the `page` crate), with `HeapPageMetadata` as metadata.
+//
+// Each slot stores a record, starting with a tag byte:
+// - tuple: `TAG_TUPLE`, tuple data, padding.
+// - forward: the tuple was moved to another page by an update. `TAG_FORWARD`, followed by the new
+//   location: page id (4), slot index (2).
+// - moved: a tuple moved here from another page. `TAG_MOVED`, followed by the location of the
+//   forwarding slot: page id (4), slot index (2), and the tuple data.
+// Dead and unused slots are free. Free slots are reused by inserts, and trailing ones are removed.
+//
+// Tuple records are padded to the size of a forward record, so that a tuple can always be replaced
+// by a forwarding pointer in place. The number of padding bytes is stored in the upper bits of the
+// tag byte.
 //
-// Header format (size in bytes):
-// ----------------------------------------------------------------------------
-// | PageId (4)| LSN (4)| PrevPageId (4)| NextPageId (4)| FreeSpacePointer(4) |
-// ----------------------------------------------------------------------------
-// ----------------------------------------------------------------
-// | TupleCount (4) | Tuple_1 offset (4) | Tuple_1 size (4) | ... |
-// ----------------------------------------------------------------
+// All integers are little-endian.
 //
+// Space of deleted or resized tuples is reclaimed by compacting the page when it runs out of free
+// space. Compaction doesn't change slot indexes.
+//
+
+#[derive(Clone, Copy)]
+struct HeapPageMetadata {
+    next_page_id: PageId,
+    prev_page_id: PageId,
+    /// First page of the table's free space map (see `free_space_map`). Only set on the first page
+    /// of the table.
+    free_space_map_page_id: PageId,
+    /// Position of the page in the list of pages, used to find its free space map entry.
+    ordinal: u32,
+}
+
+const TAG_TUPLE: u8 = 0;
+const TAG_FORWARD: u8 = 1;
+const TAG_MOVED: u8 = 2;
+const TAG_MASK: u8 = 0b11;
+const PADDING_SHIFT: u32 = 2;
 
-const OFFSET_NEXT_PAGE_ID: usize = 0x0c;
-const OFFSET_FREE_SPACE_PTR: usize = 0x10;
-const OFFSET_TUPLE_COUNT: usize = 0x14;
-const OFFSET_TUPLE_DESCRIPTORS: usize = 0x18;
-const SIZE_TUPLE_DESCRIPTOR: usize = 8;
+const RECORD_HEADER_SIZE: usize = 1;
+/// Size of a tuple location (page id and slot index) in a record.
+const LOCATION_SIZE: usize = 6;
+const FORWARD_RECORD_SIZE: usize = RECORD_HEADER_SIZE + LOCATION_SIZE;
 
-// offsets inside tuple descriptor
-const OFFSET_TUPLE_OFFSET: usize = 0;
-const OFFSET_TUPLE_SIZE: usize = 4;
+/// Additional space taken by a moved tuple.
+pub const MOVED_HEADER_SIZE: usize = LOCATION_SIZE;
 
-#[derive(PartialEq, Eq, Debug)]
-pub enum Error {
-    PageFull,
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Slot<'a> {
+    Free,
+    Tuple(&'a [u8]),
+    /// The tuple was moved to the given page and slot.
+    Forward(PageId, SlotIndex),
+    /// A tuple moved from the forwarding slot at the given page and slot.
+    Moved {
+        from: (PageId, SlotIndex),
+        tuple: &'a [u8],
+    },
 }
 
-pub use Error::*;
+/// Free space required to insert a tuple of the given size (if no free slot can be reused).
+pub fn required_space(size: usize) -> usize {
+    (RECORD_HEADER_SIZE + size).max(FORWARD_RECORD_SIZE) + page::TUPLE_DESCRIPTOR_SIZE
+}
 
-pub type Result<T> = std::result::Result<T, Error>;
+fn tuple_record(tuple: &[u8]) -> Vec<u8> {
+    let padding = FORWARD_RECORD_SIZE.saturating_sub(RECORD_HEADER_SIZE + tuple.len());
+    let mut record = vec![TAG_TUPLE | (padding as u8) << PADDING_SHIFT];
+    record.extend_from_slice(tuple);
+    record.resize(record.len() + padding, 0);
+    record
+}
 
-pub type SlotIndex = usize;
+fn location_record(tag: u8, (page_id, slot_index): (PageId, SlotIndex), tuple: &[u8]) -> Vec<u8> {
+    let mut record = vec![tag];
+    record.extend_from_slice(&page_id.0.to_le_bytes());
+    record.extend_from_slice(&(slot_index as u16).to_le_bytes());
+    record.extend_from_slice(tuple);
+    record
+}
+
+fn read_location(data: &[u8]) -> (PageId, SlotIndex) {
+    (
+        PageId(u32::from_le_bytes(data[..4].try_into().unwrap())),
+        u16::from_le_bytes(data[4..LOCATION_SIZE].try_into().unwrap()) as SlotIndex,
+    )
+}
 
 impl<T: Deref<Target = PageData>> TablePage<T> {
     pub fn from_existing(data: T) -> Self {
-        TablePage { data }
+        TablePage {
+            page: TupleBlockPage::from_existing(data),
+        }
     }
 
     pub fn unwrap(&self) -> &T {
-        &self.data
+        &self.page
     }
 
     pub fn free_space(&self) -> usize {
-        self.get_free_space_ptr()
-            - OFFSET_TUPLE_DESCRIPTORS
-            - self.get_tuple_count() * SIZE_TUPLE_DESCRIPTOR
+        self.page.free_space()
     }
 
-    fn get_free_space_ptr(&self) -> usize {
-        self.read_u32(OFFSET_FREE_SPACE_PTR) as usize
+    /// LSN of the last log record which modified the page.
+    pub fn get_lsn(&self) -> u32 {
+        self.page.lsn()
     }
 
     pub fn get_tuple_count(&self) -> usize {
-        self.read_u32(OFFSET_TUPLE_COUNT) as usize
+        self.page.tuple_count()
     }
 
     pub fn get_next_page_id(&self) -> PageId {
-        PageId(self.read_u32(OFFSET_NEXT_PAGE_ID))
+        self.page.metadata().next_page_id
     }
 
-    pub fn get_tuple(&self, index: SlotIndex) -> Option<&[u8]> {
-        let (offset, size) = self.get_tuple_descriptor(index);
-        if offset == 0 {
-            return None;
-        }
-        Some(&self.data[offset..offset + size])
+    pub fn get_prev_page_id(&self) -> PageId {
+        self.page.metadata().prev_page_id
+    }
+
+    pub fn get_free_space_map_page_id(&self) -> PageId {
+        self.page.metadata().free_space_map_page_id
+    }
+
+    pub fn get_ordinal(&self) -> u32 {
+        self.page.metadata().ordinal
     }
 
-    fn get_tuple_descriptor(&self, index: usize) -> (usize, usize) {
-        (
-            self.read_u32(
-                OFFSET_TUPLE_DESCRIPTORS + SIZE_TUPLE_DESCRIPTOR * index + OFFSET_TUPLE_OFFSET,
-            ) as usize,
-            self.read_u32(
-                OFFSET_TUPLE_DESCRIPTORS + SIZE_TUPLE_DESCRIPTOR * index + OFFSET_TUPLE_SIZE,
-            ) as usize,
-        )
+    /// Size of free space after compacting the page.
+    pub fn reclaimable_space(&self) -> usize {
+        self.page.free_space_after_compaction()
     }
 
-    #[allow(clippy::cast_ptr_alignment)]
-    fn read_u32(&self, offset: usize) -> u32 {
-        let ptr = self.data[offset..].as_ptr() as *const u32;
-        unsafe { ptr.read_unaligned() }
+    /// Returns the tuple data in the given slot, also if it was moved here from another page.
+    pub fn get_tuple(&self, index: SlotIndex) -> Option<&[u8]> {
+        match self.get_slot(index) {
+            Slot::Tuple(tuple) | Slot::Moved { tuple, .. } => Some(tuple),
+            Slot::Free | Slot::Forward(..) => None,
+        }
+    }
+
+    pub fn get_slot(&self, index: SlotIndex) -> Slot<'_> {
+        if index >= self.get_tuple_count() {
+            return Slot::Free;
+        }
+        let record = match self.page.get_tuple(index) {
+            Some(record) => record,
+            None => return Slot::Free,
+        };
+        let data = &record[RECORD_HEADER_SIZE..];
+        match record[0] & TAG_MASK {
+            TAG_TUPLE => {
+                let padding = (record[0] >> PADDING_SHIFT) as usize;
+                Slot::Tuple(&data[..data.len() - padding])
+            }
+            TAG_FORWARD => {
+                let (page_id, slot_index) = read_location(data);
+                Slot::Forward(page_id, slot_index)
+            }
+            TAG_MOVED => Slot::Moved {
+                from: read_location(data),
+                tuple: &data[LOCATION_SIZE..],
+            },
+            tag => panic!("invalid record tag {}", tag),
+        }
     }
 
     #[cfg(test)]
     pub(crate) fn data(&self) -> &PageData {
-        &self.data
+        &self.page
     }
 }
 
 impl<T: DerefMut<Target = PageData>> TablePage<T> {
     /// Initialize a new page in the given storage.
     pub fn new(data: T) -> Self {
-        let mut page = TablePage { data };
-        page.set_free_space_ptr(PAGE_SIZE as u32);
-        page.set_tuple_count(0);
-        page.set_next_page_id(INVALID_PAGE_ID);
-        page
+        TablePage {
+            page: TupleBlockPage::new(
+                data,
+                &HeapPageMetadata {
+                    next_page_id: INVALID_PAGE_ID,
+                    prev_page_id: INVALID_PAGE_ID,
+                    free_space_map_page_id: INVALID_PAGE_ID,
+                    ordinal: 0,
+                },
+            ),
+        }
     }
 
-    pub fn alloc_tuple(&mut self, size: usize) -> Result<(SlotIndex, &mut [u8])> {
-        if self.free_space() < size + SIZE_TUPLE_DESCRIPTOR {
-            return Err(Error::PageFull);
-        }
-        let end = self.get_free_space_ptr();
-        let start = end - size;
-        let index = self.get_tuple_count();
-        self.set_free_space_ptr(start as u32);
-        self.set_tuple_count((index + 1) as u32);
-        self.set_tuple_descriptor(index, start as u32, (end - start) as u32);
-        Ok((index, &mut self.data[start..end]))
+    pub fn insert_tuple(&mut self, tuple: &[u8]) -> Result<SlotIndex> {
+        self.page.insert_tuple_in_free_slot(&tuple_record(tuple))
     }
 
-    pub fn insert_tuple<'a>(&mut self, tuple: &'a [u8]) -> Result<SlotIndex> {
-        let (slot_index, new_tuple) = self.alloc_tuple(tuple.len())?;
-        new_tuple.copy_from_slice(tuple);
-        Ok(slot_index)
+    /// Insert a tuple moved from the forwarding slot `from` on another page.
+    pub fn insert_moved_tuple(
+        &mut self,
+        from: (PageId, SlotIndex),
+        tuple: &[u8],
+    ) -> Result<SlotIndex> {
+        self.page
+            .insert_tuple_in_free_slot(&location_record(TAG_MOVED, from, tuple))
     }
 
-    pub fn get_tuple_mut(&mut self, index: SlotIndex) -> Option<&mut [u8]> {
-        let (offset, size) = self.get_tuple_descriptor(index);
-        if offset == 0 {
-            return None;
-        }
-        Some(&mut self.data[offset..offset + size])
+    /// Replace the data of the tuple (or moved tuple) in the given slot. The tuple is rewritten in
+    /// place if the new version isn't larger, otherwise it's moved within the page.
+    ///
+    /// Returns `PageFull` (leaving the tuple unchanged) if it doesn't fit on the page.
+    pub fn update_tuple(&mut self, index: SlotIndex, tuple: &[u8]) -> Result<()> {
+        let record = match self.get_slot(index) {
+            Slot::Tuple(_) => tuple_record(tuple),
+            Slot::Moved { from, .. } => location_record(TAG_MOVED, from, tuple),
+            Slot::Free | Slot::Forward(..) => panic!("no tuple in slot {}", index),
+        };
+        self.page.update_tuple(index, &record)
     }
 
-    fn set_free_space_ptr(&mut self, value: u32) {
-        self.write_u32(OFFSET_FREE_SPACE_PTR, value);
+    /// Replace the tuple (or forwarding pointer) in the slot with a forwarding pointer to the
+    /// tuple's new location.
+    pub fn set_forward(&mut self, index: SlotIndex, to: (PageId, SlotIndex)) {
+        assert!(
+            matches!(self.get_slot(index), Slot::Tuple(_) | Slot::Forward(..)),
+            "no tuple in slot {}",
+            index
+        );
+        self.page
+            .update_tuple(index, &location_record(TAG_FORWARD, to, &[]))
+            .expect("tuple records are at least as large as forward records");
     }
 
-    fn set_tuple_count(&mut self, value: u32) {
-        self.write_u32(OFFSET_TUPLE_COUNT, value);
+    /// Free the slot. Its space is reclaimed on compaction.
+    pub fn delete_tuple(&mut self, index: SlotIndex) {
+        self.page.mark_dead(index);
+        self.page.truncate_free_slots();
+    }
+
+    /// Move all tuple data to the end of the page, so that free space is contiguous.
+    pub fn compact(&mut self) {
+        self.page.compact();
+    }
+
+    pub fn set_lsn(&mut self, lsn: u32) {
+        self.page.set_lsn(lsn);
     }
 
     pub fn set_next_page_id(&mut self, value: PageId) {
-        self.write_u32(OFFSET_NEXT_PAGE_ID, value.0 as u32)
+        self.page.metadata_mut().next_page_id = value;
     }
 
-    fn set_tuple_descriptor(&mut self, index: usize, offset: u32, size: u32) {
-        self.write_u32(
-            OFFSET_TUPLE_DESCRIPTORS + SIZE_TUPLE_DESCRIPTOR * index + OFFSET_TUPLE_OFFSET,
-            offset,
-        );
-        self.write_u32(
-            OFFSET_TUPLE_DESCRIPTORS + SIZE_TUPLE_DESCRIPTOR * index + OFFSET_TUPLE_SIZE,
-            size,
-        );
+    pub fn set_prev_page_id(&mut self, value: PageId) {
+        self.page.metadata_mut().prev_page_id = value;
     }
 
-    #[allow(clippy::cast_ptr_alignment)]
-    fn write_u32(&mut self, offset: usize, value: u32) {
-        let ptr = self.data[offset..].as_mut_ptr() as *mut u32;
-        unsafe {
-            ptr.write_unaligned(value);
-        }
+    pub fn set_free_space_map_page_id(&mut self, value: PageId) {
+        self.page.metadata_mut().free_space_map_page_id = value;
+    }
+
+    pub fn set_ordinal(&mut self, value: u32) {
+        self.page.metadata_mut().ordinal = value;
     }
 }
@@ -1,17 +1,17 @@
 use crate::hexdump::pretty_hex;
 use crate::table_page;
-use crate::table_page::{PageFull, TablePage};
-use buffer_pool::disk_manager::PAGE_SIZE;
+use crate::table_page::{PageFull, Slot, TablePage};
+use buffer_pool::disk_manager::{PageId, INVALID_PAGE_ID, PAGE_SIZE};
 
 #[test]
 fn test_new_page() {
     let mut page_data = [0u8; PAGE_SIZE];
     TablePage::new(&mut page_data);
     assert_snapshot!(pretty_hex(&&page_data[..]), @r###"
-    0000:   00 00 00 00  00 00 00 00  00 00 00 00  ff ff ff ff   ................
-    0010:   00 10 00 00  00 00 00 00  00 00 00 00  00 00 00 00   ................
-    0020:   00 00 00 00  00 00 00 00  00 00 00 00  00 00 00 00   ................
+    0000:   00 00 00 00  10 00 f0 0f  00 00 00 00  00 00 00 00   ................
+    0010:   00 00 00 00  00 00 00 00  00 00 00 00  00 00 00 00   ................
     *
+    0ff0:   ff ff ff ff  ff ff ff ff  ff ff ff ff  00 00 00 00   ................
     "###);
 }
 
@@ -23,14 +23,14 @@ fn test_insert_tuple() -> table_page::Result<()> {
     let slot2 = page.insert_tuple(b"Very very very long tuple")?;
     let slot3 = page.insert_tuple(b"Small")?;
     assert_snapshot!(pretty_hex(&&page.data()[..]), @r###"
-    0000:   00 00 00 00  00 00 00 00  00 00 00 00  ff ff ff ff   ................
-    0010:   d7 0f 00 00  03 00 00 00  f5 0f 00 00  0b 00 00 00   ................
-    0020:   dc 0f 00 00  19 00 00 00  d7 0f 00 00  05 00 00 00   ................
-    0030:   00 00 00 00  00 00 00 00  00 00 00 00  00 00 00 00   ................
+    0000:   00 00 00 00  10 00 c3 0f  03 00 00 00  e4 0f 0c 00   ................
+    0010:   ca 0f 1a 00  c3 0f 07 00  00 00 00 00  00 00 00 00   ................
+    0020:   00 00 00 00  00 00 00 00  00 00 00 00  00 00 00 00   ................
     *
-    0fd0:   00 00 00 00  00 00 00 53  6d 61 6c 6c  56 65 72 79   .......SmallVery
-    0fe0:   20 76 65 72  79 20 76 65  72 79 20 6c  6f 6e 67 20    very very long 
-    0ff0:   74 75 70 6c  65 48 65 6c  6c 6f 20 57  6f 72 6c 64   tupleHello World
+    0fc0:   00 00 00 04  53 6d 61 6c  6c 00 00 56  65 72 79 20   ....Small..Very 
+    0fd0:   76 65 72 79  20 76 65 72  79 20 6c 6f  6e 67 20 74   very very long t
+    0fe0:   75 70 6c 65  00 48 65 6c  6c 6f 20 57  6f 72 6c 64   uple.Hello World
+    0ff0:   ff ff ff ff  ff ff ff ff  ff ff ff ff  00 00 00 00   ................
     "###);
 
     assert_eq!(page.get_tuple(slot1).unwrap(), b"Hello World");
@@ -49,3 +49,125 @@ fn test_page_full() -> table_page::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_delete_and_reuse_slot() -> table_page::Result<()> {
+    let mut page_data = [0u8; PAGE_SIZE];
+    let mut page = TablePage::new(&mut page_data);
+    page.insert_tuple(b"AAAA")?;
+    page.insert_tuple(b"BBBB")?;
+    page.insert_tuple(b"CCCC")?;
+
+    page.delete_tuple(1);
+    assert_eq!(page.get_slot(1), Slot::Free);
+    assert_eq!(page.get_tuple(1), None);
+    assert_eq!(page.get_tuple_count(), 3);
+
+    // trailing free slots are removed
+    page.delete_tuple(2);
+    assert_eq!(page.get_tuple_count(), 1);
+
+    assert_eq!(page.insert_tuple(b"DDDD")?, 1);
+    assert_eq!(page.insert_tuple(b"EEEE")?, 2);
+    assert_eq!(page.get_tuple(0).unwrap(), b"AAAA");
+    assert_eq!(page.get_tuple(1).unwrap(), b"DDDD");
+
+    Ok(())
+}
+
+#[test]
+fn test_update_tuple() -> table_page::Result<()> {
+    let mut page_data = [0u8; PAGE_SIZE];
+    let mut page = TablePage::new(&mut page_data);
+    let slot1 = page.insert_tuple(&[1u8; 1000])?;
+    let slot2 = page.insert_tuple(&[2u8; 1000])?;
+
+    // in place
+    page.update_tuple(slot1, b"Hello")?;
+    assert_eq!(page.get_tuple(slot1).unwrap(), b"Hello");
+
+    // moved within the page
+    page.update_tuple(slot1, &[3u8; 1500])?;
+    assert_eq!(page.get_tuple(slot1).unwrap(), &[3u8; 1500][..]);
+
+    // only fits after compaction
+    page.update_tuple(slot2, &[4u8; 2000])?;
+    assert_eq!(page.get_tuple(slot1).unwrap(), &[3u8; 1500][..]);
+    assert_eq!(page.get_tuple(slot2).unwrap(), &[4u8; 2000][..]);
+
+    assert_eq!(page.update_tuple(slot1, &[5u8; 2500]), Err(PageFull));
+    assert_eq!(page.get_tuple(slot1).unwrap(), &[3u8; 1500][..]);
+
+    Ok(())
+}
+
+#[test]
+fn test_forward_and_moved_tuple() -> table_page::Result<()> {
+    let mut page_data = [0u8; PAGE_SIZE];
+    let mut page = TablePage::new(&mut page_data);
+    let slot1 = page.insert_tuple(b"Hello")?;
+    let slot2 = page.insert_moved_tuple((PageId(7), 3), b"World")?;
+
+    page.set_forward(slot1, (PageId(5), 2));
+    assert_eq!(page.get_slot(slot1), Slot::Forward(PageId(5), 2));
+    assert_eq!(page.get_tuple(slot1), None);
+
+    assert_eq!(
+        page.get_slot(slot2),
+        Slot::Moved {
+            from: (PageId(7), 3),
+            tuple: b"World"
+        }
+    );
+    page.update_tuple(slot2, b"World, again")?;
+    page.compact();
+    assert_eq!(
+        page.get_slot(slot2),
+        Slot::Moved {
+            from: (PageId(7), 3),
+            tuple: b"World, again"
+        }
+    );
+    assert_eq!(page.reclaimable_space(), page.free_space());
+
+    Ok(())
+}
+
+#[test]
+fn test_page_links() {
+    let mut page_data = [0u8; PAGE_SIZE];
+    let mut page = TablePage::new(&mut page_data);
+    assert_eq!(page.get_next_page_id(), INVALID_PAGE_ID);
+    assert_eq!(page.get_prev_page_id(), INVALID_PAGE_ID);
+    assert_eq!(page.get_free_space_map_page_id(), INVALID_PAGE_ID);
+    assert_eq!(page.get_ordinal(), 0);
+    page.set_next_page_id(PageId(1));
+    page.set_prev_page_id(PageId(2));
+    page.set_free_space_map_page_id(PageId(3));
+    page.set_ordinal(5);
+    page.set_lsn(4);
+
+    let page = TablePage::from_existing(&page_data);
+    assert_eq!(page.get_next_page_id(), PageId(1));
+    assert_eq!(page.get_prev_page_id(), PageId(2));
+    assert_eq!(page.get_free_space_map_page_id(), PageId(3));
+    assert_eq!(page.get_ordinal(), 5);
+    assert_eq!(page.get_lsn(), 4);
+}
+
+#[test]
+fn test_forward_small_tuple_on_full_page() -> table_page::Result<()> {
+    let mut page_data = [0u8; PAGE_SIZE];
+    let mut page = TablePage::new(&mut page_data);
+    let small = page.insert_tuple(b"")?;
+    while page.insert_tuple(b"x").is_ok() {}
+    assert_eq!(page.get_slot(small), Slot::Tuple(b""));
+    assert_eq!(page.reclaimable_space(), page.free_space());
+
+    // The tuple is padded, so the forwarding pointer fits in its place.
+    page.set_forward(small, (PageId(5), 2));
+    assert_eq!(page.get_slot(small), Slot::Forward(PageId(5), 2));
+    assert_eq!(page.get_slot(small + 1), Slot::Tuple(b"x"));
+
+    Ok(())
+}